@@ -0,0 +1,136 @@
+use super::SparseMem;
+use std::io::Result;
+
+/// Expected access pattern for a range, given to `Advise::advise`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// No particular pattern, undoing previous advice
+    Normal,
+    /// Accessed from lower offsets to higher ones, so read-ahead pays off
+    Sequential,
+    /// Accessed in random order, so read-ahead is wasted
+    Random,
+    /// Going to be accessed soon, so it may be prefetched
+    WillNeed,
+    /// Not going to be accessed soon, so cached data may be dropped
+    DontNeed,
+}
+
+/// Hints about how a range will be accessed, like `posix_fadvise` or `madvise`.
+///
+/// Advice never changes data; implementations are free to ignore it,
+/// which is what the default implementation does.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Advice,Advise,ReadAt,SubRange};
+///
+/// fn scan<T: ReadAt + Advise>(obj: &T, len: u64) -> std::io::Result<u64> {
+///     obj.advise(0, len, Advice::Sequential)?;
+///     let mut sum = 0u64;
+///     let mut buf = [0u8; 4096];
+///     let mut offset = 0;
+///     while offset < len {
+///         let n = obj.read_at(&mut buf[..], offset)?;
+///         if n == 0 {
+///             break;
+///         }
+///         sum += buf[..n].iter().map(|&b| b as u64).sum::<u64>();
+///         offset += n as u64;
+///     }
+///     obj.advise(0, len, Advice::DontNeed)?;
+///     Ok(sum)
+/// }
+///
+/// let v = vec![1u8; 10000];
+/// assert_eq!(scan(&SubRange::new(&v, 100, 5000), 5000).unwrap(), 5000);
+/// ```
+pub trait Advise {
+    /// Tell how `offset..offset+len` is going to be accessed
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        let _ = (offset, len, advice);
+        Ok(())
+    }
+}
+
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: Advise + ?Sized> Advise for $ptr {
+            fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+                Advise::advise(&**self, offset, len, advice)
+            }
+        }
+    )*};
+}
+
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+/// Advice needs no exclusive access, so these only borrow or lock for reading.
+impl<T: Advise + ?Sized> Advise for std::cell::RefCell<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.borrow().advise(offset, len, advice)
+    }
+}
+
+impl<T: Advise + ?Sized> Advise for std::sync::Mutex<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        match self.lock() {
+            Ok(x) => x.advise(offset, len, advice),
+            Err(_) => Err(std::io::Error::other("poisoned mutex encountered")),
+        }
+    }
+}
+
+impl<T: Advise + ?Sized> Advise for std::sync::RwLock<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        match self.read() {
+            Ok(x) => x.advise(offset, len, advice),
+            Err(_) => Err(std::io::Error::other("poisoned rwlock encountered")),
+        }
+    }
+}
+
+/// Uses `posix_fadvise` with `advise` feature on Linux, Android and FreeBSD, ignores advice otherwise.
+impl Advise for std::fs::File {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        #[cfg(all(feature = "advise", any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+        {
+            use rustix::fs::{fadvise, Advice as A};
+            let advice = match advice {
+                Advice::Normal => A::Normal,
+                Advice::Sequential => A::Sequential,
+                Advice::Random => A::Random,
+                Advice::WillNeed => A::WillNeed,
+                Advice::DontNeed => A::DontNeed,
+            };
+            // Zero length would mean "till the end of file"
+            if let Some(len) = std::num::NonZeroU64::new(len) {
+                fadvise(self, offset, Some(len), advice)?;
+            }
+        }
+        #[cfg(not(all(feature = "advise", any(target_os = "linux", target_os = "android", target_os = "freebsd"))))]
+        let _ = (offset, len, advice);
+        Ok(())
+    }
+}
+
+impl Advise for [u8] {}
+impl Advise for Vec<u8> {}
+impl<T> Advise for std::io::Cursor<T> {}
+impl Advise for SparseMem {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file() {
+        let f = crate::testing::temp_file();
+        f.set_len(100).unwrap();
+        for a in [Advice::Sequential, Advice::Random, Advice::WillNeed, Advice::DontNeed, Advice::Normal] {
+            f.advise(0, 100, a).unwrap();
+        }
+        f.advise(1000, 0, Advice::WillNeed).unwrap();
+    }
+}
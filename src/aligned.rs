@@ -0,0 +1,288 @@
+// `is_multiple_of` is newer than the supported Rust version
+#![allow(clippy::manual_is_multiple_of)]
+
+use super::{read_up_to, Advice, Advise, Discard, Preallocate, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// Upper bound of bounce buffer size for a single call; larger requests are served partially
+const MAX_BOUNCE: u64 = 1 << 20;
+
+/// Turns arbitrary reads and writes into ones aligned to `block_size`, as required by `O_DIRECT` files and raw block devices.
+///
+/// Requests whose offset, length and buffer address are all multiples of `block_size` are passed through as is.
+/// Other ones go through an `AlignedBuf` bounce buffer covering whole blocks and aligned in memory to `block_size`.
+/// Unaligned writes read the partially covered first and last blocks, patch them and write whole blocks back.
+/// Writing past the end therefore extends the object to a multiple of `block_size`, padded with zeroes.
+///
+/// A single call handles at most 1 MiB (or one block, if larger) through the bounce buffer,
+/// so it may be short; use `read_exact_at` and `write_all_at` for larger requests.
+///
+/// Read-modify-write is not atomic: concurrent unaligned writes to the same block can lose data.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Aligned,ReadAt,WriteAt};
+///
+/// let dev = std::sync::Mutex::new(vec![1u8; 1024]);
+/// let a = Aligned::new(&dev, 512);
+/// a.write_all_at(&[7, 7], 511).unwrap();
+/// let mut v = [0u8; 3];
+/// a.read_exact_at(&mut v[..], 510).unwrap();
+/// assert_eq!(v, [1, 7, 7]);
+/// ```
+pub struct Aligned<T> {
+    inner: T,
+    block_size: usize,
+}
+
+impl<T> Aligned<T> {
+    /// Panics if `block_size` is zero.
+    pub fn new(inner: T, block_size: usize) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        Aligned { inner, block_size }
+    }
+
+    /// Block size specified at construction time
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn is_aligned(&self, addr: usize, len: usize, offset: u64) -> bool {
+        let bs = self.block_size;
+        addr % bs == 0 && len % bs == 0 && offset % bs as u64 == 0
+    }
+
+    /// Block-aligned range covering the start of `offset..offset+len`, limited to bounce buffer size
+    fn covering(&self, offset: u64, len: usize) -> Result<(u64, u64)> {
+        let bs = self.block_size as u64;
+        let start = offset / bs * bs;
+        let limit = MAX_BOUNCE.max(bs) / bs * bs;
+        let end = offset
+            .checked_add(len as u64)
+            .and_then(|e| e.min(start.saturating_add(limit)).checked_add(bs - 1))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "offset overflows u64"))?;
+        Ok((start, end / bs * bs))
+    }
+}
+
+/// Zero-initialized byte buffer with its start aligned in memory, e.g. for `O_DIRECT` I/O.
+///
+/// Dereferences to `[u8]` of the requested length. The length cannot be changed, as that could move the data.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::AlignedBuf;
+///
+/// let mut b = AlignedBuf::new(8192, 4096);
+/// assert_eq!(b.as_ptr() as usize % 4096, 0);
+/// b[..2].copy_from_slice(&[1, 2]);
+/// assert_eq!(b.len(), 8192);
+/// ```
+pub struct AlignedBuf {
+    /// Overallocated by up to `align - 1` bytes
+    storage: Vec<u8>,
+    skip: usize,
+    len: usize,
+    align: usize,
+}
+
+impl AlignedBuf {
+    /// Allocate `len` zero bytes starting at a multiple of `align`. Panics if `align` is zero.
+    pub fn new(len: usize, align: usize) -> Self {
+        assert!(align > 0, "align must be nonzero");
+        let storage = vec![0u8; len + align - 1];
+        let skip = (align - storage.as_ptr() as usize % align) % align;
+        AlignedBuf {
+            storage,
+            skip,
+            len,
+            align,
+        }
+    }
+
+    /// Alignment specified at construction time
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl std::ops::Deref for AlignedBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.storage[self.skip..self.skip + self.len]
+    }
+}
+
+impl std::ops::DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.storage[self.skip..self.skip + self.len]
+    }
+}
+
+impl Clone for AlignedBuf {
+    fn clone(&self) -> Self {
+        let mut b = AlignedBuf::new(self.len, self.align);
+        b.copy_from_slice(self);
+        b
+    }
+}
+
+impl std::fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+impl<T: ReadAt> ReadAt for Aligned<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() || self.is_aligned(buf.as_ptr() as usize, buf.len(), offset) {
+            return self.inner.read_at(buf, offset);
+        }
+        let (start, end) = self.covering(offset, buf.len())?;
+        let mut b = AlignedBuf::new((end - start) as usize, self.block_size);
+        let got = read_up_to(&self.inner, &mut b[..], start)?;
+        let within = (offset - start) as usize;
+        let n = got.saturating_sub(within).min(buf.len());
+        buf[..n].copy_from_slice(&b[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt + WriteAt> WriteAt for Aligned<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() || self.is_aligned(buf.as_ptr() as usize, buf.len(), offset) {
+            return self.inner.write_at(buf, offset);
+        }
+        let bs = self.block_size;
+        let (start, end) = self.covering(offset, buf.len())?;
+        let len = (end - start) as usize;
+        let mut b = AlignedBuf::new(len, bs);
+        let within = (offset - start) as usize;
+        let n = (len - within).min(buf.len());
+        if within != 0 {
+            read_up_to(&self.inner, &mut b[..bs], start)?;
+        }
+        let tail = within + n;
+        if tail % bs != 0 && (tail > bs || within == 0) {
+            let last = tail / bs * bs;
+            read_up_to(&self.inner, &mut b[last..last + bs], start + last as u64)?;
+        }
+        b[within..tail].copy_from_slice(&buf[..n]);
+        self.inner.write_all_at(&b[..], start)?;
+        Ok(n)
+    }
+}
+
+impl<T: SizeAt> SizeAt for Aligned<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+impl<T: SyncAt> SyncAt for Aligned<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.sync_range(offset, len)
+    }
+}
+
+impl<T: Advise> Advise for Aligned<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.inner.advise(offset, len, advice)
+    }
+}
+
+impl<T: Preallocate> Preallocate for Aligned<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.allocate(offset, len)
+    }
+}
+
+/// Forwarded as is: ranges need no alignment, as no buffers are involved.
+impl<T: Discard> Discard for Aligned<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.punch_hole(offset, len)
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.zero_range(offset, len)
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.discard(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Fails on any unaligned request
+    struct Strict(RefCell<Vec<u8>>);
+    fn check(addr: usize, len: usize, offset: u64) -> Result<()> {
+        if addr % 4 != 0 || len % 4 != 0 || offset % 4 != 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "unaligned"));
+        }
+        Ok(())
+    }
+    impl ReadAt for Strict {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            check(buf.as_ptr() as usize, buf.len(), offset)?;
+            self.0.read_at(buf, offset)
+        }
+    }
+    impl WriteAt for Strict {
+        fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+            check(buf.as_ptr() as usize, buf.len(), offset)?;
+            self.0.write_at(buf, offset)
+        }
+    }
+
+    #[test]
+    fn read_modify_write() {
+        let a = Aligned::new(Strict(RefCell::new((0..12).collect())), 4);
+        a.write_all_at(&[20, 21, 22, 23, 24, 25], 3).unwrap();
+        assert_eq!(*a.get_ref().0.borrow(), [0, 1, 2, 20, 21, 22, 23, 24, 25, 9, 10, 11]);
+        a.write_all_at(&[30], 1).unwrap();
+        a.write_all_at(&[40, 41], 11).unwrap();
+        assert_eq!(a.get_ref().0.borrow().len(), 16);
+
+        let mut v = [0u8; 7];
+        a.read_exact_at(&mut v[..], 9).unwrap();
+        assert_eq!(v, [9, 10, 40, 41, 0, 0, 0]);
+        assert_eq!(a.read_at(&mut v[..], 1).unwrap(), 7);
+        assert_eq!(v, [30, 2, 20, 21, 22, 23, 24]);
+        assert_eq!(a.read_at(&mut v[..], 14).unwrap(), 2);
+    }
+
+    #[test]
+    fn aligned_buf() {
+        for align in [1, 3, 512, 4096] {
+            let b = AlignedBuf::new(10, align);
+            assert_eq!(b.as_ptr() as usize % align, 0);
+            assert_eq!(b.clone()[..], [0; 10]);
+        }
+    }
+}
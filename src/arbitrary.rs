@@ -0,0 +1,129 @@
+//! `Arbitrary` implementations for `proptest` and `quickcheck`
+
+use super::testing::Op;
+use super::{SparseMem, WriteAtMut};
+
+/// Object of `len` bytes with `data` written at `offset` and zeros elsewhere
+fn sparse(data: &[u8], offset: u64, len: u64) -> SparseMem {
+    let mut m = SparseMem::with_len(len);
+    m.write_all_at(data, offset).expect("writes to SparseMem do not fail");
+    m
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_impls {
+    use super::super::testing::Config;
+    use super::*;
+    use proptest::arbitrary::{any, Arbitrary};
+    use proptest::collection::vec;
+    use proptest::strategy::{BoxedStrategy, Just, Strategy};
+
+    /// Uses `max_size` and `max_len` of `Config` to bound offsets and lengths
+    impl Arbitrary for Op {
+        type Parameters = Config;
+        type Strategy = BoxedStrategy<Op>;
+
+        fn arbitrary_with(config: Config) -> Self::Strategy {
+            let (max_size, max_len) = (config.max_size.max(1), config.max_len.max(1));
+            proptest::prop_oneof![
+                7 => (0..=max_size, 0..=max_len).prop_map(|(offset, len)| Op::Read { offset, len }),
+                7 => (0..max_size, vec(any::<u8>(), 1..=max_len)).prop_map(|(offset, data)| Op::Write { offset, data }),
+                2 => (0..=max_size).prop_map(Op::SetLen),
+                2 => Just(Op::Size),
+                2 => Just(Op::Flush),
+            ]
+            .boxed()
+        }
+    }
+
+    /// Up to 64 KiB with a run of up to 4 KiB of random bytes somewhere in it
+    impl Arbitrary for SparseMem {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<SparseMem>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            (vec(any::<u8>(), 0..4096), 0..61440u64, 0..4096u64)
+                .prop_map(|(data, offset, tail)| sparse(&data, offset, offset + data.len() as u64 + tail))
+                .boxed()
+        }
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+mod quickcheck_impls {
+    use super::*;
+    use quickcheck::{Arbitrary, Gen};
+
+    const MAX_SIZE: u64 = 65536;
+
+    /// Offsets below 64 KiB, lengths bounded by the size of `Gen`
+    impl Arbitrary for Op {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let offset = u64::arbitrary(g) % MAX_SIZE;
+            let len = usize::arbitrary(g) % (g.size() + 1);
+            match u8::arbitrary(g) % 10 {
+                0..=3 => Op::Read { offset, len },
+                4..=7 => {
+                    let mut data = Vec::<u8>::arbitrary(g);
+                    if data.is_empty() {
+                        data.push(u8::arbitrary(g));
+                    }
+                    Op::Write { offset, data }
+                }
+                8 => Op::SetLen(offset),
+                _ => g.choose(&[Op::Size, Op::Flush]).unwrap().clone(),
+            }
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Op>> {
+            match self.clone() {
+                Op::Write { offset, data } => Box::new(
+                    data.shrink().filter(|x| !x.is_empty()).map(move |data| Op::Write { offset, data }),
+                ),
+                Op::Read { offset, len } => Box::new(len.shrink().map(move |len| Op::Read { offset, len })),
+                _ => quickcheck::empty_shrinker(),
+            }
+        }
+    }
+
+    /// Up to 64 KiB with a run of random bytes somewhere in it
+    impl Arbitrary for SparseMem {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let data = Vec::<u8>::arbitrary(g);
+            let offset = u64::arbitrary(g) % (MAX_SIZE - data.len().min(4096) as u64);
+            let tail = u64::arbitrary(g) % 4096;
+            sparse(&data, offset, offset + data.len() as u64 + tail)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::check_ops;
+    use super::*;
+
+    #[cfg(feature = "proptest")]
+    #[test]
+    fn proptest_sparse_mem() {
+        use proptest::arbitrary::any;
+        use proptest::collection::vec;
+        use proptest::test_runner::TestRunner;
+
+        let strategy = (any::<SparseMem>(), vec(any::<Op>(), 0..50));
+        TestRunner::default()
+            .run(&strategy, |(mut m, ops)| {
+                check_ops(&mut m, &ops).unwrap();
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[cfg(feature = "quickcheck")]
+    #[test]
+    fn quickcheck_sparse_mem() {
+        fn prop(mut m: SparseMem, ops: Vec<Op>) -> bool {
+            check_ops(&mut m, &ops).is_ok()
+        }
+        quickcheck::QuickCheck::new().tests(50).quickcheck(prop as fn(SparseMem, Vec<Op>) -> bool);
+    }
+}
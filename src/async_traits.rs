@@ -0,0 +1,406 @@
+use super::DerefWrapper;
+use futures_util::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+use std::pin::Pin;
+
+/// Future type returned by methods of async traits, boxed to keep the traits usable as trait objects.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Async version of `ReadAt`.
+///
+/// Requires `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{AsyncReadAt,BoxFuture};
+///
+/// struct Zeroes;
+/// impl AsyncReadAt for Zeroes {
+///     fn read_at<'a>(&'a self, buf: &'a mut [u8], _offset: u64) -> BoxFuture<'a, std::io::Result<usize>> {
+///         Box::pin(async move {
+///             for b in buf.iter_mut() { *b = 0; }
+///             Ok(buf.len())
+///         })
+///     }
+/// }
+///
+/// async fn use_it(r: &dyn AsyncReadAt) -> std::io::Result<[u8; 4]> {
+///     let mut v = [1u8; 4];
+///     r.read_exact_at(&mut v[..], 100).await?;
+///     Ok(v)
+/// }
+/// # let _ = use_it(&Zeroes);
+/// ```
+pub trait AsyncReadAt: Sync {
+    /// Async version of `ReadAt::read_at`
+    fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>>;
+
+    /// Async version of `ReadAt::read_exact_at`
+    fn read_exact_at<'a>(&'a self, mut buf: &'a mut [u8], mut offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            while !buf.is_empty() {
+                match self.read_at(buf, offset).await {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        let tmp = buf;
+                        buf = &mut tmp[n..];
+                        offset += n as u64;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if !buf.is_empty() {
+                Err(Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer"))
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+/// Async version of `ReadAtMut`.
+///
+/// Requires `async` feature.
+pub trait AsyncReadAtMut: Send {
+    /// Async version of `ReadAtMut::read_at`
+    fn read_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>>;
+
+    /// Async version of `ReadAtMut::read_exact_at`
+    fn read_exact_at<'a>(&'a mut self, mut buf: &'a mut [u8], mut offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            while !buf.is_empty() {
+                match self.read_at(buf, offset).await {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        let tmp = buf;
+                        buf = &mut tmp[n..];
+                        offset += n as u64;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if !buf.is_empty() {
+                Err(Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer"))
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+impl<T: AsyncReadAt + Send + ?Sized> AsyncReadAtMut for T {
+    fn read_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        AsyncReadAt::read_at(&*self, buf, offset)
+    }
+    fn read_exact_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        AsyncReadAt::read_exact_at(&*self, buf, offset)
+    }
+}
+
+/// Async version of `WriteAt`.
+///
+/// Requires `async` feature.
+pub trait AsyncWriteAt: Sync {
+    /// Async version of `WriteAt::write_at`
+    fn write_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>>;
+
+    /// Async version of `WriteAt::write_all_at`
+    fn write_all_at<'a>(&'a self, mut buf: &'a [u8], mut offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            while !buf.is_empty() {
+                match self.write_at(buf, offset).await {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ));
+                    }
+                    Ok(n) => {
+                        buf = &buf[n..];
+                        offset += n as u64
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Async version of `WriteAtMut`.
+///
+/// Requires `async` feature.
+pub trait AsyncWriteAtMut: Send {
+    /// Async version of `WriteAtMut::write_at`
+    fn write_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>>;
+
+    /// Async version of `WriteAtMut::write_all_at`
+    fn write_all_at<'a>(&'a mut self, mut buf: &'a [u8], mut offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            while !buf.is_empty() {
+                match self.write_at(buf, offset).await {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ));
+                    }
+                    Ok(n) => {
+                        buf = &buf[n..];
+                        offset += n as u64
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<T: AsyncWriteAt + Send + ?Sized> AsyncWriteAtMut for T {
+    fn write_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        AsyncWriteAt::write_at(&*self, buf, offset)
+    }
+    fn write_all_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        AsyncWriteAt::write_all_at(&*self, buf, offset)
+    }
+}
+
+/// A combined AsyncReadAt and AsyncWriteAt for trait objects.
+///
+/// Requires `async` feature.
+pub trait AsyncReadWriteAt: AsyncReadAt + AsyncWriteAt {}
+impl<T: AsyncReadAt + AsyncWriteAt + ?Sized> AsyncReadWriteAt for T {}
+
+/// A combined AsyncReadAtMut and AsyncWriteAtMut for trait objects.
+///
+/// Requires `async` feature.
+pub trait AsyncReadWriteAtMut: AsyncReadAtMut + AsyncWriteAtMut {}
+impl<T: AsyncReadAtMut + AsyncWriteAtMut + ?Sized> AsyncReadWriteAtMut for T {}
+
+impl<T: AsyncReadAt + ?Sized> AsyncReadAt for &T {
+    fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        AsyncReadAt::read_at(*self, buf, offset)
+    }
+    fn read_exact_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        AsyncReadAt::read_exact_at(*self, buf, offset)
+    }
+}
+
+impl<T: AsyncWriteAt + ?Sized> AsyncWriteAt for &T {
+    fn write_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        AsyncWriteAt::write_at(*self, buf, offset)
+    }
+    fn write_all_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        AsyncWriteAt::write_all_at(*self, buf, offset)
+    }
+}
+
+impl<U> AsyncReadAtMut for DerefWrapper<U>
+where U: std::ops::DerefMut + Send, U::Target: AsyncReadAtMut
+{
+    fn read_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        AsyncReadAtMut::read_at(std::ops::DerefMut::deref_mut(&mut self.0), buf, offset)
+    }
+    fn read_exact_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        AsyncReadAtMut::read_exact_at(std::ops::DerefMut::deref_mut(&mut self.0), buf, offset)
+    }
+}
+
+impl<U> AsyncWriteAtMut for DerefWrapper<U>
+where U: std::ops::DerefMut + Send, U::Target: AsyncWriteAtMut
+{
+    fn write_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        AsyncWriteAtMut::write_at(std::ops::DerefMut::deref_mut(&mut self.0), buf, offset)
+    }
+    fn write_all_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        AsyncWriteAtMut::write_all_at(std::ops::DerefMut::deref_mut(&mut self.0), buf, offset)
+    }
+}
+
+/// The lock is held until the returned future completes.
+impl<T> AsyncReadAt for futures_util::lock::Mutex<T>
+where T:AsyncReadAtMut+?Sized
+{
+    fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let mut se = self.lock().await;
+            AsyncReadAtMut::read_at(&mut *se, buf, offset).await
+        })
+    }
+    fn read_exact_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut se = self.lock().await;
+            AsyncReadAtMut::read_exact_at(&mut *se, buf, offset).await
+        })
+    }
+}
+
+/// The lock is held until the returned future completes.
+impl<T> AsyncWriteAt for futures_util::lock::Mutex<T>
+where T:AsyncWriteAtMut+?Sized
+{
+    fn write_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let mut se = self.lock().await;
+            AsyncWriteAtMut::write_at(&mut *se, buf, offset).await
+        })
+    }
+    fn write_all_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut se = self.lock().await;
+            AsyncWriteAtMut::write_all_at(&mut *se, buf, offset).await
+        })
+    }
+}
+
+/// Async version of `ReadWriteSeek`: seeks and then reads or writes a `futures::io` object
+/// for each call of `read_at` or `write_at`.
+///
+/// Requires `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{AsyncReadWriteSeek,AsyncReadAtMut};
+///
+/// async fn middle() -> std::io::Result<[u8; 2]> {
+///     let c = futures_util::io::Cursor::new(vec![3u8, 4, 5, 6]);
+///     let mut rws = AsyncReadWriteSeek(c);
+///     let mut v = [0u8; 2];
+///     rws.read_exact_at(&mut v[..], 1).await?;
+///     Ok(v)
+/// }
+/// # let _ = middle();
+/// ```
+pub struct AsyncReadWriteSeek<T: AsyncSeek>(pub T);
+
+async fn seek_to<T: AsyncSeek + Unpin>(t: &mut T, offset: u64) -> Result<()> {
+    let o = t.seek(SeekFrom::Start(offset)).await?;
+    if o != offset {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "seek hasn't returned the required offset",
+        ));
+    }
+    Ok(())
+}
+
+impl<T: AsyncRead + AsyncSeek + Unpin + Send> AsyncReadAtMut for AsyncReadWriteSeek<T> {
+    fn read_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            seek_to(&mut self.0, offset).await?;
+            self.0.read(buf).await
+        })
+    }
+    fn read_exact_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            seek_to(&mut self.0, offset).await?;
+            self.0.read_exact(buf).await
+        })
+    }
+}
+
+impl<T: AsyncWrite + AsyncSeek + Unpin + Send> AsyncWriteAtMut for AsyncReadWriteSeek<T> {
+    fn write_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            seek_to(&mut self.0, offset).await?;
+            self.0.write(buf).await
+        })
+    }
+    fn write_all_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            seek_to(&mut self.0, offset).await?;
+            self.0.write_all(buf).await
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use futures_util::lock::Mutex;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct ThreadWaker(std::thread::Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor for tests
+    pub(crate) fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = Box::pin(f);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(x) => return x,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    /// Serves at most one byte per call, yielding once before completion
+    struct Slow(Vec<u8>);
+    impl AsyncReadAtMut for Slow {
+        fn read_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+            Box::pin(async move {
+                let mut yielded = false;
+                std::future::poll_fn(|cx| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                }).await;
+                let n = buf.len().min(1);
+                crate::ReadAt::read_at(&self.0, &mut buf[..n], offset)
+            })
+        }
+    }
+    impl AsyncWriteAtMut for Slow {
+        fn write_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+            Box::pin(async move { crate::WriteAtMut::write_at(&mut self.0, &buf[..buf.len().min(1)], offset) })
+        }
+    }
+
+    #[test]
+    fn mutex_wrapping_works() {
+        let obj: Box<dyn AsyncReadWriteAtMut> = Box::new(Slow(vec![1, 2, 3, 4]));
+        let m = Mutex::new(DerefWrapper(obj));
+        let shared: &dyn AsyncReadWriteAt = &m;
+
+        block_on(async {
+            shared.write_all_at(&[8, 9], 2).await.unwrap();
+            let mut v = [0u8; 4];
+            shared.read_exact_at(&mut v[..], 0).await.unwrap();
+            assert_eq!(v, [1, 2, 8, 9]);
+            let e = shared.read_exact_at(&mut v[..], 2).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        });
+    }
+
+    #[test]
+    fn read_write_seek() {
+        let mut rws = AsyncReadWriteSeek(futures_util::io::Cursor::new(vec![1u8, 2, 3]));
+        block_on(async {
+            rws.write_all_at(&[7, 8], 2).await.unwrap();
+            let mut v = [0u8; 3];
+            rws.read_exact_at(&mut v[..], 1).await.unwrap();
+            assert_eq!(v, [2, 7, 8]);
+            assert_eq!(rws.read_at(&mut v[..], 3).await.unwrap(), 1);
+        });
+        assert_eq!(rws.0.into_inner(), vec![1, 2, 7, 8]);
+    }
+}
@@ -0,0 +1,222 @@
+use super::block_cache::BlockCache;
+use super::{ReadAt, SizeAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// Fixed part of a gzip member header, up to `XLEN`
+const HEADER_LEN: usize = 12;
+/// CRC32 and ISIZE
+const TRAILER_LEN: u64 = 8;
+const DEFAULT_CACHE: usize = 16;
+
+/// Position of a BGZF block in the file and in the decompressed data
+struct Block {
+    offset: u64,
+    /// Compressed size, including header and trailer
+    size: u32,
+    /// Range of deflate data within the block
+    data: (u32, u32),
+    start: u64,
+    len: u32,
+}
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// `BSIZE` of the `BC` subfield in gzip extra field `extra`, if there is one
+fn bsize(extra: &[u8]) -> Option<u16> {
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        if rest[..2] == *b"BC" && len == 2 && rest.len() >= 6 {
+            return Some(u16::from_le_bytes([rest[4], rest[5]]));
+        }
+        rest = rest.get(4 + len..)?;
+    }
+    None
+}
+
+/// Random access to the decompressed content of a BGZF file, like `.bam` or `.vcf.gz` compressed with `bgzip`.
+///
+/// BGZF is gzip made of independent members of at most 64 KiB, each telling its own compressed size.
+/// `open` walks the headers of all blocks to build an index, reading only headers and trailers.
+/// Each read decompresses whole blocks; the most recently used ones (16 by default) are cached.
+/// The CRC32 of decompressed blocks is not verified, only their sizes.
+///
+/// Indexes like BAI and tabix use virtual offsets (compressed offset of a block shifted left by 16 bits,
+/// plus an offset within the decompressed block), which `offset_of_virtual` and `virtual_offset` convert.
+///
+/// A single call returns data from at most one block, so it may be short; use `read_exact_at` for larger requests.
+///
+/// Requires `bgzf` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{BgzfReader,ReadAt};
+///
+/// let r = BgzfReader::open(std::fs::File::open("reads.bam")?)?;
+/// let mut magic = [0u8; 4];
+/// r.read_exact_at(&mut magic[..], 0)?;
+/// assert_eq!(&magic, b"BAM\x01");
+///
+/// // Jump to a record found in a BAI index
+/// let offset = r.offset_of_virtual(0x1_2345_0012).expect("offset is in the file");
+/// r.read_exact_at(&mut magic[..], offset)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct BgzfReader<T> {
+    inner: T,
+    blocks: Vec<Block>,
+    cache: BlockCache,
+}
+
+impl<T: ReadAt + SizeAt> BgzfReader<T> {
+    /// Index blocks of `inner`. Fails if it is not BGZF, i.e. a gzip member has no `BC` subfield.
+    pub fn open(inner: T) -> Result<Self> {
+        let size = inner.size()?;
+        let mut blocks = vec![];
+        let (mut offset, mut start) = (0u64, 0u64);
+        let mut extra = vec![];
+        while offset < size {
+            let mut h = [0u8; HEADER_LEN];
+            inner.read_exact_at(&mut h, offset)?;
+            if h[..4] != [31, 139, 8, 4] {
+                return Err(invalid("not a BGZF block"));
+            }
+            let xlen = u16::from_le_bytes([h[10], h[11]]) as usize;
+            extra.resize(xlen, 0);
+            inner.read_exact_at(&mut extra, offset + HEADER_LEN as u64)?;
+            let block_size = bsize(&extra).ok_or_else(|| invalid("gzip member without BGZF block size"))? as u32 + 1;
+            let data = ((HEADER_LEN + xlen) as u32, block_size.saturating_sub(TRAILER_LEN as u32));
+            if data.0 > data.1 || offset + block_size as u64 > size {
+                return Err(invalid("malformed BGZF block size"));
+            }
+            let mut isize = [0u8; 4];
+            inner.read_exact_at(&mut isize, offset + block_size as u64 - 4)?;
+            let len = u32::from_le_bytes(isize);
+            blocks.push(Block { offset, size: block_size, data, start, len });
+            offset += block_size as u64;
+            start += len as u64;
+        }
+        Ok(BgzfReader { inner, blocks, cache: BlockCache::new(DEFAULT_CACHE) })
+    }
+}
+
+impl<T> BgzfReader<T> {
+    /// Number of decompressed blocks to keep in memory
+    pub fn cache_blocks(mut self, blocks: usize) -> Self {
+        self.cache.set_capacity(blocks);
+        self
+    }
+
+    /// Number of blocks in the file, including the empty end-of-file marker block
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Decompressed offset pointed to by virtual offset `voffset`, if it is valid for this file
+    pub fn offset_of_virtual(&self, voffset: u64) -> Option<u64> {
+        let (coffset, within) = (voffset >> 16, voffset & 0xffff);
+        let i = self.blocks.binary_search_by_key(&coffset, |b| b.offset).ok()?;
+        let b = &self.blocks[i];
+        (within <= b.len as u64).then(|| b.start + within)
+    }
+
+    /// Virtual offset of decompressed offset `offset`, if it is within the file
+    pub fn virtual_offset(&self, offset: u64) -> Option<u64> {
+        let b = self.blocks.get(self.block_of(offset))?;
+        Some(b.offset << 16 | (offset - b.start))
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Index of the block containing `offset`, or the number of blocks if none
+    fn block_of(&self, offset: u64) -> usize {
+        self.blocks.partition_point(|b| b.start + b.len as u64 <= offset)
+    }
+}
+
+impl<T: ReadAt> ReadAt for BgzfReader<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let i = self.block_of(offset);
+        let b = match self.blocks.get(i) {
+            Some(b) if !buf.is_empty() => b,
+            _ => return Ok(0),
+        };
+        let data = self.cache.get(i as u64, || {
+            let mut compressed = vec![0u8; b.size as usize];
+            self.inner.read_exact_at(&mut compressed, b.offset)?;
+            let deflated = &compressed[b.data.0 as usize..b.data.1 as usize];
+            let data = miniz_oxide::inflate::decompress_to_vec_with_limit(deflated, b.len as usize)
+                .map_err(|_| invalid("corrupted BGZF block"))?;
+            if data.len() != b.len as usize {
+                return Err(invalid("BGZF block size differs from its trailer"));
+            }
+            Ok(data)
+        })?;
+        let within = (offset - b.start) as usize;
+        let n = buf.len().min(data.len() - within);
+        buf[..n].copy_from_slice(&data[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl<T> SizeAt for BgzfReader<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.blocks.last().map_or(0, |b| b.start + b.len as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BGZF block with `data`, with a zero CRC
+    fn block(data: &[u8]) -> Vec<u8> {
+        let deflated = miniz_oxide::deflate::compress_to_vec(data, 6);
+        let mut b = vec![31, 139, 8, 4, 0, 0, 0, 0, 0, 255, 6, 0, b'B', b'C', 2, 0];
+        let bsize = (b.len() + 2 + deflated.len() + 8 - 1) as u16;
+        b.extend_from_slice(&bsize.to_le_bytes());
+        b.extend_from_slice(&deflated);
+        b.extend_from_slice(&[0; 4]);
+        b.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        b
+    }
+
+    #[test]
+    fn blocks_and_virtual_offsets() {
+        let data: Vec<u8> = (0..150_000u32).map(|x| (x % 253) as u8).collect();
+        let mut file = vec![];
+        for chunk in data.chunks(60_000) {
+            file.extend_from_slice(&block(chunk));
+        }
+        let second = file.len() as u64;
+        file.extend_from_slice(&block(&[]));
+        let r = BgzfReader::open(&file).unwrap().cache_blocks(2);
+        assert_eq!((r.blocks(), r.size().unwrap()), (4, 150_000));
+
+        let mut v = vec![0u8; 150_000];
+        r.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v, data);
+        assert_eq!(r.read_at(&mut v, 59_990).unwrap(), 10);
+        assert_eq!(r.read_at(&mut v, 150_000).unwrap(), 0);
+
+        let vo = r.virtual_offset(125_000).unwrap();
+        assert_eq!(vo & 0xffff, 5000);
+        assert_eq!(r.offset_of_virtual(vo), Some(125_000));
+        assert_eq!(r.offset_of_virtual(second << 16), Some(150_000));
+        assert_eq!(r.offset_of_virtual(5 << 16), None);
+
+        file[12] = b'X';
+        assert!(BgzfReader::open(&file).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use super::{ReadAt, CursorAt};
+use binrw::{BinRead, BinResult, Endian};
+use std::io::{Seek, SeekFrom};
+
+/// Parsing of `binrw` structures at offsets of a `ReadAt` object, without mutable access to it.
+///
+/// Each call parses from a fresh `CursorAt` positioned at `offset`. Positions seen by the parser
+/// (e.g. targets of `FilePtr` or `#[br(seek_before)]`) are absolute offsets in the object,
+/// but seeking relative to the end is not supported.
+///
+/// Requires `binrw` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,ReadBinAt};
+/// use binrw::Endian;
+///
+/// let data: &dyn ReadAt = &&b"..\x01\x00\x02\x00"[..];
+/// let v: [u16; 2] = data.read_bin_at(2, Endian::Little).unwrap();
+/// assert_eq!(v, [1, 2]);
+/// ```
+pub trait ReadBinAt: ReadAt {
+    /// Parse `T` stored at `offset`, with default arguments
+    fn read_bin_at<T: BinRead>(&self, offset: u64, endian: Endian) -> BinResult<T>
+    where
+        for<'a> T::Args<'a>: Default,
+    {
+        self.read_bin_args_at(offset, endian, Default::default())
+    }
+
+    /// Parse `T` stored at `offset`, with arguments `args`
+    fn read_bin_args_at<T: BinRead>(&self, offset: u64, endian: Endian, args: T::Args<'_>) -> BinResult<T> {
+        let mut cursor = CursorAt::new(self);
+        cursor.seek(SeekFrom::Start(offset))?;
+        T::read_options(&mut cursor, endian, args)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadBinAt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // Used by the `count` code generated by `binread`
+    use std::convert::TryFrom;
+
+    #[binrw::binread]
+    #[br(big)]
+    struct Record {
+        len: u8,
+        #[br(count = len)]
+        name: Vec<u8>,
+        #[br(seek_before = SeekFrom::Start(0))]
+        first: u16,
+    }
+
+    #[test]
+    fn absolute_positions() {
+        let data = b"xyz\x02hi".to_vec();
+        let r: Record = data.read_bin_at(3, Endian::Little).unwrap();
+        assert_eq!(r.len, 2);
+        assert_eq!(r.name, b"hi");
+        assert_eq!(r.first, u16::from_be_bytes(*b"xy"));
+        assert!(data.read_bin_at::<u32>(4, Endian::Big).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+// Block device ioctls not wrapped by `rustix` are raw calls
+#![allow(unsafe_code)]
+
+use rustix::ioctl::{ioctl, opcode};
+use std::fs::File;
+use std::io::Result;
+
+pub(crate) fn is_block_device(f: &File) -> Result<bool> {
+    use std::os::unix::fs::FileTypeExt;
+    Ok(f.metadata()?.file_type().is_block_device())
+}
+
+/// `BLKGETSIZE64`: size of the device in bytes
+pub(crate) fn size(f: &File) -> Result<u64> {
+    // SAFETY: `BLKGETSIZE64` writes a `u64`; the request number is encoded with `size_t`, as in the kernel headers
+    Ok(unsafe { ioctl(f, rustix::ioctl::Getter::<{ opcode::read::<usize>(0x12, 114) }, u64>::new())? })
+}
+
+/// `BLKDISCARD`: tell the device that `offset..offset+len` is unused
+#[cfg(feature = "discard")]
+pub(crate) fn discard(f: &File, offset: u64, len: u64) -> Result<()> {
+    // SAFETY: `BLKDISCARD` takes a pointer to a `[start, length]` pair of `u64`s and only reads it
+    unsafe { ioctl(f, rustix::ioctl::Setter::<{ opcode::none(0x12, 119) }, [u64; 2]>::new([offset, len]))? };
+    Ok(())
+}
+
+/// `BLKZEROOUT`: write zeroes to `offset..offset+len`, offloaded to the device if it can
+#[cfg(feature = "discard")]
+pub(crate) fn zero_out(f: &File, offset: u64, len: u64) -> Result<()> {
+    // SAFETY: `BLKZEROOUT` takes a pointer to a `[start, length]` pair of `u64`s and only reads it
+    unsafe { ioctl(f, rustix::ioctl::Setter::<{ opcode::none(0x12, 127) }, [u64; 2]>::new([offset, len]))? };
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Small cache of decompressed blocks shared by the archive readers
+
+use std::collections::VecDeque;
+use std::io::Result;
+use std::sync::{Arc, Mutex};
+
+/// Keeps the `capacity` most recently used blocks, by key
+pub(crate) struct BlockCache {
+    capacity: usize,
+    /// Most recently used first
+    blocks: Mutex<VecDeque<(u64, Arc<Vec<u8>>)>>,
+}
+
+impl BlockCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        BlockCache { capacity, blocks: Mutex::new(VecDeque::new()) }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.blocks.get_mut().unwrap_or_else(|e| e.into_inner()).truncate(capacity);
+    }
+
+    /// Cached block `key`, or the result of `load` if there is none.
+    /// The lock is not held while loading, so concurrent misses of the same block may load it twice.
+    pub(crate) fn get(&self, key: u64, load: impl FnOnce() -> Result<Vec<u8>>) -> Result<Arc<Vec<u8>>> {
+        {
+            let mut blocks = self.blocks.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(i) = blocks.iter().position(|(k, _)| *k == key) {
+                let entry = blocks.remove(i).expect("index is in range");
+                let block = entry.1.clone();
+                blocks.push_front(entry);
+                return Ok(block);
+            }
+        }
+        let block = Arc::new(load()?);
+        if self.capacity > 0 {
+            let mut blocks = self.blocks.lock().unwrap_or_else(|e| e.into_inner());
+            blocks.retain(|(k, _)| *k != key);
+            blocks.push_front((key, block.clone()));
+            blocks.truncate(self.capacity);
+        }
+        Ok(block)
+    }
+}
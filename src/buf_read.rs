@@ -0,0 +1,236 @@
+use super::{read_up_to, Advice, Advise, Discard, DiscardMut, Preallocate, PreallocateMut, ReadAt, ReadAtMut, ResizeAt, ResizeAtMut, SizeAt};
+use std::io::Result;
+
+/// Block cache for expensive `ReadAt` objects.
+///
+/// On a miss, the whole aligned `block_size`-byte block covering the requested offset is read
+/// from the wrapped object and stored; reads are then served from cached blocks.
+/// At most `max_blocks` blocks are kept, least recently used blocks are evicted first.
+///
+/// A `read_at` call spanning several blocks is served from consecutive blocks until the buffer is full or EOF is reached.
+///
+/// The cache is changed on reads, so only `ReadAtMut` is implemented. Wrap it in `RefCell` or `Mutex` to get `ReadAt`.
+///
+/// If the wrapped object gets modified (e.g. by other handle), call `invalidate_range` to avoid stale reads.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{BufReadAt,ReadAtMut};
+///
+/// let data: Vec<u8> = (0..=255).collect();
+/// let mut c = BufReadAt::new(&data, 64, 4);
+/// let mut v = [0u8; 2];
+/// c.read_exact_at(&mut v[..], 100).unwrap();
+/// assert_eq!(v, [100, 101]);
+/// ```
+pub struct BufReadAt<T: ReadAt> {
+    inner: T,
+    /// `(block start offset, data)`; data is shorter than `block_size` only at EOF. Most recently used is at the end.
+    cache: Vec<(u64, Box<[u8]>)>,
+    block_size: usize,
+    max_blocks: usize,
+}
+
+impl<T: ReadAt> BufReadAt<T> {
+    /// Create an empty cache. Panics if `block_size` is zero.
+    pub fn new(inner: T, block_size: usize, max_blocks: usize) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        BufReadAt {
+            inner,
+            cache: Vec::with_capacity(max_blocks),
+            block_size,
+            max_blocks,
+        }
+    }
+
+    /// Evict all cached blocks overlapping `offset..offset+len`
+    pub fn invalidate_range(&mut self, offset: u64, len: u64) {
+        let end = offset.saturating_add(len);
+        let bs = self.block_size as u64;
+        self.cache
+            .retain(|&(start, _)| !(start < end && start.saturating_add(bs) > offset));
+    }
+
+    /// Evict all cached blocks
+    pub fn invalidate_all(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Block size specified at construction time
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Maximum number of cached blocks specified at construction time
+    pub fn max_blocks(&self) -> usize {
+        self.max_blocks
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object, dropping the cache
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Find or fetch the block starting at `start`, moving it to most recently used position
+    fn block(&mut self, start: u64) -> Result<&[u8]> {
+        if let Some(i) = self.cache.iter().position(|&(s, _)| s == start) {
+            let entry = self.cache.remove(i);
+            self.cache.push(entry);
+        } else {
+            let mut data = vec![0; self.block_size];
+            let n = read_up_to(&self.inner, &mut data[..], start)?;
+            data.truncate(n);
+            if self.cache.len() >= self.max_blocks {
+                self.cache.remove(0);
+            }
+            self.cache.push((start, data.into_boxed_slice()));
+        }
+        Ok(&self.cache[self.cache.len() - 1].1)
+    }
+}
+
+impl<T: ReadAt> ReadAtMut for BufReadAt<T> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if self.max_blocks == 0 {
+            return self.inner.read_at(buf, offset);
+        }
+        let bs = self.block_size as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = match offset.checked_add(done as u64) {
+                Some(x) => x,
+                None => break,
+            };
+            let start = pos / bs * bs;
+            let data = match self.block(start) {
+                Ok(x) => x,
+                Err(e) if done == 0 => return Err(e),
+                Err(_) => break,
+            };
+            let skip = (pos - start) as usize;
+            if skip >= data.len() {
+                break;
+            }
+            let n = (data.len() - skip).min(buf.len() - done);
+            let short_block = (data.len() as u64) < bs;
+            buf[done..done + n].copy_from_slice(&data[skip..skip + n]);
+            done += n;
+            if short_block {
+                break;
+            }
+        }
+        Ok(done)
+    }
+}
+
+impl<T: ReadAt + SizeAt> SizeAt for BufReadAt<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+/// Resizes the wrapped object, dropping all cached blocks.
+impl<T: ReadAt + ResizeAt> ResizeAtMut for BufReadAt<T> {
+    fn set_len(&mut self, new_len: u64) -> Result<()> {
+        self.invalidate_all();
+        self.inner.set_len(new_len)
+    }
+}
+
+impl<T: ReadAt + Advise> Advise for BufReadAt<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.inner.advise(offset, len, advice)
+    }
+}
+
+/// Cached blocks overlapping the range are evicted, then the call is forwarded.
+impl<T: ReadAt + Preallocate> PreallocateMut for BufReadAt<T> {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate_range(offset, len);
+        self.inner.allocate(offset, len)
+    }
+}
+
+/// Cached blocks overlapping the range are evicted, then the call is forwarded.
+impl<T: ReadAt + Discard> DiscardMut for BufReadAt<T> {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate_range(offset, len);
+        self.inner.punch_hole(offset, len)
+    }
+    fn zero_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate_range(offset, len);
+        self.inner.zero_range(offset, len)
+    }
+    fn discard(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate_range(offset, len);
+        self.inner.discard(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct Counting<'a>(&'a [u8], Cell<usize>);
+    impl<'a> ReadAt for Counting<'a> {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            self.1.set(self.1.get() + 1);
+            self.0.read_at(buf, offset)
+        }
+    }
+
+    #[test]
+    fn caching() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut c = BufReadAt::new(Counting(&data, Cell::new(0)), 16, 2);
+        let mut v = [0u8; 4];
+
+        c.read_exact_at(&mut v[..], 18).unwrap();
+        assert_eq!(v, [18, 19, 20, 21]);
+        assert_eq!(c.get_ref().1.get(), 1);
+        c.read_exact_at(&mut v[..], 20).unwrap();
+        assert_eq!(v, [20, 21, 22, 23]);
+        assert_eq!(c.get_ref().1.get(), 1);
+
+        // crossing block boundary and EOF
+        c.read_exact_at(&mut v[..], 30).unwrap();
+        assert_eq!(v, [30, 31, 32, 33]);
+        assert_eq!(c.get_ref().1.get(), 2);
+        assert_eq!(c.read_at(&mut v[..], 98).unwrap(), 2);
+        assert_eq!(c.read_at(&mut v[..], 100).unwrap(), 0);
+
+        // block at 16 was evicted by the block at 96
+        c.read_exact_at(&mut v[..], 16).unwrap();
+        assert_eq!(c.get_ref().1.get(), 5);
+
+        // one call spans several blocks
+        let mut big = [0u8; 40];
+        assert_eq!(c.read_at(&mut big[..], 10).unwrap(), 40);
+        assert_eq!(big[39], 49);
+    }
+
+    #[test]
+    fn invalidation() {
+        let data = std::cell::RefCell::new(vec![0u8; 64]);
+        let mut c = BufReadAt::new(&data, 16, 4);
+        let mut v = [0u8; 2];
+        c.read_exact_at(&mut v[..], 16).unwrap();
+        crate::WriteAt::write_all_at(&data, &[5, 5], 16).unwrap();
+
+        c.read_exact_at(&mut v[..], 16).unwrap();
+        assert_eq!(v, [0, 0]);
+        c.invalidate_range(0, 16);
+        c.read_exact_at(&mut v[..], 16).unwrap();
+        assert_eq!(v, [0, 0]);
+        c.invalidate_range(31, 1);
+        c.read_exact_at(&mut v[..], 16).unwrap();
+        assert_eq!(v, [5, 5]);
+    }
+}
@@ -0,0 +1,260 @@
+use super::{Advice, Advise, DiscardMut, PreallocateMut, ReadAtMut, SizeAt, SyncAtMut, WriteAtMut};
+use std::collections::BTreeMap;
+use std::io::Result;
+
+/// Write coalescing buffer for objects where many small writes are expensive.
+///
+/// Written data is kept in memory as runs of contiguous bytes; a write overlapping or adjacent to
+/// buffered runs is merged into them, even if it comes before them, so out-of-order records coalesce as well.
+/// When buffered data reaches `capacity` bytes, all runs are written to the wrapped object in offset order.
+/// Writes of at least `capacity` bytes bypass the buffer (after flushing it).
+///
+/// Buffered data is written on `flush`, `into_inner`, before reads, and on drop.
+/// Errors on drop are ignored, so call `flush` explicitly to observe them.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{BufWriterAt,SyncAtMut,WriteAtMut};
+///
+/// let file = std::cell::RefCell::new(vec![]);
+/// let mut w = BufWriterAt::new(&file, 1024);
+/// w.write_all_at(&[3, 4], 2).unwrap();
+/// w.write_all_at(&[1, 2], 0).unwrap();
+/// assert_eq!(w.buffered(), 4);
+/// assert!(file.borrow().is_empty());
+///
+/// w.flush().unwrap();
+/// assert_eq!(*file.borrow(), [1, 2, 3, 4]);
+/// ```
+pub struct BufWriterAt<T: WriteAtMut> {
+    /// Only `None` after `into_inner`
+    inner: Option<T>,
+    /// Non-overlapping, non-adjacent runs by start offset
+    runs: BTreeMap<u64, Vec<u8>>,
+    buffered: usize,
+    capacity: usize,
+}
+
+impl<T: WriteAtMut> BufWriterAt<T> {
+    /// Create an empty buffer. Panics if `capacity` is zero.
+    pub fn new(inner: T, capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be nonzero");
+        BufWriterAt {
+            inner: Some(inner),
+            runs: BTreeMap::new(),
+            buffered: 0,
+            capacity,
+        }
+    }
+
+    /// Capacity specified at construction time
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of bytes not yet written to the wrapped object
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Write buffered data and get back the wrapped object
+    pub fn into_inner(mut self) -> Result<T> {
+        self.flush_buf()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    /// Write all runs in offset order. Runs not written because of an error are kept.
+    fn flush_buf(&mut self) -> Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while let Some((start, data)) = self.runs.pop_first() {
+            if let Err(e) = inner.write_all_at(&data[..], start) {
+                self.runs.insert(start, data);
+                return Err(e);
+            }
+            self.buffered -= data.len();
+        }
+        Ok(())
+    }
+
+    /// Merge `buf` at `offset..end` with runs it overlaps or touches
+    fn merge(&mut self, buf: &[u8], offset: u64, end: u64) {
+        let touching: Vec<u64> = self
+            .runs
+            .range(..=end)
+            .rev()
+            .take_while(|(&s, d)| s + d.len() as u64 >= offset)
+            .map(|(&s, _)| s)
+            .collect();
+        let mut start = offset;
+        let mut run_end = end;
+        for s in &touching {
+            start = start.min(*s);
+            run_end = run_end.max(s + self.runs[s].len() as u64);
+        }
+        let mut run = vec![0; (run_end - start) as usize];
+        for s in touching {
+            let old = self.runs.remove(&s).unwrap();
+            self.buffered -= old.len();
+            let at = (s - start) as usize;
+            run[at..at + old.len()].copy_from_slice(&old[..]);
+        }
+        let at = (offset - start) as usize;
+        run[at..at + buf.len()].copy_from_slice(buf);
+        self.buffered += run.len();
+        self.runs.insert(start, run);
+    }
+}
+
+impl<T: WriteAtMut> WriteAtMut for BufWriterAt<T> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        let end = match offset.checked_add(buf.len() as u64) {
+            // Let the wrapped object report overflowing writes
+            Some(x) if buf.len() < self.capacity => x,
+            _ => {
+                self.flush_buf()?;
+                return self.inner.as_mut().unwrap().write_at(buf, offset);
+            }
+        };
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.merge(buf, offset, end);
+        if self.buffered >= self.capacity {
+            self.flush_buf()?;
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Reads write buffered data first, so they observe all previous writes.
+impl<T: ReadAtMut + WriteAtMut> ReadAtMut for BufWriterAt<T> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.flush_buf()?;
+        self.inner.as_mut().unwrap().read_at(buf, offset)
+    }
+}
+
+/// Size of the wrapped object, extended by buffered data written past its end
+impl<T: WriteAtMut + SizeAt> SizeAt for BufWriterAt<T> {
+    fn size(&self) -> Result<u64> {
+        let buffered_end = match self.runs.iter().next_back() {
+            Some((&s, d)) => s + d.len() as u64,
+            None => 0,
+        };
+        Ok(self.get_ref().size()?.max(buffered_end))
+    }
+}
+
+/// `flush` writes buffered data, then flushes the wrapped object. Other methods flush first, then forward.
+impl<T: WriteAtMut + SyncAtMut> SyncAtMut for BufWriterAt<T> {
+    fn flush(&mut self) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+    fn sync_all(&mut self) -> Result<()> {
+        SyncAtMut::flush(self)?;
+        self.inner.as_mut().unwrap().sync_all()
+    }
+    fn sync_data(&mut self) -> Result<()> {
+        SyncAtMut::flush(self)?;
+        self.inner.as_mut().unwrap().sync_data()
+    }
+    fn sync_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        SyncAtMut::flush(self)?;
+        self.inner.as_mut().unwrap().sync_range(offset, len)
+    }
+}
+
+impl<T: WriteAtMut + Advise> Advise for BufWriterAt<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.get_ref().advise(offset, len, advice)
+    }
+}
+
+/// Forwarded as is: allocation leaves data intact, so buffered data stays valid.
+impl<T: WriteAtMut + PreallocateMut> PreallocateMut for BufWriterAt<T> {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.inner.as_mut().unwrap().allocate(offset, len)
+    }
+}
+
+/// Buffered data is written first, then the call is forwarded.
+impl<T: WriteAtMut + DiscardMut> DiscardMut for BufWriterAt<T> {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.as_mut().unwrap().punch_hole(offset, len)
+    }
+    fn zero_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.as_mut().unwrap().zero_range(offset, len)
+    }
+    fn discard(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.as_mut().unwrap().discard(offset, len)
+    }
+}
+
+impl<T: WriteAtMut> Drop for BufWriterAt<T> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.flush_buf();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct Counting(Vec<u8>, usize);
+    impl WriteAtMut for Counting {
+        fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+            self.1 += 1;
+            self.0.write_at(buf, offset)
+        }
+    }
+
+    #[test]
+    fn coalesces() {
+        let mut w = BufWriterAt::new(Counting(vec![], 0), 8);
+        w.write_all_at(&[5], 5).unwrap();
+        w.write_all_at(&[1], 1).unwrap();
+        w.write_all_at(&[2, 3], 2).unwrap();
+        w.write_all_at(&[0, 0, 4], 3).unwrap();
+        assert_eq!(w.buffered(), 5);
+        assert_eq!(w.runs.len(), 1);
+        assert_eq!(w.get_ref().1, 0);
+
+        // Reaches capacity: two runs written
+        w.write_all_at(&[7, 7, 7], 10).unwrap();
+        assert_eq!(w.buffered(), 0);
+        assert_eq!(w.get_ref().1, 2);
+
+        w.write_all_at(&[9; 8], 20).unwrap();
+        w.write_all_at(&[8], 0).unwrap();
+        let c = w.into_inner().unwrap();
+        assert_eq!(c.1, 4);
+        assert_eq!(c.0[..6], [8, 1, 2, 0, 0, 4]);
+        assert_eq!(c.0.len(), 28);
+    }
+
+    #[test]
+    fn flushes_on_drop() {
+        let file = RefCell::new(vec![0u8; 4]);
+        let mut w = BufWriterAt::new(&file, 16);
+        w.write_all_at(&[1], 3).unwrap();
+        let mut v = [0u8; 1];
+        w.read_exact_at(&mut v[..], 3).unwrap();
+        assert_eq!(v, [1]);
+        w.write_all_at(&[2], 0).unwrap();
+        drop(w);
+        assert_eq!(*file.borrow(), [2, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,133 @@
+use super::{read_up_to, ReadAt, SizeAt, SubRange};
+use bytes::{Bytes, BytesMut};
+use std::convert::TryFrom;
+use std::io::Result;
+
+/// Reading into freshly allocated (or shared, if possible) `bytes::Bytes`.
+///
+/// Unlike `read_at`, the result is only shorter than `len` when end of file is reached.
+///
+/// Implemented for `Bytes` without copying, for `SubRange` of such objects,
+/// and with a copy for other in-memory objects and files.
+/// For other `ReadAt` objects, use `read_bytes_at` function.
+///
+/// Requires `bytes` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadBytesAt,SubRange};
+/// use bytes::Bytes;
+///
+/// let data = Bytes::from_static(b"hello, world");
+/// let r = SubRange::new(data.clone(), 7, 5);
+/// let b = r.read_bytes_at(0, 100).unwrap();
+/// assert_eq!(&b[..], b"world");
+/// // Memory is shared with the original
+/// assert_eq!(b.as_ptr(), data[7..].as_ptr());
+/// ```
+pub trait ReadBytesAt {
+    /// Read up to `len` bytes starting from `offset`.
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes>;
+}
+
+/// Read up to `len` bytes from any `ReadAt` into newly allocated `Bytes`.
+///
+/// Requires `bytes` feature.
+pub fn read_bytes_at<T: ReadAt + ?Sized>(t: &T, offset: u64, len: usize) -> Result<Bytes> {
+    let mut buf = BytesMut::zeroed(len);
+    let n = read_up_to(t, &mut buf[..], offset)?;
+    buf.truncate(n);
+    Ok(buf.freeze())
+}
+
+/// Range of `0..data_len` covered by a read of `len` bytes starting at `offset`
+fn clamp(data_len: usize, offset: u64, len: usize) -> std::ops::Range<usize> {
+    match usize::try_from(offset) {
+        Ok(o) if o < data_len => o..o + len.min(data_len - o),
+        _ => 0..0,
+    }
+}
+
+impl ReadAt for Bytes {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(&self[..], buf, offset)
+    }
+}
+
+impl SizeAt for Bytes {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl ReadBytesAt for Bytes {
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes> {
+        Ok(self.slice(clamp(self.len(), offset, len)))
+    }
+}
+
+impl ReadBytesAt for [u8] {
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes> {
+        Ok(Bytes::copy_from_slice(&self[clamp(self.len(), offset, len)]))
+    }
+}
+
+impl ReadBytesAt for Vec<u8> {
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes> {
+        ReadBytesAt::read_bytes_at(&self[..], offset, len)
+    }
+}
+
+#[cfg(any(target_os = "redox", unix, target_os = "vxworks", target_os = "hermit"))]
+impl ReadBytesAt for std::fs::File {
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes> {
+        read_bytes_at(self, offset, len)
+    }
+}
+
+impl<T: ReadBytesAt + ?Sized> ReadBytesAt for &T {
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes> {
+        ReadBytesAt::read_bytes_at(*self, offset, len)
+    }
+}
+
+impl<T: ReadBytesAt> ReadBytesAt for SubRange<T> {
+    fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Bytes> {
+        let len = match self.remaining_at(offset) {
+            Some(r) => r.min(len as u64) as usize,
+            None => 0,
+        };
+        if len == 0 {
+            return Ok(Bytes::new());
+        }
+        match self.base().checked_add(offset) {
+            Some(o) => self.get_ref().read_bytes_at(o, len),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "sub range offset overflows u64",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_reads() {
+        let v: Vec<u8> = (0..10).collect();
+        assert_eq!(&v.read_bytes_at(8, 5).unwrap()[..], &[8, 9]);
+        assert!(v.read_bytes_at(10, 5).unwrap().is_empty());
+        assert!(v.read_bytes_at(u64::MAX, 5).unwrap().is_empty());
+
+        let b = Bytes::from(v);
+        assert_eq!(&b.read_bytes_at(2, 3).unwrap()[..], &[2, 3, 4]);
+        assert_eq!(&read_bytes_at(&b, 7, 5).unwrap()[..], &[7, 8, 9]);
+
+        let r = SubRange::new(&b, 2, 3);
+        assert_eq!(&r.read_bytes_at(1, 10).unwrap()[..], &[3, 4]);
+        assert!(r.read_bytes_at(3, 10).unwrap().is_empty());
+    }
+}
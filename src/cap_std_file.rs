@@ -0,0 +1,135 @@
+use super::{Advice, Advise, Preallocate, ResizeAt, SizeAt, SyncAt};
+#[cfg(any(
+    target_os = "redox", unix, target_os = "vxworks", target_os = "hermit",
+    all(windows, feature = "windows-native"),
+    all(feature = "wasi", target_os = "wasi", not(target_env = "p2")),
+))]
+use super::{ReadAt, WriteAt};
+#[cfg(any(
+    target_os = "redox", unix, target_os = "vxworks", target_os = "hermit",
+    all(windows, feature = "windows-native"),
+    all(feature = "wasi", target_os = "wasi", not(target_env = "p2")),
+))]
+use std::io::{IoSlice, IoSliceMut};
+#[cfg(all(windows, not(feature = "windows-native")))]
+use super::{ReadAtMut, WriteAtMut};
+use cap_std::fs::File;
+use cap_std::io_lifetimes::views::FilelikeView;
+use cap_std::io_lifetimes::AsFilelike;
+use std::io::Result;
+
+/// Borrow the file as `std::fs::File` without taking it out of the capability-based API
+fn view(f: &File) -> FilelikeView<'_, std::fs::File> {
+    f.as_filelike_view::<std::fs::File>()
+}
+
+/// Same as for `std::fs::File`.
+#[cfg(any(
+    target_os = "redox", unix, target_os = "vxworks", target_os = "hermit",
+    all(windows, feature = "windows-native"),
+    all(feature = "wasi", target_os = "wasi", not(target_env = "p2")),
+))]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(&*view(self), buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        ReadAt::read_exact_at(&*view(self), buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        ReadAt::read_vectored_at(&*view(self), bufs, offset)
+    }
+}
+
+/// Same as for `std::fs::File`.
+#[cfg(any(
+    target_os = "redox", unix, target_os = "vxworks", target_os = "hermit",
+    all(windows, feature = "windows-native"),
+    all(feature = "wasi", target_os = "wasi", not(target_env = "p2")),
+))]
+impl WriteAt for File {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAt::write_at(&*view(self), buf, offset)
+    }
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        WriteAt::write_all_at(&*view(self), buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        WriteAt::write_vectored_at(&*view(self), bufs, offset)
+    }
+}
+
+/// Note that cursor is affected, like for `std::fs::File`.
+#[cfg(all(windows, not(feature = "windows-native")))]
+impl ReadAtMut for File {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(&*view(self), buf, offset)
+    }
+}
+
+/// Note that cursor is affected, like for `std::fs::File`.
+#[cfg(all(windows, not(feature = "windows-native")))]
+impl WriteAtMut for File {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_write(&*view(self), buf, offset)
+    }
+}
+
+impl SizeAt for File {
+    fn size(&self) -> Result<u64> {
+        SizeAt::size(&*view(self))
+    }
+}
+
+impl ResizeAt for File {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        File::set_len(self, new_len)
+    }
+}
+
+impl SyncAt for File {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+    fn sync_all(&self) -> Result<()> {
+        File::sync_all(self)
+    }
+    fn sync_data(&self) -> Result<()> {
+        File::sync_data(self)
+    }
+}
+
+impl Preallocate for File {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        Preallocate::allocate(&*view(self), offset, len)
+    }
+}
+
+impl Advise for File {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        Advise::advise(&*view(self), offset, len, advice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cap_std::ambient_authority;
+    use cap_std::fs::{Dir, OpenOptions};
+
+    #[test]
+    fn in_dir() {
+        let dir = Dir::open_ambient_dir(std::env::temp_dir(), ambient_authority()).unwrap();
+        let name = format!("read_write_at_cap_std_{}", std::process::id());
+        let f = dir.open_with(&name, OpenOptions::new().read(true).write(true).create(true).truncate(true)).unwrap();
+        dir.remove_file(&name).unwrap();
+        f.write_all_at(b"world", 6).unwrap();
+        f.write_all_at(b"hello ", 0).unwrap();
+        assert_eq!(f.size().unwrap(), 11);
+        let mut v = [0u8; 11];
+        f.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(&v, b"hello world");
+        f.allocate(0, 20).unwrap();
+        assert_eq!(f.size().unwrap(), 20);
+    }
+}
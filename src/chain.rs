@@ -0,0 +1,236 @@
+use super::{Advice, Advise, Discard, Preallocate, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// Several objects concatenated into one address space.
+///
+/// Each part is given with its length; bytes at or after the part's length are never accessed.
+/// A part shorter than declared (e.g. a file that was not extended yet) reads as a short read.
+/// Reads and writes spanning several parts are split; if a part returns less than requested,
+/// the operation stops there and returns the amount processed so far.
+///
+/// Reading past the end of the last part returns `0`, writing there fails with `WriteZero`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Chain,ReadAt};
+///
+/// let a = vec![1u8, 2, 3];
+/// let b = vec![4u8, 5];
+/// let c = Chain::new(vec![(&a, 3), (&b, 2)]);
+/// let mut v = [0u8; 4];
+/// c.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(v, [2, 3, 4, 5]);
+/// ```
+pub struct Chain<T> {
+    parts: Vec<(T, u64)>,
+    /// Offset of each part in the combined address space, then total length
+    starts: Vec<u64>,
+}
+
+impl<T> Chain<T> {
+    /// Create a chain from `(object, length)` pairs. Panics if the total length overflows `u64`.
+    pub fn new(parts: Vec<(T, u64)>) -> Self {
+        let mut starts = Vec::with_capacity(parts.len() + 1);
+        let mut pos = 0u64;
+        starts.push(0);
+        for (_, len) in &parts {
+            pos = pos.checked_add(*len).expect("total chain length overflows u64");
+            starts.push(pos);
+        }
+        Chain { parts, starts }
+    }
+
+    /// Total length of all parts
+    pub fn len(&self) -> u64 {
+        self.starts[self.parts.len()]
+    }
+
+    /// Whether the total length is zero
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parts with their lengths, in order
+    pub fn parts(&self) -> &[(T, u64)] {
+        &self.parts
+    }
+
+    /// Get back the parts
+    pub fn into_inner(self) -> Vec<(T, u64)> {
+        self.parts
+    }
+
+    /// Index of the part containing `offset`, `None` if `offset` is at or beyond the end
+    fn part_at(&self, offset: u64) -> Option<usize> {
+        if offset >= self.len() {
+            return None;
+        }
+        // Zero-length parts share their start with the next one, so take the last match
+        Some(self.starts.partition_point(|&s| s <= offset) - 1)
+    }
+
+    /// Call `f(part, offset in part, len)` for each part overlapping `offset..offset+len`, stopping at the first error
+    fn for_range<F>(&self, offset: u64, len: u64, mut f: F) -> Result<()>
+    where
+        F: FnMut(&T, u64, u64) -> Result<()>,
+    {
+        let end = offset.saturating_add(len).min(self.len());
+        let first = match self.part_at(offset) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        for part in first..self.parts.len() {
+            let start = self.starts[part];
+            if start >= end {
+                break;
+            }
+            let from = offset.max(start);
+            let to = end.min(self.starts[part + 1]);
+            if to > from {
+                f(&self.parts[part].0, from - start, to - from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Call `f(part, buffer range, offset in part)` for consecutive pieces of a `len`-byte request,
+    /// stopping at the first short or failed piece.
+    fn split<F>(&self, len: usize, offset: u64, mut f: F) -> Result<usize>
+    where
+        F: FnMut(&T, std::ops::Range<usize>, u64) -> Result<usize>,
+    {
+        let mut done = 0;
+        let mut part = match self.part_at(offset) {
+            Some(x) => x,
+            None => return Ok(0),
+        };
+        while done < len && part < self.parts.len() {
+            let pos = offset + done as u64;
+            let in_part = pos - self.starts[part];
+            let avail = self.starts[part + 1] - pos;
+            let n = avail.min((len - done) as u64) as usize;
+            if n > 0 {
+                let r = f(&self.parts[part].0, done..done + n, in_part);
+                let m = match r {
+                    Ok(m) => m,
+                    Err(e) if done == 0 => return Err(e),
+                    Err(_) => return Ok(done),
+                };
+                done += m;
+                if m < n {
+                    break;
+                }
+            }
+            part += 1;
+        }
+        Ok(done)
+    }
+}
+
+impl<T: ReadAt> ReadAt for Chain<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.split(buf.len(), offset, |t, r, o| t.read_at(&mut buf[r], o))
+    }
+}
+
+impl<T: WriteAt> WriteAt for Chain<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if offset >= self.len() {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                "write past the end of a chain",
+            ));
+        }
+        self.split(buf.len(), offset, |t, r, o| t.write_at(&buf[r], o))
+    }
+}
+
+impl<T> SizeAt for Chain<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len())
+    }
+}
+
+/// Forwarded to every part; `sync_range` only to parts overlapping the range.
+impl<T: SyncAt> SyncAt for Chain<T> {
+    fn flush(&self) -> Result<()> {
+        self.parts.iter().try_for_each(|(t, _)| t.flush())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.parts.iter().try_for_each(|(t, _)| t.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.parts.iter().try_for_each(|(t, _)| t.sync_data())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.sync_range(o, l))
+    }
+}
+
+/// Forwarded to parts overlapping the range.
+impl<T: Advise> Advise for Chain<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.advise(o, l, advice))
+    }
+}
+
+/// Forwarded to parts overlapping the range, so parts shorter than declared get extended.
+impl<T: Preallocate> Preallocate for Chain<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.allocate(o, l))
+    }
+}
+
+/// Forwarded to parts overlapping the range.
+impl<T: Discard> Discard for Chain<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.punch_hole(o, l))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.zero_range(o, l))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.discard(o, l))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn straddling() {
+        let a = RefCell::new(vec![0u8; 2]);
+        let empty = RefCell::new(vec![]);
+        let b = RefCell::new(vec![0u8; 3]);
+        let c = Chain::new(vec![(&a, 2), (&empty, 0), (&b, 3)]);
+        assert_eq!(c.len(), 5);
+
+        assert_eq!(c.write_at(&[1, 2, 3, 4, 5, 6], 1).unwrap(), 4);
+        assert_eq!(*a.borrow(), vec![0, 1]);
+        assert_eq!(*b.borrow(), vec![2, 3, 4]);
+        assert_eq!(c.write_at(&[1], 5).unwrap_err().kind(), ErrorKind::WriteZero);
+
+        let mut v = [9u8; 6];
+        assert_eq!(c.read_at(&mut v[..], 0).unwrap(), 5);
+        assert_eq!(v, [0, 1, 2, 3, 4, 9]);
+        assert_eq!(c.read_at(&mut v[..], 5).unwrap(), 0);
+    }
+
+    #[test]
+    fn short_part() {
+        // Declared longer than it actually is
+        let a = vec![1u8];
+        let b = vec![2u8];
+        let c = Chain::new(vec![(&a, 2), (&b, 1)]);
+        let mut v = [0u8; 3];
+        assert_eq!(c.read_at(&mut v[..], 0).unwrap(), 1);
+        assert_eq!(c.read_at(&mut v[..], 2).unwrap(), 1);
+        assert_eq!(v[0], 2);
+    }
+}
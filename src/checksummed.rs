@@ -0,0 +1,340 @@
+// `is_multiple_of` is newer than the supported Rust version
+#![allow(clippy::manual_is_multiple_of)]
+
+use super::crc::crc32c;
+use super::{read_up_to, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// Upper bound of data handled by a single call; larger requests are served partially
+const MAX_CHUNK: u64 = 1 << 20;
+
+/// Bytes of a stored checksum
+const SUM_LEN: usize = 4;
+
+/// A block is valid if its checksum matches, or if both are zero, as in storage that was never written
+fn is_valid(block: &[u8], sum: u32) -> bool {
+    (sum == 0 && block.iter().all(|&b| b == 0)) || crc32c(block) == sum
+}
+
+/// Where `Checksummed` keeps checksums in the wrapped object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumLayout {
+    /// Each block is directly followed by its checksum, so block `i` is stored at `i * (block_size + 4)`.
+    /// The object can grow.
+    Interleaved,
+    /// Blocks are stored at their own offsets below `start`, and checksums are an array starting at `start`.
+    /// The size is fixed to `start`, rounded down to a multiple of the block size.
+    Sidecar {
+        /// Offset of the checksum of block `0`
+        start: u64,
+    },
+}
+
+/// Error payload of reads that found a block not matching its checksum, from `Checksummed` or `VerityReader`.
+///
+/// It comes inside an `std::io::Error` of kind `InvalidData`, and can be told apart from other errors with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumError {
+    offset: u64,
+}
+
+impl ChecksumError {
+    pub(crate) fn new(offset: u64) -> Self {
+        ChecksumError { offset }
+    }
+
+    /// Offset of the start of the corrupted block, as seen through the wrapper
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl std::fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "checksum mismatch in block at offset {}", self.offset)
+    }
+}
+
+impl std::error::Error for ChecksumError {}
+
+/// Keeps a CRC32C checksum of every block of the wrapped object, verifying blocks on read and updating checksums on write.
+///
+/// Reads of a block not matching its checksum fail with a `ChecksumError`.
+/// Blocks with both data and checksum all zeros count as valid, so fresh or sparse storage can be used without formatting;
+/// a corruption zeroing both goes unnoticed.
+///
+/// Partially covered blocks of writes are read, verified and patched, then data and checksums are written.
+/// This is neither atomic nor crash-safe: an interrupted write may leave blocks that fail verification afterwards,
+/// and concurrent writes to the same block can lose data.
+///
+/// A single call handles at most 1 MiB (or one block, if larger), so it may be short;
+/// use `read_exact_at` and `write_all_at` for larger requests.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Checksummed,ChecksumError,ChecksumLayout,ReadAt,WriteAt};
+/// use std::sync::Mutex;
+///
+/// let c = Checksummed::new(Mutex::new(vec![]), 512, ChecksumLayout::Interleaved);
+/// c.write_all_at(b"hello", 1000).unwrap();
+/// c.get_ref().lock().unwrap()[1017] ^= 1;
+///
+/// let e = c.read_exact_at(&mut [0u8; 5], 1000).unwrap_err();
+/// let c = e.get_ref().and_then(|e| e.downcast_ref::<ChecksumError>()).unwrap();
+/// assert_eq!(c.offset(), 512);
+/// ```
+pub struct Checksummed<T> {
+    inner: T,
+    block_size: usize,
+    layout: ChecksumLayout,
+}
+
+impl<T> Checksummed<T> {
+    /// Panics if `block_size` is zero.
+    pub fn new(inner: T, block_size: usize, layout: ChecksumLayout) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        Checksummed { inner, block_size, layout }
+    }
+
+    /// Block size specified at construction time
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Layout specified at construction time
+    pub fn layout(&self) -> ChecksumLayout {
+        self.layout
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn blocks_per_call(&self) -> u64 {
+        (MAX_CHUNK / self.block_size as u64).max(1)
+    }
+
+    /// Offset of block `block` in the wrapped object
+    fn block_offset(&self, block: u64) -> Result<u64> {
+        let stride = match self.layout {
+            ChecksumLayout::Interleaved => self.block_size + SUM_LEN,
+            ChecksumLayout::Sidecar { .. } => self.block_size,
+        };
+        block
+            .checked_mul(stride as u64)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "offset overflows u64"))
+    }
+
+    /// End of the data that can be written, if fixed
+    fn limit(&self) -> Option<u64> {
+        match self.layout {
+            ChecksumLayout::Interleaved => None,
+            ChecksumLayout::Sidecar { start } => Some(start / self.block_size as u64 * self.block_size as u64),
+        }
+    }
+}
+
+impl<T: ReadAt> Checksummed<T> {
+    /// Read blocks starting with `first` into `data` without verifying them, returning their checksums.
+    /// Whatever is past the end of the wrapped object reads as zeros.
+    fn fetch(&self, first: u64, data: &mut [u8]) -> Result<Vec<u32>> {
+        let bs = self.block_size;
+        let count = data.len() / bs;
+        let mut sums = vec![0u8; count * SUM_LEN];
+        match self.layout {
+            ChecksumLayout::Interleaved => {
+                let mut records = vec![0u8; count * (bs + SUM_LEN)];
+                read_up_to(&self.inner, &mut records, self.block_offset(first)?)?;
+                for (i, r) in records.chunks(bs + SUM_LEN).enumerate() {
+                    data[i * bs..(i + 1) * bs].copy_from_slice(&r[..bs]);
+                    sums[i * SUM_LEN..(i + 1) * SUM_LEN].copy_from_slice(&r[bs..]);
+                }
+            }
+            ChecksumLayout::Sidecar { start } => {
+                let n = read_up_to(&self.inner, data, self.block_offset(first)?)?;
+                data[n..].fill(0);
+                read_up_to(&self.inner, &mut sums, start + first * SUM_LEN as u64)?;
+            }
+        }
+        Ok(sums.chunks(SUM_LEN).map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]])).collect())
+    }
+
+    /// Read and verify blocks starting with `first` into `data`
+    fn load(&self, first: u64, data: &mut [u8]) -> Result<()> {
+        let sums = self.fetch(first, data)?;
+        let bs = self.block_size;
+        match data.chunks(bs).zip(sums).position(|(block, sum)| !is_valid(block, sum)) {
+            Some(i) => {
+                let offset = (first + i as u64) * bs as u64;
+                Err(Error::new(ErrorKind::InvalidData, ChecksumError::new(offset)))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: ReadAt + SizeAt> Checksummed<T> {
+    /// Verify all blocks, returning offsets of the ones not matching their checksums
+    pub fn scrub(&self) -> Result<Vec<u64>> {
+        let bs = self.block_size as u64;
+        let blocks = self.size()? / bs;
+        let mut bad = vec![];
+        let mut data = vec![];
+        let mut first = 0;
+        while first < blocks {
+            let count = self.blocks_per_call().min(blocks - first);
+            data.resize((count * bs) as usize, 0);
+            let sums = self.fetch(first, &mut data)?;
+            for (i, (block, sum)) in data.chunks(bs as usize).zip(sums).enumerate() {
+                if !is_valid(block, sum) {
+                    bad.push((first + i as u64) * bs);
+                }
+            }
+            first += count;
+        }
+        Ok(bad)
+    }
+}
+
+impl<T: WriteAt> Checksummed<T> {
+    /// Write blocks starting with `first` along with their checksums
+    fn store(&self, first: u64, data: &[u8]) -> Result<()> {
+        let bs = self.block_size;
+        let sums = data.chunks(bs).map(|b| crc32c(b).to_le_bytes());
+        match self.layout {
+            ChecksumLayout::Interleaved => {
+                let mut records = Vec::with_capacity(data.len() / bs * (bs + SUM_LEN));
+                for (block, sum) in data.chunks(bs).zip(sums) {
+                    records.extend_from_slice(block);
+                    records.extend_from_slice(&sum);
+                }
+                self.inner.write_all_at(&records, self.block_offset(first)?)
+            }
+            ChecksumLayout::Sidecar { start } => {
+                self.inner.write_all_at(data, self.block_offset(first)?)?;
+                self.inner.write_all_at(&sums.flatten().collect::<Vec<u8>>(), start + first * SUM_LEN as u64)
+            }
+        }
+    }
+}
+
+impl<T: ReadAt + SizeAt> ReadAt for Checksummed<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let size = self.size()?;
+        if buf.is_empty() || offset >= size {
+            return Ok(0);
+        }
+        let bs = self.block_size as u64;
+        let first = offset / bs;
+        let end = (offset + buf.len() as u64).min(size).min((first + self.blocks_per_call()) * bs);
+        let mut data = vec![0u8; ((end.div_ceil(bs) - first) * bs) as usize];
+        self.load(first, &mut data)?;
+        let within = (offset - first * bs) as usize;
+        let n = (end - offset) as usize;
+        buf[..n].copy_from_slice(&data[within..within + n]);
+        Ok(n)
+    }
+}
+
+/// Writes past the end of a `Sidecar` layout are short
+impl<T: ReadAt + WriteAt> WriteAt for Checksummed<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let bs = self.block_size as u64;
+        let first = offset / bs;
+        let mut end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "offset overflows u64"))?
+            .min((first + self.blocks_per_call()).saturating_mul(bs));
+        if let Some(limit) = self.limit() {
+            end = end.min(limit);
+        }
+        if end <= offset {
+            return Ok(0);
+        }
+        let last = end.div_ceil(bs);
+        let mut data = vec![0u8; ((last - first) * bs) as usize];
+        let within = (offset - first * bs) as usize;
+        let tail = (end - first * bs) as usize;
+        // Only partially covered blocks need their old contents
+        if within != 0 {
+            self.load(first, &mut data[..bs as usize])?;
+        }
+        if tail % bs as usize != 0 && (last - first > 1 || within == 0) {
+            let l = data.len() - bs as usize;
+            self.load(last - 1, &mut data[l..])?;
+        }
+        data[within..tail].copy_from_slice(&buf[..tail - within]);
+        self.store(first, &data)?;
+        Ok(tail - within)
+    }
+}
+
+impl<T: SizeAt> SizeAt for Checksummed<T> {
+    fn size(&self) -> Result<u64> {
+        let bs = self.block_size as u64;
+        match self.limit() {
+            Some(limit) => Ok(limit),
+            None => Ok(self.inner.size()? / (bs + SUM_LEN as u64) * bs),
+        }
+    }
+}
+
+impl<T: SyncAt> SyncAt for Checksummed<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn layouts() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+
+        let c = Checksummed::new(Mutex::new(vec![0u8; 1100]), 100, ChecksumLayout::Sidecar { start: 1000 });
+        assert_eq!(c.size().unwrap(), 1000);
+        c.write_all_at(&[1; 150], 50).unwrap();
+        assert_eq!(c.write_at(&[1; 10], 995).unwrap(), 5);
+        assert_eq!(c.write_at(&[1; 10], 1000).unwrap(), 0);
+        let mut v = [0u8; 1000];
+        c.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v.iter().filter(|&&x| x == 1).count(), 155);
+        assert_eq!(c.scrub().unwrap(), []);
+        c.get_ref().lock().unwrap()[120] = 7;
+        c.get_ref().lock().unwrap()[1036] ^= 1;
+        assert_eq!(c.scrub().unwrap(), [100, 900]);
+        assert!(c.read_exact_at(&mut v[..10], 0).is_ok());
+        let e = c.read_exact_at(&mut v[..10], 195).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert!(e.get_ref().unwrap().downcast_ref::<ChecksumError>().is_some());
+
+        let c = Checksummed::new(Mutex::new(vec![]), 16, ChecksumLayout::Interleaved);
+        let data: Vec<u8> = (0..100).collect();
+        c.write_all_at(&data, 3).unwrap();
+        assert_eq!(c.get_ref().lock().unwrap().len(), 7 * 20);
+        assert_eq!(c.size().unwrap(), 112);
+        c.write_all_at(&[0xff; 2], 15).unwrap();
+        let mut v = vec![0u8; 112];
+        c.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v[..3], [0; 3]);
+        assert_eq!(v[15..17], [0xff; 2]);
+        assert_eq!(v[17..103], data[14..]);
+        assert_eq!(c.read_at(&mut v, 112).unwrap(), 0);
+    }
+}
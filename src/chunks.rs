@@ -0,0 +1,127 @@
+use super::{read_up_to, ReadAt};
+use std::io::Result;
+
+/// Iterator over consecutive chunks of `len` bytes starting at `offset`, returned by `chunks_at`.
+pub struct ChunksAt<'a, T: ?Sized> {
+    inner: &'a T,
+    offset: u64,
+    remaining: u64,
+    chunk_size: usize,
+}
+
+/// Read `len` bytes starting at `offset` as chunks of `chunk_size` bytes, e.g. to stream a device range to a hasher.
+///
+/// The last chunk may be shorter. Iteration stops early at end of file, or after yielding an error.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::chunks_at;
+///
+/// let data: Vec<u8> = (0..10).collect();
+/// let chunks: Vec<Vec<u8>> = chunks_at(&data, 3, 100, 4).collect::<std::io::Result<_>>().unwrap();
+/// assert_eq!(chunks, [vec![3, 4, 5, 6], vec![7, 8, 9]]);
+/// ```
+pub fn chunks_at<T: ReadAt + ?Sized>(inner: &T, offset: u64, len: u64, chunk_size: usize) -> ChunksAt<'_, T> {
+    assert!(chunk_size > 0, "chunk size must be nonzero");
+    ChunksAt {
+        inner,
+        offset,
+        remaining: len,
+        chunk_size,
+    }
+}
+
+impl<T: ReadAt + ?Sized> Iterator for ChunksAt<'_, T> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let mut buf = vec![0u8; self.remaining.min(self.chunk_size as u64) as usize];
+        let n = match read_up_to(self.inner, &mut buf[..], self.offset) {
+            Ok(n) => n,
+            Err(e) => {
+                self.remaining = 0;
+                return Some(Err(e));
+            }
+        };
+        if n == 0 {
+            self.remaining = 0;
+            return None;
+        }
+        if n < buf.len() {
+            self.remaining = 0;
+        } else {
+            self.remaining -= n as u64;
+        }
+        self.offset += n as u64;
+        buf.truncate(n);
+        Some(Ok(buf))
+    }
+}
+
+/// Async version of `chunks_at`, as a `Stream`.
+///
+/// Requires `async` feature.
+#[cfg(feature = "async")]
+pub fn async_chunks_at<T: crate::AsyncReadAt + ?Sized>(
+    inner: &T,
+    offset: u64,
+    len: u64,
+    chunk_size: usize,
+) -> impl futures_util::Stream<Item = Result<Vec<u8>>> + Send + '_ {
+    assert!(chunk_size > 0, "chunk size must be nonzero");
+    futures_util::stream::unfold((offset, len), move |(offset, remaining)| async move {
+        if remaining == 0 {
+            return None;
+        }
+        let mut buf = vec![0u8; remaining.min(chunk_size as u64) as usize];
+        let mut n = 0;
+        while n < buf.len() {
+            match inner.read_at(&mut buf[n..], offset + n as u64).await {
+                Ok(0) => break,
+                Ok(x) => n += x,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Some((Err(e), (offset, 0))),
+            }
+        }
+        if n == 0 {
+            return None;
+        }
+        let remaining = if n < buf.len() { 0 } else { remaining - n as u64 };
+        buf.truncate(n);
+        Some((Ok(buf), (offset + n as u64, remaining)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_multiple_and_errors() {
+        let data = vec![1u8; 8];
+        let lens: Vec<usize> = chunks_at(&data, 0, 8, 4).map(|c| c.unwrap().len()).collect();
+        assert_eq!(lens, [4, 4]);
+        assert_eq!(chunks_at(&data, 8, 8, 4).count(), 0);
+
+        let r: Vec<Result<Vec<u8>>> = chunks_at(&FailsAfter4, 2, 100, 2).collect();
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0].as_ref().unwrap(), &[0, 0]);
+        assert!(r[1].is_err());
+    }
+
+    struct FailsAfter4;
+    impl ReadAt for FailsAfter4 {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            if offset >= 4 {
+                return Err(std::io::Error::other("bad sector"));
+            }
+            let n = buf.len().min(4 - offset as usize);
+            buf[..n].fill(0);
+            Ok(n)
+        }
+    }
+}
@@ -0,0 +1,428 @@
+// `is_multiple_of` is newer than the supported Rust version
+#![allow(clippy::manual_is_multiple_of)]
+
+use super::crc::crc32c;
+use super::{ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::{Mutex, MutexGuard};
+
+const MAGIC: &[u8; 8] = b"RWACSTR1";
+const SUPERBLOCK_LEN: u64 = 16;
+const RECORD_MAGIC: &[u8; 4] = b"rblk";
+const HEADER_LEN: usize = 32;
+/// `block` of records that only change the size
+const SIZE_RECORD: u64 = u64::MAX;
+/// Upper bound of data handled by a single call; larger requests are served partially
+const MAX_CHUNK: u64 = 1 << 20;
+
+/// Codecs of records
+const RAW: u8 = 0;
+const ZEROS: u8 = 1;
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 2;
+#[cfg(feature = "lz4")]
+const LZ4: u8 = 3;
+
+/// How `CompressedStore` compresses newly written blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard with the given level. Requires `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// LZ4 block format. Requires `lz4` feature.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+/// Zstandard level 3 if `zstd` feature is enabled, LZ4 otherwise
+impl Default for Compression {
+    fn default() -> Self {
+        #[cfg(feature = "zstd")]
+        {
+            Compression::Zstd(3)
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            Compression::Lz4
+        }
+    }
+}
+
+/// Codec and payload of a block to store. Incompressible blocks are stored as is.
+fn encode(compression: Compression, block: &[u8]) -> Result<(u8, Vec<u8>)> {
+    if block.iter().all(|&b| b == 0) {
+        return Ok((ZEROS, vec![]));
+    }
+    let (codec, payload) = match compression {
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => (ZSTD, zstd::bulk::compress(block, level)?),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => (LZ4, lz4_flex::block::compress(block)),
+    };
+    if payload.len() >= block.len() {
+        return Ok((RAW, block.to_vec()));
+    }
+    Ok((codec, payload))
+}
+
+fn decode(codec: u8, payload: &[u8], block: &mut [u8]) -> Result<()> {
+    let n = match codec {
+        RAW if payload.len() == block.len() => {
+            block.copy_from_slice(payload);
+            block.len()
+        }
+        ZEROS => {
+            block.fill(0);
+            block.len()
+        }
+        #[cfg(feature = "zstd")]
+        ZSTD => zstd::bulk::decompress_to_buffer(payload, block)?,
+        #[cfg(feature = "lz4")]
+        LZ4 => lz4_flex::block::decompress_into(payload, block).map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+        RAW => 0,
+        _ => return Err(Error::new(ErrorKind::Unsupported, "block is stored with an unknown codec or one that is not enabled")),
+    };
+    if n != block.len() {
+        return Err(Error::new(ErrorKind::InvalidData, "stored block has a wrong size"));
+    }
+    Ok(())
+}
+
+/// Fields of a record header
+struct Header {
+    check: u32,
+    codec: u8,
+    len: u32,
+    block: u64,
+    size: u64,
+}
+
+impl Header {
+    /// Header followed by `payload`, with the checksum covering both
+    fn record(codec: u8, block: u64, size: u64, payload: &[u8]) -> Vec<u8> {
+        let mut r = Vec::with_capacity(HEADER_LEN + payload.len());
+        r.extend_from_slice(RECORD_MAGIC);
+        r.extend_from_slice(&[0; 4]);
+        r.extend_from_slice(&[codec, 0, 0, 0]);
+        r.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        r.extend_from_slice(&block.to_le_bytes());
+        r.extend_from_slice(&size.to_le_bytes());
+        r.extend_from_slice(payload);
+        let check = crc32c(&r[8..]);
+        r[4..8].copy_from_slice(&check.to_le_bytes());
+        r
+    }
+
+    fn parse(h: &[u8]) -> Option<Header> {
+        let u32_at = |i: usize| u32::from_le_bytes([h[i], h[i + 1], h[i + 2], h[i + 3]]);
+        let u64_at = |i: usize| u32_at(i) as u64 | (u32_at(i + 4) as u64) << 32;
+        if &h[..4] != RECORD_MAGIC {
+            return None;
+        }
+        Some(Header { check: u32_at(4), codec: h[8], len: u32_at(12), block: u64_at(16), size: u64_at(24) })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Offset of the record in the wrapped object
+    offset: u64,
+    len: u32,
+    codec: u8,
+}
+
+struct State {
+    index: BTreeMap<u64, Entry>,
+    size: u64,
+    /// Where the next record goes
+    end: u64,
+}
+
+impl State {
+    fn apply(&mut self, h: &Header, block_size: u64) {
+        if h.block == SIZE_RECORD {
+            self.index.split_off(&h.size.div_ceil(block_size));
+        } else {
+            self.index.insert(h.block, Entry { offset: self.end, len: h.len, codec: h.codec });
+        }
+        self.size = h.size;
+        self.end += (HEADER_LEN + h.len as usize) as u64;
+    }
+}
+
+/// Uncompressed view of fixed-size blocks stored compressed in the wrapped object, e.g. for cold archival storage.
+///
+/// The wrapped object is a log: after a small header, every write appends a record with the new contents of each block
+/// it touches, compressed with zstd or LZ4, and an in-memory index points to the latest record of each block.
+/// Blocks of zeros take no space beyond the record header, and blocks that were never written read as zeros.
+/// `open` rebuilds the index by scanning all records, stopping at the first torn or corrupted one,
+/// so a crash loses at most the writes in progress.
+///
+/// Space of overwritten blocks is never reclaimed, and shrinking does not shrink the wrapped object,
+/// so this suits data written mostly once. Writes are served one at a time.
+/// A single call handles at most 1 MiB (or one block, if larger), so it may be short;
+/// use `read_exact_at` and `write_all_at` for larger requests.
+///
+/// Requires `zstd` or `lz4` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{CompressedStore,Compression,ReadAt,SizeAt,WriteAt};
+/// use std::sync::Mutex;
+///
+/// let s = CompressedStore::create(Mutex::new(vec![]), 4096, Compression::default()).unwrap();
+/// s.write_all_at(&[b'a'; 100_000], 0).unwrap();
+/// assert!(s.stored_size() < 10_000);
+///
+/// let s = CompressedStore::open(s.into_inner(), Compression::default()).unwrap();
+/// assert_eq!(s.size().unwrap(), 100_000);
+/// let mut buf = [0u8; 3];
+/// s.read_exact_at(&mut buf[..], 99_997).unwrap();
+/// assert_eq!(&buf, b"aaa");
+/// ```
+pub struct CompressedStore<T> {
+    inner: T,
+    block_size: usize,
+    compression: Compression,
+    state: Mutex<State>,
+}
+
+impl<T> CompressedStore<T> {
+    /// Block size of the store
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Bytes used in the wrapped object, including records of overwritten blocks
+    pub fn stored_size(&self) -> u64 {
+        self.lock().end
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: WriteAt + ResizeAt> CompressedStore<T> {
+    /// Start an empty store in `inner`, erasing it. Panics if `block_size` is zero or does not fit in `u32`.
+    pub fn create(inner: T, block_size: usize, compression: Compression) -> Result<Self> {
+        assert!(block_size > 0 && u32::try_from(block_size).is_ok(), "block_size must be nonzero and fit in u32");
+        inner.set_len(0)?;
+        let mut sb = MAGIC.to_vec();
+        sb.extend_from_slice(&(block_size as u32).to_le_bytes());
+        sb.extend_from_slice(&[0; 4]);
+        inner.write_all_at(&sb, 0)?;
+        let state = State { index: BTreeMap::new(), size: 0, end: SUPERBLOCK_LEN };
+        Ok(CompressedStore { inner, block_size, compression, state: Mutex::new(state) })
+    }
+}
+
+impl<T: ReadAt + SizeAt> CompressedStore<T> {
+    /// Open a store created by `create`, compressing blocks written from now on with `compression`
+    pub fn open(inner: T, compression: Compression) -> Result<Self> {
+        let mut sb = [0u8; SUPERBLOCK_LEN as usize];
+        inner.read_exact_at(&mut sb, 0)?;
+        let block_size = u32::from_le_bytes([sb[8], sb[9], sb[10], sb[11]]) as usize;
+        if &sb[..8] != MAGIC || block_size == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "not a CompressedStore"));
+        }
+        let total = inner.size()?;
+        let mut state = State { index: BTreeMap::new(), size: 0, end: SUPERBLOCK_LEN };
+        let mut record = vec![0u8; HEADER_LEN];
+        while state.end + HEADER_LEN as u64 <= total {
+            record.resize(HEADER_LEN, 0);
+            inner.read_exact_at(&mut record, state.end)?;
+            let h = match Header::parse(&record) {
+                Some(h) if h.len as usize <= block_size && state.end + (HEADER_LEN as u64 + h.len as u64) <= total => h,
+                _ => break,
+            };
+            record.resize(HEADER_LEN + h.len as usize, 0);
+            inner.read_exact_at(&mut record[HEADER_LEN..], state.end + HEADER_LEN as u64)?;
+            if crc32c(&record[8..]) != h.check {
+                break;
+            }
+            state.apply(&h, block_size as u64);
+        }
+        Ok(CompressedStore { inner, block_size, compression, state: Mutex::new(state) })
+    }
+}
+
+impl<T: ReadAt> CompressedStore<T> {
+    /// Read the block stored in `entry` into `block`
+    fn load(&self, entry: Option<Entry>, block: &mut [u8]) -> Result<()> {
+        match entry {
+            None => {
+                block.fill(0);
+                Ok(())
+            }
+            Some(e) => {
+                let mut payload = vec![0u8; e.len as usize];
+                self.inner.read_exact_at(&mut payload, e.offset + HEADER_LEN as u64)?;
+                decode(e.codec, &payload, block)
+            }
+        }
+    }
+}
+
+impl<T: WriteAt> CompressedStore<T> {
+    /// Append a record for `block`, which is `None` for a size change
+    fn append(&self, state: &mut State, block: Option<(u64, &[u8])>, size: u64) -> Result<()> {
+        let (codec, payload, index) = match block {
+            Some((index, data)) => {
+                let (codec, payload) = encode(self.compression, data)?;
+                (codec, payload, index)
+            }
+            None => (RAW, vec![], SIZE_RECORD),
+        };
+        let record = Header::record(codec, index, size, &payload);
+        self.inner.write_all_at(&record, state.end)?;
+        let h = Header { check: 0, codec, len: payload.len() as u32, block: index, size };
+        state.apply(&h, self.block_size as u64);
+        Ok(())
+    }
+}
+
+impl<T: ReadAt> ReadAt for CompressedStore<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let bs = self.block_size as u64;
+        let first = offset / bs;
+        // Records never change once written, so they can be read without holding the lock
+        let (end, entries) = {
+            let s = self.lock();
+            if buf.is_empty() || offset >= s.size {
+                return Ok(0);
+            }
+            let end = offset.saturating_add(buf.len() as u64).min(s.size).min((first + (MAX_CHUNK / bs).max(1)) * bs);
+            let entries: Vec<Option<Entry>> = (first..end.div_ceil(bs)).map(|b| s.index.get(&b).copied()).collect();
+            (end, entries)
+        };
+        let mut block = vec![0u8; self.block_size];
+        let mut pos = offset;
+        for (b, entry) in (first..).zip(entries) {
+            self.load(entry, &mut block)?;
+            let take = ((b + 1) * bs).min(end) - pos;
+            let within = (pos - b * bs) as usize;
+            let done = (pos - offset) as usize;
+            buf[done..done + take as usize].copy_from_slice(&block[within..within + take as usize]);
+            pos += take;
+        }
+        Ok((end - offset) as usize)
+    }
+}
+
+impl<T: ReadAt + WriteAt> WriteAt for CompressedStore<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let bs = self.block_size as u64;
+        let first = offset / bs;
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "offset overflows u64"))?
+            .min((first + (MAX_CHUNK / bs).max(1)).saturating_mul(bs));
+        let mut s = self.lock();
+        let mut block = vec![0u8; self.block_size];
+        let mut pos = offset;
+        while pos < end {
+            let b = pos / bs;
+            let within = (pos - b * bs) as usize;
+            let take = (((b + 1) * bs).min(end) - pos) as usize;
+            // Only partially covered blocks need their old contents
+            if take < self.block_size {
+                self.load(s.index.get(&b).copied(), &mut block)?;
+            }
+            let done = (pos - offset) as usize;
+            block[within..within + take].copy_from_slice(&buf[done..done + take]);
+            let size = s.size.max(pos + take as u64);
+            self.append(&mut s, Some((b, &block)), size)?;
+            pos += take as u64;
+        }
+        Ok((end - offset) as usize)
+    }
+}
+
+impl<T> SizeAt for CompressedStore<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.lock().size)
+    }
+}
+
+/// Appends a record; the wrapped object never shrinks
+impl<T: ReadAt + WriteAt> ResizeAt for CompressedStore<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        let bs = self.block_size as u64;
+        let mut s = self.lock();
+        // Bytes cut from the last block must read as zeros if the store grows again
+        let b = new_len / bs;
+        if new_len < s.size && new_len % bs != 0 {
+            if let Some(e) = s.index.get(&b).copied() {
+                let mut block = vec![0u8; self.block_size];
+                self.load(Some(e), &mut block)?;
+                block[(new_len % bs) as usize..].fill(0);
+                self.append(&mut s, Some((b, &block)), new_len)?;
+            }
+        }
+        self.append(&mut s, None, new_len)
+    }
+}
+
+impl<T: SyncAt> SyncAt for CompressedStore<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_and_reopen() {
+        let s = CompressedStore::create(Mutex::new(vec![1u8; 50]), 1000, Compression::default()).unwrap();
+        let data: Vec<u8> = (0..5000u32).map(|x| (x / 100) as u8).collect();
+        s.write_all_at(&data, 0).unwrap();
+        s.write_all_at(&[0xff; 10], 2995).unwrap();
+        s.write_all_at(&[0; 1000], 4000).unwrap();
+        s.set_len(2999).unwrap();
+        s.set_len(4600).unwrap();
+        assert_eq!(s.size().unwrap(), 4600);
+        let stored = s.stored_size();
+
+        let mut inner = s.into_inner().into_inner().unwrap();
+        assert_eq!(inner.len() as u64, stored);
+        // A torn record at the end is ignored
+        inner.extend_from_slice(&Header::record(RAW, 0, 10, &[5; 1000])[..500]);
+        let s = CompressedStore::open(Mutex::new(inner), Compression::default()).unwrap();
+        assert_eq!(s.stored_size(), stored);
+        let mut v = vec![1u8; 4600];
+        s.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v[..2995], data[..2995]);
+        assert_eq!(v[2995..2999], [0xff; 4]);
+        assert!(v[2999..].iter().all(|&b| b == 0));
+        assert_eq!(s.read_at(&mut v, 4600).unwrap(), 0);
+
+        s.write_all_at(b"x", 10_000).unwrap();
+        assert_eq!(s.size().unwrap(), 10_001);
+        assert!(CompressedStore::open(Mutex::new(vec![0u8; 16]), Compression::default()).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+use super::{ReadAt, WriteAt};
+use std::io::{ErrorKind, Result};
+
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Copying ranges between objects, reusing one buffer across calls.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::Copier;
+/// use std::cell::RefCell;
+///
+/// let src: Vec<u8> = (0..10).collect();
+/// let dst = RefCell::new(vec![]);
+/// let mut progress = vec![];
+/// let mut c = Copier::new(4).with_progress(|done| progress.push(done));
+/// assert_eq!(c.copy_range(&src, 2, &dst, 1, 100).unwrap(), 8);
+/// drop(c);
+/// assert_eq!(*dst.borrow(), [0, 2, 3, 4, 5, 6, 7, 8, 9]);
+/// assert_eq!(progress, [4, 8]);
+/// ```
+pub struct Copier<'a> {
+    buf: Vec<u8>,
+    progress: Option<Box<dyn FnMut(u64) + 'a>>,
+}
+
+impl<'a> Copier<'a> {
+    /// Create a copier with a buffer of `buffer_size` bytes. Panics if `buffer_size` is zero.
+    pub fn new(buffer_size: usize) -> Self {
+        assert!(buffer_size > 0, "buffer_size must be nonzero");
+        Copier {
+            buf: vec![0; buffer_size],
+            progress: None,
+        }
+    }
+
+    /// Call `progress` with the number of bytes copied so far by the current call, after each chunk
+    pub fn with_progress(mut self, progress: impl FnMut(u64) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Copy up to `len` bytes from `src` at `src_offset` to `dst` at `dst_offset`, returning the number of bytes copied.
+    ///
+    /// Copying stops early at end of `src`. Short reads and writes are retried, as are `Interrupted` errors of reads.
+    /// On error, some data may already have been written.
+    pub fn copy_range<R, W>(&mut self, src: &R, src_offset: u64, dst: &W, dst_offset: u64, len: u64) -> Result<u64>
+    where
+        R: ReadAt + ?Sized,
+        W: WriteAt + ?Sized,
+    {
+        self.copy_buffered(src, src_offset, dst, dst_offset, len, 0)
+    }
+
+    /// Copy up to `len` bytes between files in the kernel with `copy_file_range`, returning the number of bytes copied.
+    ///
+    /// No data passes through userspace, and filesystems like Btrfs or XFS may share extents (reflink) instead of copying.
+    /// If the kernel cannot do the copy (e.g. files on different filesystems with old kernels, or overlapping ranges),
+    /// the rest of the range is copied through the buffer like `copy_range` does.
+    ///
+    /// Requires `copy_file_range` feature. Linux and Android only.
+    #[cfg(all(feature = "copy_file_range", any(target_os = "linux", target_os = "android")))]
+    pub fn copy_file_range(
+        &mut self,
+        src: &std::fs::File,
+        src_offset: u64,
+        dst: &std::fs::File,
+        dst_offset: u64,
+        len: u64,
+    ) -> Result<u64> {
+        use rustix::io::Errno;
+        let mut done = 0u64;
+        while done < len {
+            let (mut so, mut d) = (src_offset + done, dst_offset + done);
+            // Linux copies at most about 2 GiB per call anyway
+            let chunk = (len - done).min(1 << 30) as usize;
+            match rustix::fs::copy_file_range(src, Some(&mut so), dst, Some(&mut d), chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    done += n as u64;
+                    if let Some(p) = self.progress.as_mut() {
+                        p(done);
+                    }
+                }
+                Err(Errno::INTR) => {}
+                Err(Errno::XDEV) | Err(Errno::NOSYS) | Err(Errno::OPNOTSUPP) | Err(Errno::INVAL) => {
+                    return self.copy_buffered(src, src_offset, dst, dst_offset, len, done);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(done)
+    }
+
+    /// Send up to `len` bytes of `file` at `offset` to `socket`, returning the number of bytes sent.
+    ///
+    /// On Linux and Android this uses `sendfile`, so data goes from page cache to the socket without passing through userspace.
+    /// When `file` cannot be used with `sendfile`, and on other platforms, data goes through the buffer instead.
+    /// Sending stops early at end of `file`. `socket` is expected to be in blocking mode.
+    ///
+    /// Requires `sendfile` feature. Unix only.
+    #[cfg(all(feature = "sendfile", unix))]
+    pub fn send_range_to_socket<F, S>(&mut self, file: &F, offset: u64, len: u64, socket: &mut S) -> Result<u64>
+    where
+        F: ReadAt + std::os::unix::io::AsFd + ?Sized,
+        S: std::io::Write + std::os::unix::io::AsFd + ?Sized,
+    {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return self.send_buffered(file, offset, len, socket, 0);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            use rustix::io::Errno;
+            let mut done = 0u64;
+            while done < len {
+                let mut o = offset + done;
+                let chunk = (len - done).min(1 << 30) as usize;
+                match rustix::fs::sendfile(&*socket, file, Some(&mut o), chunk) {
+                    Ok(0) => return Ok(done),
+                    Ok(n) => {
+                        done += n as u64;
+                        if let Some(p) = self.progress.as_mut() {
+                            p(done);
+                        }
+                    }
+                    Err(Errno::INTR) => {}
+                    Err(Errno::INVAL) | Err(Errno::NOSYS) | Err(Errno::OPNOTSUPP) => {
+                        return self.send_buffered(file, offset, len, socket, done);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Ok(done)
+        }
+    }
+
+    /// `send_range_to_socket` through the buffer, continuing after `done` bytes were already sent
+    #[cfg(all(feature = "sendfile", unix))]
+    fn send_buffered<F, S>(&mut self, file: &F, offset: u64, len: u64, socket: &mut S, mut done: u64) -> Result<u64>
+    where
+        F: ReadAt + ?Sized,
+        S: std::io::Write + ?Sized,
+    {
+        while done < len {
+            let want = (len - done).min(self.buf.len() as u64) as usize;
+            let n = match file.read_at(&mut self.buf[..want], offset + done) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            socket.write_all(&self.buf[..n])?;
+            done += n as u64;
+            if let Some(p) = self.progress.as_mut() {
+                p(done);
+            }
+        }
+        Ok(done)
+    }
+
+    /// Copy up to `len` bytes to a file like `copy_range`, but only read and write data extents of `src`,
+    /// returning the number of bytes covered (holes included).
+    ///
+    /// `holes` tells what to do with the destination range of a hole (or zero extent) of `src`.
+    /// If the destination ends up shorter than the copied range (because the range ended in a hole), it is extended.
+    ///
+    /// Requires `sparse_copy` feature. Linux and Android only.
+    #[cfg(all(feature = "sparse_copy", any(target_os = "linux", target_os = "android")))]
+    pub fn copy_sparse<R>(
+        &mut self,
+        src: &R,
+        src_offset: u64,
+        dst: &std::fs::File,
+        dst_offset: u64,
+        len: u64,
+        holes: HoleMode,
+    ) -> Result<u64>
+    where
+        R: ReadAt + crate::Extents + ?Sized,
+    {
+        let mut done = 0;
+        for e in src.extents(src_offset..src_offset.saturating_add(len))? {
+            let end = e.end() - src_offset;
+            if e.kind == crate::ExtentKind::Data {
+                done = self.copy_buffered(src, src_offset, dst, dst_offset, end, e.offset - src_offset)?;
+                if done < end {
+                    // `src` was truncated meanwhile
+                    break;
+                }
+                continue;
+            }
+            if holes == HoleMode::Punch {
+                self.punch_hole(dst, dst_offset + (e.offset - src_offset), e.len)?;
+            }
+            done = end;
+            if let Some(p) = self.progress.as_mut() {
+                p(done);
+            }
+        }
+        if dst.metadata()?.len() < dst_offset + done {
+            dst.set_len(dst_offset + done)?;
+        }
+        Ok(done)
+    }
+
+    /// Deallocate a range of `dst`, writing zeroes if the filesystem cannot do that
+    #[cfg(all(feature = "sparse_copy", any(target_os = "linux", target_os = "android")))]
+    fn punch_hole(&mut self, dst: &std::fs::File, offset: u64, len: u64) -> Result<()> {
+        use rustix::fs::{fallocate, FallocateFlags};
+        match fallocate(dst, FallocateFlags::PUNCH_HOLE | FallocateFlags::KEEP_SIZE, offset, len) {
+            Ok(()) => Ok(()),
+            Err(rustix::io::Errno::OPNOTSUPP) => {
+                self.buf.fill(0);
+                let mut done = 0u64;
+                while done < len {
+                    let n = (len - done).min(self.buf.len() as u64) as usize;
+                    dst.write_all_at(&self.buf[..n], offset + done)?;
+                    done += n as u64;
+                }
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// `copy_range` continuing after `done` bytes were already copied
+    fn copy_buffered<R, W>(&mut self, src: &R, src_offset: u64, dst: &W, dst_offset: u64, len: u64, mut done: u64) -> Result<u64>
+    where
+        R: ReadAt + ?Sized,
+        W: WriteAt + ?Sized,
+    {
+        while done < len {
+            let want = (len - done).min(self.buf.len() as u64) as usize;
+            let n = match src.read_at(&mut self.buf[..want], src_offset + done) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            dst.write_all_at(&self.buf[..n], dst_offset + done)?;
+            done += n as u64;
+            if let Some(p) = self.progress.as_mut() {
+                p(done);
+            }
+        }
+        Ok(done)
+    }
+}
+
+/// What `Copier::copy_sparse` does with the destination range of a hole in the source.
+///
+/// Requires `sparse_copy` feature.
+#[cfg(all(feature = "sparse_copy", any(target_os = "linux", target_os = "android")))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HoleMode {
+    /// Leave it as is; fits a fresh (or already zeroed) destination
+    Skip,
+    /// Punch a hole there with `fallocate`, so that it reads as zeroes
+    Punch,
+}
+
+impl Default for Copier<'_> {
+    fn default() -> Self {
+        Copier::new(DEFAULT_BUFFER_SIZE)
+    }
+}
+
+/// Copy up to `len` bytes from `src` at `src_offset` to `dst` at `dst_offset`, returning the number of bytes copied.
+///
+/// This is `Copier::copy_range` with a default buffer size; use `Copier` to reuse the buffer or track progress.
+pub fn copy_range<R, W>(src: &R, src_offset: u64, dst: &W, dst_offset: u64, len: u64) -> Result<u64>
+where
+    R: ReadAt + ?Sized,
+    W: WriteAt + ?Sized,
+{
+    let buffer_size = len.min(DEFAULT_BUFFER_SIZE as u64).max(1) as usize;
+    Copier::new(buffer_size).copy_range(src, src_offset, dst, dst_offset, len)
+}
+
+/// Copy up to `len` bytes between files in the kernel, returning the number of bytes copied.
+///
+/// This is `Copier::copy_file_range` with a default buffer size for the fallback.
+///
+/// Requires `copy_file_range` feature. Linux and Android only.
+#[cfg(all(feature = "copy_file_range", any(target_os = "linux", target_os = "android")))]
+pub fn copy_file_range(src: &std::fs::File, src_offset: u64, dst: &std::fs::File, dst_offset: u64, len: u64) -> Result<u64> {
+    Copier::default().copy_file_range(src, src_offset, dst, dst_offset, len)
+}
+
+/// Send up to `len` bytes of `file` at `offset` to `socket`, returning the number of bytes sent.
+///
+/// This is `Copier::send_range_to_socket` with a default buffer size for the fallback.
+/// Useful for network servers (HTTP ranges, NBD) built on top of file-like objects.
+///
+/// Requires `sendfile` feature. Unix only.
+#[cfg(all(feature = "sendfile", unix))]
+pub fn send_range_to_socket<F, S>(file: &F, offset: u64, len: u64, socket: &mut S) -> Result<u64>
+where
+    F: ReadAt + std::os::unix::io::AsFd + ?Sized,
+    S: std::io::Write + std::os::unix::io::AsFd + ?Sized,
+{
+    Copier::default().send_range_to_socket(file, offset, len, socket)
+}
+
+/// Copy up to `len` bytes to a file, preserving holes of `src`; returns the number of bytes covered.
+///
+/// This is `Copier::copy_sparse` with a default buffer size.
+///
+/// Requires `sparse_copy` feature. Linux and Android only.
+#[cfg(all(feature = "sparse_copy", any(target_os = "linux", target_os = "android")))]
+pub fn copy_sparse<R>(
+    src: &R,
+    src_offset: u64,
+    dst: &std::fs::File,
+    dst_offset: u64,
+    len: u64,
+    holes: HoleMode,
+) -> Result<u64>
+where
+    R: ReadAt + crate::Extents + ?Sized,
+{
+    Copier::default().copy_sparse(src, src_offset, dst, dst_offset, len, holes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Returns at most 3 bytes per read
+    struct Trickle(Vec<u8>);
+    impl ReadAt for Trickle {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            let n = buf.len().min(3);
+            self.0.read_at(&mut buf[..n], offset)
+        }
+    }
+
+    #[test]
+    fn short_reads() {
+        let src = Trickle((0..20).collect());
+        let dst = RefCell::new(vec![9u8; 4]);
+        assert_eq!(copy_range(&src, 5, &dst, 2, 10).unwrap(), 10);
+        let expected: Vec<u8> = [9, 9].iter().copied().chain(5..15).collect();
+        assert_eq!(*dst.borrow(), expected);
+        assert_eq!(copy_range(&src, 18, &dst, 0, 10).unwrap(), 2);
+    }
+
+    #[cfg(all(feature = "copy_file_range", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn in_kernel() {
+        let (f, g) = (crate::testing::temp_file(), crate::testing::temp_file());
+        f.write_all_at(b"0123456789", 0).unwrap();
+        // The kernel refuses overlapping ranges of one file, so they are copied through the buffer instead
+        assert_eq!(copy_file_range(&f, 0, &f, 5, 8).unwrap(), 8);
+        let mut v = [0u8; 13];
+        f.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(&v, b"0123401234567");
+
+        assert_eq!(copy_file_range(&f, 3, &g, 1, 100).unwrap(), 10);
+        let mut v = [9u8; 11];
+        g.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(&v, b"\x003401234567");
+    }
+
+    #[cfg(all(feature = "sendfile", unix))]
+    #[test]
+    fn to_socket() {
+        use std::io::Read;
+        let f = crate::testing::temp_file();
+        f.write_all_at(b"0123456789", 0).unwrap();
+        let (mut a, mut b) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert_eq!(send_range_to_socket(&f, 2, 5, &mut a).unwrap(), 5);
+        assert_eq!(send_range_to_socket(&f, 8, 5, &mut a).unwrap(), 2);
+        drop(a);
+        let mut v = vec![];
+        b.read_to_end(&mut v).unwrap();
+        assert_eq!(v, b"2345689");
+    }
+
+    #[cfg(all(feature = "sparse_copy", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn sparse() {
+        const MIB: u64 = 1 << 20;
+        let (src, dst) = (crate::testing::temp_file(), crate::testing::temp_file());
+        src.write_all_at(b"head", 0).unwrap();
+        src.write_all_at(b"middle", MIB).unwrap();
+        src.set_len(3 * MIB).unwrap();
+        dst.write_all_at(&vec![0xAA; 4 * MIB as usize], 0).unwrap();
+
+        assert_eq!(copy_sparse(&src, 0, &dst, 0, 10 * MIB, HoleMode::Punch).unwrap(), 3 * MIB);
+        let mut v = vec![0u8; 4 * MIB as usize];
+        dst.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(&v[..4], b"head");
+        assert_eq!(&v[MIB as usize..][..6], b"middle");
+        assert!(v[4..MIB as usize].iter().all(|&b| b == 0));
+        assert!(v[MIB as usize + 6..3 * MIB as usize].iter().all(|&b| b == 0));
+        assert!(v[3 * MIB as usize..].iter().all(|&b| b == 0xAA));
+
+        let dst = crate::testing::temp_file();
+        assert_eq!(copy_sparse(&src, 2, &dst, 0, 2 * MIB, HoleMode::Skip).unwrap(), 2 * MIB);
+        assert_eq!(dst.metadata().unwrap().len(), 2 * MIB);
+        dst.read_exact_at(&mut v[..8], MIB - 2).unwrap();
+        assert_eq!(&v[..8], b"middle\0\0");
+    }
+}
@@ -0,0 +1,360 @@
+//! Variants of the positional traits depending only on `core`, for `no_std` users.
+//!
+//! Available without `std` feature. Instead of `std::io::Error`, implementations choose their error type
+//! via `ErrorType`, like `embedded-io` traits do.
+//!
+//! With `std` feature, `FromStd` and `IntoStd` bridge these traits and the main ones of the crate.
+//!
+//! # Examples
+//!
+//! ```
+//! use read_write_at::core_io::{ReadAt,WriteAtMut};
+//!
+//! let mut flash = [0xFFu8; 16];
+//! WriteAtMut::write_all_at(&mut flash[..], &[1, 2], 4).unwrap();
+//! let mut v = [0u8; 3];
+//! flash[..].read_exact_at(&mut v[..], 3).unwrap();
+//! assert_eq!(v, [0xFF, 1, 2]);
+//! ```
+
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Error type of an object, shared by all its traits
+pub trait ErrorType {
+    /// Error returned by the object's methods
+    type Error: fmt::Debug;
+}
+
+impl<T: ErrorType + ?Sized> ErrorType for &T {
+    type Error = T::Error;
+}
+
+impl<T: ErrorType + ?Sized> ErrorType for &mut T {
+    type Error = T::Error;
+}
+
+/// Error of `read_exact_at`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadExactError<E> {
+    /// End of the object reached before the buffer was filled
+    UnexpectedEof,
+    /// Error of the underlying `read_at`
+    Other(E),
+}
+
+/// Error of `write_all_at`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteAllError<E> {
+    /// Underlying `write_at` returned `0`
+    WriteZero,
+    /// Error of the underlying `write_at`
+    Other(E),
+}
+
+/// Error of in-memory objects: writing beyond their fixed end, an offset not fitting in `usize`, or a vector failing to grow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds;
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("access out of bounds")
+    }
+}
+
+/// `core` version of `read_write_at::ReadAt`
+pub trait ReadAt: ErrorType {
+    /// Read up to `buf.len()` bytes at `offset`, returning the number of bytes read; `0` means end of the object
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, Self::Error>;
+
+    /// Fill whole `buf` with data at `offset`
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> Result<(), ReadExactError<Self::Error>> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => return Err(ReadExactError::UnexpectedEof),
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    offset += n as u64;
+                }
+                Err(e) => return Err(ReadExactError::Other(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `core` version of `read_write_at::ReadAtMut`
+pub trait ReadAtMut: ErrorType {
+    /// Read up to `buf.len()` bytes at `offset`, returning the number of bytes read; `0` means end of the object
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Self::Error>;
+
+    /// Fill whole `buf` with data at `offset`
+    fn read_exact_at(&mut self, mut buf: &mut [u8], mut offset: u64) -> Result<(), ReadExactError<Self::Error>> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => return Err(ReadExactError::UnexpectedEof),
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    offset += n as u64;
+                }
+                Err(e) => return Err(ReadExactError::Other(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAtMut for T {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Self::Error> {
+        ReadAt::read_at(self, buf, offset)
+    }
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), ReadExactError<Self::Error>> {
+        ReadAt::read_exact_at(self, buf, offset)
+    }
+}
+
+/// `core` version of `read_write_at::WriteAt`
+pub trait WriteAt: ErrorType {
+    /// Write up to `buf.len()` bytes at `offset`, returning the number of bytes written
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize, Self::Error>;
+
+    /// Write whole `buf` at `offset`
+    fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> Result<(), WriteAllError<Self::Error>> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => return Err(WriteAllError::WriteZero),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) => return Err(WriteAllError::Other(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `core` version of `read_write_at::WriteAtMut`
+pub trait WriteAtMut: ErrorType {
+    /// Write up to `buf.len()` bytes at `offset`, returning the number of bytes written
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, Self::Error>;
+
+    /// Write whole `buf` at `offset`
+    fn write_all_at(&mut self, mut buf: &[u8], mut offset: u64) -> Result<(), WriteAllError<Self::Error>> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => return Err(WriteAllError::WriteZero),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) => return Err(WriteAllError::Other(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: WriteAt + ?Sized> WriteAtMut for T {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, Self::Error> {
+        WriteAt::write_at(self, buf, offset)
+    }
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> Result<(), WriteAllError<Self::Error>> {
+        WriteAt::write_all_at(self, buf, offset)
+    }
+}
+
+/// `core` version of `read_write_at::SizeAt`
+pub trait SizeAt: ErrorType {
+    /// Total size of the object in bytes
+    fn size(&self) -> Result<u64, Self::Error>;
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, Self::Error> {
+        (**self).read_at(buf, offset)
+    }
+}
+
+impl<T: WriteAt + ?Sized> WriteAt for &T {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize, Self::Error> {
+        (**self).write_at(buf, offset)
+    }
+}
+
+impl<T: SizeAt + ?Sized> SizeAt for &T {
+    fn size(&self) -> Result<u64, Self::Error> {
+        (**self).size()
+    }
+}
+
+impl ErrorType for [u8] {
+    type Error = OutOfBounds;
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, OutOfBounds> {
+        let data = match usize::try_from(offset) {
+            Ok(o) if o < self.len() => &self[o..],
+            _ => return Ok(0),
+        };
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+/// Writes in place. Writing at or after the end of the slice fails with `OutOfBounds`.
+impl WriteAtMut for [u8] {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, OutOfBounds> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let data = match usize::try_from(offset) {
+            Ok(o) if o < self.len() => &mut self[o..],
+            _ => return Err(OutOfBounds),
+        };
+        let n = data.len().min(buf.len());
+        data[..n].copy_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl SizeAt for [u8] {
+    fn size(&self) -> Result<u64, OutOfBounds> {
+        Ok(self.len() as u64)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ErrorType for alloc::vec::Vec<u8> {
+    type Error = OutOfBounds;
+}
+
+#[cfg(feature = "alloc")]
+impl ReadAt for alloc::vec::Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, OutOfBounds> {
+        ReadAt::read_at(&self[..], buf, offset)
+    }
+}
+
+/// Writing past the end extends the vector, filling the gap with zeroes.
+#[cfg(feature = "alloc")]
+impl WriteAtMut for alloc::vec::Vec<u8> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, OutOfBounds> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let end = usize::try_from(offset)
+            .ok()
+            .and_then(|o| o.checked_add(buf.len()))
+            .ok_or(OutOfBounds)?;
+        if self.len() < end {
+            self.try_reserve(end - self.len()).map_err(|_| OutOfBounds)?;
+            self.resize(end, 0);
+        }
+        self[end - buf.len()..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SizeAt for alloc::vec::Vec<u8> {
+    fn size(&self) -> Result<u64, OutOfBounds> {
+        Ok(self.len() as u64)
+    }
+}
+
+/// Gives the `core` traits for an object implementing the main `std` ones, with `std::io::Error` as error type.
+///
+/// Requires `std` feature.
+#[cfg(feature = "std")]
+pub struct FromStd<T>(pub T);
+
+#[cfg(feature = "std")]
+impl<T> ErrorType for FromStd<T> {
+    type Error = std::io::Error;
+}
+
+#[cfg(feature = "std")]
+impl<T: crate::ReadAt> ReadAt for FromStd<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        self.0.read_at(buf, offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: crate::WriteAt> WriteAt for FromStd<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+        self.0.write_at(buf, offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: crate::SizeAt> SizeAt for FromStd<T> {
+    fn size(&self) -> std::io::Result<u64> {
+        self.0.size()
+    }
+}
+
+/// Gives the main `std` traits for an object implementing the `core` ones.
+/// Errors become `std::io::Error` of `Other` kind.
+///
+/// Requires `std` feature.
+#[cfg(feature = "std")]
+pub struct IntoStd<T>(pub T);
+
+#[cfg(feature = "std")]
+fn other<E: fmt::Debug>(e: E) -> std::io::Error {
+    std::io::Error::other(format!("{:?}", e))
+}
+
+#[cfg(feature = "std")]
+impl<T: ReadAt> crate::ReadAt for IntoStd<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        self.0.read_at(buf, offset).map_err(other)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: WriteAt> crate::WriteAt for IntoStd<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+        self.0.write_at(buf, offset).map_err(other)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: SizeAt> crate::SizeAt for IntoStd<T> {
+    fn size(&self) -> std::io::Result<u64> {
+        self.0.size().map_err(other)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_and_bridges() {
+        let mut a = [0u8; 4];
+        assert_eq!(WriteAtMut::write_at(&mut a[..], &[1, 2], 3), Ok(1));
+        assert_eq!(WriteAtMut::write_all_at(&mut a[..], &[1], 4), Err(WriteAllError::Other(OutOfBounds)));
+        let mut v = [0u8; 2];
+        assert_eq!(a[..].read_exact_at(&mut v[..], 3), Err(ReadExactError::UnexpectedEof));
+
+        let mut vec = alloc::vec::Vec::new();
+        WriteAtMut::write_all_at(&mut vec, &[5], 2).unwrap();
+        assert_eq!(vec, [0, 0, 5]);
+        assert_eq!(WriteAtMut::write_at(&mut vec, &[], 1 << 50), Ok(0));
+        assert_eq!(WriteAtMut::write_at(&mut vec, &[1], 1 << 50), Err(OutOfBounds));
+        assert_eq!(vec.len(), 3);
+
+        let s = IntoStd(&vec[..]);
+        assert_eq!(crate::SizeAt::size(&s).unwrap(), 3);
+        let f = FromStd(std::sync::Mutex::new(vec));
+        f.read_exact_at(&mut v[..], 1).unwrap();
+        assert_eq!(v, [0, 5]);
+    }
+}
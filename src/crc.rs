@@ -0,0 +1,37 @@
+//! Table-driven CRC32 variants
+
+/// Table of the reflected CRC32 with polynomial `poly`
+const fn table(poly: u32) -> [u32; 256] {
+    let mut t = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { (c >> 1) ^ poly } else { c >> 1 };
+            k += 1;
+        }
+        t[i] = c;
+        i += 1;
+    }
+    t
+}
+
+const CRC32C_TABLE: [u32; 256] = table(0x82f6_3b78);
+#[cfg(any(feature = "xz", feature = "zip"))]
+const CRC32_TABLE: [u32; 256] = table(0xedb8_8320);
+
+fn crc(table: &[u32; 256], data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &b| table[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+/// CRC32C (Castagnoli), as used by iSCSI and ext4
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    crc(&CRC32C_TABLE, data)
+}
+
+/// CRC32 of zlib, gzip and xz
+#[cfg(any(feature = "xz", feature = "zip"))]
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    crc(&CRC32_TABLE, data)
+}
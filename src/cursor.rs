@@ -0,0 +1,226 @@
+use super::{ReadAt, WriteAt};
+use std::io::{BufRead, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// A `Read + Write + Seek + BufRead` adapter over a `ReadAt` and/or `WriteAt`, keeping its own position.
+///
+/// This is the opposite of `ReadWriteSeek`. As the inner object is only accessed via `&self`,
+/// multiple cursors can share one object (e.g. via `&T` or `Arc`).
+/// Writes go directly to the inner object, after discarding read-ahead data of this cursor
+/// (but not of other cursors sharing the object).
+///
+/// `SeekFrom::End` needs the total length, which should be specified using `with_len`,
+/// otherwise such seeks fail with `Unsupported`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::CursorAt;
+/// use std::io::{BufRead,Seek,SeekFrom};
+///
+/// let data = b"hello\nworld\n".to_vec();
+/// let mut c1 = CursorAt::with_len(&data, data.len() as u64);
+/// let mut c2 = CursorAt::new(&data);
+///
+/// c1.seek(SeekFrom::End(-6)).unwrap();
+/// let mut line = String::new();
+/// c1.read_line(&mut line).unwrap();
+/// assert_eq!(line, "world\n");
+///
+/// line.clear();
+/// c2.read_line(&mut line).unwrap();
+/// assert_eq!(line, "hello\n");
+/// assert_eq!(c2.position(), 6);
+/// ```
+pub struct CursorAt<T> {
+    inner: T,
+    pos: u64,
+    total_len: Option<u64>,
+    buf: Box<[u8]>,
+    /// Buffered data is `buf[buf_start..buf_end]`, corresponding to offsets starting from `pos`
+    buf_start: usize,
+    buf_end: usize,
+}
+
+impl<T> CursorAt<T> {
+    /// Create a cursor at position 0, without known length
+    pub fn new(inner: T) -> Self {
+        CursorAt {
+            inner,
+            pos: 0,
+            total_len: None,
+            buf: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            buf_start: 0,
+            buf_end: 0,
+        }
+    }
+
+    /// Create a cursor at position 0, `SeekFrom::End` being relative to `total_len`
+    pub fn with_len(inner: T, total_len: u64) -> Self {
+        let mut c = CursorAt::new(inner);
+        c.total_len = Some(total_len);
+        c
+    }
+
+    /// Current position, i.e. offset of the next byte to be read
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn discard_buffer(&mut self) {
+        self.buf_start = 0;
+        self.buf_end = 0;
+    }
+}
+
+/// Former name of `CursorAt`
+pub type ReadAtCursor<T> = CursorAt<T>;
+
+impl<T: ReadAt> Read for CursorAt<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.buf_start == self.buf_end && buf.len() >= self.buf.len() {
+            let n = self.inner.read_at(buf, self.pos)?;
+            self.pos += n as u64;
+            return Ok(n);
+        }
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt> BufRead for CursorAt<T> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.buf_start == self.buf_end {
+            let n = self.inner.read_at(&mut self.buf[..], self.pos)?;
+            self.buf_start = 0;
+            self.buf_end = n;
+        }
+        Ok(&self.buf[self.buf_start..self.buf_end])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.buf_end - self.buf_start);
+        self.buf_start += amt;
+        self.pos += amt as u64;
+    }
+}
+
+/// `SeekFrom::End` takes the written data into account. `flush` does nothing, as writes are not buffered.
+impl<T: WriteAt> Write for CursorAt<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.discard_buffer();
+        let n = self.inner.write_at(buf, self.pos)?;
+        self.pos += n as u64;
+        if let Some(len) = self.total_len.as_mut() {
+            *len = (*len).max(self.pos);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<T> Seek for CursorAt<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(x) => (x, 0),
+            SeekFrom::Current(x) => (self.pos, x),
+            SeekFrom::End(x) => match self.total_len {
+                Some(len) => (len, x),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::Unsupported,
+                        "seeking from end requires known length",
+                    ))
+                }
+            },
+        };
+        let newpos = if delta >= 0 {
+            base.checked_add(delta as u64)
+        } else {
+            base.checked_sub(delta.unsigned_abs())
+        };
+        let newpos = match newpos {
+            Some(x) => x,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "invalid seek to a negative or overflowing position",
+                ))
+            }
+        };
+        if newpos != self.pos {
+            self.discard_buffer();
+            self.pos = newpos;
+        }
+        Ok(newpos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_seek() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut c = CursorAt::new(&data[..]);
+        let mut b = [0u8; 3];
+        c.read_exact(&mut b).unwrap();
+        assert_eq!(b, [0, 1, 2]);
+        assert_eq!(c.seek(SeekFrom::Current(10)).unwrap(), 13);
+        c.read_exact(&mut b).unwrap();
+        assert_eq!(b, [13, 14, 15]);
+        assert_eq!(c.seek(SeekFrom::Current(-16)).unwrap(), 0);
+        assert_eq!(c.seek(SeekFrom::Current(-1)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(c.seek(SeekFrom::End(0)).unwrap_err().kind(), ErrorKind::Unsupported);
+
+        let mut rest = vec![];
+        c.seek(SeekFrom::Start(95)).unwrap();
+        c.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, vec![95, 96, 97, 98, 99]);
+        assert_eq!(c.position(), 100);
+    }
+
+    #[test]
+    fn interleaved_writes() {
+        let data = std::sync::Mutex::new(b"abcdef".to_vec());
+        let mut c = CursorAt::with_len(&data, 6);
+        let mut b = [0u8; 2];
+        c.read_exact(&mut b).unwrap();
+        c.write_all(b"XY").unwrap();
+        c.read_exact(&mut b).unwrap();
+        assert_eq!(&b, b"ef");
+        c.write_all(b"!").unwrap();
+        assert_eq!(c.seek(SeekFrom::End(0)).unwrap(), 7);
+        assert_eq!(*data.lock().unwrap(), b"abXYef!");
+    }
+
+    #[test]
+    fn large_reads_bypass_buffer() {
+        let data = vec![7u8; BUFFER_SIZE * 3];
+        let mut c = CursorAt::with_len(&data[..], data.len() as u64);
+        c.fill_buf().unwrap();
+        c.consume(1);
+        let mut b = vec![0u8; BUFFER_SIZE * 2];
+        assert_eq!(c.read(&mut b[..]).unwrap(), BUFFER_SIZE - 1);
+        assert_eq!(c.read(&mut b[..]).unwrap(), BUFFER_SIZE * 2);
+        assert_eq!(c.seek(SeekFrom::End(-1)).unwrap(), BUFFER_SIZE as u64 * 3 - 1);
+    }
+}
@@ -0,0 +1,120 @@
+use super::{ReadAt, SizeAt, SyncAt, WriteAt};
+use std::fs::File;
+use std::io::{Result, Seek, SeekFrom};
+use std::os::windows::fs::FileExt;
+use std::sync::{Mutex, MutexGuard};
+
+/// Windows `File` wrapper giving `ReadAt` and `WriteAt` that keep the cursor where it was.
+///
+/// `seek_read` and `seek_write` move the cursor, so the position is saved before them and restored afterwards.
+/// Calls through the wrapper are serialized, but other handles to the same file (or `get_ref`)
+/// observe the cursor moving during them, so this is only a best effort for code ported from Unix.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{CursorRestoring,ReadAt};
+/// use std::io::{Seek,SeekFrom};
+///
+/// let path = std::env::temp_dir().join(format!("read_write_at_restoring_doc_{}", std::process::id()));
+/// std::fs::write(&path, [1u8, 2, 3]).unwrap();
+/// let f = CursorRestoring::new(std::fs::File::open(&path).unwrap());
+/// let mut v = [0u8; 2];
+/// f.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(f.get_ref().seek(SeekFrom::Current(0)).unwrap(), 0);
+/// # drop(f);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct CursorRestoring<T = File> {
+    inner: T,
+    lock: Mutex<()>,
+}
+
+impl<T> CursorRestoring<T> {
+    /// Wrap `inner`
+    pub fn new(inner: T) -> Self {
+        CursorRestoring {
+            inner,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        // Guards no data
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Run `f`, then seek `file` back to where it was before
+fn restoring<R>(mut file: &File, f: impl FnOnce() -> Result<R>) -> Result<R> {
+    let pos = file.stream_position()?;
+    let r = f();
+    file.seek(SeekFrom::Start(pos))?;
+    r
+}
+
+impl ReadAt for CursorRestoring<File> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let _g = self.lock();
+        restoring(&self.inner, || self.inner.seek_read(buf, offset))
+    }
+}
+
+impl WriteAt for CursorRestoring<File> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let _g = self.lock();
+        restoring(&self.inner, || self.inner.seek_write(buf, offset))
+    }
+}
+
+impl SizeAt for CursorRestoring<File> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+impl SyncAt for CursorRestoring<File> {
+    fn flush(&self) -> Result<()> {
+        SyncAt::flush(&self.inner)
+    }
+    fn sync_all(&self) -> Result<()> {
+        SyncAt::sync_all(&self.inner)
+    }
+    fn sync_data(&self) -> Result<()> {
+        SyncAt::sync_data(&self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn keeps_cursor() {
+        let path = crate::testing::temp_path();
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        let f = CursorRestoring::new(file);
+        f.get_ref().write_all(&[1, 2]).unwrap();
+        f.write_all_at(&[9], 5).unwrap();
+        f.get_ref().write_all(&[3]).unwrap();
+        let mut v = [0u8; 6];
+        f.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [1, 2, 3, 0, 0, 9]);
+        let mut rest = vec![];
+        f.get_ref().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [0, 0, 9]);
+        drop(f);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
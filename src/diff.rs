@@ -0,0 +1,131 @@
+use super::{read_up_to, ReadAt, SizeAt};
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+
+/// Blocks compared per read, to keep reads large with small blocks
+const READ_SIZE: usize = 1 << 20;
+
+/// Append `range`, merging it with the previous one if they touch
+fn push_range(v: &mut Vec<Range<u64>>, range: Range<u64>) {
+    match v.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => v.push(range),
+    }
+}
+
+/// Compare blocks of `range`, which starts at a block boundary
+fn diff_part<A, B>(a: &A, b: &B, block_size: usize, range: Range<u64>) -> Result<Vec<Range<u64>>>
+where
+    A: ReadAt + ?Sized,
+    B: ReadAt + ?Sized,
+{
+    let chunk = (READ_SIZE / block_size).max(1) * block_size;
+    let (mut buf_a, mut buf_b) = (vec![0u8; chunk], vec![0u8; chunk]);
+    let mut diff = Vec::new();
+    let mut offset = range.start;
+    while offset < range.end {
+        let len = chunk.min((range.end - offset) as usize);
+        let na = read_up_to(a, &mut buf_a[..len], offset)?;
+        let nb = read_up_to(b, &mut buf_b[..len], offset)?;
+        // Past the end of one object everything differs; past both ends there is nothing
+        let n = na.max(nb);
+        buf_a[na..n].fill(0);
+        buf_b[nb..n].fill(0);
+        for (i, (x, y)) in buf_a[..n].chunks(block_size).zip(buf_b[..n].chunks(block_size)).enumerate() {
+            let start = i * block_size;
+            let short = start + x.len() > na || start + y.len() > nb;
+            if short || x != y {
+                let start = offset + start as u64;
+                push_range(&mut diff, start..start + x.len() as u64);
+            }
+        }
+        if n < len {
+            break;
+        }
+        offset += len as u64;
+    }
+    Ok(diff)
+}
+
+fn check_block_size(block_size: usize) -> Result<()> {
+    if block_size == 0 {
+        return Err(Error::new(ErrorKind::InvalidInput, "block_size must be nonzero"));
+    }
+    Ok(())
+}
+
+/// List ranges where `a` and `b` differ, comparing them block by block.
+///
+/// Ranges are block-aligned and merged, except that the last one ends at the end of the larger object.
+/// If the sizes differ, everything past the end of the smaller object counts as different,
+/// even if the larger one has zeros there. See `diff_ranges_parallel` to compare with multiple threads.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::diff_ranges;
+///
+/// let a = vec![0u8; 10000];
+/// let mut b = a.clone();
+/// b[5000] = 1;
+/// b.extend_from_slice(&[0; 100]);
+/// assert_eq!(diff_ranges(&a, &b, 1000).unwrap(), [5000..6000, 10000..10100]);
+/// ```
+pub fn diff_ranges<A, B>(a: &A, b: &B, block_size: usize) -> Result<Vec<Range<u64>>>
+where
+    A: ReadAt + SizeAt + ?Sized,
+    B: ReadAt + SizeAt + ?Sized,
+{
+    check_block_size(block_size)?;
+    let end = a.size()?.max(b.size()?);
+    diff_part(a, b, block_size, 0..end)
+}
+
+/// Like `diff_ranges`, but with `threads` threads comparing consecutive parts of the objects in parallel,
+/// which helps with fast storage or high-latency backends
+pub fn diff_ranges_parallel<A, B>(a: &A, b: &B, block_size: usize, threads: usize) -> Result<Vec<Range<u64>>>
+where
+    A: ReadAt + SizeAt + Sync + ?Sized,
+    B: ReadAt + SizeAt + Sync + ?Sized,
+{
+    check_block_size(block_size)?;
+    let end = a.size()?.max(b.size()?);
+    let blocks = end.div_ceil(block_size as u64);
+    let per_thread = blocks.div_ceil(threads.max(1) as u64).max(1) * block_size as u64;
+    let parts: Vec<Result<Vec<Range<u64>>>> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..end)
+            .step_by(per_thread as usize)
+            .map(|start| s.spawn(move || diff_part(a, b, block_size, start..end.min(start + per_thread))))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+    });
+    let mut diff = Vec::new();
+    for part in parts {
+        for range in part? {
+            push_range(&mut diff, range);
+        }
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel() {
+        let a: Vec<u8> = (0..100_000u32).map(|x| x as u8).collect();
+        let mut b = a.clone();
+        for i in [0, 1, 511, 512, 30_000, 99_999] {
+            b[i] ^= 1;
+        }
+        b.truncate(99_000);
+        let expected = [0..1024, 29_696..30_208, 98_816..100_000];
+        assert_eq!(diff_ranges(&a, &b, 512).unwrap(), expected);
+        for threads in [1, 3, 7, 1000] {
+            assert_eq!(diff_ranges_parallel(&a, &b, 512, threads).unwrap(), expected);
+        }
+        assert!(diff_ranges(&a, &a, 1).unwrap().is_empty());
+        assert!(diff_ranges(&a, &b, 0).is_err());
+    }
+}
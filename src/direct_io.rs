@@ -0,0 +1,83 @@
+//! Helpers for bypassing the page cache with `O_DIRECT`.
+//!
+//! `O_DIRECT` files typically require offsets, lengths and buffer addresses of all requests to be multiples
+//! of the logical block size of the underlying device, failing with `InvalidInput` otherwise.
+//! Use [`AlignedBuf`](crate::AlignedBuf) for buffers, or wrap the file in [`Aligned`](crate::Aligned)
+//! (see [`open_aligned`]) to handle arbitrary requests.
+//!
+//! Not every filesystem supports `O_DIRECT`; opening a file there fails with `InvalidInput`.
+
+use super::Aligned;
+use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+use std::fs::{File, OpenOptions};
+use std::io::Result;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Open `path` with `options`, adding `O_DIRECT` flag
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{direct_io,AlignedBuf,ReadAt};
+///
+/// let f = direct_io::open("/dev/sda", std::fs::OpenOptions::new().read(true)).unwrap();
+/// let mut b = AlignedBuf::new(4096, 4096);
+/// f.read_exact_at(&mut b[..], 0).unwrap();
+/// ```
+pub fn open<P: AsRef<Path>>(path: P, options: &OpenOptions) -> Result<File> {
+    let mut options = options.clone();
+    options.custom_flags(OFlags::DIRECT.bits() as i32);
+    options.open(path)
+}
+
+/// Open `path` with `O_DIRECT` and wrap it in `Aligned` with given `block_size`,
+/// so requests with any offset, length and buffer address work.
+pub fn open_aligned<P: AsRef<Path>>(path: P, options: &OpenOptions, block_size: usize) -> Result<Aligned<File>> {
+    Ok(Aligned::new(open(path, options)?, block_size))
+}
+
+/// Turn `O_DIRECT` on or off for an already opened file
+pub fn set_direct(file: &File, enable: bool) -> Result<()> {
+    let mut flags = fcntl_getfl(file)?;
+    flags.set(OFlags::DIRECT, enable);
+    Ok(fcntl_setfl(file, flags)?)
+}
+
+/// Check whether `O_DIRECT` is on for a file
+pub fn is_direct(file: &File) -> Result<bool> {
+    Ok(fcntl_getfl(file)?.contains(OFlags::DIRECT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AlignedBuf, ReadAt, WriteAt};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn direct_file() {
+        let path = crate::testing::temp_path();
+        let f = match open_aligned(&path, OpenOptions::new().read(true).write(true).create(true), 4096) {
+            Ok(f) => f,
+            // Filesystem without `O_DIRECT` support, e.g. older tmpfs
+            Err(e) if e.kind() == ErrorKind::InvalidInput => return,
+            Err(e) => panic!("{}", e),
+        };
+        let _ = std::fs::remove_file(&path);
+        assert!(is_direct(f.get_ref()).unwrap());
+
+        let mut b = AlignedBuf::new(4096, 4096);
+        b[0] = 5;
+        f.get_ref().write_all_at(&b[..], 0).unwrap();
+        f.write_all_at(&[1, 2, 3], 4095).unwrap();
+        let mut v = [0u8; 3];
+        f.read_exact_at(&mut v[..], 4094).unwrap();
+        assert_eq!(v, [0, 1, 2]);
+        f.get_ref().read_exact_at(&mut b[..], 0).unwrap();
+        assert_eq!(b[..2], [5, 0]);
+
+        set_direct(f.get_ref(), false).unwrap();
+        assert!(!is_direct(f.get_ref()).unwrap());
+    }
+}
@@ -0,0 +1,221 @@
+use super::{Discard, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::collections::BTreeMap;
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::ops::Range;
+use std::sync::{Mutex, MutexGuard};
+
+/// Records which blocks of the wrapped object were modified since the last reset, e.g. for incremental backups.
+///
+/// Writes, discards and resizes mark the blocks they touch; a failed call marks everything it asked for,
+/// as it may have changed data partially. Reads are forwarded as is.
+/// Dirty blocks are kept as merged ranges, so long sequential writes cost little memory.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{DirtyTracking,WriteAt};
+/// use std::sync::Mutex;
+///
+/// let d = DirtyTracking::new(Mutex::new(vec![0u8; 65536]), 4096);
+/// d.write_all_at(&[1; 10], 4095).unwrap();
+/// d.write_all_at(&[1; 10], 40000).unwrap();
+/// assert_eq!(d.take_dirty_extents(), [0..8192, 36864..40960]);
+/// assert!(d.dirty_extents().is_empty());
+/// ```
+pub struct DirtyTracking<T> {
+    inner: T,
+    block_size: u64,
+    /// Start block to end block (exclusive) of disjoint, non-adjacent ranges
+    dirty: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl<T> DirtyTracking<T> {
+    /// Wrap `inner`, with nothing dirty yet. Panics if `block_size` is zero.
+    pub fn new(inner: T, block_size: u64) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        DirtyTracking { inner, block_size, dirty: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Block size specified at construction time
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Dirty byte ranges, block-aligned, in order and merged. The last one may extend past the end of the object.
+    pub fn dirty_extents(&self) -> Vec<Range<u64>> {
+        self.extents(&self.lock())
+    }
+
+    /// Dirty byte ranges like `dirty_extents`, clearing them at the same time
+    pub fn take_dirty_extents(&self) -> Vec<Range<u64>> {
+        let mut dirty = self.lock();
+        let v = self.extents(&dirty);
+        dirty.clear();
+        v
+    }
+
+    /// Whether any byte of `offset..offset+len` is in a dirty block
+    pub fn is_dirty(&self, offset: u64, len: u64) -> bool {
+        if len == 0 {
+            return false;
+        }
+        let (first, end) = self.blocks(offset, len);
+        self.lock().range(..end).next_back().is_some_and(|(_, e)| *e > first)
+    }
+
+    /// Number of dirty blocks
+    pub fn dirty_blocks(&self) -> u64 {
+        self.lock().iter().map(|(s, e)| e - s).sum()
+    }
+
+    /// Mark a range as dirty, e.g. after changing the object bypassing this wrapper
+    pub fn mark_dirty(&self, offset: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let (mut first, mut end) = self.blocks(offset, len);
+        let mut dirty = self.lock();
+        // Absorb all ranges overlapping or adjacent to the new one
+        while let Some((&s, &e)) = dirty.range(..=end).next_back() {
+            if e < first {
+                break;
+            }
+            dirty.remove(&s);
+            first = first.min(s);
+            end = end.max(e);
+        }
+        dirty.insert(first, end);
+    }
+
+    /// Forget all dirty marks, e.g. after a backup was taken
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<u64, u64>> {
+        self.dirty.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// First block and the block after the last one of a nonempty range
+    fn blocks(&self, offset: u64, len: u64) -> (u64, u64) {
+        let end = offset.saturating_add(len);
+        (offset / self.block_size, (end - 1) / self.block_size + 1)
+    }
+
+    fn extents(&self, dirty: &BTreeMap<u64, u64>) -> Vec<Range<u64>> {
+        let bs = self.block_size;
+        dirty.iter().map(|(s, e)| s.saturating_mul(bs)..e.saturating_mul(bs)).collect()
+    }
+
+    /// Mark what a write-like call changed: `done` bytes if it succeeded, all of `len` otherwise
+    fn track<R>(&self, offset: u64, len: u64, result: Result<R>, done: impl FnOnce(&R) -> u64) -> Result<R> {
+        let n = result.as_ref().map_or(len, done);
+        self.mark_dirty(offset, n);
+        result
+    }
+}
+
+impl<T: ReadAt> ReadAt for DirtyTracking<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.inner.read_at(buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.inner.read_exact_at(buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        self.inner.read_vectored_at(bufs, offset)
+    }
+}
+
+impl<T: WriteAt> WriteAt for DirtyTracking<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.track(offset, buf.len() as u64, self.inner.write_at(buf, offset), |n| *n as u64)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let len = bufs.iter().map(|b| b.len() as u64).sum();
+        self.track(offset, len, self.inner.write_vectored_at(bufs, offset), |n| *n as u64)
+    }
+}
+
+impl<T: SizeAt> SizeAt for DirtyTracking<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+/// Marks the bytes between the old and the new size
+impl<T: ResizeAt + SizeAt> ResizeAt for DirtyTracking<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        let old = self.inner.size()?;
+        let (a, b) = (old.min(new_len), old.max(new_len));
+        self.track(a, b - a, self.inner.set_len(new_len), |_| b - a)
+    }
+}
+
+impl<T: SyncAt> SyncAt for DirtyTracking<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.sync_range(offset, len)
+    }
+}
+
+impl<T: Discard> Discard for DirtyTracking<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.track(offset, len, self.inner.punch_hole(offset, len), |_| len)
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.track(offset, len, self.inner.zero_range(offset, len), |_| len)
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.track(offset, len, self.inner.discard(offset, len), |_| len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn merging() {
+        let d = DirtyTracking::new(Mutex::new(vec![0u8; 100]), 10);
+        d.mark_dirty(25, 10);
+        d.mark_dirty(55, 1);
+        assert_eq!(d.dirty_extents(), [20..40, 50..60]);
+        d.mark_dirty(41, 5);
+        assert_eq!(d.dirty_extents(), [20..60]);
+        d.mark_dirty(0, 1);
+        d.mark_dirty(80, 0);
+        assert_eq!(d.dirty_extents(), [0..10, 20..60]);
+        assert_eq!(d.dirty_blocks(), 5);
+        assert!(d.is_dirty(9, 11) && d.is_dirty(59, 1));
+        assert!(!d.is_dirty(10, 10) && !d.is_dirty(60, 40));
+        d.mark_dirty(5, 50);
+        assert_eq!(d.dirty_extents(), [0..60]);
+
+        d.reset();
+        d.set_len(150).unwrap();
+        d.set_len(120).unwrap();
+        assert_eq!(d.take_dirty_extents(), [100..150]);
+        d.write_all_at(&[1; 5], 200).unwrap();
+        assert_eq!(d.dirty_extents(), [200..210]);
+    }
+}
@@ -0,0 +1,236 @@
+// `is_multiple_of` is newer than the supported Rust version
+#![allow(clippy::manual_is_multiple_of)]
+
+use std::io::{Error, Result};
+
+/// Deallocating or zeroing ranges without writing buffers of zeroes, like `fallocate` with `FALLOC_FL_PUNCH_HOLE`.
+///
+/// Ranges are clipped to the size of the object; the size never changes.
+/// Default implementations of `zero_range` and `discard` fall back to `punch_hole`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Discard,SubRange};
+///
+/// let v = std::sync::Mutex::new(vec![1u8; 8]);
+/// SubRange::new(&v, 2, 4).punch_hole(1, 100).unwrap();
+/// assert_eq!(*v.lock().unwrap(), [1, 1, 1, 0, 0, 0, 1, 1]);
+/// ```
+pub trait Discard {
+    /// Deallocate `offset..offset+len`; it then reads as zeroes.
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()>;
+    /// Make `offset..offset+len` read as zeroes, keeping it allocated if the implementation can.
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.punch_hole(offset, len)
+    }
+    /// Tell that data in `offset..offset+len` is not needed anymore, e.g. `TRIM` for SSDs.
+    /// Contents of the range are unspecified afterwards.
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.punch_hole(offset, len)
+    }
+}
+
+/// `Discard`, but requiring `&mut self`.
+///
+/// Default implementations fall back to `punch_hole`, like in `Discard`.
+pub trait DiscardMut {
+    /// Deallocate `offset..offset+len`; it then reads as zeroes.
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()>;
+    /// Make `offset..offset+len` read as zeroes, keeping it allocated if the implementation can.
+    fn zero_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.punch_hole(offset, len)
+    }
+    /// Tell that data in `offset..offset+len` is not needed anymore.
+    /// Contents of the range are unspecified afterwards.
+    fn discard(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.punch_hole(offset, len)
+    }
+}
+
+impl<T: Discard + ?Sized> DiscardMut for T {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        Discard::punch_hole(self, offset, len)
+    }
+    fn zero_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        Discard::zero_range(self, offset, len)
+    }
+    fn discard(&mut self, offset: u64, len: u64) -> Result<()> {
+        Discard::discard(self, offset, len)
+    }
+}
+
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: Discard + ?Sized> Discard for $ptr {
+            fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+                Discard::punch_hole(&**self, offset, len)
+            }
+            fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+                Discard::zero_range(&**self, offset, len)
+            }
+            fn discard(&self, offset: u64, len: u64) -> Result<()> {
+                Discard::discard(&**self, offset, len)
+            }
+        }
+    )*};
+}
+
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+macro_rules! forward_through_lock {
+    ($($lock:ty, |$s:ident| $guard:expr);*) => {$(
+        impl<T: DiscardMut + ?Sized> Discard for $lock {
+            fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+                let $s = self;
+                DiscardMut::punch_hole(&mut *$guard, offset, len)
+            }
+            fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+                let $s = self;
+                DiscardMut::zero_range(&mut *$guard, offset, len)
+            }
+            fn discard(&self, offset: u64, len: u64) -> Result<()> {
+                let $s = self;
+                DiscardMut::discard(&mut *$guard, offset, len)
+            }
+        }
+    )*};
+}
+
+forward_through_lock!(
+    std::cell::RefCell<T>, |s| s.borrow_mut();
+    std::sync::Mutex<T>, |s| s.lock().map_err(|_| Error::other("poisoned mutex encountered"))?;
+    std::sync::RwLock<T>, |s| s.write().map_err(|_| Error::other("poisoned rwlock encountered"))?
+);
+
+/// Uses `fallocate` with `FALLOC_FL_PUNCH_HOLE` (falling back to `FALLOC_FL_ZERO_RANGE` for `zero_range` of regular files).
+/// For block devices, `zero_range` and `discard` use `BLKZEROOUT` and `BLKDISCARD` ioctls.
+///
+/// Requires `discard` feature. Linux and Android only.
+#[cfg(all(feature = "discard", any(target_os = "linux", target_os = "android")))]
+impl Discard for std::fs::File {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        use rustix::fs::{fallocate, FallocateFlags};
+        let len = match clip(self, offset, len)? {
+            0 => return Ok(()),
+            x => x,
+        };
+        fallocate(self, FallocateFlags::PUNCH_HOLE | FallocateFlags::KEEP_SIZE, offset, len)?;
+        Ok(())
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        use rustix::fs::{fallocate, FallocateFlags};
+        let len = match clip(self, offset, len)? {
+            0 => return Ok(()),
+            x => x,
+        };
+        if crate::blk::is_block_device(self)? {
+            check_sectors(self, offset, len)?;
+            return crate::blk::zero_out(self, offset, len);
+        }
+        match fallocate(self, FallocateFlags::ZERO_RANGE | FallocateFlags::KEEP_SIZE, offset, len) {
+            Err(rustix::io::Errno::OPNOTSUPP) => Discard::punch_hole(self, offset, len),
+            x => Ok(x?),
+        }
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        let clipped = match clip(self, offset, len)? {
+            0 => return Ok(()),
+            x => x,
+        };
+        if crate::blk::is_block_device(self)? {
+            check_sectors(self, offset, clipped)?;
+            return crate::blk::discard(self, offset, clipped);
+        }
+        Discard::punch_hole(self, offset, clipped)
+    }
+}
+
+/// Length of `offset..offset+len` clipped to the size of `f`, which is queried with `BLKGETSIZE64` for block devices
+#[cfg(all(feature = "discard", any(target_os = "linux", target_os = "android")))]
+fn clip(f: &std::fs::File, offset: u64, len: u64) -> Result<u64> {
+    let size = match crate::blk::is_block_device(f)? {
+        true => crate::blk::size(f)?,
+        false => f.metadata()?.len(),
+    };
+    Ok(size.saturating_sub(offset).min(len))
+}
+
+/// Fail with `InvalidInput` unless `offset..offset+len` is made of whole logical sectors of block device `f`
+#[cfg(all(feature = "discard", any(target_os = "linux", target_os = "android")))]
+fn check_sectors(f: &std::fs::File, offset: u64, len: u64) -> Result<()> {
+    let sector = u64::from(rustix::fs::ioctl_blksszget(f)?);
+    if offset % sector != 0 || len % sector != 0 {
+        return Err(Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "range is not aligned to logical sectors of the block device",
+        ));
+    }
+    Ok(())
+}
+
+impl DiscardMut for Vec<u8> {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        let start = offset.min(self.len() as u64) as usize;
+        let end = offset.saturating_add(len).min(self.len() as u64) as usize;
+        self[start..end].fill(0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadAt, SizeAt, SparseMem, WriteAtMut};
+
+    #[test]
+    fn sparse_pages() {
+        const PS: u64 = SparseMem::PAGE_SIZE as u64;
+        let mut m = SparseMem::new();
+        m.write_all_at(&vec![1u8; 3 * PS as usize], 0).unwrap();
+        DiscardMut::punch_hole(&mut m, PS - 1, PS + 2).unwrap();
+        assert_eq!(m.allocated_pages(), 2);
+        assert_eq!(m.size().unwrap(), 3 * PS);
+        let mut v = [9u8; 4];
+        m.read_exact_at(&mut v[..], PS - 2).unwrap();
+        assert_eq!(v, [1, 0, 0, 0]);
+        m.read_exact_at(&mut v[..], 2 * PS - 1).unwrap();
+        assert_eq!(v, [0, 0, 1, 1]);
+    }
+
+    #[cfg(all(feature = "discard", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn file() {
+        let f = crate::testing::temp_file();
+        crate::WriteAt::write_all_at(&f, &[1u8; 10000], 0).unwrap();
+        Discard::punch_hole(&f, 10, 20).unwrap();
+        Discard::zero_range(&f, 9000, 5000).unwrap();
+        assert_eq!(f.size().unwrap(), 10000);
+        let mut v = vec![0u8; 10000];
+        ReadAt::read_exact_at(&f, &mut v[..], 0).unwrap();
+        assert!(v[10..30].iter().chain(&v[9000..]).all(|&b| b == 0));
+        assert!(v[..10].iter().chain(&v[30..9000]).all(|&b| b == 1));
+    }
+
+    /// Block devices are sized with `BLKGETSIZE64` even without `linux-blk`, so ranges are not clipped away.
+    /// Destroys the data of the device, so it runs only with `READ_WRITE_AT_TEST_LOOP_DEVICE` set to a scratch loop device.
+    #[cfg(all(feature = "discard", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn loop_device() {
+        use crate::{ReadAt, WriteAt};
+        use std::io::ErrorKind;
+        let path = match std::env::var_os("READ_WRITE_AT_TEST_LOOP_DEVICE") {
+            Some(p) => p,
+            None => return,
+        };
+        assert!(std::path::Path::new(&path).file_name().unwrap().to_string_lossy().starts_with("loop"));
+        let f = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        assert!(crate::blk::size(&f).unwrap() >= 8192);
+        f.write_all_at(&[1; 8192], 0).unwrap();
+        assert_eq!(Discard::zero_range(&f, 1, 4096).unwrap_err().kind(), ErrorKind::InvalidInput);
+        Discard::zero_range(&f, 0, 4096).unwrap();
+        let mut v = vec![9; 8192];
+        f.read_exact_at(&mut v, 0).unwrap();
+        assert!(v[..4096].iter().all(|&b| b == 0) && v[4096..].iter().all(|&b| b == 1));
+    }
+}
@@ -0,0 +1,229 @@
+// `is_multiple_of` is newer than the supported Rust version
+#![allow(clippy::manual_is_multiple_of)]
+
+use super::{read_up_to, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use aes::cipher::KeyInit;
+use aes::{Aes128, Aes256};
+use std::io::{Error, ErrorKind, Result};
+use xts_mode::{get_tweak_default, Xts128};
+
+/// Upper bound of data handled by a single call; larger requests are served partially
+const MAX_CHUNK: u64 = 1 << 20;
+
+/// Boxed, as expanded keys take more than a kilobyte
+enum Cipher {
+    Aes128(Box<Xts128<Aes128>>),
+    Aes256(Box<Xts128<Aes256>>),
+}
+
+impl Cipher {
+    fn encrypt(&self, area: &mut [u8], sector_size: usize, first: u64) {
+        match self {
+            Cipher::Aes128(x) => x.encrypt_area(area, sector_size, first.into(), get_tweak_default),
+            Cipher::Aes256(x) => x.encrypt_area(area, sector_size, first.into(), get_tweak_default),
+        }
+    }
+
+    fn decrypt(&self, area: &mut [u8], sector_size: usize, first: u64) {
+        match self {
+            Cipher::Aes128(x) => x.decrypt_area(area, sector_size, first.into(), get_tweak_default),
+            Cipher::Aes256(x) => x.decrypt_area(area, sector_size, first.into(), get_tweak_default),
+        }
+    }
+}
+
+/// Transparent AES-XTS encryption of the wrapped object, sector by sector, as in full-disk encryption.
+///
+/// The wrapped object stores ciphertext, the tweak of each sector being its index in little endian, like `aes-xts-plain64`
+/// in dm-crypt. Sizes of data and ciphertext are the same, so the wrapped object can be a partition or an image as is.
+/// XTS provides confidentiality only: modified ciphertext decrypts to garbage instead of failing,
+/// and sectors that were never written (e.g. holes) read as garbage rather than zeros.
+///
+/// Partially covered sectors of writes are read, decrypted, patched and encrypted again; writing past the end
+/// therefore extends the object to a multiple of the sector size. This is not atomic: concurrent writes to the same sector
+/// can lose data. A single call handles at most 1 MiB (or one sector, if larger), so it may be short;
+/// use `read_exact_at` and `write_all_at` for larger requests.
+///
+/// Requires `xts` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{EncryptedDevice,ReadAt,WriteAt};
+/// use std::sync::Mutex;
+///
+/// let key = [0x42u8; 64];
+/// let d = EncryptedDevice::new(Mutex::new(vec![]), &key, 512).unwrap();
+/// d.write_all_at(b"secret", 1000).unwrap();
+/// assert_eq!(d.get_ref().lock().unwrap().len(), 1024);
+/// assert!(!d.get_ref().lock().unwrap().windows(6).any(|w| w == b"secret"));
+///
+/// let mut buf = [0u8; 6];
+/// d.read_exact_at(&mut buf[..], 1000).unwrap();
+/// assert_eq!(&buf, b"secret");
+/// ```
+pub struct EncryptedDevice<T> {
+    inner: T,
+    cipher: Cipher,
+    sector_size: usize,
+}
+
+impl<T> EncryptedDevice<T> {
+    /// Encrypt `inner` with a 32-byte (AES-128-XTS) or 64-byte (AES-256-XTS) `key`, which consists of the data key
+    /// followed by the tweak key. Fails if the key has another length, or if `sector_size` is below 16 bytes.
+    pub fn new(inner: T, key: &[u8], sector_size: usize) -> Result<Self> {
+        if sector_size < 16 {
+            return Err(Error::new(ErrorKind::InvalidInput, "sector size must be at least 16 bytes"));
+        }
+        let (k1, k2) = key.split_at(key.len() / 2);
+        let cipher = match key.len() {
+            32 => Cipher::Aes128(Box::new(Xts128::new(Aes128::new(k1.into()), Aes128::new(k2.into())))),
+            64 => Cipher::Aes256(Box::new(Xts128::new(Aes256::new(k1.into()), Aes256::new(k2.into())))),
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "XTS key must be 32 or 64 bytes long")),
+        };
+        Ok(EncryptedDevice { inner, cipher, sector_size })
+    }
+
+    /// Sector size specified at construction time
+    pub fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// First sector and the sector after the last one covering the start of `offset..offset+len`
+    fn covering(&self, offset: u64, len: usize) -> Result<(u64, u64)> {
+        let ss = self.sector_size as u64;
+        let first = offset / ss;
+        let end = offset
+            .checked_add(len as u64)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "offset overflows u64"))?;
+        Ok((first, end.div_ceil(ss).min(first + (MAX_CHUNK / ss).max(1))))
+    }
+}
+
+impl<T: ReadAt> EncryptedDevice<T> {
+    /// Read and decrypt whole sectors starting with `first` into `data`, returning the number of bytes available
+    fn load(&self, first: u64, data: &mut [u8]) -> Result<usize> {
+        let ss = self.sector_size;
+        let n = read_up_to(&self.inner, data, first * ss as u64)? / ss * ss;
+        self.cipher.decrypt(&mut data[..n], ss, first);
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt> ReadAt for EncryptedDevice<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let ss = self.sector_size as u64;
+        let (first, last) = self.covering(offset, buf.len())?;
+        let mut data = vec![0u8; ((last - first) * ss) as usize];
+        let got = self.load(first, &mut data)?;
+        let within = (offset - first * ss) as usize;
+        let n = got.saturating_sub(within).min(buf.len());
+        buf[..n].copy_from_slice(&data[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt + WriteAt> WriteAt for EncryptedDevice<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let ss = self.sector_size;
+        let (first, last) = self.covering(offset, buf.len())?;
+        let mut data = vec![0u8; (last - first) as usize * ss];
+        let within = (offset - first * ss as u64) as usize;
+        let n = (data.len() - within).min(buf.len());
+        // Only partially covered sectors need their old contents; missing ones are zeros
+        if within != 0 {
+            self.load(first, &mut data[..ss])?;
+        }
+        let tail = within + n;
+        if tail % ss != 0 && (last - first > 1 || within == 0) {
+            let l = data.len() - ss;
+            self.load(last - 1, &mut data[l..])?;
+        }
+        data[within..tail].copy_from_slice(&buf[..n]);
+        self.cipher.encrypt(&mut data, ss, first);
+        self.inner.write_all_at(&data, first * ss as u64)?;
+        Ok(n)
+    }
+}
+
+impl<T: SizeAt> SizeAt for EncryptedDevice<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+/// Only whole sectors can be encrypted, so `new_len` must be a multiple of the sector size
+impl<T: ResizeAt> ResizeAt for EncryptedDevice<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        if new_len % self.sector_size as u64 != 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "size must be a multiple of the sector size"));
+        }
+        self.inner.set_len(new_len)
+    }
+}
+
+impl<T: SyncAt> SyncAt for EncryptedDevice<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.sync_range(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn sectors() {
+        // IEEE 1619 vector 4: sectors of 512 bytes with bytes 0..=255 twice, starting with sector 0
+        let key: Vec<u8> = [0x27, 0x18, 0x28, 0x18, 0x28, 0x45, 0x90, 0x45, 0x23, 0x53, 0x60, 0x28, 0x74, 0x71, 0x35, 0x26]
+            .iter()
+            .chain(&[0x31, 0x41, 0x59, 0x26, 0x53, 0x58, 0x97, 0x93, 0x23, 0x84, 0x62, 0x64, 0x33, 0x83, 0x27, 0x95])
+            .copied()
+            .collect();
+        let d = EncryptedDevice::new(Mutex::new(vec![]), &key, 512).unwrap();
+        let plain: Vec<u8> = (0..1024).map(|x| x as u8).collect();
+        d.write_all_at(&plain, 0).unwrap();
+        assert_eq!(d.get_ref().lock().unwrap()[..4], [0x27, 0xa7, 0x47, 0x9b]);
+
+        d.write_all_at(&[1; 3], 510).unwrap();
+        d.write_all_at(&[2; 3], 1500).unwrap();
+        let mut v = vec![0u8; 1536];
+        d.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v[..510], plain[..510]);
+        assert_eq!(v[510..513], [1; 3]);
+        assert_eq!(v[513..1024], plain[513..]);
+        assert_eq!(v[1024..1500], [0; 476][..]);
+        assert_eq!(v[1500..1503], [2; 3]);
+        assert_eq!(d.read_at(&mut v, 1536).unwrap(), 0);
+
+        assert!(d.set_len(1000).is_err());
+        assert!(EncryptedDevice::new((), &[0; 16], 512).is_err());
+        assert!(EncryptedDevice::new((), &key, 8).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use super::{read_up_to, BufReadAt, Chain, Instrumented, Rate, ReadAt, ReadAtMut, SizeAt, SubRange, Take, Throttled};
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Result};
+
+/// Extension methods for `ReadAt` returning freshly allocated vectors.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::ReadAtExt;
+///
+/// let data = b"hello, world".to_vec();
+/// assert_eq!(data.read_range(7, 100).unwrap(), b"world");
+/// assert_eq!(data.read_exact_to_vec(0, 5).unwrap(), b"hello");
+/// assert_eq!(data.read_to_end_at(5).unwrap(), b", world");
+/// ```
+pub trait ReadAtExt: ReadAt {
+    /// Read up to `len` bytes at `offset`; the result is only shorter at end of file
+    fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        let n = read_up_to(self, &mut buf[..], offset)?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    /// Read exactly `len` bytes at `offset`, failing with `UnexpectedEof` at end of file
+    fn read_exact_to_vec(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.read_exact_at(&mut buf[..], offset)?;
+        Ok(buf)
+    }
+
+    /// Read everything from `offset` to end of file.
+    ///
+    /// `size` is only a hint: reading goes on past it if the object has grown.
+    fn read_to_end_at(&self, offset: u64) -> Result<Vec<u8>>
+    where
+        Self: SizeAt,
+    {
+        const STEP: usize = 8 * 1024;
+        let hint = usize::try_from(self.size()?.saturating_sub(offset))
+            .map_err(|_| Error::new(ErrorKind::OutOfMemory, "object does not fit in memory"))?;
+        let mut buf = vec![0u8; hint.max(STEP)];
+        let mut filled = 0;
+        loop {
+            let n = read_up_to(self, &mut buf[filled..], offset + filled as u64)?;
+            filled += n;
+            if filled < buf.len() {
+                break;
+            }
+            buf.resize(filled + STEP, 0);
+        }
+        buf.truncate(filled);
+        Ok(buf)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAtExt for T {}
+
+/// Builder methods wrapping an object into the adapters of this crate, so that stacks read like iterator chains.
+///
+/// `take` and `chain` have the same names as methods of `std::io::Read` (and `Iterator`);
+/// if those traits are in scope too, call them as e.g. `Combinators::take(x, 10)`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Combinators,ReadAtMut};
+///
+/// let data: Vec<u8> = (0..100).collect();
+/// let mut r = (&data).slice(10..50).take(20).cached(8, 4);
+/// let mut v = [0u8; 3];
+/// r.read_exact_at(&mut v[..], 18).unwrap_err();
+/// r.read_exact_at(&mut v[..], 17).unwrap();
+/// assert_eq!(v, [27, 28, 29]);
+/// ```
+pub trait Combinators: Sized {
+    /// `SubRange` covering `range` of this object
+    fn slice(self, range: std::ops::Range<u64>) -> SubRange<Self> {
+        let len = range.end.saturating_sub(range.start);
+        SubRange::new(self, range.start, len)
+    }
+
+    /// `Chain` of this object followed by `other`, with their current sizes
+    fn chain(self, other: Self) -> Result<Chain<Self>>
+    where
+        Self: SizeAt,
+    {
+        let (a, b) = (self.size()?, other.size()?);
+        if a.checked_add(b).is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput, "total chain length overflows u64"));
+        }
+        Ok(Chain::new(vec![(self, a), (other, b)]))
+    }
+
+    /// `Take` exposing the first `limit` bytes of this object
+    fn take(self, limit: u64) -> Take<Self> {
+        Take::new(self, limit)
+    }
+
+    /// `BufReadAt` caching up to `max_blocks` blocks of `block_size` bytes
+    fn cached(self, block_size: usize, max_blocks: usize) -> BufReadAt<Self>
+    where
+        Self: ReadAt,
+    {
+        BufReadAt::new(self, block_size, max_blocks)
+    }
+
+    /// `Throttled` limiting reads and writes to `rate` each
+    fn throttled(self, rate: Rate) -> Throttled<Self> {
+        Throttled::new(self, rate, rate)
+    }
+
+    /// `Instrumented` collecting statistics of calls
+    fn instrumented(self) -> Instrumented<Self> {
+        Instrumented::new(self)
+    }
+
+    /// `Traced` emitting `tracing` spans for calls. Requires `tracing` feature.
+    #[cfg(feature = "tracing")]
+    fn traced(self) -> super::Traced<Self> {
+        super::Traced::new(self)
+    }
+
+    /// Same as `traced`: calls are logged through `tracing`, whose subscribers can print them or forward them to `log`.
+    /// For counters and latencies instead of a log, use `instrumented`. Requires `tracing` feature.
+    #[cfg(feature = "tracing")]
+    fn logged(self) -> super::Traced<Self> {
+        super::Traced::new(self)
+    }
+}
+
+impl<T: ReadAtMut> Combinators for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reports a stale size
+    struct Grown(Vec<u8>);
+    impl ReadAt for Grown {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            self.0.read_at(buf, offset)
+        }
+    }
+    impl SizeAt for Grown {
+        fn size(&self) -> Result<u64> {
+            Ok(3)
+        }
+    }
+
+    #[test]
+    fn stale_size() {
+        let g = Grown((0..20000u32).map(|x| x as u8).collect());
+        let v = g.read_to_end_at(1).unwrap();
+        assert_eq!(v.len(), 19999);
+        assert_eq!(v[..2], [1, 2]);
+        assert_eq!(g.read_to_end_at(30000).unwrap().len(), 0);
+        assert_eq!(g.read_exact_to_vec(19999, 2).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn chained() {
+        let (a, b) = (vec![1u8, 2], vec![3u8]);
+        let c = Combinators::chain(&a, &b).unwrap().slice(1..3);
+        assert_eq!(c.read_to_end_at(0).unwrap(), [2, 3]);
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::Result;
+use std::ops::Range;
+
+/// Allocation state of an `Extent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExtentKind {
+    /// Stored data
+    Data,
+    /// Not allocated, reads as zeroes
+    Hole,
+    /// Allocated (or virtual), but known to read as zeroes
+    Zero,
+}
+
+/// A range of an object with the same allocation state, returned by `Extents::extents`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Extent {
+    /// Start of the range
+    pub offset: u64,
+    /// Length of the range
+    pub len: u64,
+    /// What is stored there
+    pub kind: ExtentKind,
+}
+
+impl Extent {
+    /// Offset just after the range
+    pub fn end(&self) -> u64 {
+        self.offset + self.len
+    }
+}
+
+/// Objects that can tell which parts of them hold data and which are holes, like sparse files.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Extent,ExtentKind,Extents,SparseMem,WriteAtMut};
+///
+/// let mut m = SparseMem::with_len(3 * 4096);
+/// m.write_all_at(b"x", 5000).unwrap();
+/// assert_eq!(m.extents(0..u64::MAX).unwrap(), [
+///     Extent { offset: 0, len: 4096, kind: ExtentKind::Hole },
+///     Extent { offset: 4096, len: 4096, kind: ExtentKind::Data },
+///     Extent { offset: 8192, len: 4096, kind: ExtentKind::Hole },
+/// ]);
+/// ```
+pub trait Extents {
+    /// Extents covering `range` clipped to the size of the object, in order and without gaps.
+    ///
+    /// Granularity is up to the implementation: data extents may include zeroes,
+    /// and adjacent extents of the same kind are not necessarily merged.
+    fn extents(&self, range: Range<u64>) -> Result<Vec<Extent>>;
+}
+
+/// Append an extent, merging it with the previous one if it is of the same kind
+pub(crate) fn push_extent(v: &mut Vec<Extent>, offset: u64, len: u64, kind: ExtentKind) {
+    if len == 0 {
+        return;
+    }
+    match v.last_mut() {
+        Some(last) if last.kind == kind && last.end() == offset => last.len += len,
+        _ => v.push(Extent { offset, len, kind }),
+    }
+}
+
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: Extents + ?Sized> Extents for $ptr {
+            fn extents(&self, range: Range<u64>) -> Result<Vec<Extent>> {
+                Extents::extents(&**self, range)
+            }
+        }
+    )*};
+}
+
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+/// Uses `SEEK_DATA` and `SEEK_HOLE` with `extents` feature on Linux, Android, FreeBSD and macOS,
+/// restoring the file position afterwards. Otherwise the whole file is reported as data.
+impl Extents for std::fs::File {
+    fn extents(&self, range: Range<u64>) -> Result<Vec<Extent>> {
+        let end = range.end.min(self.metadata()?.len());
+        let mut v = vec![];
+        if range.start >= end {
+            return Ok(v);
+        }
+        #[cfg(all(
+            feature = "extents",
+            any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "macos")
+        ))]
+        {
+            use rustix::fs::{seek, SeekFrom};
+            let saved = seek(self, SeekFrom::Current(0))?;
+            let result = seek_extents(self, range.start, end, &mut v);
+            seek(self, SeekFrom::Start(saved))?;
+            result?;
+        }
+        #[cfg(not(all(
+            feature = "extents",
+            any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "macos")
+        )))]
+        push_extent(&mut v, range.start, end - range.start, ExtentKind::Data);
+        Ok(v)
+    }
+}
+
+#[cfg(all(
+    feature = "extents",
+    any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "macos")
+))]
+fn seek_extents(f: &std::fs::File, mut pos: u64, end: u64, v: &mut Vec<Extent>) -> Result<()> {
+    use rustix::fs::{seek, SeekFrom};
+    use rustix::io::Errno;
+    while pos < end {
+        let data = match seek(f, SeekFrom::Data(pos)) {
+            Ok(d) => d.min(end),
+            // Past the last data extent
+            Err(Errno::NXIO) => end,
+            // Hole tracking is not supported, treat everything as data
+            Err(Errno::INVAL) => pos,
+            Err(e) => return Err(e.into()),
+        };
+        push_extent(v, pos, data - pos, ExtentKind::Hole);
+        if data >= end {
+            break;
+        }
+        let hole = match seek(f, SeekFrom::Hole(data)) {
+            Ok(h) => h.min(end),
+            Err(Errno::INVAL) => end,
+            Err(e) => return Err(e.into()),
+        };
+        push_extent(v, data, hole - data, ExtentKind::Data);
+        pos = hole;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseMem, WriteAtMut, ZeroDevice};
+
+    #[test]
+    fn clipped_and_merged() {
+        let mut m = SparseMem::with_len(10000);
+        m.write_all_at(&[1; 5000], 100).unwrap();
+        assert_eq!(m.extents(50..9000).unwrap(), [
+            Extent { offset: 50, len: 8142, kind: ExtentKind::Data },
+            Extent { offset: 8192, len: 808, kind: ExtentKind::Hole },
+        ]);
+        assert_eq!(m.extents(10000..20000).unwrap(), []);
+        assert_eq!(ZeroDevice { len: 5 }.extents(3..10).unwrap(), [Extent { offset: 3, len: 2, kind: ExtentKind::Zero }]);
+
+        let mut m = SparseMem::new();
+        m.write_all_at(&[1], u64::MAX - 1).unwrap();
+        assert_eq!(m.extents(u64::MAX - 2..u64::MAX).unwrap(), [Extent { offset: u64::MAX - 2, len: 2, kind: ExtentKind::Data }]);
+    }
+}
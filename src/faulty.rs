@@ -0,0 +1,335 @@
+use super::rng::Rng;
+use super::{Discard, IoOp, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Fail(ErrorKind),
+    Short(usize),
+}
+
+/// A rule of `FaultyDevice`: what to inject and when.
+///
+/// All conditions set by the builder methods must hold for the fault to fire;
+/// without any, it fires on every call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    action: Action,
+    ops: Option<Vec<IoOp>>,
+    range: Option<Range<u64>>,
+    call: Option<u64>,
+    probability: Option<f64>,
+    times: Option<u64>,
+}
+
+impl Fault {
+    fn new(action: Action) -> Self {
+        Fault { action, ops: None, range: None, call: None, probability: None, times: None }
+    }
+
+    /// Fail the call with an error of `kind`
+    pub fn error(kind: ErrorKind) -> Self {
+        Fault::new(Action::Fail(kind))
+    }
+
+    /// Fail the call with `Interrupted`, which callers are expected to retry
+    pub fn interrupted() -> Self {
+        Fault::error(ErrorKind::Interrupted)
+    }
+
+    /// Transfer at most `max` bytes of a read or write; `0` looks like the end of the object.
+    /// Calls other than reads and writes are passed through.
+    pub fn short(max: usize) -> Self {
+        Fault::new(Action::Short(max))
+    }
+
+    /// Only fire on calls of kind `op`. Can be given multiple times to allow several kinds.
+    pub fn on(mut self, op: IoOp) -> Self {
+        self.ops.get_or_insert_with(Vec::new).push(op);
+        self
+    }
+
+    /// Only fire on calls touching `range` of the object. Calls without a range
+    /// (like `size` or `flush`) never match; `set_len` touches the bytes between the old and the new size.
+    pub fn in_range(mut self, range: Range<u64>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Only fire on the call with 0-based index `n`, counting all calls to the device
+    pub fn at_call(mut self, n: u64) -> Self {
+        self.call = Some(n);
+        self
+    }
+
+    /// Fire with probability `p` (`0.0..=1.0`), drawn from the device's seeded generator
+    pub fn with_probability(mut self, p: f64) -> Self {
+        self.probability = Some(p);
+        self
+    }
+
+    /// Fire at most `n` times, then stay inactive
+    pub fn times(mut self, n: u64) -> Self {
+        self.times = Some(n);
+        self
+    }
+
+    fn matches(&self, op: IoOp, call: u64, range: Option<Range<u64>>) -> bool {
+        if self.times == Some(0) {
+            return false;
+        }
+        if let Some(ref ops) = self.ops {
+            if !ops.contains(&op) {
+                return false;
+            }
+        }
+        if let Some(ref r) = self.range {
+            match range {
+                Some(x) if x.start < r.end && r.start < x.end => {}
+                _ => return false,
+            }
+        }
+        !matches!(self.call, Some(n) if n != call)
+    }
+}
+
+struct State {
+    rng: Rng,
+    calls: u64,
+    injected: u64,
+    faults: Vec<Fault>,
+}
+
+/// Injects errors, short reads and writes or `Interrupted` into calls to the wrapped object according to `Fault` rules,
+/// to exercise error handling and retry logic.
+///
+/// Each call is checked against the rules in the order they were added, and the first one that fires decides the outcome;
+/// otherwise the call is forwarded. Probabilistic rules use a generator seeded with `seed`, so that a failing
+/// scenario can be reproduced. Vectored calls are split into plain ones.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Fault,FaultyDevice,IoOp,ReadAt};
+/// use std::io::ErrorKind;
+///
+/// let data = vec![7u8; 8192];
+/// let dev = FaultyDevice::new(&data)
+///     .fault(Fault::error(ErrorKind::InvalidData).on(IoOp::Read).in_range(4096..4097))
+///     .fault(Fault::interrupted().with_probability(0.5))
+///     .fault(Fault::short(100));
+///
+/// // `read_exact_at` retries `Interrupted` and continues after short reads
+/// let mut buf = [0u8; 4096];
+/// dev.read_exact_at(&mut buf[..], 0).unwrap();
+/// assert_eq!(dev.read_exact_at(&mut buf[..], 1).unwrap_err().kind(), ErrorKind::InvalidData);
+/// ```
+pub struct FaultyDevice<T> {
+    inner: T,
+    state: Mutex<State>,
+}
+
+impl<T> FaultyDevice<T> {
+    /// Wrap `inner`, without any faults and with seed `0`
+    pub fn new(inner: T) -> Self {
+        FaultyDevice { inner, state: Mutex::new(State { rng: Rng::new(0), calls: 0, injected: 0, faults: vec![] }) }
+    }
+
+    /// Reseed the generator for probabilistic faults
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.state.get_mut().unwrap_or_else(|e| e.into_inner()).rng = Rng::new(seed);
+        self
+    }
+
+    /// Add a rule
+    pub fn fault(mut self, fault: Fault) -> Self {
+        self.state.get_mut().unwrap_or_else(|e| e.into_inner()).faults.push(fault);
+        self
+    }
+
+    /// Add a rule to a device in use, e.g. to make it fail in the middle of a test
+    pub fn add_fault(&self, fault: Fault) {
+        self.lock().faults.push(fault);
+    }
+
+    /// Remove all rules
+    pub fn clear_faults(&self) {
+        self.lock().faults.clear();
+    }
+
+    /// Number of calls seen so far
+    pub fn calls(&self) -> u64 {
+        self.lock().calls
+    }
+
+    /// Number of faults injected so far
+    pub fn injected(&self) -> u64 {
+        self.lock().injected
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // Rules are always consistent, so poisoning is harmless
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn check(&self, op: IoOp, range: Option<Range<u64>>) -> Option<Action> {
+        let mut s = self.lock();
+        let s = &mut *s;
+        let call = s.calls;
+        s.calls += 1;
+        for f in &mut s.faults {
+            if !f.matches(op, call, range.clone()) {
+                continue;
+            }
+            if let Some(p) = f.probability {
+                if s.rng.next_f64() >= p {
+                    continue;
+                }
+            }
+            if let Some(ref mut n) = f.times {
+                *n -= 1;
+            }
+            if matches!(f.action, Action::Short(_)) && op != IoOp::Read && op != IoOp::Write {
+                return None;
+            }
+            s.injected += 1;
+            return Some(f.action);
+        }
+        None
+    }
+
+    /// Apply faults to a call without a byte count
+    fn call<R>(&self, op: IoOp, range: Option<Range<u64>>, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        match self.check(op, range) {
+            Some(Action::Fail(kind)) => Err(injected(kind)),
+            _ => f(),
+        }
+    }
+}
+
+fn injected(kind: ErrorKind) -> Error {
+    Error::new(kind, "injected fault")
+}
+
+fn span(offset: u64, len: u64) -> Option<Range<u64>> {
+    Some(offset..offset.saturating_add(len))
+}
+
+impl<T: ReadAt> ReadAt for FaultyDevice<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        match self.check(IoOp::Read, span(offset, buf.len() as u64)) {
+            Some(Action::Fail(kind)) => Err(injected(kind)),
+            Some(Action::Short(max)) => {
+                let len = buf.len().min(max);
+                self.inner.read_at(&mut buf[..len], offset)
+            }
+            None => self.inner.read_at(buf, offset),
+        }
+    }
+}
+
+impl<T: WriteAt> WriteAt for FaultyDevice<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        match self.check(IoOp::Write, span(offset, buf.len() as u64)) {
+            Some(Action::Fail(kind)) => Err(injected(kind)),
+            Some(Action::Short(max)) => self.inner.write_at(&buf[..buf.len().min(max)], offset),
+            None => self.inner.write_at(buf, offset),
+        }
+    }
+}
+
+impl<T: SizeAt> SizeAt for FaultyDevice<T> {
+    fn size(&self) -> Result<u64> {
+        self.call(IoOp::Size, None, || self.inner.size())
+    }
+}
+
+impl<T: ResizeAt + SizeAt> ResizeAt for FaultyDevice<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        let old = self.inner.size()?;
+        self.call(IoOp::Resize, Some(old.min(new_len)..old.max(new_len)), || self.inner.set_len(new_len))
+    }
+}
+
+impl<T: SyncAt> SyncAt for FaultyDevice<T> {
+    fn flush(&self) -> Result<()> {
+        self.call(IoOp::Sync, None, || self.inner.flush())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.call(IoOp::Sync, None, || self.inner.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.call(IoOp::Sync, None, || self.inner.sync_data())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Sync, span(offset, len), || self.inner.sync_range(offset, len))
+    }
+}
+
+impl<T: Discard> Discard for FaultyDevice<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Discard, span(offset, len), || self.inner.punch_hole(offset, len))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Discard, span(offset, len), || self.inner.zero_range(offset, len))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Discard, span(offset, len), || self.inner.discard(offset, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules() {
+        let v = Mutex::new(vec![0u8; 100]);
+        let dev = FaultyDevice::new(&v)
+            .fault(Fault::error(ErrorKind::Other).at_call(1))
+            .fault(Fault::short(3).on(IoOp::Write).times(2))
+            .fault(Fault::error(ErrorKind::TimedOut).on(IoOp::Sync));
+        assert_eq!(dev.write_at(&[1; 10], 0).unwrap(), 3);
+        assert_eq!(dev.write_at(&[1; 10], 0).unwrap_err().kind(), ErrorKind::Other);
+        assert_eq!(dev.write_at(&[1; 10], 0).unwrap(), 3);
+        assert_eq!(dev.write_at(&[1; 10], 0).unwrap(), 10);
+        assert_eq!(dev.flush().unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!((dev.calls(), dev.injected()), (5, 4));
+
+        dev.clear_faults();
+        dev.add_fault(Fault::error(ErrorKind::InvalidData).in_range(50..60));
+        let mut buf = [0u8; 10];
+        dev.read_exact_at(&mut buf[..], 40).unwrap();
+        dev.read_exact_at(&mut buf[..], 41).unwrap_err();
+        dev.set_len(55).unwrap_err();
+        dev.set_len(100).unwrap();
+        dev.set_len(20).unwrap_err();
+    }
+
+    #[test]
+    fn seeded() {
+        let outcomes = |seed| {
+            let data = vec![0u8; 10];
+            let dev = FaultyDevice::new(&data).seed(seed).fault(Fault::interrupted().with_probability(0.3));
+            let mut buf = [0u8; 1];
+            (0..100).map(|_| dev.read_at(&mut buf[..], 0).is_err()).collect::<Vec<_>>()
+        };
+        let a = outcomes(1);
+        assert_eq!(a, outcomes(1));
+        assert_ne!(a, outcomes(2));
+        let n = a.iter().filter(|x| **x).count();
+        assert!(n > 10 && n < 50, "{}", n);
+    }
+}
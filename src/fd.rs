@@ -0,0 +1,70 @@
+use super::{ReadAt, WriteAt};
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+
+/// Uses `pread` and `pwrite`, so the descriptor needs to be seekable (a regular file or a block device).
+impl ReadAt for BorrowedFd<'_> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        Ok(rustix::io::pread(self, buf, offset)?)
+    }
+    /// Single `preadv` call, with at most `IOV_MAX` buffers
+    // Target list is from `rustix::io::preadv`
+    #[cfg(not(any(
+        target_os = "cygwin", target_os = "espidf", target_os = "haiku", target_os = "horizon",
+        target_os = "nto", target_os = "redox", target_os = "solaris", target_os = "vita",
+    )))]
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let n = bufs.len().min(crate::IOV_MAX);
+        Ok(rustix::io::preadv(self, &mut bufs[..n], offset)?)
+    }
+}
+
+impl WriteAt for BorrowedFd<'_> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        Ok(rustix::io::pwrite(self, buf, offset)?)
+    }
+    /// Single `pwritev` call, with at most `IOV_MAX` buffers
+    // Target list is from `rustix::io::preadv`
+    #[cfg(not(any(
+        target_os = "cygwin", target_os = "espidf", target_os = "haiku", target_os = "horizon",
+        target_os = "nto", target_os = "redox", target_os = "solaris", target_os = "vita",
+    )))]
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        Ok(rustix::io::pwritev(self, &bufs[..bufs.len().min(crate::IOV_MAX)], offset)?)
+    }
+}
+
+impl ReadAt for OwnedFd {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.as_fd().read_at(buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        self.as_fd().read_vectored_at(bufs, offset)
+    }
+}
+
+impl WriteAt for OwnedFd {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.as_fd().write_at(buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        self.as_fd().write_vectored_at(bufs, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptors() {
+        let f = crate::testing::temp_file();
+        let fd = OwnedFd::from(f);
+        fd.as_fd().write_all_at(b"hello", 3).unwrap();
+        fd.write_vectored_at(&[IoSlice::new(b"ab"), IoSlice::new(b"c")], 0).unwrap();
+        let mut v = [0u8; 8];
+        fd.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(&v, b"abchello");
+        assert_eq!(fd.as_fd().read_at(&mut v[..], 8).unwrap(), 0);
+    }
+}
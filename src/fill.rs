@@ -0,0 +1,152 @@
+use super::extents::push_extent;
+use super::{Discard, Extent, ExtentKind, Extents, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::ops::Range;
+use std::io::{Error, ErrorKind, Result};
+
+/// `/dev/zero` analogue of `len` bytes: reads give zeroes, writes are discarded.
+///
+/// Reading past `len` returns `0`, writing there fails with `WriteZero`.
+/// Use `u64::MAX` as `len` for an unbounded device.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Chain,ReadAt,ZeroDevice};
+///
+/// // Pad data to 8 bytes
+/// let data = vec![1u8, 2, 3];
+/// let padded = Chain::new(vec![
+///     (Box::new(&data) as Box<dyn ReadAt>, 3),
+///     (Box::new(ZeroDevice { len: 5 }), 5),
+/// ]);
+/// let mut v = [9u8; 8];
+/// padded.read_exact_at(&mut v[..], 0).unwrap();
+/// assert_eq!(v, [1, 2, 3, 0, 0, 0, 0, 0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZeroDevice {
+    /// Size of the device
+    pub len: u64,
+}
+
+/// Like `ZeroDevice`, but reads give `byte` instead of zeroes.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{FillDevice,ReadAt};
+///
+/// let d = FillDevice { byte: 0xFF, len: 3 };
+/// let mut v = [0u8; 4];
+/// assert_eq!(d.read_at(&mut v[..], 1).unwrap(), 2);
+/// assert_eq!(v, [0xFF, 0xFF, 0, 0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillDevice {
+    /// Value of every byte
+    pub byte: u8,
+    /// Size of the device
+    pub len: u64,
+}
+
+/// Number of bytes available at `offset` of a `len`-byte device, clamped to `buflen`
+fn available(len: u64, offset: u64, buflen: usize) -> usize {
+    len.saturating_sub(offset).min(buflen as u64) as usize
+}
+
+fn discard(len: u64, buf: &[u8], offset: u64) -> Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    match available(len, offset, buf.len()) {
+        0 => Err(Error::new(
+            ErrorKind::WriteZero,
+            "write past the end of a device",
+        )),
+        n => Ok(n),
+    }
+}
+
+impl ReadAt for FillDevice {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = available(self.len, offset, buf.len());
+        buf[..n].fill(self.byte);
+        Ok(n)
+    }
+}
+
+impl WriteAt for FillDevice {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        discard(self.len, buf, offset)
+    }
+}
+
+impl SizeAt for FillDevice {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len)
+    }
+}
+
+impl SyncAt for FillDevice {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ReadAt for ZeroDevice {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        FillDevice { byte: 0, len: self.len }.read_at(buf, offset)
+    }
+}
+
+impl WriteAt for ZeroDevice {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        discard(self.len, buf, offset)
+    }
+}
+
+impl SizeAt for ZeroDevice {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len)
+    }
+}
+
+impl SyncAt for ZeroDevice {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Discard for ZeroDevice {
+    fn punch_hole(&self, _offset: u64, _len: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Extents for ZeroDevice {
+    fn extents(&self, range: Range<u64>) -> Result<Vec<Extent>> {
+        let end = range.end.min(self.len);
+        let mut v = vec![];
+        push_extent(&mut v, range.start, end.saturating_sub(range.start), ExtentKind::Zero);
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let z = ZeroDevice { len: u64::MAX };
+        let mut v = [1u8; 4];
+        assert_eq!(z.read_at(&mut v[..], u64::MAX - 2).unwrap(), 2);
+        assert_eq!(v, [0, 0, 1, 1]);
+        assert_eq!(z.write_at(&v[..], 0).unwrap(), 4);
+
+        let f = FillDevice { byte: 7, len: 2 };
+        assert_eq!(f.read_at(&mut v[..], 2).unwrap(), 0);
+        assert_eq!(f.write_at(&v[..], 1).unwrap(), 1);
+        assert_eq!(f.write_at(&v[..], 2).unwrap_err().kind(), ErrorKind::WriteZero);
+    }
+}
@@ -0,0 +1,296 @@
+use super::{ReadAt, SizeAt, SyncAt, WriteAt};
+use fuser::{
+    FileAttr, FileType, Filesystem, KernelConfig, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow, FUSE_ROOT_ID,
+};
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const FILE_ID: u64 = 2;
+/// How long the kernel may cache attributes. The size can change by writes from other handles of the device.
+const TTL: Duration = Duration::from_secs(1);
+
+/// Exports an object as the only file in a FUSE filesystem, so that any program can open it.
+///
+/// The mountpoint directory gets a single file named `name` (`device` by default),
+/// owned by the mounting user, with `0644` permissions (`0444` if read-only).
+/// Reads and writes go to the object as is; writes may extend it if the object allows that,
+/// but truncating or otherwise changing the size fails with `EPERM`. `fsync` maps to `sync_data` and `sync_all`.
+/// Requires `fuse` feature and `fusermount` utility to mount.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{FuseFile,SubRange};
+///
+/// // Make the second partition of an image visible as /mnt/part/device
+/// let img = std::fs::File::open("disk.img").unwrap();
+/// let part = SubRange::new(img, 1048576, 1048576 * 100);
+/// FuseFile::new(part).read_only(true).mount("/mnt/part").unwrap();
+/// ```
+pub struct FuseFile<T> {
+    device: T,
+    name: String,
+    read_only: bool,
+    uid: u32,
+    gid: u32,
+    mtime: SystemTime,
+}
+
+impl<T: ReadAt + WriteAt + SizeAt + SyncAt> FuseFile<T> {
+    /// Wrap `device` to be mounted
+    pub fn new(device: T) -> Self {
+        FuseFile { device, name: "device".to_owned(), read_only: false, uid: 0, gid: 0, mtime: SystemTime::now() }
+    }
+
+    /// Set the file name. It can't contain `/`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Refuse writes
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Get a reference to the exported object
+    pub fn get_ref(&self) -> &T {
+        &self.device
+    }
+
+    /// Get back the exported object
+    pub fn into_inner(self) -> T {
+        self.device
+    }
+
+    fn options(&self) -> Vec<MountOption> {
+        let mut options = vec![MountOption::FSName("read_write_at".to_owned()), MountOption::DefaultPermissions];
+        if self.read_only {
+            options.push(MountOption::RO);
+        }
+        options
+    }
+
+    fn check_name(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.contains('/') || self.name == "." || self.name == ".." {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid FUSE file name"));
+        }
+        Ok(())
+    }
+
+    /// Mount at `mountpoint` and serve requests until it gets unmounted
+    pub fn mount<P: AsRef<Path>>(self, mountpoint: P) -> Result<()> {
+        self.check_name()?;
+        let options = self.options();
+        fuser::mount2(self, mountpoint, &options)
+    }
+
+    /// Mount at `mountpoint` and serve requests in a background thread. Dropping the returned session unmounts.
+    pub fn spawn_mount<P: AsRef<Path>>(self, mountpoint: P) -> Result<fuser::BackgroundSession>
+    where
+        T: Send + 'static,
+    {
+        self.check_name()?;
+        let options = self.options();
+        fuser::spawn_mount2(self, mountpoint, &options)
+    }
+
+    fn attr(&self, ino: u64) -> Result<FileAttr> {
+        let (kind, size, perm, nlink) = match ino {
+            FUSE_ROOT_ID => (FileType::Directory, 0, if self.read_only { 0o555 } else { 0o755 }, 2),
+            _ => (FileType::RegularFile, self.device.size()?, if self.read_only { 0o444 } else { 0o644 }, 1),
+        };
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: self.mtime,
+            mtime: self.mtime,
+            ctime: self.mtime,
+            crtime: self.mtime,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+
+    /// Read as much as possible of `len` bytes at `offset`, short only at the end of the object
+    fn read_full(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        let mut filled = 0;
+        while filled < len {
+            match self.device.read_at(&mut buf[filled..], offset + filled as u64) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        buf.truncate(filled);
+        Ok(buf)
+    }
+}
+
+/// `errno` value to report for `e`
+fn errno(e: &Error) -> i32 {
+    if let Some(x) = e.raw_os_error() {
+        return x;
+    }
+    match e.kind() {
+        ErrorKind::NotFound => libc::ENOENT,
+        ErrorKind::PermissionDenied => libc::EACCES,
+        ErrorKind::InvalidInput => libc::EINVAL,
+        ErrorKind::WriteZero => libc::ENOSPC,
+        ErrorKind::Unsupported => libc::ENOTSUP,
+        ErrorKind::OutOfMemory => libc::ENOMEM,
+        ErrorKind::TimedOut => libc::ETIMEDOUT,
+        _ => libc::EIO,
+    }
+}
+
+impl<T: ReadAt + WriteAt + SizeAt + SyncAt> Filesystem for FuseFile<T> {
+    fn init(&mut self, req: &Request<'_>, _config: &mut KernelConfig) -> std::result::Result<(), libc::c_int> {
+        self.uid = req.uid();
+        self.gid = req.gid();
+        Ok(())
+    }
+
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        if parent != FUSE_ROOT_ID || name != OsStr::new(&self.name) {
+            return reply.error(libc::ENOENT);
+        }
+        match self.attr(FILE_ID) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        // Timestamps are silently kept, so that `touch` and copying tools work
+        let attr = match self.attr(ino) {
+            Ok(x) => x,
+            Err(e) => return reply.error(errno(&e)),
+        };
+        if mode.is_some() || uid.is_some() || gid.is_some() || size.is_some_and(|s| s != attr.size) {
+            return reply.error(libc::EPERM);
+        }
+        reply.attr(&TTL, &attr)
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if ino != FILE_ID {
+            return reply.error(libc::EISDIR);
+        }
+        if self.read_only && flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return reply.error(libc::EROFS);
+        }
+        reply.opened(0, 0)
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.read_full(offset as u64, size as usize) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        if self.read_only {
+            return reply.error(libc::EROFS);
+        }
+        match self.device.write_all_at(data, offset as u64) {
+            Ok(()) => {
+                self.mtime = SystemTime::now();
+                reply.written(data.len() as u32)
+            }
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn flush(&mut self, _req: &Request<'_>, _ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        match self.device.flush() {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn fsync(&mut self, _req: &Request<'_>, _ino: u64, _fh: u64, datasync: bool, reply: ReplyEmpty) {
+        let result = if datasync { self.device.sync_data() } else { self.device.sync_all() };
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        if ino != FUSE_ROOT_ID {
+            return reply.error(libc::ENOTDIR);
+        }
+        let entries = [
+            (FUSE_ROOT_ID, FileType::Directory, "."),
+            (FUSE_ROOT_ID, FileType::Directory, ".."),
+            (FILE_ID, FileType::RegularFile, &*self.name),
+        ];
+        for (i, (ino, kind, name)) in entries.iter().enumerate().skip(offset as usize) {
+            // The offset is that of the next entry
+            if reply.add(*ino, i as i64 + 1, *kind, name) {
+                break;
+            }
+        }
+        reply.ok()
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::Result;
+
+/// Sector sizes of block device-like objects, e.g. for choosing `Aligned` block size or I/O granularity.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Aligned,BlockGeometry};
+///
+/// fn aligned_for<T: BlockGeometry>(dev: T) -> std::io::Result<Aligned<T>> {
+///     let block_size = dev.logical_block_size()?;
+///     Ok(Aligned::new(dev, block_size as usize))
+/// }
+/// ```
+pub trait BlockGeometry {
+    /// Smallest unit the object can be addressed in; reads and writes should be aligned to it
+    fn logical_block_size(&self) -> Result<u32>;
+    /// Unit the object is stored in; smaller or unaligned writes may need read-modify-write underneath
+    fn physical_block_size(&self) -> Result<u32> {
+        self.logical_block_size()
+    }
+}
+
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: BlockGeometry + ?Sized> BlockGeometry for $ptr {
+            fn logical_block_size(&self) -> Result<u32> {
+                BlockGeometry::logical_block_size(&**self)
+            }
+            fn physical_block_size(&self) -> Result<u32> {
+                BlockGeometry::physical_block_size(&**self)
+            }
+        }
+    )*};
+}
+
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+/// Block devices report `BLKSSZGET` and `BLKPBSZGET` ioctls.
+/// Other files (e.g. disk images) are byte-addressable, with `st_blksize` as the physical block size.
+///
+/// Requires `linux-blk` feature. Linux and Android only.
+#[cfg(all(feature = "linux-blk", any(target_os = "linux", target_os = "android")))]
+impl BlockGeometry for std::fs::File {
+    fn logical_block_size(&self) -> Result<u32> {
+        if crate::blk::is_block_device(self)? {
+            Ok(rustix::fs::ioctl_blksszget(self)?)
+        } else {
+            Ok(1)
+        }
+    }
+    fn physical_block_size(&self) -> Result<u32> {
+        use std::convert::TryFrom;
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+        let m = self.metadata()?;
+        if m.file_type().is_block_device() {
+            Ok(rustix::fs::ioctl_blkpbszget(self)?)
+        } else {
+            Ok(u32::try_from(m.blksize()).unwrap_or(u32::MAX))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "linux-blk", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn regular_file() {
+        use super::*;
+        use crate::{SizeAt, SubRange};
+        let f = crate::testing::temp_file();
+        f.set_len(5000).unwrap();
+        assert_eq!(f.size().unwrap(), 5000);
+        let s = SubRange::new(&f, 10, 100);
+        assert_eq!(s.logical_block_size().unwrap(), 1);
+        assert!(s.physical_block_size().unwrap() >= 1);
+    }
+}
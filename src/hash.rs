@@ -0,0 +1,107 @@
+use super::{read_up_to, ReadAt};
+use digest::{Digest, Output};
+use std::io::Result;
+
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// Hash up to `len` bytes of `dev` at `offset` with `D`, e.g. `sha2::Sha256`.
+///
+/// Hashing stops early at end of `dev`, so `hash_range::<D, _>(dev, 0, u64::MAX)` hashes the whole object.
+/// `Interrupted` errors are retried.
+///
+/// Requires `digest` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::hash_range;
+/// use sha2::{Digest, Sha256};
+///
+/// let data = b"hello, world".to_vec();
+/// assert_eq!(hash_range::<Sha256, _>(&data, 7, 100).unwrap(), Sha256::digest(b"world"));
+/// ```
+pub fn hash_range<D: Digest, T: ReadAt + ?Sized>(dev: &T, offset: u64, len: u64) -> Result<Output<D>> {
+    hash_range_with_progress::<D, T>(dev, offset, len, |_| {})
+}
+
+/// Like `hash_range`, calling `progress` with the number of bytes hashed so far after each chunk
+pub fn hash_range_with_progress<D, T>(dev: &T, offset: u64, len: u64, mut progress: impl FnMut(u64)) -> Result<Output<D>>
+where
+    D: Digest,
+    T: ReadAt + ?Sized,
+{
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; (len.min(BUFFER_SIZE as u64)) as usize];
+    let mut done = 0u64;
+    while done < len {
+        let chunk = (len - done).min(buf.len() as u64) as usize;
+        let n = read_up_to(dev, &mut buf[..chunk], offset + done)?;
+        hasher.update(&buf[..n]);
+        done += n as u64;
+        if n > 0 {
+            progress(done);
+        }
+        if n < chunk {
+            break;
+        }
+    }
+    Ok(hasher.finalize())
+}
+
+/// Async version of `hash_range_with_progress`.
+///
+/// Requires `digest` and `async` features.
+#[cfg(feature = "async")]
+pub async fn async_hash_range<D, T>(dev: &T, offset: u64, len: u64, mut progress: impl FnMut(u64)) -> Result<Output<D>>
+where
+    D: Digest,
+    T: crate::AsyncReadAt + ?Sized,
+{
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; (len.min(BUFFER_SIZE as u64)) as usize];
+    let mut done = 0u64;
+    while done < len {
+        let chunk = (len - done).min(buf.len() as u64) as usize;
+        let n = match dev.read_at(&mut buf[..chunk], offset + done).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..n]);
+        done += n as u64;
+        progress(done);
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Sha256;
+
+    #[test]
+    fn chunks_and_progress() {
+        let data: Vec<u8> = (0..600_000u32).map(|x| (x % 251) as u8).collect();
+        let mut progress = vec![];
+        let h = hash_range_with_progress::<Sha256, _>(&data, 100, u64::MAX, |n| progress.push(n)).unwrap();
+        assert_eq!(h, Sha256::digest(&data[100..]));
+        assert_eq!(progress, [262_144, 524_288, 599_900]);
+        assert_eq!(hash_range::<Sha256, _>(&data, 700_000, 10).unwrap(), Sha256::digest(b""));
+
+        #[cfg(feature = "async")]
+        {
+            use crate::{AsyncReadAt, BoxFuture};
+
+            struct Mem<'a>(&'a [u8]);
+            impl AsyncReadAt for Mem<'_> {
+                fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+                    Box::pin(async move { ReadAt::read_at(self.0, buf, offset) })
+                }
+            }
+            let mem = Mem(&data);
+            let f = async_hash_range::<Sha256, _>(&mem, 0, 1000, |_| {});
+            assert_eq!(crate::async_traits::tests::block_on(f).unwrap(), Sha256::digest(&data[..1000]));
+        }
+    }
+}
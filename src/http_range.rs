@@ -0,0 +1,371 @@
+#[cfg(feature = "http-async")]
+use super::{AsyncReadAt, BoxFuture};
+#[cfg(feature = "http")]
+use super::{ReadAt, SizeAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// `Range` header value for `offset..offset+len`, `len` being nonzero
+pub(crate) fn range(offset: u64, len: u64) -> String {
+    format!("bytes={}-{}", offset, offset + len - 1)
+}
+
+/// Value for `If-Range`: a strong `ETag`, or else `Last-Modified`. Weak `ETag`s are not allowed there.
+fn validator(etag: Option<&str>, last_modified: Option<&str>) -> Option<String> {
+    match etag {
+        Some(e) if !e.starts_with("W/") => Some(e.to_owned()),
+        _ => last_modified.map(|x| x.to_owned()),
+    }
+}
+
+/// Parse `Content-Range: bytes first-last/total` (or `bytes */total`) into start offset and total length
+pub(crate) fn content_range(v: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (range, total) = v.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let total = if total == "*" { None } else { Some(total.parse().ok()?) };
+    let start = if range == "*" { None } else { Some(range.split_once('-')?.0.parse().ok()?) };
+    Some((start, total))
+}
+
+pub(crate) fn no_ranges() -> Error {
+    Error::new(ErrorKind::Unsupported, "HTTP server does not support range requests")
+}
+
+pub(crate) fn changed() -> Error {
+    Error::new(ErrorKind::InvalidData, "remote resource changed since it was opened")
+}
+
+pub(crate) fn status_error(status: u16) -> Error {
+    let kind = match status {
+        404 | 410 => ErrorKind::NotFound,
+        401 | 403 => ErrorKind::PermissionDenied,
+        _ => ErrorKind::Other,
+    };
+    Error::new(kind, format!("HTTP status {}", status))
+}
+
+/// Size of the resource from the reply to the initial `bytes=0-0` request
+fn probe_reply(status: u16, content_range_header: Option<&str>) -> Result<u64> {
+    match (status, content_range_header.and_then(content_range)) {
+        (206, Some((Some(0), Some(total)))) => Ok(total),
+        // Empty resources have no byte 0
+        (416, Some((None, Some(total)))) => Ok(total),
+        (200, _) | (206, _) => Err(no_ranges()),
+        (416, _) => Ok(0),
+        (x, _) => Err(status_error(x)),
+    }
+}
+
+/// Check the reply to a range request, with `validator` sent as `If-Range`
+fn check_reply(status: u16, content_range_header: Option<&str>, size: u64, offset: u64) -> Result<()> {
+    match status {
+        206 => match content_range_header.and_then(content_range) {
+            Some((Some(start), total)) if start == offset && total.unwrap_or(size) == size => Ok(()),
+            _ => Err(changed()),
+        },
+        // `If-Range` mismatch sends the whole new version
+        200 => Err(changed()),
+        // The resource got shorter
+        416 => Err(changed()),
+        x => Err(status_error(x)),
+    }
+}
+
+/// `ReadAt` over a remote file, issuing an HTTP `GET` with a `Range` header for each read.
+///
+/// Opening checks that the server supports ranges and remembers the size, as well as the `ETag` (or `Last-Modified`)
+/// of the resource. Reads send it as `If-Range`, so they fail with `InvalidData` instead of mixing data of two versions
+/// if the resource gets replaced.
+///
+/// Each `read_at` is a separate request, so wrap it into `BufReadAt` or `ReadAhead` for small or sequential reads.
+/// Requires `http` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{HttpRangeReader,ReadAt,SizeAt};
+///
+/// let r = HttpRangeReader::new("https://example.com/disk.img").unwrap();
+/// let mut header = [0u8; 512];
+/// r.read_exact_at(&mut header[..], r.size().unwrap() - 512).unwrap();
+/// ```
+#[cfg(feature = "http")]
+pub struct HttpRangeReader {
+    agent: ureq::Agent,
+    url: String,
+    size: u64,
+    validator: Option<String>,
+}
+
+#[cfg(feature = "http")]
+pub(crate) fn ureq_error(e: ureq::Error) -> Error {
+    match e {
+        ureq::Error::Status(status, _) => status_error(status),
+        e => Error::other(e),
+    }
+}
+
+/// Read the body of a reply into `buf`, up to its end
+#[cfg(feature = "http")]
+pub(crate) fn read_body(resp: ureq::Response, buf: &mut [u8]) -> Result<usize> {
+    let mut body = resp.into_reader();
+    let mut filled = 0;
+    while filled < buf.len() {
+        match std::io::Read::read(&mut body, &mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(x) => filled += x,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(feature = "http")]
+impl HttpRangeReader {
+    /// Open `url` with a default `ureq::Agent`
+    pub fn new(url: &str) -> Result<Self> {
+        HttpRangeReader::with_agent(ureq::Agent::new(), url)
+    }
+
+    /// Open `url` with an `ureq::Agent` configured by the caller, e.g. with timeouts or a proxy
+    pub fn with_agent(agent: ureq::Agent, url: &str) -> Result<Self> {
+        let resp = match agent.get(url).set("Range", &range(0, 1)).call() {
+            Ok(x) => x,
+            Err(ureq::Error::Status(416, x)) => x,
+            Err(e) => return Err(ureq_error(e)),
+        };
+        let size = probe_reply(resp.status(), resp.header("Content-Range"))?;
+        let validator = validator(resp.header("ETag"), resp.header("Last-Modified"));
+        Ok(HttpRangeReader { agent, url: url.to_owned(), size, validator })
+    }
+
+    /// URL given at construction time
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// `ETag` or `Last-Modified` value the reads are validated against
+    pub fn validator(&self) -> Option<&str> {
+        self.validator.as_deref()
+    }
+}
+
+/// Reads are clipped to the size found when opening.
+#[cfg(feature = "http")]
+impl ReadAt for HttpRangeReader {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = self.size.saturating_sub(offset).min(buf.len() as u64) as usize;
+        if n == 0 {
+            return Ok(0);
+        }
+        let mut req = self.agent.get(&self.url).set("Range", &range(offset, n as u64));
+        if let Some(v) = &self.validator {
+            req = req.set("If-Range", v);
+        }
+        let resp = match req.call() {
+            Ok(x) => x,
+            Err(ureq::Error::Status(416, _)) => return Err(changed()),
+            Err(e) => return Err(ureq_error(e)),
+        };
+        check_reply(resp.status(), resp.header("Content-Range"), self.size, offset)?;
+        read_body(resp, &mut buf[..n])
+    }
+}
+
+#[cfg(feature = "http")]
+impl SizeAt for HttpRangeReader {
+    fn size(&self) -> Result<u64> {
+        Ok(self.size)
+    }
+}
+
+/// `AsyncReadAt` counterpart of `HttpRangeReader`, based on `reqwest` (and thus requiring a Tokio runtime).
+///
+/// Requires `http-async` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{AsyncHttpRangeReader,AsyncReadAt};
+///
+/// async fn trailer() -> std::io::Result<[u8; 22]> {
+///     let r = AsyncHttpRangeReader::new("https://example.com/archive.zip").await?;
+///     let mut v = [0u8; 22];
+///     r.read_exact_at(&mut v[..], r.size() - 22).await?;
+///     Ok(v)
+/// }
+/// ```
+#[cfg(feature = "http-async")]
+pub struct AsyncHttpRangeReader {
+    client: reqwest::Client,
+    url: String,
+    size: u64,
+    validator: Option<String>,
+}
+
+#[cfg(feature = "http-async")]
+fn header<'a>(resp: &'a reqwest::Response, name: &str) -> Option<&'a str> {
+    resp.headers().get(name).and_then(|x| x.to_str().ok())
+}
+
+#[cfg(feature = "http-async")]
+impl AsyncHttpRangeReader {
+    /// Open `url` with a default `reqwest::Client`
+    pub async fn new(url: &str) -> Result<Self> {
+        AsyncHttpRangeReader::with_client(reqwest::Client::new(), url).await
+    }
+
+    /// Open `url` with a `reqwest::Client` configured by the caller
+    pub async fn with_client(client: reqwest::Client, url: &str) -> Result<Self> {
+        let resp = client.get(url).header("Range", range(0, 1)).send().await.map_err(Error::other)?;
+        let size = probe_reply(resp.status().as_u16(), header(&resp, "Content-Range"))?;
+        let validator = validator(header(&resp, "ETag"), header(&resp, "Last-Modified"));
+        Ok(AsyncHttpRangeReader { client, url: url.to_owned(), size, validator })
+    }
+
+    /// URL given at construction time
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Size found when opening
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// `ETag` or `Last-Modified` value the reads are validated against
+    pub fn validator(&self) -> Option<&str> {
+        self.validator.as_deref()
+    }
+
+    async fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = self.size.saturating_sub(offset).min(buf.len() as u64) as usize;
+        if n == 0 {
+            return Ok(0);
+        }
+        let mut req = self.client.get(&self.url).header("Range", range(offset, n as u64));
+        if let Some(v) = &self.validator {
+            req = req.header("If-Range", v);
+        }
+        let resp = req.send().await.map_err(Error::other)?;
+        check_reply(resp.status().as_u16(), header(&resp, "Content-Range"), self.size, offset)?;
+        let body = resp.bytes().await.map_err(Error::other)?;
+        let filled = body.len().min(n);
+        buf[..filled].copy_from_slice(&body[..filled]);
+        Ok(filled)
+    }
+}
+
+/// Reads are clipped to the size found when opening.
+#[cfg(feature = "http-async")]
+impl AsyncReadAt for AsyncHttpRangeReader {
+    fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(self.read(buf, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Resource served by `server`: contents and `ETag`
+    type Resource = Arc<Mutex<(Vec<u8>, String)>>;
+
+    /// Minimal HTTP/1.1 server honoring `Range` and `If-Range`, one request per connection
+    fn server(resource: Resource) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for conn in listener.incoming() {
+                let mut conn = conn.unwrap();
+                let mut range = None;
+                let mut if_range = None;
+                for line in BufReader::new(&conn).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((k, v)) = line.split_once(": ") {
+                        match &*k.to_ascii_lowercase() {
+                            "range" => range = Some(v.trim_start_matches("bytes=").to_owned()),
+                            "if-range" => if_range = Some(v.to_owned()),
+                            _ => {}
+                        }
+                    }
+                }
+                let (data, etag) = &*resource.lock().unwrap();
+                let range = range.filter(|_| !matches!(&if_range, Some(x) if x != etag));
+                let (status, headers, body) = match range.and_then(|r| {
+                    let (a, b) = r.split_once('-')?;
+                    Some((a.parse::<usize>().ok()?, b.parse::<usize>().ok()?))
+                }) {
+                    Some((a, _)) if a >= data.len() => {
+                        ("416 Range Not Satisfiable", format!("Content-Range: bytes */{}\r\n", data.len()), &[][..])
+                    }
+                    Some((a, b)) => {
+                        let b = b.min(data.len() - 1);
+                        ("206 Partial Content", format!("Content-Range: bytes {}-{}/{}\r\n", a, b, data.len()), &data[a..=b])
+                    }
+                    None => ("200 OK", String::new(), &data[..]),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nETag: {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    etag,
+                    headers,
+                    body.len()
+                );
+                conn.write_all(head.as_bytes()).unwrap();
+                conn.write_all(body).unwrap();
+            }
+        });
+        url
+    }
+
+    fn resource() -> Resource {
+        Arc::new(Mutex::new(((0..=255u8).cycle().take(10_000).collect(), "\"v1\"".to_owned())))
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(content_range("bytes 10-19/100"), Some((Some(10), Some(100))));
+        assert_eq!(content_range("bytes */0"), Some((None, Some(0))));
+        assert_eq!(content_range("bytes 0-0/*"), Some((Some(0), None)));
+        assert_eq!(validator(Some("W/\"x\""), Some("Mon")), Some("Mon".to_owned()));
+        assert_eq!(probe_reply(200, None).unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn blocking() {
+        let res = resource();
+        let r = HttpRangeReader::new(&server(res.clone())).unwrap();
+        assert_eq!(r.size().unwrap(), 10_000);
+        assert_eq!(r.validator(), Some("\"v1\""));
+        let mut v = [0u8; 100];
+        assert_eq!(r.read_at(&mut v[..], 9_950).unwrap(), 50);
+        assert_eq!(v[0], (9_950 % 256) as u8);
+        assert_eq!(r.read_at(&mut v[..], 10_000).unwrap(), 0);
+        res.lock().unwrap().1 = "\"v2\"".to_owned();
+        assert_eq!(r.read_at(&mut v[..], 0).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        res.lock().unwrap().0.clear();
+        assert_eq!(HttpRangeReader::new(r.url()).unwrap().size().unwrap(), 0);
+    }
+
+    #[cfg(feature = "http-async")]
+    #[test]
+    fn nonblocking() {
+        let url = server(resource());
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let r = AsyncHttpRangeReader::new(&url).await.unwrap();
+            assert_eq!(r.size(), 10_000);
+            let mut v = [0u8; 10];
+            r.read_exact_at(&mut v[..], 300).await.unwrap();
+            assert_eq!(v[0], 44);
+        });
+    }
+}
@@ -0,0 +1,365 @@
+use super::{Discard, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of buckets in a `LatencyHistogram`
+pub const LATENCY_BUCKETS: usize = 32;
+
+/// Kind of operation counted by `Instrumented`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IoOp {
+    /// `read_at` and `read_vectored_at`
+    Read,
+    /// `write_at` and `write_vectored_at`
+    Write,
+    /// `size`
+    Size,
+    /// `set_len`
+    Resize,
+    /// `flush`, `sync_all`, `sync_data` and `sync_range`
+    Sync,
+    /// `punch_hole`, `zero_range` and `discard`
+    Discard,
+}
+
+impl IoOp {
+    /// All kinds, in the order of their indexes in `IoStats`
+    pub const ALL: [IoOp; 6] = [IoOp::Read, IoOp::Write, IoOp::Size, IoOp::Resize, IoOp::Sync, IoOp::Discard];
+
+    /// Lowercase name, as used for the `op` label of `metrics`
+    pub fn name(self) -> &'static str {
+        match self {
+            IoOp::Read => "read",
+            IoOp::Write => "write",
+            IoOp::Size => "size",
+            IoOp::Resize => "resize",
+            IoOp::Sync => "sync",
+            IoOp::Discard => "discard",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Latencies in power-of-two buckets: bucket `0` counts calls shorter than 1µs,
+/// bucket `i` those from 2<sup>i-1</sup>µs to 2<sup>i</sup>µs, the last one also everything longer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    /// Number of calls in each bucket
+    pub fn buckets(&self) -> &[u64; LATENCY_BUCKETS] {
+        &self.buckets
+    }
+
+    /// Exclusive upper bound of bucket `i`; `Duration::MAX` for the last one
+    pub fn bucket_bound(i: usize) -> Duration {
+        if i + 1 >= LATENCY_BUCKETS {
+            Duration::MAX
+        } else {
+            Duration::from_micros(1 << i)
+        }
+    }
+
+    /// Total number of calls
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Upper bound of the bucket holding the `q`-th quantile (`0.0..=1.0`), `None` if empty.
+    /// E.g. `quantile(0.99)` is a duration which at least 99% of calls did not exceed.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(LatencyHistogram::bucket_bound(i));
+            }
+        }
+        None
+    }
+}
+
+fn bucket_of(latency: Duration) -> usize {
+    let us = latency.as_micros();
+    if us == 0 {
+        0
+    } else {
+        ((128 - us.leading_zeros()) as usize).min(LATENCY_BUCKETS - 1)
+    }
+}
+
+/// Statistics of one kind of operation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpStats {
+    /// Calls, including failed ones
+    pub count: u64,
+    /// Calls that returned an error, including `Interrupted`
+    pub errors: u64,
+    /// Bytes read or written; always zero for other operations
+    pub bytes: u64,
+    /// How long calls took
+    pub latency: LatencyHistogram,
+}
+
+/// Snapshot of all statistics of an `Instrumented`, indexed by `IoOp`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoStats {
+    ops: [OpStats; 6],
+}
+
+impl IoStats {
+    /// Statistics of `op`
+    pub fn get(&self, op: IoOp) -> &OpStats {
+        &self.ops[op.index()]
+    }
+}
+
+impl std::ops::Index<IoOp> for IoStats {
+    type Output = OpStats;
+    fn index(&self, op: IoOp) -> &OpStats {
+        self.get(op)
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    count: AtomicU64,
+    errors: AtomicU64,
+    bytes: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS],
+}
+
+/// Counts calls, errors and bytes transferred and records latency histograms for each `IoOp` of the wrapped object.
+///
+/// Counters are atomics, so the wrapper is cheap enough to stay in production stacks;
+/// `stats` takes a snapshot at any time, e.g. for a status page or periodic logging.
+/// With `metrics` feature, `metrics_label` also reports everything through the `metrics` crate facade as
+/// `read_write_at_ops_total`, `read_write_at_errors_total`, `read_write_at_bytes_total` counters
+/// and `read_write_at_op_duration_seconds` histogram, labeled with `device` and `op`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Combinators,IoOp,ReadAt};
+///
+/// let data = vec![0u8; 4096];
+/// let r = (&data).instrumented();
+/// let mut buf = [0u8; 512];
+/// r.read_exact_at(&mut buf[..], 512).unwrap();
+/// r.read_exact_at(&mut buf[..], 4000).unwrap_err();
+///
+/// let stats = r.stats();
+/// assert_eq!(stats[IoOp::Read].count, 3);
+/// assert_eq!(stats[IoOp::Read].bytes, 512 + 96);
+/// println!("p99 of reads: {:?}", stats[IoOp::Read].latency.quantile(0.99).unwrap());
+/// ```
+pub struct Instrumented<T> {
+    inner: T,
+    counters: [Counters; 6],
+    #[cfg(feature = "metrics")]
+    label: Option<String>,
+}
+
+impl<T> Instrumented<T> {
+    /// Wrap `inner` with zeroed counters
+    pub fn new(inner: T) -> Self {
+        Instrumented {
+            inner,
+            counters: Default::default(),
+            #[cfg(feature = "metrics")]
+            label: None,
+        }
+    }
+
+    /// Also report to the `metrics` facade, with `device` label set to `device`.
+    /// Requires `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_label(mut self, device: &str) -> Self {
+        self.label = Some(device.to_owned());
+        self
+    }
+
+    /// Take a snapshot of the counters. Counters of different operations are read one by one,
+    /// so calls in progress may be reflected partially.
+    pub fn stats(&self) -> IoStats {
+        let mut stats = IoStats::default();
+        for (s, c) in stats.ops.iter_mut().zip(self.counters.iter()) {
+            s.count = c.count.load(Ordering::Relaxed);
+            s.errors = c.errors.load(Ordering::Relaxed);
+            s.bytes = c.bytes.load(Ordering::Relaxed);
+            for (b, l) in s.latency.buckets.iter_mut().zip(c.latency.iter()) {
+                *b = l.load(Ordering::Relaxed);
+            }
+        }
+        stats
+    }
+
+    /// Zero all counters. Does not affect what was already reported to `metrics`.
+    pub fn reset(&self) {
+        for c in &self.counters {
+            c.count.store(0, Ordering::Relaxed);
+            c.errors.store(0, Ordering::Relaxed);
+            c.bytes.store(0, Ordering::Relaxed);
+            for l in &c.latency {
+                l.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn record<R>(&self, op: IoOp, f: impl FnOnce() -> Result<R>, bytes: impl FnOnce(&R) -> u64) -> Result<R> {
+        let start = Instant::now();
+        let result = f();
+        let latency = start.elapsed();
+        let n = result.as_ref().map(bytes).unwrap_or(0);
+        let c = &self.counters[op.index()];
+        c.count.fetch_add(1, Ordering::Relaxed);
+        if result.is_err() {
+            c.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        c.bytes.fetch_add(n, Ordering::Relaxed);
+        c.latency[bucket_of(latency)].fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        if let Some(ref device) = self.label {
+            let labels = [("device", device.clone()), ("op", op.name().to_owned())];
+            metrics::counter!("read_write_at_ops_total", &labels).increment(1);
+            if result.is_err() {
+                metrics::counter!("read_write_at_errors_total", &labels).increment(1);
+            }
+            if n > 0 {
+                metrics::counter!("read_write_at_bytes_total", &labels).increment(n);
+            }
+            metrics::histogram!("read_write_at_op_duration_seconds", &labels).record(latency.as_secs_f64());
+        }
+        result
+    }
+
+    fn transfer(&self, op: IoOp, f: impl FnOnce() -> Result<usize>) -> Result<usize> {
+        self.record(op, f, |n| *n as u64)
+    }
+
+    fn call<R>(&self, op: IoOp, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        self.record(op, f, |_| 0)
+    }
+}
+
+impl<T: ReadAt> ReadAt for Instrumented<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.transfer(IoOp::Read, || self.inner.read_at(buf, offset))
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        self.transfer(IoOp::Read, || self.inner.read_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: WriteAt> WriteAt for Instrumented<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.transfer(IoOp::Write, || self.inner.write_at(buf, offset))
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        self.transfer(IoOp::Write, || self.inner.write_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: SizeAt> SizeAt for Instrumented<T> {
+    fn size(&self) -> Result<u64> {
+        self.call(IoOp::Size, || self.inner.size())
+    }
+}
+
+impl<T: ResizeAt> ResizeAt for Instrumented<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.call(IoOp::Resize, || self.inner.set_len(new_len))
+    }
+}
+
+impl<T: SyncAt> SyncAt for Instrumented<T> {
+    fn flush(&self) -> Result<()> {
+        self.call(IoOp::Sync, || self.inner.flush())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.call(IoOp::Sync, || self.inner.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.call(IoOp::Sync, || self.inner.sync_data())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Sync, || self.inner.sync_range(offset, len))
+    }
+}
+
+impl<T: Discard> Discard for Instrumented<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Discard, || self.inner.punch_hole(offset, len))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Discard, || self.inner.zero_range(offset, len))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(IoOp::Discard, || self.inner.discard(offset, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn counters() {
+        let v = Mutex::new(vec![0u8; 100]);
+        let t = Instrumented::new(&v);
+        t.write_all_at(&[1, 2, 3], 10).unwrap();
+        t.set_len(50).unwrap();
+        assert_eq!(t.size().unwrap(), 50);
+        let mut buf = [0u8; 10];
+        assert_eq!(t.read_at(&mut buf[..], 45).unwrap(), 5);
+        t.read_exact_at(&mut buf[..], 45).unwrap_err();
+
+        let s = t.stats();
+        assert_eq!((s[IoOp::Write].count, s[IoOp::Write].bytes), (1, 3));
+        assert_eq!((s[IoOp::Read].count, s[IoOp::Read].bytes, s[IoOp::Read].errors), (3, 10, 0));
+        assert_eq!(s[IoOp::Resize].count, 1);
+        assert_eq!(s[IoOp::Size].latency.count(), 1);
+        assert_eq!(s[IoOp::Sync], OpStats::default());
+        assert!(s[IoOp::Read].latency.quantile(1.0).unwrap() < Duration::MAX);
+
+        t.reset();
+        assert_eq!(t.stats(), IoStats::default());
+    }
+
+    #[test]
+    fn histogram() {
+        assert_eq!(bucket_of(Duration::from_nanos(999)), 0);
+        assert_eq!(bucket_of(Duration::from_micros(1)), 1);
+        assert_eq!(bucket_of(Duration::from_micros(1000)), 10);
+        assert_eq!(bucket_of(Duration::from_secs(1 << 20)), LATENCY_BUCKETS - 1);
+        let mut h = LatencyHistogram::default();
+        h.buckets[2] = 90;
+        h.buckets[10] = 10;
+        assert_eq!(h.quantile(0.5), Some(Duration::from_micros(4)));
+        assert_eq!(h.quantile(0.9), Some(Duration::from_micros(4)));
+        assert_eq!(h.quantile(0.91), Some(Duration::from_micros(1024)));
+        assert_eq!(LatencyHistogram::default().quantile(0.5), None);
+    }
+}
@@ -0,0 +1,164 @@
+use super::{ReadAt, ReadAtMut, WriteAt, WriteAtMut};
+use std::io::Result;
+use std::mem::size_of;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Byte order of the integers read by `ReadIntAt` and written by `WriteIntAt`: `LittleEndian` or `BigEndian`
+pub trait ByteOrder: private::Sealed {
+    /// Whether the least significant byte comes first
+    const IS_LITTLE: bool;
+}
+
+/// Least significant byte first
+pub enum LittleEndian {}
+/// Most significant byte first
+pub enum BigEndian {}
+/// Alias for `LittleEndian`
+pub type LE = LittleEndian;
+/// Alias for `BigEndian`
+pub type BE = BigEndian;
+
+impl private::Sealed for LittleEndian {}
+impl private::Sealed for BigEndian {}
+impl ByteOrder for LittleEndian {
+    const IS_LITTLE: bool = true;
+}
+impl ByteOrder for BigEndian {
+    const IS_LITTLE: bool = false;
+}
+
+macro_rules! read_int_trait {
+    ($(#[$attr:meta])* $name:ident: $base:ident, $($mut_:ident)?) => {
+        $(#[$attr])*
+        pub trait $name: $base {
+            /// Read a byte at `offset`
+            fn read_u8_at(&$($mut_)? self, offset: u64) -> Result<u8> {
+                let mut b = [0u8];
+                self.read_exact_at(&mut b, offset)?;
+                Ok(b[0])
+            }
+
+            /// Read a signed byte at `offset`
+            fn read_i8_at(&$($mut_)? self, offset: u64) -> Result<i8> {
+                Ok(self.read_u8_at(offset)? as i8)
+            }
+
+            read_int_trait!(@method ($($mut_)?) read_u16_at u16);
+            read_int_trait!(@method ($($mut_)?) read_u32_at u32);
+            read_int_trait!(@method ($($mut_)?) read_u64_at u64);
+            read_int_trait!(@method ($($mut_)?) read_u128_at u128);
+            read_int_trait!(@method ($($mut_)?) read_i16_at i16);
+            read_int_trait!(@method ($($mut_)?) read_i32_at i32);
+            read_int_trait!(@method ($($mut_)?) read_i64_at i64);
+            read_int_trait!(@method ($($mut_)?) read_i128_at i128);
+            read_int_trait!(@method ($($mut_)?) read_f32_at f32);
+            read_int_trait!(@method ($($mut_)?) read_f64_at f64);
+        }
+
+        impl<T: $base + ?Sized> $name for T {}
+    };
+    (@method ($($mut_:ident)?) $method:ident $t:ty) => {
+        #[doc = concat!("Read `", stringify!($t), "` at `offset`, in byte order `B`")]
+        fn $method<B: ByteOrder>(&$($mut_)? self, offset: u64) -> Result<$t> {
+            let mut b = [0u8; size_of::<$t>()];
+            self.read_exact_at(&mut b, offset)?;
+            Ok(if B::IS_LITTLE { <$t>::from_le_bytes(b) } else { <$t>::from_be_bytes(b) })
+        }
+    };
+}
+
+macro_rules! write_int_trait {
+    ($(#[$attr:meta])* $name:ident: $base:ident, $($mut_:ident)?) => {
+        $(#[$attr])*
+        pub trait $name: $base {
+            /// Write a byte at `offset`
+            fn write_u8_at(&$($mut_)? self, value: u8, offset: u64) -> Result<()> {
+                self.write_all_at(&[value], offset)
+            }
+
+            /// Write a signed byte at `offset`
+            fn write_i8_at(&$($mut_)? self, value: i8, offset: u64) -> Result<()> {
+                self.write_u8_at(value as u8, offset)
+            }
+
+            write_int_trait!(@method ($($mut_)?) write_u16_at u16);
+            write_int_trait!(@method ($($mut_)?) write_u32_at u32);
+            write_int_trait!(@method ($($mut_)?) write_u64_at u64);
+            write_int_trait!(@method ($($mut_)?) write_u128_at u128);
+            write_int_trait!(@method ($($mut_)?) write_i16_at i16);
+            write_int_trait!(@method ($($mut_)?) write_i32_at i32);
+            write_int_trait!(@method ($($mut_)?) write_i64_at i64);
+            write_int_trait!(@method ($($mut_)?) write_i128_at i128);
+            write_int_trait!(@method ($($mut_)?) write_f32_at f32);
+            write_int_trait!(@method ($($mut_)?) write_f64_at f64);
+        }
+
+        impl<T: $base + ?Sized> $name for T {}
+    };
+    (@method ($($mut_:ident)?) $method:ident $t:ty) => {
+        #[doc = concat!("Write `value` of type `", stringify!($t), "` at `offset`, in byte order `B`")]
+        fn $method<B: ByteOrder>(&$($mut_)? self, value: $t, offset: u64) -> Result<()> {
+            let b = if B::IS_LITTLE { value.to_le_bytes() } else { value.to_be_bytes() };
+            self.write_all_at(&b, offset)
+        }
+    };
+}
+
+read_int_trait!(
+    /// Typed integer reads for `ReadAt` objects, like `byteorder::ReadBytesExt` does for `std::io::Read`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use read_write_at::{ReadIntAt,BE,LE};
+    ///
+    /// let data = &[1u8, 2, 3, 4][..];
+    /// assert_eq!(data.read_u16_at::<BE>(1).unwrap(), 0x0203);
+    /// assert_eq!(data.read_u16_at::<LE>(1).unwrap(), 0x0302);
+    /// assert_eq!(data.read_u8_at(3).unwrap(), 4);
+    /// ```
+    ReadIntAt: ReadAt,
+);
+read_int_trait!(
+    /// Typed integer reads for `ReadAtMut` objects, like `ReadIntAt`
+    ReadIntAtMut: ReadAtMut, mut
+);
+write_int_trait!(
+    /// Typed integer writes for `WriteAt` objects, like `byteorder::WriteBytesExt` does for `std::io::Write`.
+    WriteIntAt: WriteAt,
+);
+write_int_trait!(
+    /// Typed integer writes for `WriteAtMut` objects, like `WriteIntAt`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use read_write_at::{WriteIntAtMut,BE};
+    ///
+    /// let mut v = vec![];
+    /// v.write_u32_at::<BE>(0xCAFE, 2).unwrap();
+    /// assert_eq!(v, [0, 0, 0, 0, 0xCA, 0xFE]);
+    /// ```
+    WriteIntAtMut: WriteAtMut, mut
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{ReadIntAt, WriteIntAtMut, BE, LE};
+
+    #[test]
+    fn round_trip() {
+        let mut v = vec![];
+        v.write_i64_at::<LE>(-2, 1).unwrap();
+        v.write_f32_at::<BE>(1.5, 9).unwrap();
+        v.write_i8_at(-1, 0).unwrap();
+        assert_eq!(v[..3], [0xFF, 0xFE, 0xFF]);
+        assert_eq!(v.read_i64_at::<LE>(1).unwrap(), -2);
+        assert_eq!(v.read_u64_at::<BE>(1).unwrap(), 0xFEFF_FFFF_FFFF_FFFF);
+        assert_eq!(v.read_f32_at::<BE>(9).unwrap(), 1.5);
+        assert_eq!(v.read_u128_at::<LE>(0).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
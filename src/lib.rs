@@ -6,27 +6,496 @@
 //! This crate focuses on the abstraction itself, providing mostly wrappers and helper functions.
 //! 
 //! Traits are given in two varieties: with mutable `&mut self` and immutable `&self` methods.
+//!
+//! `SizeAt` gives total size of an object, forwarded by wrappers where it makes sense.
+//! `ResizeAt` and `ResizeAtMut` allow truncating or extending it.
+//! `SyncAt` and `SyncAtMut` give control over flushing and durability of written data.
 //! 
 //! libstd's platform-specific FileExt traits are forwarded for std::fs::File.
+//! On Windows, they move the cursor, so only `Mut` traits are implemented, unless `windows-native` feature allows `ReadAt` and `WriteAt` at the cost of an unspecified cursor position.
+//! Alternatively, `CursorRestoring` wraps a `File` and puts the cursor back after each call.
+//! On targets without `FileExt`, `PortableFile` gives the immutable traits by seeking under a lock.
+//! On WASI (preview 1), `wasi` feature implements them for `File` via `fd_pread` and `fd_pwrite`.
+//! With `rustix` feature on Unix, raw `BorrowedFd` and `OwnedFd` descriptors (e.g. received over a Unix socket) get them via `pread` and `pwrite`.
+//! 
+//! Vectored IO is supported via `read_vectored_at` and `write_vectored_at`.
+//! With `rustix` feature, they map to `preadv` and `pwritev` for `std::fs::File` on Unix.
 //! 
 //! There is a generic wrapper for using `Read+Seek` or `Read+Write+Seek` objects
 //! 
+//! `CursorAt` goes the other way, giving `Read+Write+Seek+BufRead` for a `ReadAt` and `WriteAt`.
+//! 
+//! Byte slices, `Vec<u8>`, `Box<[u8]>` and `Arc<[u8]>` can be used directly as in-memory objects.
+//! So can `std::io::Cursor`s over them, ignoring the cursor position.
+//! `SparseMem` is an in-memory object allocating only pages that were written to.
+//! `Extents` tells which ranges of an object hold data and which are holes; with `extents` feature it uses `SEEK_DATA`/`SEEK_HOLE` for files.
+//! `Discard` punches holes, zeroes or discards ranges; with `discard` feature it maps to `fallocate` for files and `BLKZEROOUT`/`BLKDISCARD` for Linux block devices.
+//! `Preallocate` reserves storage for a range up front; with `preallocate` feature it uses `fallocate`/`posix_fallocate` for files, otherwise it only extends them.
+//! `Advise` passes access pattern hints down the stack: to `posix_fadvise` for files with `advise` feature, to `madvise` for memory maps; in-memory objects ignore them.
+//! `BlockGeometry` reports logical and physical sector sizes; with `linux-blk` feature `File`s that are raw block devices report them (and their size via `SizeAt`) using `BLKSSZGET`, `BLKPBSZGET` and `BLKGETSIZE64` ioctls.
+//! `RangeLock` gives advisory byte-range locks for coordinating processes; with `range_lock` feature `File`s use OFD `fcntl` locks on Unix and `LockFileEx` on Windows.
+//! `RangeLocked` does the same between threads in-process, locking the range of each read (shared) and write (exclusive) of the wrapped object.
+//! 
 //! Immutable version of traits are implemented for `RefCell`s or `Mutex`s over mutable versions.
-//! You may need to use `DerefWrapper` it you use trait ojects although.
+//! `RwLock` is supported as well, allowing concurrent reads of `ReadAt` objects.
+//! Over `ReadAtMut`-only types of this crate (e.g. `ReadWriteSeek`), reads take the write lock instead.
+//! With `parking_lot` feature, its `Mutex` and `RwLock` are supported too.
+//! `&T`, `&mut T`, `Box<T>`, `Rc<T>` and `Arc<T>` forward the immutable traits.
+//! Mutable traits are forwarded by `Box` and `&mut` of this crate's trait objects, like `Box<dyn ReadWriteAtMut>`.
+//! For other `DerefMut` types, there is `DerefWrapper`.
+//! 
+//! `SnapshotWriteAt` provides copy-on-write snapshots with commit and rollback, also over read-only bases (as `CowOverlay`).
+//! 
+//! `SubRange` exposes a part of an object as a separate object; `slice` nests them, giving a `Slice`.
+//! `Take` limits accesses to the first bytes of an object.
+//! `Chain` goes the other way, concatenating several objects into one.
+//! `Stripe` interleaves fixed-size stripes of several objects, RAID-0 style.
+//! `Mirror` keeps several replicas of an object, RAID-1 style, failing over on errors.
+//!
+//! `ReadOnly` hides write methods of an object, `DenyWrites` makes them fail instead.
+//!
+//! `ZeroDevice` and `FillDevice` read as constant bytes and discard writes, like `/dev/zero`.
+//! 
+//! `BufReadAt` caches blocks of expensive to read objects.
+//! `PageCache` caches both reads and writes, with write-through or write-back policy.
+//! `ReadAhead` prefetches following blocks in background on sequential reads.
+//! `Throttled` limits bandwidth and IOPS of reads and writes with token buckets, e.g. for background scrubs and backups.
+//! `Instrumented` counts calls, bytes and errors and records latency histograms per operation; with `metrics` feature it also reports them to the `metrics` crate facade.
+//! With `tracing` feature, `Traced` emits a span with offset and length and a completion event with result and duration for each call.
+//! `FaultyDevice` injects errors, short transfers and `Interrupted` by offset, call number or seeded probability, for testing error handling.
+//! `Recorder` records calls (with optional data hashes) into a trace of `TraceEntry` lines, and `replay` re-issues a trace against another backend.
+//! `MockDevice` answers a scripted sequence of expected calls with canned replies and panics on anything else, for unit tests.
+//! The `testing` module checks an implementation against an in-memory model with random sequences of reads, writes, resizes and flushes.
+//! With `proptest` or `quickcheck` feature, `testing::Op` and `SparseMem` implement their `Arbitrary`, to fuzz code over operation sequences and random devices.
+//! `SimulatedDevice` delays calls by an HDD-, SSD- or network-like `Profile` of latency, seeks and bandwidth, in real or virtual time.
+//! `DirtyTracking` records which blocks were modified since the last reset, for incremental backups.
+//! `diff_ranges` lists the extents where two objects differ, optionally using multiple threads.
+//! `TarArchive` indexes the members of a tar archive in one pass, exposing each one as a `SubRange`.
+//! With `digest` feature, `hash_range` hashes a range with any `Digest`, optionally reporting progress (`async_hash_range` with `async` feature).
+//! `Checksummed` keeps a CRC32C of every block, interleaved or in a sidecar region, failing reads of corrupted blocks with `ChecksumError`.
+//! With `digest` feature, `VerityReader` verifies reads against a dm-verity style hash tree up to a trusted root hash; `VerityBuilder` generates the tree.
+//! With `xts` feature, `EncryptedDevice` encrypts sectors with AES-XTS, as in full-disk encryption.
+//! With `stream-cipher` feature, `StreamEncrypted` encrypts with a seekable stream cipher like AES-CTR or ChaCha20 by absolute offset.
+//! With `zstd` or `lz4` feature, `CompressedStore` keeps compressed blocks in a log with an index, exposing the uncompressed data.
+//! With `zstd` feature, `SeekableZstdReader` gives random access to files in the zstd seekable format.
+//! With `bgzf` feature, `BgzfReader` gives random access to BGZF files like `.bam`, converting virtual offsets.
+//! With `xz` feature, `XzReader` gives random access to multi-block `.xz` files, decompressing only the needed blocks.
+//! With `zip` feature, `ZipArchive` indexes a zip file, exposing stored entries as `SubRange`s and deflated ones via a cached decoder.
+//! `BufWriterAt` coalesces small adjacent writes into larger ones.
+//! `Aligned` turns arbitrary requests into block-aligned ones, for `O_DIRECT` files and raw block devices.
+//! `AlignedBuf` is a buffer aligned in memory; with `direct_io` feature, the `direct_io` module opens files with `O_DIRECT`.
+//! 
+//! `ReadAtExt` reads ranges into new vectors.
+//! `Combinators` builds stacks of adapters fluently: `obj.slice(a..b).take(n).cached(block_size, max_blocks)`.
+//! `chunks_at` iterates over a range in chunks (`async_chunks_at` gives a `Stream` with `async` feature).
+//! `copy_range` copies a range between objects; `Copier` does it reusing a buffer and reporting progress.
+//! With `copy_file_range` feature, `copy_file_range` copies between files in the kernel on Linux, possibly by reflinking.
+//! With `sendfile` feature, `send_range_to_socket` streams a range to a socket, zero-copy on Linux.
+//! With `sparse_copy` feature, `copy_sparse` copies to a file on Linux preserving holes of the source, either skipping or punching them in the destination.
+//! `ReadIntAt` and `WriteIntAt` (with `Mut` versions) read and write integers and floats in a chosen byte order (`LE` or `BE`).
+//! With `bytemuck` feature, `ReadPodAt` and `WritePodAt` (with `Mut` versions) read and write plain-old-data structs and slices of them.
+//! With `binrw` feature, `ReadBinAt` parses `binrw` structures at offsets of shared `ReadAt` objects.
+//! With `object` feature, `ObjectReadAt` lets the `object` crate parse executables from `ReadAt` objects, via its `ReadCache`.
+//! With `positioned-io` feature, `FromPositionedIo` and `IntoPositionedIo` convert between traits of that crate and this one.
+//! With `cap-std` feature, `cap_std::fs::File` gets the same impls as `std::fs::File`, so capability-based code needs no ambient `File`.
+//! With `nbd-client` feature, `NbdDevice` opens a Network Block Device export over TCP, a Unix socket or any other stream.
+//! With `nbd-server` feature, `NbdServer` goes the other way, exporting any `ReadAt + WriteAt + SizeAt + SyncAt` object, e.g. to attach it as a kernel block device.
+//! With `remote` feature, the `remote` module has a simpler protocol of its own: `remote::serve` exports such an object over TCP and `RemoteDevice` connects to it.
+//! With `ssh` feature, `SftpFile` reads and writes remote files over SFTP using `ssh2`.
+//! With `fuse` feature on Unix, `FuseFile` mounts any `ReadAt + WriteAt + SizeAt + SyncAt` object as a file in a FUSE filesystem, for use by programs that only know how to open files.
+//! With `ublk` feature on Linux, `UblkServer` turns such an object that also implements `Discard` into a real `/dev/ublkbN` block device.
+//! With `http` feature, `HttpRangeReader` reads remote files with `Range` requests validated by `If-Range`; `http-async` gives `AsyncHttpRangeReader` based on `reqwest`.
+//! With `s3` feature, `S3Bucket` opens objects of S3-compatible stores as `ObjectRangeReader` (ranged `GetObject`) or creates them with `ObjectWriter` (multipart upload).
+//! With `webdav` feature, `WebDavFile` reads files on WebDAV servers such as Nextcloud, and writes them using `PartialUpdate` extensions.
+//! 
+//! With `async` feature, there are async versions of the traits: `AsyncReadAt` and so on.
+//! Their immutable versions are implemented for `futures::lock::Mutex` over mutable versions.
+//! With `tokio` feature, they are implemented for `tokio::fs::File`.
+//! With `uring` feature, `UringFile` implements them on Linux via io_uring, with batched submission.
+//! `AsyncReadWriteSeek` wraps `futures::io` objects, like `ReadWriteSeek` does for `std::io` ones.
+//! 
+//! On Linux, `ProcMemReadAt` reads memory of other processes via `/proc/PID/mem`.
+//! 
+//! With `bytes` feature, `ReadBytesAt` reads into `bytes::Bytes`, sharing memory instead of copying where possible.
+//! 
+//! With `mmap` feature, `memmap2` maps implement the traits, and `MmapDevice` is a memory-mapped file that remaps on resize.
+//! 
+//! With `sstable` feature, `SstBlockReadAt` gives access to data blocks of LevelDB / RocksDB SST files.
+//! 
+//! Without default `std` feature, the crate is `no_std` and offers only the `core_io` module, with variants of the traits not tied to `std::io::Error`
+//! (with `alloc` feature, implemented for `Vec<u8>` too).
+//! 
+//! With `embedded-storage` feature, `NorFlashAt` gives `core_io` traits for NOR flash drivers (erasing sectors before writes as needed),
+//! and `NorFlashDevice` emulates NOR flash on top of them; with `embedded-storage-async`, `AsyncNorFlashDevice` does it over the async traits.
 //! 
 //! TODO:
 //! 
-//! * vectored IO
-//! * async?
 //! * reading to uninitialized buffers?
-//! * `bytes` crate intergration?
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(any(feature = "mmap", feature = "uring", feature = "wasi", feature = "discard", feature = "linux-blk", feature = "range_lock", feature = "ublk")), forbid(unsafe_code))]
+// Only `mmap`, `uring`, `wasi_file`, `blk`, `range_lock` and `ublk` modules opt out of it
+#![cfg_attr(any(feature = "mmap", feature = "uring", feature = "wasi", feature = "discard", feature = "linux-blk", feature = "range_lock", feature = "ublk"), deny(unsafe_code))]
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod core_io;
 
-use std::io::{Read,Write,Seek,SeekFrom,Result,Error,ErrorKind};
+#[cfg(feature = "std")]
+use std::io::{Read,Write,Seek,SeekFrom,Result,Error,ErrorKind,IoSlice,IoSliceMut};
+#[cfg(feature = "std")]
+use std::convert::TryFrom;
+
+#[cfg(all(feature = "std", feature = "rustix", unix))]
+mod fd;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+pub use snapshot::{CowOverlay,SnapshotWriteAt};
+#[cfg(feature = "std")]
+mod sub_range;
+#[cfg(feature = "std")]
+pub use sub_range::{Slice,SubRange,SubRegionAt};
+#[cfg(feature = "std")]
+mod take;
+#[cfg(feature = "std")]
+pub use take::Take;
+#[cfg(feature = "std")]
+mod chunks;
+#[cfg(feature = "std")]
+pub use chunks::{chunks_at,ChunksAt};
+#[cfg(feature = "async")]
+pub use chunks::async_chunks_at;
+#[cfg(feature = "std")]
+mod ext;
+#[cfg(feature = "std")]
+pub use ext::{Combinators,ReadAtExt};
+#[cfg(feature = "std")]
+mod copy;
+#[cfg(feature = "std")]
+pub use copy::{copy_range,Copier};
+#[cfg(all(feature = "copy_file_range", any(target_os = "linux", target_os = "android")))]
+pub use copy::copy_file_range;
+#[cfg(all(feature = "sendfile", unix))]
+pub use copy::send_range_to_socket;
+#[cfg(all(feature = "sparse_copy", any(target_os = "linux", target_os = "android")))]
+pub use copy::{copy_sparse,HoleMode};
+#[cfg(feature = "std")]
+mod chain;
+#[cfg(feature = "std")]
+pub use chain::Chain;
+#[cfg(feature = "std")]
+mod stripe;
+#[cfg(feature = "std")]
+pub use stripe::Stripe;
+#[cfg(feature = "std")]
+mod mirror;
+#[cfg(feature = "std")]
+pub use mirror::{Mirror,ReadPolicy};
+#[cfg(feature = "std")]
+mod read_only;
+#[cfg(feature = "std")]
+pub use read_only::{ReadOnly,DenyWrites};
+#[cfg(feature = "std")]
+mod fill;
+#[cfg(feature = "std")]
+pub use fill::{ZeroDevice,FillDevice};
+#[cfg(feature = "std")]
+mod sparse_mem;
+#[cfg(feature = "std")]
+pub use sparse_mem::SparseMem;
+#[cfg(feature = "std")]
+mod extents;
+#[cfg(feature = "std")]
+pub use extents::{Extent,ExtentKind,Extents};
+#[cfg(feature = "std")]
+mod discard;
+#[cfg(feature = "std")]
+pub use discard::{Discard,DiscardMut};
+#[cfg(feature = "std")]
+mod preallocate;
+#[cfg(feature = "std")]
+pub use preallocate::{Preallocate,PreallocateMut};
+#[cfg(feature = "std")]
+mod advise;
+#[cfg(feature = "std")]
+pub use advise::{Advice,Advise};
+#[cfg(feature = "std")]
+mod geometry;
+#[cfg(feature = "std")]
+pub use geometry::BlockGeometry;
+#[cfg(feature = "std")]
+mod range_lock;
+#[cfg(feature = "std")]
+pub use range_lock::{LockMode,RangeLock};
+#[cfg(feature = "std")]
+mod range_locked;
+#[cfg(feature = "std")]
+pub use range_locked::{RangeGuard,RangeLocked};
+#[cfg(any(feature = "nbd-client", feature = "nbd-server"))]
+mod nbd;
+#[cfg(feature = "nbd-client")]
+mod nbd_client;
+#[cfg(feature = "nbd-client")]
+pub use nbd_client::NbdDevice;
+#[cfg(feature = "nbd-server")]
+mod nbd_server;
+#[cfg(feature = "nbd-server")]
+pub use nbd_server::NbdServer;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "remote")]
+pub use remote::RemoteDevice;
+#[cfg(feature = "ssh")]
+mod sftp;
+#[cfg(feature = "ssh")]
+pub use sftp::SftpFile;
+#[cfg(all(feature = "fuse", unix))]
+mod fuse;
+#[cfg(all(feature = "fuse", unix))]
+pub use fuse::FuseFile;
+#[cfg(all(feature = "ublk", target_os = "linux"))]
+mod ublk;
+#[cfg(all(feature = "ublk", target_os = "linux"))]
+pub use ublk::{UblkServer,stop_ublk};
+#[cfg(any(feature = "http", feature = "http-async"))]
+mod http_range;
+#[cfg(feature = "http")]
+pub use http_range::HttpRangeReader;
+#[cfg(feature = "http-async")]
+pub use http_range::AsyncHttpRangeReader;
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "s3")]
+pub use s3::{S3Bucket,ObjectRangeReader,ObjectWriter};
+#[cfg(feature = "webdav")]
+mod webdav;
+#[cfg(feature = "webdav")]
+pub use webdav::{WebDavFile,PartialUpdate};
+#[cfg(all(any(feature = "discard", feature = "linux-blk"), any(target_os = "linux", target_os = "android")))]
+mod blk;
+#[cfg(feature = "std")]
+mod cursor;
+#[cfg(feature = "std")]
+pub use cursor::{CursorAt,ReadAtCursor};
+#[cfg(feature = "std")]
+mod buf_read;
+#[cfg(feature = "std")]
+pub use buf_read::BufReadAt;
+#[cfg(feature = "std")]
+mod page_cache;
+#[cfg(feature = "std")]
+pub use page_cache::{PageCache,WritePolicy};
+#[cfg(feature = "std")]
+mod read_ahead;
+#[cfg(feature = "std")]
+pub use read_ahead::ReadAhead;
+#[cfg(feature = "std")]
+mod throttled;
+#[cfg(feature = "std")]
+pub use throttled::{Rate,Throttled};
+#[cfg(feature = "std")]
+mod instrumented;
+#[cfg(feature = "std")]
+pub use instrumented::{Instrumented,IoOp,IoStats,LatencyHistogram,OpStats,LATENCY_BUCKETS};
+#[cfg(feature = "tracing")]
+mod traced;
+#[cfg(feature = "tracing")]
+pub use traced::Traced;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+mod faulty;
+#[cfg(feature = "std")]
+pub use faulty::{Fault,FaultyDevice};
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "std")]
+pub use trace::{replay,Recorder,ReplayReport,TraceEntry};
+#[cfg(feature = "std")]
+mod mock;
+#[cfg(feature = "std")]
+pub use mock::MockDevice;
+#[cfg(feature = "std")]
+mod simulated;
+#[cfg(feature = "std")]
+pub use simulated::{Profile,SimulatedDevice};
+#[cfg(feature = "std")]
+mod dirty;
+#[cfg(feature = "std")]
+pub use dirty::DirtyTracking;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "std")]
+pub use diff::{diff_ranges,diff_ranges_parallel};
+#[cfg(feature = "std")]
+mod tar;
+#[cfg(feature = "std")]
+pub use tar::{TarArchive,TarMember};
+#[cfg(feature = "digest")]
+mod hash;
+#[cfg(feature = "digest")]
+pub use hash::{hash_range,hash_range_with_progress};
+#[cfg(all(feature = "digest", feature = "async"))]
+pub use hash::async_hash_range;
+#[cfg(feature = "std")]
+mod crc;
+#[cfg(feature = "std")]
+mod checksummed;
+#[cfg(feature = "std")]
+pub use checksummed::{Checksummed,ChecksumError,ChecksumLayout};
+#[cfg(feature = "digest")]
+mod verity;
+#[cfg(feature = "digest")]
+pub use verity::{VerityBuilder,VerityReader};
+#[cfg(feature = "xts")]
+mod encrypted;
+#[cfg(feature = "xts")]
+pub use encrypted::EncryptedDevice;
+#[cfg(feature = "stream-cipher")]
+mod stream_encrypted;
+#[cfg(feature = "stream-cipher")]
+pub use stream_encrypted::StreamEncrypted;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+mod compressed;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub use compressed::{CompressedStore,Compression};
+#[cfg(any(feature = "zstd", feature = "bgzf", feature = "xz", feature = "zip"))]
+mod block_cache;
+#[cfg(feature = "zstd")]
+mod seekable_zstd;
+#[cfg(feature = "zstd")]
+pub use seekable_zstd::SeekableZstdReader;
+#[cfg(feature = "bgzf")]
+mod bgzf;
+#[cfg(feature = "bgzf")]
+pub use bgzf::BgzfReader;
+#[cfg(feature = "xz")]
+mod xz;
+#[cfg(feature = "xz")]
+pub use xz::XzReader;
+#[cfg(feature = "zip")]
+mod zip;
+#[cfg(feature = "zip")]
+pub use zip::{ZipArchive,ZipEntry,ZipEntryReader};
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+mod arbitrary;
+#[cfg(feature = "std")]
+mod buf_write;
+#[cfg(feature = "std")]
+pub use buf_write::BufWriterAt;
+#[cfg(feature = "std")]
+mod aligned;
+#[cfg(feature = "std")]
+pub use aligned::{Aligned,AlignedBuf};
+#[cfg(feature = "std")]
+mod int_at;
+#[cfg(feature = "std")]
+pub use int_at::{ByteOrder,LittleEndian,BigEndian,LE,BE,ReadIntAt,ReadIntAtMut,WriteIntAt,WriteIntAtMut};
+#[cfg(feature = "bytemuck")]
+mod pod_at;
+#[cfg(feature = "bytemuck")]
+pub use pod_at::{ReadPodAt,ReadPodAtMut,WritePodAt,WritePodAtMut};
+#[cfg(feature = "binrw")]
+mod binrw_at;
+#[cfg(feature = "binrw")]
+pub use binrw_at::ReadBinAt;
+#[cfg(feature = "object")]
+mod object_at;
+#[cfg(feature = "object")]
+pub use object_at::ObjectReadAt;
+#[cfg(feature = "positioned-io")]
+mod positioned;
+#[cfg(feature = "positioned-io")]
+pub use positioned::{FromPositionedIo,IntoPositionedIo};
+#[cfg(feature = "cap-std")]
+mod cap_std_file;
+#[cfg(feature = "async")]
+mod async_traits;
+#[cfg(feature = "async")]
+pub use async_traits::{AsyncReadAt,AsyncReadAtMut,AsyncWriteAt,AsyncWriteAtMut,AsyncReadWriteAt,AsyncReadWriteAtMut,AsyncReadWriteSeek,BoxFuture};
+#[cfg(all(feature = "tokio", any(unix, windows)))]
+mod tokio_file;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub use uring::UringFile;
+#[cfg(feature = "std")]
+mod portable_file;
+#[cfg(feature = "std")]
+pub use portable_file::PortableFile;
+// libstd's `std::os::wasi::fs::FileExt` is unstable, and there is no WASI preview 1 on preview 2 targets
+#[cfg(all(feature = "wasi", target_os = "wasi", not(target_env = "p2")))]
+mod wasi_file;
+#[cfg(all(feature = "std", windows))]
+mod cursor_restoring;
+#[cfg(all(feature = "std", windows))]
+pub use cursor_restoring::CursorRestoring;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod proc_mem;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use proc_mem::ProcMemReadAt;
+#[cfg(all(feature = "direct_io", any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd")))]
+pub mod direct_io;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapDevice;
+#[cfg(feature = "embedded-storage")]
+mod nor_flash;
+#[cfg(feature = "embedded-storage")]
+pub use nor_flash::{FlashError,NorFlashAt,NorFlashDevice};
+#[cfg(feature = "embedded-storage-async")]
+pub use nor_flash::AsyncNorFlashDevice;
+#[cfg(feature = "parking_lot")]
+mod parking_lot_locks;
+#[cfg(feature = "bytes")]
+mod bytes_at;
+#[cfg(feature = "bytes")]
+pub use bytes_at::{ReadBytesAt,read_bytes_at};
+#[cfg(feature = "sstable")]
+mod sstable;
+#[cfg(feature = "sstable")]
+pub use sstable::SstBlockReadAt;
 
 /// Read-only generalisation of [`std::os::unix::fs::FileExt`](https://doc.rust-lang.org/stable/std/os/unix/fs/trait.FileExt.html)
+/// 
+/// # Examples
+/// 
+/// Implementing the trait gives `read_exact_at` for free:
+/// 
+/// ```
+/// use read_write_at::ReadAt;
+/// 
+/// /// Each byte is equal to the lowest byte of its offset
+/// struct Pattern;
+/// 
+/// impl ReadAt for Pattern {
+///     fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+///         // Short reads are allowed, `read_exact_at` handles them
+///         let n = buf.len().min(2);
+///         let buf = &mut buf[..n];
+///         for (i, b) in buf.iter_mut().enumerate() {
+///             *b = (offset + i as u64) as u8;
+///         }
+///         Ok(buf.len())
+///     }
+/// }
+/// 
+/// let mut v = [0u8; 3];
+/// Pattern.read_exact_at(&mut v[..], 254).unwrap();
+/// assert_eq!(v, [254, 255, 0]);
+/// 
+/// // A window into the object is also a `ReadAt`
+/// let r = read_write_at::SubRange::new(Pattern, 10, 2);
+/// assert_eq!(r.read_at(&mut v[..], 0).unwrap(), 2);
+/// assert_eq!(v[..2], [10, 11]);
+/// ```
+#[cfg(feature = "std")]
 pub trait ReadAt {
     /// Reads a number of bytes starting from a given offset.
     /// Returns the number of bytes read.
@@ -57,11 +526,71 @@ pub trait ReadAt {
             Ok(())
         }
     }
+
+    /// Like `read_at`, but reads into multiple buffers, filling them in order.
+    /// Returns the total number of bytes read.
+    /// 
+    /// The default implementation calls `read_at` for each buffer, retrying short reads
+    /// until the buffer is full or end of file is reached.
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let mut total = 0;
+        for buf in bufs.iter_mut() {
+            let mut filled = 0;
+            while filled < buf.len() {
+                match self.read_at(&mut buf[filled..], offset + total as u64) {
+                    Ok(0) => return Ok(total),
+                    Ok(n) => {
+                        filled += n;
+                        total += n;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) if total > 0 => return Ok(total),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(total)
+    }
 }
 /// Similar to `ReadAt`, but functions may allow to change object state,
 /// including cursor moves if the object has concept of a cursor
 /// 
 /// Note that `ReadAtMut` implementations from `RefCell` and `Mutex` do not check for cursor moves.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::ReadAtMut;
+/// 
+/// /// Counts how many times it was read from
+/// struct Counting {
+///     data: Vec<u8>,
+///     reads: usize,
+/// }
+/// 
+/// impl ReadAtMut for Counting {
+///     fn read_at(&mut self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+///         self.reads += 1;
+///         let data = self.data.get(offset as usize..).unwrap_or(&[]);
+///         let n = data.len().min(buf.len()).min(1);
+///         buf[..n].copy_from_slice(&data[..n]);
+///         Ok(n)
+///     }
+/// }
+/// 
+/// let mut c = Counting { data: vec![1, 2, 3], reads: 0 };
+/// let mut v = [0u8; 2];
+/// c.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(v, [2, 3]);
+/// assert_eq!(c.reads, 2);
+/// 
+/// // `RefCell` (or `Mutex`) turns it into a `ReadAt`, usable by `&` reference
+/// let rc = std::cell::RefCell::new(c);
+/// read_write_at::ReadAt::read_exact_at(&rc, &mut v[..], 0).unwrap();
+/// assert_eq!(v, [1, 2]);
+/// assert_eq!(rc.borrow().reads, 4);
+/// ```
+#[cfg(feature = "std")]
 pub trait ReadAtMut {
     /// Similar to `ReadAt::read_at`, but it is allowed to change object internal state.
     fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize>;
@@ -87,8 +616,34 @@ pub trait ReadAtMut {
             Ok(())
         }
     }
+
+    /// Like `read_at`, but reads into multiple buffers, filling them in order.
+    /// Returns the total number of bytes read.
+    /// 
+    /// The default implementation calls `read_at` for each buffer, retrying short reads
+    /// until the buffer is full or end of file is reached.
+    fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let mut total = 0;
+        for buf in bufs.iter_mut() {
+            let mut filled = 0;
+            while filled < buf.len() {
+                match self.read_at(&mut buf[filled..], offset + total as u64) {
+                    Ok(0) => return Ok(total),
+                    Ok(n) => {
+                        filled += n;
+                        total += n;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) if total > 0 => return Ok(total),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(total)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<T: ReadAt+?Sized> ReadAtMut for T{ 
     fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
         ReadAt::read_at(self, buf, offset)
@@ -96,9 +651,42 @@ impl<T: ReadAt+?Sized> ReadAtMut for T{
     fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<()> {
         ReadAt::read_exact_at(self, buf, offset)
     }
+    fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        ReadAt::read_vectored_at(self, bufs, offset)
+    }
 }
 
 /// Write counterpart of `ReadAt`.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::{WriteAt,ReadAt};
+/// use std::cell::RefCell;
+/// 
+/// /// Remembers all writes, accepting at most two bytes at once
+/// struct Journal(RefCell<Vec<(u64, Vec<u8>)>>);
+/// 
+/// impl WriteAt for Journal {
+///     fn write_at(&self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+///         let buf = &buf[..buf.len().min(2)];
+///         self.0.borrow_mut().push((offset, buf.to_vec()));
+///         Ok(buf.len())
+///     }
+/// }
+/// 
+/// let j = Journal(RefCell::new(vec![]));
+/// j.write_all_at(&[1, 2, 3], 10).unwrap();
+/// assert_eq!(*j.0.borrow(), vec![(10, vec![1, 2]), (12, vec![3])]);
+/// 
+/// // `Mutex` over a `WriteAtMut` is a `WriteAt` as well
+/// let m = std::sync::Mutex::new(read_write_at::ReadWriteSeek(std::io::Cursor::new(vec![0u8; 4])));
+/// m.write_all_at(&[7, 7], 1).unwrap();
+/// let mut v = [0u8; 4];
+/// m.read_exact_at(&mut v[..], 0).unwrap();
+/// assert_eq!(v, [0, 7, 7, 0]);
+/// ```
+#[cfg(feature = "std")]
 pub trait WriteAt {
     /// Writes data contained in buffer `buf` at offset `offset`. May actually write less bytes than you request.
     /// Obviously, it is expected to change information referenced by this object despite of accepting `&self`,
@@ -126,11 +714,64 @@ pub trait WriteAt {
         }
         Ok(())
     }
+
+    /// Like `write_at`, but writes data from multiple buffers, in order.
+    /// Returns the total number of bytes written.
+    /// 
+    /// The default implementation calls `write_at` for each buffer, retrying short writes
+    /// until the buffer is fully written or a write returns `0`.
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let mut total = 0;
+        for buf in bufs.iter() {
+            let mut written = 0;
+            while written < buf.len() {
+                match self.write_at(&buf[written..], offset + total as u64) {
+                    Ok(0) => return Ok(total),
+                    Ok(n) => {
+                        written += n;
+                        total += n;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) if total > 0 => return Ok(total),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(total)
+    }
 }
 /// Similar to `WriteAt`, but functions may allow to change object state,
 /// including cursor moves if the object has concept of a cursor.
 /// 
 /// Note that `WriteAtMut` implementations from `RefCell` and `Mutex` do not check for cursor moves.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::WriteAtMut;
+/// use std::collections::BTreeMap;
+/// 
+/// /// Stores only the bytes that were written
+/// struct Sparse(BTreeMap<u64, u8>);
+/// 
+/// impl WriteAtMut for Sparse {
+///     fn write_at(&mut self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+///         for (i, &b) in buf.iter().enumerate() {
+///             self.0.insert(offset + i as u64, b);
+///         }
+///         Ok(buf.len())
+///     }
+/// }
+/// 
+/// let mut s = Sparse(BTreeMap::new());
+/// s.write_all_at(&[1, 2], 1000).unwrap();
+/// 
+/// // Shared access from several places via `RefCell`
+/// let rc = std::cell::RefCell::new(s);
+/// read_write_at::WriteAt::write_all_at(&rc, &[3], 5).unwrap();
+/// assert_eq!(rc.borrow().0.len(), 3);
+/// ```
+#[cfg(feature = "std")]
 pub trait WriteAtMut {
     /// Writes a number of bytes starting from a given offset.
     /// Returns the number of bytes written.
@@ -162,26 +803,474 @@ pub trait WriteAtMut {
         }
         Ok(())
     }
+
+    /// Like `write_at`, but writes data from multiple buffers, in order.
+    /// Returns the total number of bytes written.
+    /// 
+    /// The default implementation calls `write_at` for each buffer, retrying short writes
+    /// until the buffer is fully written or a write returns `0`.
+    fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let mut total = 0;
+        for buf in bufs.iter() {
+            let mut written = 0;
+            while written < buf.len() {
+                match self.write_at(&buf[written..], offset + total as u64) {
+                    Ok(0) => return Ok(total),
+                    Ok(n) => {
+                        written += n;
+                        total += n;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) if total > 0 => return Ok(total),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(total)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<T: WriteAt+?Sized> WriteAtMut for T{ 
     fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
         WriteAt::write_at(self, buf, offset)
     }
+    fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        WriteAt::write_vectored_at(self, bufs, offset)
+    }
+}
+
+
+/// Objects that know their total size, e.g. a length of a file.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::{SizeAt,SubRange};
+/// 
+/// struct Fixed;
+/// impl SizeAt for Fixed {
+///     fn size(&self) -> std::io::Result<u64> {
+///         Ok(100)
+///     }
+/// }
+/// 
+/// assert_eq!(Fixed.size().unwrap(), 100);
+/// assert_eq!(SubRange::new(Fixed, 10, 20).size().unwrap(), 20);
+/// ```
+#[cfg(feature = "std")]
+pub trait SizeAt {
+    /// Size of the (virtual) file in bytes. Reads starting at or after this offset are expected to return `0`.
+    fn size(&self) -> Result<u64>;
+}
+
+/// Objects that can be truncated or extended, like `std::fs::File::set_len`.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::{ResizeAt,SizeAt};
+/// 
+/// let v = std::sync::Mutex::new(vec![1u8, 2, 3]);
+/// v.set_len(5).unwrap();
+/// assert_eq!(v.size().unwrap(), 5);
+/// assert_eq!(*v.lock().unwrap(), vec![1, 2, 3, 0, 0]);
+/// ```
+#[cfg(feature = "std")]
+pub trait ResizeAt {
+    /// Set size of the (virtual) file to `new_len` bytes, filling extended part with zeroes.
+    fn set_len(&self, new_len: u64) -> Result<()>;
+}
+
+/// Objects that can be truncated or extended, but require `&mut self` for it.
+#[cfg(feature = "std")]
+pub trait ResizeAtMut {
+    /// Set size of the (virtual) file to `new_len` bytes, filling extended part with zeroes.
+    fn set_len(&mut self, new_len: u64) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<T: ResizeAt+?Sized> ResizeAtMut for T {
+    fn set_len(&mut self, new_len: u64) -> Result<()> {
+        ResizeAt::set_len(self, new_len)
+    }
+}
+
+/// Controlling persistence of written data.
+/// 
+/// Default implementations fall back to the stronger operation: `sync_range` to `sync_data`,
+/// `sync_data` to `sync_all`, `sync_all` to `flush`.
+/// In-memory objects implement them as no-ops.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::{SyncAt,WriteAt};
+/// 
+/// fn append_record<T:WriteAt+SyncAt>(wal: &T, offset: u64, record: &[u8]) -> std::io::Result<()> {
+///     wal.write_all_at(record, offset)?;
+///     wal.sync_range(offset, record.len() as u64)
+/// }
+/// 
+/// let wal = std::sync::Mutex::new(vec![]);
+/// append_record(&wal, 0, b"hello").unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub trait SyncAt {
+    /// Push buffered data to the underlying object, like `Write::flush`
+    fn flush(&self) -> Result<()>;
+    /// Make data and metadata durable, like `File::sync_all`
+    fn sync_all(&self) -> Result<()> {
+        self.flush()
+    }
+    /// Make data durable, not necessarily metadata, like `File::sync_data`
+    fn sync_data(&self) -> Result<()> {
+        self.sync_all()
+    }
+    /// Make data in `offset..offset+len` durable. May sync more than that.
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        let _ = (offset, len);
+        self.sync_data()
+    }
+}
+
+/// Controlling persistence of written data, but requiring `&mut self`.
+/// 
+/// Default implementations fall back to the stronger operation, like in `SyncAt`.
+#[cfg(feature = "std")]
+pub trait SyncAtMut {
+    /// Push buffered data to the underlying object, like `Write::flush`
+    fn flush(&mut self) -> Result<()>;
+    /// Make data and metadata durable, like `File::sync_all`
+    fn sync_all(&mut self) -> Result<()> {
+        self.flush()
+    }
+    /// Make data durable, not necessarily metadata, like `File::sync_data`
+    fn sync_data(&mut self) -> Result<()> {
+        self.sync_all()
+    }
+    /// Make data in `offset..offset+len` durable. May sync more than that.
+    fn sync_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        let _ = (offset, len);
+        self.sync_data()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: SyncAt+?Sized> SyncAtMut for T {
+    fn flush(&mut self) -> Result<()> {
+        SyncAt::flush(self)
+    }
+    fn sync_all(&mut self) -> Result<()> {
+        SyncAt::sync_all(self)
+    }
+    fn sync_data(&mut self) -> Result<()> {
+        SyncAt::sync_data(self)
+    }
+    fn sync_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        SyncAt::sync_range(self, offset, len)
+    }
+}
+
+#[cfg(feature = "std")]
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: ReadAt+?Sized> ReadAt for $ptr {
+            fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+                ReadAt::read_at(&**self, buf, offset)
+            }
+            fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+                ReadAt::read_exact_at(&**self, buf, offset)
+            }
+            fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+                ReadAt::read_vectored_at(&**self, bufs, offset)
+            }
+        }
+
+        impl<T: WriteAt+?Sized> WriteAt for $ptr {
+            fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+                WriteAt::write_at(&**self, buf, offset)
+            }
+            fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+                WriteAt::write_all_at(&**self, buf, offset)
+            }
+            fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+                WriteAt::write_vectored_at(&**self, bufs, offset)
+            }
+        }
+
+        impl<T: SizeAt+?Sized> SizeAt for $ptr {
+            fn size(&self) -> Result<u64> {
+                SizeAt::size(&**self)
+            }
+        }
+
+        impl<T: ResizeAt+?Sized> ResizeAt for $ptr {
+            fn set_len(&self, new_len: u64) -> Result<()> {
+                ResizeAt::set_len(&**self, new_len)
+            }
+        }
+
+        impl<T: SyncAt+?Sized> SyncAt for $ptr {
+            fn flush(&self) -> Result<()> {
+                SyncAt::flush(&**self)
+            }
+            fn sync_all(&self) -> Result<()> {
+                SyncAt::sync_all(&**self)
+            }
+            fn sync_data(&self) -> Result<()> {
+                SyncAt::sync_data(&**self)
+            }
+            fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+                SyncAt::sync_range(&**self, offset, len)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "std")]
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+// `impl<T: ReadAtMut+?Sized> ReadAtMut for Box<T>` would overlap with `impl<T: ReadAt+?Sized> ReadAtMut for T`,
+// so mutable traits are forwarded only for boxed and borrowed trait objects of this crate's traits.
+#[cfg(feature = "std")]
+macro_rules! forward_mut_through_trait_object {
+    ($l:lifetime: $($obj:ty),*) => {$(
+        impl<$l> ReadAtMut for Box<$obj> {
+            fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+                ReadAtMut::read_at(&mut **self, buf, offset)
+            }
+            fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<()> {
+                ReadAtMut::read_exact_at(&mut **self, buf, offset)
+            }
+            fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+                ReadAtMut::read_vectored_at(&mut **self, bufs, offset)
+            }
+        }
+
+        impl<$l> ReadAtMut for &mut $obj {
+            fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+                ReadAtMut::read_at(&mut **self, buf, offset)
+            }
+            fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<()> {
+                ReadAtMut::read_exact_at(&mut **self, buf, offset)
+            }
+            fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+                ReadAtMut::read_vectored_at(&mut **self, bufs, offset)
+            }
+        }
+    )*};
+    (write $l:lifetime: $($obj:ty),*) => {$(
+        impl<$l> WriteAtMut for Box<$obj> {
+            fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+                WriteAtMut::write_at(&mut **self, buf, offset)
+            }
+            fn write_all_at(&mut self, buf: &[u8], offset: u64) -> Result<()> {
+                WriteAtMut::write_all_at(&mut **self, buf, offset)
+            }
+            fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+                WriteAtMut::write_vectored_at(&mut **self, bufs, offset)
+            }
+        }
+
+        impl<$l> WriteAtMut for &mut $obj {
+            fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+                WriteAtMut::write_at(&mut **self, buf, offset)
+            }
+            fn write_all_at(&mut self, buf: &[u8], offset: u64) -> Result<()> {
+                WriteAtMut::write_all_at(&mut **self, buf, offset)
+            }
+            fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+                WriteAtMut::write_vectored_at(&mut **self, bufs, offset)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "std")]
+forward_mut_through_trait_object!('a:
+    dyn ReadAtMut + 'a, dyn ReadAtMut + Send + 'a, dyn ReadAtMut + Send + Sync + 'a,
+    dyn ReadWriteAtMut + 'a, dyn ReadWriteAtMut + Send + 'a, dyn ReadWriteAtMut + Send + Sync + 'a
+);
+#[cfg(feature = "std")]
+forward_mut_through_trait_object!(write 'a:
+    dyn WriteAtMut + 'a, dyn WriteAtMut + Send + 'a, dyn WriteAtMut + Send + Sync + 'a,
+    dyn ReadWriteAtMut + 'a, dyn ReadWriteAtMut + Send + 'a, dyn ReadWriteAtMut + Send + Sync + 'a
+);
+
+#[cfg(feature = "std")]
+impl SizeAt for std::fs::File {
+    /// With `linux-blk` feature, block devices (whose metadata length is zero) are queried with `BLKGETSIZE64`.
+    fn size(&self) -> Result<u64> {
+        #[cfg(all(feature = "linux-blk", any(target_os = "linux", target_os = "android")))]
+        if blk::is_block_device(self)? {
+            return blk::size(self);
+        }
+        Ok(self.metadata()?.len())
+    }
 }
 
+#[cfg(feature = "std")]
+impl ResizeAt for std::fs::File {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        std::fs::File::set_len(self, new_len)
+    }
+}
+
+/// `sync_range` syncs the whole file data.
+#[cfg(feature = "std")]
+impl SyncAt for std::fs::File {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+    fn sync_all(&self) -> Result<()> {
+        std::fs::File::sync_all(self)
+    }
+    fn sync_data(&self) -> Result<()> {
+        std::fs::File::sync_data(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl SyncAt for [u8] {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl SyncAt for Vec<u8> {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> SyncAt for std::io::Cursor<T> {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl SizeAt for [u8] {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+#[cfg(feature = "std")]
+impl SizeAt for Vec<u8> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ResizeAtMut for Vec<u8> {
+    fn set_len(&mut self, new_len: u64) -> Result<()> {
+        let new_len = match usize::try_from(new_len) {
+            Ok(x) => x,
+            Err(_) => return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "length is too large for a vector",
+            )),
+        };
+        if new_len > self.len() && self.try_reserve(new_len - self.len()).is_err() {
+            return Err(Error::new(
+                ErrorKind::OutOfMemory,
+                "cannot allocate memory to extend a vector",
+            ));
+        }
+        self.resize(new_len, 0);
+        Ok(())
+    }
+}
+
+/// Resizes the underlying vector. Cursor position is not changed.
+#[cfg(feature = "std")]
+impl ResizeAtMut for std::io::Cursor<Vec<u8>> {
+    fn set_len(&mut self, new_len: u64) -> Result<()> {
+        ResizeAtMut::set_len(self.get_mut(), new_len)
+    }
+}
+
+/// Size of the underlying buffer, regardless of cursor position.
+#[cfg(feature = "std")]
+impl<T:AsRef<[u8]>> SizeAt for std::io::Cursor<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.get_ref().as_ref().len() as u64)
+    }
+}
+
+/// Like `read_exact_at`, but treats EOF as success, returning the number of bytes read.
+#[cfg(feature = "std")]
+pub(crate) fn read_up_to<T: ReadAt + ?Sized>(t: &T, buf: &mut [u8], offset: u64) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match t.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
 
 /// A combined ReadAt and WriteAt for trait objects.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::{ReadWriteAt,ReadWriteSeek};
+/// 
+/// let m = std::sync::Mutex::new(ReadWriteSeek(std::io::Cursor::new(vec![0u8; 4])));
+/// let obj: Box<dyn ReadWriteAt> = Box::new(m);
+/// 
+/// obj.write_all_at(&[5, 6], 2).unwrap();
+/// let mut v = [0u8; 4];
+/// obj.read_exact_at(&mut v[..], 0).unwrap();
+/// assert_eq!(v, [0, 0, 5, 6]);
+/// ```
+#[cfg(feature = "std")]
 pub trait ReadWriteAt : ReadAt + WriteAt {}
+#[cfg(feature = "std")]
 impl<T:ReadAt+WriteAt> ReadWriteAt for T {}
 
-/// A combined ReadAtMut and WriteAtMut for trait objects
+/// A combined ReadAtMut and WriteAtMut for trait objects.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use read_write_at::{ReadWriteAtMut,ReadWriteSeek,ReadAt};
+/// 
+/// let mut obj: Box<dyn ReadWriteAtMut> = Box::new(ReadWriteSeek(std::io::Cursor::new(vec![0u8; 4])));
+/// obj.write_all_at(&[5, 6], 2).unwrap();
+/// 
+/// // Boxed trait objects can get shared access via `RefCell` directly
+/// let rc = std::cell::RefCell::new(obj);
+/// let mut v = [0u8; 4];
+/// rc.read_exact_at(&mut v[..], 0).unwrap();
+/// assert_eq!(v, [0, 0, 5, 6]);
+/// ```
+#[cfg(feature = "std")]
 pub trait ReadWriteAtMut : ReadAtMut + WriteAtMut {}
+#[cfg(feature = "std")]
 impl<T:ReadAtMut+WriteAtMut> ReadWriteAtMut for T {}
 
 
+/// Longer buffer lists make `preadv` and `pwritev` fail with `EINVAL`, so the rest is left for the following calls.
+/// This is the value on Linux, macOS and BSDs.
+#[cfg(all(feature = "rustix", unix, not(any(
+    target_os = "cygwin", target_os = "espidf", target_os = "haiku", target_os = "horizon",
+    target_os = "nto", target_os = "redox", target_os = "solaris", target_os = "vita",
+))))]
+#[cfg(feature = "std")]
+const IOV_MAX: usize = 1024;
+
 // cfg line is copied from https://doc.rust-lang.org/stable/src/std/os/mod.rs.html at 2020-06-22
-#[cfg(any(target_os = "redox", unix, target_os = "vxworks", target_os = "hermit"))]
+#[cfg(all(feature = "std", any(target_os = "redox", unix, target_os = "vxworks", target_os = "hermit")))]
 impl WriteAt for std::fs::File {
     fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
         std::os::unix::fs::FileExt::write_at(self, buf, offset)
@@ -189,10 +1278,19 @@ impl WriteAt for std::fs::File {
     fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
         std::os::unix::fs::FileExt::write_all_at(self, buf, offset)
     }
+    /// Single `pwritev` call if `rustix` feature is enabled, with at most `IOV_MAX` buffers
+    // Target list is from `rustix::io::preadv`
+    #[cfg(all(feature = "rustix", unix, not(any(
+        target_os = "cygwin", target_os = "espidf", target_os = "haiku", target_os = "horizon",
+        target_os = "nto", target_os = "redox", target_os = "solaris", target_os = "vita",
+    ))))]
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        Ok(rustix::io::pwritev(self, &bufs[..bufs.len().min(IOV_MAX)], offset)?)
+    }
 }
 
 // cfg line is copied from https://doc.rust-lang.org/stable/src/std/os/mod.rs.html at 2020-06-22
-#[cfg(any(target_os = "redox", unix, target_os = "vxworks", target_os = "hermit"))]
+#[cfg(all(feature = "std", any(target_os = "redox", unix, target_os = "vxworks", target_os = "hermit")))]
 impl ReadAt for std::fs::File {
     fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
         std::os::unix::fs::FileExt::read_at(self, buf, offset)
@@ -200,16 +1298,26 @@ impl ReadAt for std::fs::File {
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
         std::os::unix::fs::FileExt::read_exact_at(self, buf, offset)
     }
+    /// Single `preadv` call if `rustix` feature is enabled, with at most `IOV_MAX` buffers
+    // Target list is from `rustix::io::preadv`
+    #[cfg(all(feature = "rustix", unix, not(any(
+        target_os = "cygwin", target_os = "espidf", target_os = "haiku", target_os = "horizon",
+        target_os = "nto", target_os = "redox", target_os = "solaris", target_os = "vita",
+    ))))]
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let n = bufs.len().min(IOV_MAX);
+        Ok(rustix::io::preadv(self, &mut bufs[..n], offset)?)
+    }
 }
 
-#[cfg(windows)]
+#[cfg(all(feature = "std", windows, not(feature = "windows-native")))]
 /// Note that cursor is affected. That why it's `WriteAtMut` instead of `WriteAt`
 impl WriteAtMut for std::fs::File {
     fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
         std::os::windows::fs::FileExt::seek_write(self, buf, offset)
     }
 }
-#[cfg(windows)]
+#[cfg(all(feature = "std", windows, not(feature = "windows-native")))]
 /// Note that cursor is affected. That why it's `ReadAtMut` instead of `ReadAt`
 impl ReadAtMut for std::fs::File {
     fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
@@ -217,10 +1325,152 @@ impl ReadAtMut for std::fs::File {
     }
 }
 
+// `seek_read` and `seek_write` are `ReadFile` and `WriteFile` with the offset in `OVERLAPPED`.
+// They don't depend on the cursor, so concurrent calls through `&File` are fine; they only leave the cursor moved.
+#[cfg(all(windows, feature = "windows-native"))]
+/// The cursor is left at an unspecified position, so don't mix with `Write` and `Seek` on the same handle.
+impl WriteAt for std::fs::File {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_write(self, buf, offset)
+    }
+}
+#[cfg(all(windows, feature = "windows-native"))]
+/// The cursor is left at an unspecified position, so don't mix with `Read` and `Seek` on the same handle.
+impl ReadAt for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let data = match usize::try_from(offset) {
+            Ok(o) if o < self.len() => &self[o..],
+            _ => return Ok(0),
+        };
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(&self[..], buf, offset)
+    }
+}
+
+/// Writes in place. Writing at or after the end of the slice fails with `WriteZero`.
+#[cfg(feature = "std")]
+impl WriteAtMut for [u8] {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let data = match usize::try_from(offset) {
+            Ok(o) if o < self.len() => &mut self[o..],
+            _ => return Err(Error::new(
+                ErrorKind::WriteZero,
+                "write past the end of a slice",
+            )),
+        };
+        let n = data.len().min(buf.len());
+        data[..n].copy_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Writes in place. Writing at or after the end of the slice fails with `WriteZero`.
+#[cfg(feature = "std")]
+impl WriteAtMut for &mut [u8] {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut **self, buf, offset)
+    }
+}
+
+/// Writes in place. Writing at or after the end of the slice fails with `WriteZero`.
+#[cfg(feature = "std")]
+impl WriteAtMut for Box<[u8]> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut self[..], buf, offset)
+    }
+}
+
+/// Writing past the end extends the vector, filling the gap with zeroes.
+#[cfg(feature = "std")]
+impl WriteAtMut for Vec<u8> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let end = usize::try_from(offset).ok().and_then(|o| o.checked_add(buf.len()));
+        let end = match end {
+            Some(x) => x,
+            None => return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "offset is too large for a vector",
+            )),
+        };
+        if end > self.len() {
+            if self.try_reserve(end - self.len()).is_err() {
+                return Err(Error::new(
+                    ErrorKind::OutOfMemory,
+                    "cannot allocate memory to extend a vector",
+                ));
+            }
+            self.resize(end, 0);
+        }
+        self[end - buf.len()..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+/// Reads the underlying buffer directly. Cursor position is neither used nor changed.
+#[cfg(feature = "std")]
+impl<T:AsRef<[u8]>> ReadAt for std::io::Cursor<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(self.get_ref().as_ref(), buf, offset)
+    }
+}
+
+/// Writes to the underlying vector, extending it like `Vec<u8>` does. Cursor position is neither used nor changed.
+#[cfg(feature = "std")]
+impl WriteAtMut for std::io::Cursor<Vec<u8>> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(self.get_mut(), buf, offset)
+    }
+}
+
+/// Writes to the underlying vector, extending it like `Vec<u8>` does. Cursor position is neither used nor changed.
+#[cfg(feature = "std")]
+impl WriteAtMut for std::io::Cursor<&mut Vec<u8>> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut **self.get_mut(), buf, offset)
+    }
+}
+
+/// Writes to the underlying slice in place, like `[u8]` does. Cursor position is neither used nor changed.
+#[cfg(feature = "std")]
+impl WriteAtMut for std::io::Cursor<&mut [u8]> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut **self.get_mut(), buf, offset)
+    }
+}
+
+/// Writes to the underlying slice in place, like `[u8]` does. Cursor position is neither used nor changed.
+#[cfg(feature = "std")]
+impl WriteAtMut for std::io::Cursor<Box<[u8]>> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut self.get_mut()[..], buf, offset)
+    }
+}
+
 /// A wrapper that calls `Seek::seek` and `Read::read` or `Write::write` for each call of `read_at` or `write_at`
 /// Can be used for read-only access as well.
 /// 
-/// Example:
+/// # Examples
 /// 
 /// ```
 /// use read_write_at::{ReadWriteSeek,ReadAtMut};
@@ -233,8 +1483,10 @@ impl ReadAtMut for std::fs::File {
 /// rws.read_exact_at(&mut v2[..], 1).unwrap();
 /// assert_eq!(v2, vec![4,5]);
 /// ```
+#[cfg(feature = "std")]
 pub struct ReadWriteSeek<T:Seek>(pub T);
 
+#[cfg(feature = "std")]
 impl<T:Read+Seek> ReadAtMut for ReadWriteSeek<T> {
     fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
         let o = Seek::seek(&mut self.0, SeekFrom::Start(offset))?;
@@ -256,8 +1508,19 @@ impl<T:Read+Seek> ReadAtMut for ReadWriteSeek<T> {
         }
         Read::read_exact(&mut self.0, buf)
     }
+    fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let o = Seek::seek(&mut self.0, SeekFrom::Start(offset))?;
+        if o != offset {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "seek hasn't returned the required offset",
+            ));
+        }
+        Read::read_vectored(&mut self.0, bufs)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<T:Write+Seek> WriteAtMut for ReadWriteSeek<T> {
     fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
         let o = Seek::seek(&mut self.0, SeekFrom::Start(offset))?;
@@ -279,12 +1542,32 @@ impl<T:Write+Seek> WriteAtMut for ReadWriteSeek<T> {
         }
         Write::write_all(&mut self.0, buf)
     }
+    fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let o = Seek::seek(&mut self.0, SeekFrom::Start(offset))?;
+        if o != offset {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "seek hasn't returned the required offset",
+            ));
+        }
+        Write::write_vectored(&mut self.0, bufs)
+    }
 }
 
 
-/// A wrapper struct to allow accessing `RefCell` and `Mutex` helper impls for trait objects.
+/// Only `flush` is forwarded, other methods fall back to it.
+#[cfg(feature = "std")]
+impl<T:Write+Seek> SyncAtMut for ReadWriteSeek<T> {
+    fn flush(&mut self) -> Result<()> {
+        Write::flush(&mut self.0)
+    }
+}
+
+/// A wrapper struct to allow accessing `RefCell` and `Mutex` helper impls for other `DerefMut` types.
 ///
-/// Example:
+/// Not needed for `Box` and `&mut` of this crate's trait objects (`dyn ReadAtMut`, `dyn ReadWriteAtMut + Send` and so on).
+///
+/// # Examples
 /// 
 /// ```
 /// use read_write_at::{ReadWriteSeek,ReadWriteAtMut,ReadWriteAt,DerefWrapper};
@@ -300,8 +1583,10 @@ impl<T:Write+Seek> WriteAtMut for ReadWriteSeek<T> {
 /// obj2.read_exact_at(&mut v2[..], 1).unwrap();
 /// assert_eq!(v2, vec![4,5]);
 /// ```
+#[cfg(feature = "std")]
 pub struct DerefWrapper<T: std::ops::DerefMut> (pub T);
 
+#[cfg(feature = "std")]
 impl<T,U> ReadAtMut for DerefWrapper<U>
 where T:ReadAtMut+?Sized, U: std::ops::DerefMut<Target = T>
 {
@@ -311,8 +1596,48 @@ where T:ReadAtMut+?Sized, U: std::ops::DerefMut<Target = T>
     fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<()> {
         ReadAtMut::read_exact_at(std::ops::DerefMut::deref_mut(&mut self.0), buf, offset)
     }
+    fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        ReadAtMut::read_vectored_at(std::ops::DerefMut::deref_mut(&mut self.0), bufs, offset)
+    }
 }
 
+#[cfg(feature = "std")]
+impl<T,U> SizeAt for DerefWrapper<U>
+where T:SizeAt+?Sized, U: std::ops::DerefMut<Target = T>
+{
+    fn size(&self) -> Result<u64> {
+        SizeAt::size(std::ops::Deref::deref(&self.0))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T,U> ResizeAtMut for DerefWrapper<U>
+where T:ResizeAtMut+?Sized, U: std::ops::DerefMut<Target = T>
+{
+    fn set_len(&mut self, new_len: u64) -> Result<()> {
+        ResizeAtMut::set_len(std::ops::DerefMut::deref_mut(&mut self.0), new_len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T,U> SyncAtMut for DerefWrapper<U>
+where T:SyncAtMut+?Sized, U: std::ops::DerefMut<Target = T>
+{
+    fn flush(&mut self) -> Result<()> {
+        SyncAtMut::flush(std::ops::DerefMut::deref_mut(&mut self.0))
+    }
+    fn sync_all(&mut self) -> Result<()> {
+        SyncAtMut::sync_all(std::ops::DerefMut::deref_mut(&mut self.0))
+    }
+    fn sync_data(&mut self) -> Result<()> {
+        SyncAtMut::sync_data(std::ops::DerefMut::deref_mut(&mut self.0))
+    }
+    fn sync_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        SyncAtMut::sync_range(std::ops::DerefMut::deref_mut(&mut self.0), offset, len)
+    }
+}
+
+#[cfg(feature = "std")]
 impl<T,U> WriteAtMut for DerefWrapper<U>
 where T:WriteAtMut+?Sized, U: std::ops::DerefMut<Target = T>
 {
@@ -322,10 +1647,14 @@ where T:WriteAtMut+?Sized, U: std::ops::DerefMut<Target = T>
     fn write_all_at(&mut self, buf: &[u8], offset: u64) -> Result<()> {
         WriteAtMut::write_all_at(std::ops::DerefMut::deref_mut(&mut self.0), buf, offset)
     }
+    fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        WriteAtMut::write_vectored_at(std::ops::DerefMut::deref_mut(&mut self.0), bufs, offset)
+    }
 }
 
 
 
+#[cfg(feature = "std")]
 impl<T> ReadAt for std::cell::RefCell<T> 
 where T:ReadAtMut+?Sized
 {
@@ -337,8 +1666,13 @@ where T:ReadAtMut+?Sized
         let mut se = self.borrow_mut();
         ReadAtMut::read_exact_at(&mut *se, buf, offset)
     }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let mut se = self.borrow_mut();
+        ReadAtMut::read_vectored_at(&mut *se, bufs, offset)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<T> WriteAt for std::cell::RefCell<T> 
 where T:WriteAtMut+?Sized
 {
@@ -350,10 +1684,15 @@ where T:WriteAtMut+?Sized
         let mut se = self.borrow_mut();
         WriteAtMut::write_all_at(&mut *se, buf, offset)
     }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let mut se = self.borrow_mut();
+        WriteAtMut::write_vectored_at(&mut *se, bufs, offset)
+    }
 }
 
 
 
+#[cfg(feature = "std")]
 impl<T> ReadAt for std::sync::Mutex<T> 
 where T:ReadAtMut+?Sized
 {
@@ -361,8 +1700,7 @@ where T:ReadAtMut+?Sized
         let se = self.lock();
         let mut se = match se {
             Ok(x) => x,
-            Err(_) =>  return Err(Error::new(
-                ErrorKind::Other,
+            Err(_) =>  return Err(Error::other(
                 "poisoned mutex encountered",
             )),
         };
@@ -372,15 +1710,25 @@ where T:ReadAtMut+?Sized
         let se = self.lock();
         let mut se = match se {
             Ok(x) => x,
-            Err(_) =>  return Err(Error::new(
-                ErrorKind::Other,
+            Err(_) =>  return Err(Error::other(
                 "poisoned mutex encountered",
             )),
         };
         ReadAtMut::read_exact_at(&mut *se, buf, offset)
     }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let se = self.lock();
+        let mut se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned mutex encountered",
+            )),
+        };
+        ReadAtMut::read_vectored_at(&mut *se, bufs, offset)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<T> WriteAt for std::sync::Mutex<T> 
 where T:WriteAtMut+?Sized
 {
@@ -388,8 +1736,7 @@ where T:WriteAtMut+?Sized
         let se = self.lock();
         let mut se = match se {
             Ok(x) => x,
-            Err(_) =>  return Err(Error::new(
-                ErrorKind::Other,
+            Err(_) =>  return Err(Error::other(
                 "poisoned mutex encountered",
             )),
         };
@@ -399,19 +1746,256 @@ where T:WriteAtMut+?Sized
         let se = self.lock();
         let mut se = match se {
             Ok(x) => x,
-            Err(_) =>  return Err(Error::new(
-                ErrorKind::Other,
+            Err(_) =>  return Err(Error::other(
+                "poisoned mutex encountered",
+            )),
+        };
+        WriteAtMut::write_all_at(&mut *se, buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let se = self.lock();
+        let mut se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
                 "poisoned mutex encountered",
             )),
         };
+        WriteAtMut::write_vectored_at(&mut *se, bufs, offset)
+    }
+}
+
+
+/// Reads take a shared lock, so they do not block each other.
+/// That's why inner object must be `ReadAt`, not just `ReadAtMut`; use `Mutex` for the latter.
+#[cfg(feature = "std")]
+impl<T> ReadAt for std::sync::RwLock<T> 
+where T:ReadAt+?Sized
+{
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let se = self.read();
+        let se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned rwlock encountered",
+            )),
+        };
+        ReadAt::read_at(&*se, buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let se = self.read();
+        let se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned rwlock encountered",
+            )),
+        };
+        ReadAt::read_exact_at(&*se, buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let se = self.read();
+        let se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned rwlock encountered",
+            )),
+        };
+        ReadAt::read_vectored_at(&*se, bufs, offset)
+    }
+}
+
+/// Writes take an exclusive lock.
+#[cfg(feature = "std")]
+impl<T> WriteAt for std::sync::RwLock<T> 
+where T:WriteAtMut+?Sized
+{
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let se = self.write();
+        let mut se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned rwlock encountered",
+            )),
+        };
+        WriteAtMut::write_at(&mut *se, buf, offset)
+    }
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        let se = self.write();
+        let mut se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned rwlock encountered",
+            )),
+        };
         WriteAtMut::write_all_at(&mut *se, buf, offset)
     }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let se = self.write();
+        let mut se = match se {
+            Ok(x) => x,
+            Err(_) =>  return Err(Error::other(
+                "poisoned rwlock encountered",
+            )),
+        };
+        WriteAtMut::write_vectored_at(&mut *se, bufs, offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T:SizeAt+?Sized> SizeAt for std::cell::RefCell<T> {
+    fn size(&self) -> Result<u64> {
+        SizeAt::size(&*self.borrow())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T:SizeAt+?Sized> SizeAt for std::sync::Mutex<T> {
+    fn size(&self) -> Result<u64> {
+        match self.lock() {
+            Ok(x) => SizeAt::size(&*x),
+            Err(_) => Err(Error::other("poisoned mutex encountered")),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T:SizeAt+?Sized> SizeAt for std::sync::RwLock<T> {
+    fn size(&self) -> Result<u64> {
+        match self.read() {
+            Ok(x) => SizeAt::size(&*x),
+            Err(_) => Err(Error::other("poisoned rwlock encountered")),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T:ResizeAtMut+?Sized> ResizeAt for std::cell::RefCell<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        ResizeAtMut::set_len(&mut *self.borrow_mut(), new_len)
+    }
 }
 
+#[cfg(feature = "std")]
+impl<T:ResizeAtMut+?Sized> ResizeAt for std::sync::Mutex<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        match self.lock() {
+            Ok(mut x) => ResizeAtMut::set_len(&mut *x, new_len),
+            Err(_) => Err(Error::other("poisoned mutex encountered")),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T:ResizeAtMut+?Sized> ResizeAt for std::sync::RwLock<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        match self.write() {
+            Ok(mut x) => ResizeAtMut::set_len(&mut *x, new_len),
+            Err(_) => Err(Error::other("poisoned rwlock encountered")),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T:SyncAtMut+?Sized> SyncAt for std::cell::RefCell<T> {
+    fn flush(&self) -> Result<()> {
+        SyncAtMut::flush(&mut *self.borrow_mut())
+    }
+    fn sync_all(&self) -> Result<()> {
+        SyncAtMut::sync_all(&mut *self.borrow_mut())
+    }
+    fn sync_data(&self) -> Result<()> {
+        SyncAtMut::sync_data(&mut *self.borrow_mut())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        SyncAtMut::sync_range(&mut *self.borrow_mut(), offset, len)
+    }
+}
+
+#[cfg(feature = "std")]
+macro_rules! sync_through_lock {
+    ($($lock:ident)::*, $method:ident, $poisoned:expr) => {
+        impl<T:SyncAtMut+?Sized> SyncAt for $($lock)::*<T> {
+            fn flush(&self) -> Result<()> {
+                match self.$method() {
+                    Ok(mut x) => SyncAtMut::flush(&mut *x),
+                    Err(_) => Err(Error::other($poisoned)),
+                }
+            }
+            fn sync_all(&self) -> Result<()> {
+                match self.$method() {
+                    Ok(mut x) => SyncAtMut::sync_all(&mut *x),
+                    Err(_) => Err(Error::other($poisoned)),
+                }
+            }
+            fn sync_data(&self) -> Result<()> {
+                match self.$method() {
+                    Ok(mut x) => SyncAtMut::sync_data(&mut *x),
+                    Err(_) => Err(Error::other($poisoned)),
+                }
+            }
+            fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+                match self.$method() {
+                    Ok(mut x) => SyncAtMut::sync_range(&mut *x, offset, len),
+                    Err(_) => Err(Error::other($poisoned)),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "std")]
+sync_through_lock!(std::sync::Mutex, lock, "poisoned mutex encountered");
+#[cfg(feature = "std")]
+sync_through_lock!(std::sync::RwLock, write, "poisoned rwlock encountered");
+
+// `RwLock<T: ReadAtMut>` can't get `ReadAt` generically alongside `RwLock<T: ReadAt>`,
+// so it is provided for this crate's `ReadAtMut`-only types, taking the write lock like `Mutex`.
+#[cfg(feature = "std")]
+macro_rules! read_through_rwlock_write_guard {
+    ($([$($g:tt)*] $t:ty where [$($w:tt)*]),*) => {$(
+        /// Reads need `&mut`, so they take the write lock and are not concurrent.
+        impl<$($g)*> ReadAt for std::sync::RwLock<$t> where $($w)* {
+            fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+                let mut se = match self.write() {
+                    Ok(x) => x,
+                    Err(_) => return Err(Error::other("poisoned rwlock encountered")),
+                };
+                ReadAtMut::read_at(&mut *se, buf, offset)
+            }
+            fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+                let mut se = match self.write() {
+                    Ok(x) => x,
+                    Err(_) => return Err(Error::other("poisoned rwlock encountered")),
+                };
+                ReadAtMut::read_exact_at(&mut *se, buf, offset)
+            }
+            fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+                let mut se = match self.write() {
+                    Ok(x) => x,
+                    Err(_) => return Err(Error::other("poisoned rwlock encountered")),
+                };
+                ReadAtMut::read_vectored_at(&mut *se, bufs, offset)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "std")]
+read_through_rwlock_write_guard!(
+    [T] ReadWriteSeek<T> where [T: Read+Seek],
+    [T] BufReadAt<T> where [T: ReadAt],
+    ['a] Box<dyn ReadAtMut + 'a> where [],
+    ['a] Box<dyn ReadAtMut + Send + 'a> where [],
+    ['a] Box<dyn ReadAtMut + Send + Sync + 'a> where [],
+    ['a] Box<dyn ReadWriteAtMut + 'a> where [],
+    ['a] Box<dyn ReadWriteAtMut + Send + 'a> where [],
+    ['a] Box<dyn ReadWriteAtMut + Send + Sync + 'a> where []
+);
+
 
 //pub struct DerefWrapper
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
+#[allow(clippy::useless_vec, clippy::diverging_sub_expression)]
 mod tests {
     use super::*;
     use std::sync::mpsc::{channel, Sender, Receiver};
@@ -452,6 +2036,20 @@ mod tests {
         i_want_immut(&rc2);
     }
 
+    #[test]
+    fn check_pointer_forwarding() {
+        let mut o = i_have_obj2();
+        i_want_mut(&mut o);
+        i_want_mut(&mut &mut *o);
+        let arc = std::sync::Arc::new(std::sync::Mutex::new(o));
+        i_want_immut(&arc);
+        i_want_immut3(&arc);
+        let shared: Box<dyn ReadWriteAt> = Box::new(std::rc::Rc::new(arc));
+        let mut v = [0u8; 2];
+        ReadAtMut::read_exact_at(&mut &mut &*shared, &mut v[..], 0).unwrap();
+        assert_eq!(v, [4, 44]);
+    }
+
 
     fn i_have_obj2() -> Box<dyn ReadWriteAtMut + Send> { 
         let v = vec![4u8, 5,6,7,8,9,10,11];
@@ -496,6 +2094,236 @@ mod tests {
         g2.join().unwrap();
     }
 
+    /// Each `read_at` waits until all readers are inside `read_at` simultaneously
+    struct Rendezvous(Vec<u8>, std::sync::Barrier);
+    impl ReadAt for Rendezvous {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            self.1.wait();
+            ReadAt::read_at(&self.0, buf, offset)
+        }
+    }
+    impl WriteAtMut for Rendezvous {
+        fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+            WriteAtMut::write_at(&mut self.0, buf, offset)
+        }
+    }
+
+    #[test]
+    fn check_rwlock_wrapping_works() {
+        const READERS: usize = 4;
+        let o = Rendezvous(vec![1, 2, 3, 4], std::sync::Barrier::new(READERS));
+        let rw = std::sync::Arc::new(std::sync::RwLock::new(o));
+
+        let read_all = |expected: [u8; 2]| {
+            let threads: Vec<_> = (0..READERS).map(|_| {
+                let rw = rw.clone();
+                std::thread::spawn(move || {
+                    let mut v = [0u8; 2];
+                    rw.read_exact_at(&mut v[..], 1).unwrap();
+                    v
+                })
+            }).collect();
+            for t in threads {
+                assert_eq!(t.join().unwrap(), expected);
+            }
+        };
+
+        read_all([2, 3]);
+        let rw2 = rw.clone();
+        std::thread::spawn(move || rw2.write_all_at(&[9, 9], 2).unwrap()).join().unwrap();
+        read_all([2, 9]);
+
+        let rw = std::sync::RwLock::new(ReadWriteSeek(std::io::Cursor::new(vec![4u8, 5, 6, 7, 8, 9])));
+        i_want_immut(&rw);
+        let rw = std::sync::RwLock::new(i_have_obj2());
+        i_want_immut3(&rw);
+        let mut v = [0u8; 2];
+        rw.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [4, 44]);
+    }
+
+    /// Serves at most two bytes per call
+    struct Stingy(Vec<u8>);
+    impl ReadAt for Stingy {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            let data = self.0.get(offset as usize..).unwrap_or(&[]);
+            let n = data.len().min(buf.len()).min(2);
+            buf[..n].copy_from_slice(&data[..n]);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn check_vectored_default_handles_short_reads() {
+        let o = Stingy((0..10).collect());
+        let (mut a, mut b, mut c) = ([0u8; 3], [0u8; 1], [0u8; 4]);
+        let n = {
+            let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b), IoSliceMut::new(&mut c)];
+            ReadAt::read_vectored_at(&o, &mut bufs[..], 1).unwrap()
+        };
+        assert_eq!(n, 8);
+        assert_eq!((a, b, c), ([1, 2, 3], [4], [5, 6, 7, 8]));
+
+        let n = {
+            let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut c)];
+            ReadAt::read_vectored_at(&o, &mut bufs[..], 5).unwrap()
+        };
+        assert_eq!(n, 5);
+        assert_eq!((a, c), ([5, 6, 7], [8, 9, 7, 8]));
+    }
+
+    /// Only vectored methods are expected to be called
+    struct VectoredOnly(ReadWriteSeek<std::io::Cursor<Vec<u8>>>);
+    impl ReadAtMut for VectoredOnly {
+        fn read_at(&mut self, _buf: &mut [u8], _offset: u64) -> Result<usize> {
+            unreachable!()
+        }
+        fn read_vectored_at(&mut self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+            self.0.read_vectored_at(bufs, offset)
+        }
+    }
+    impl WriteAtMut for VectoredOnly {
+        fn write_at(&mut self, _buf: &[u8], _offset: u64) -> Result<usize> {
+            unreachable!()
+        }
+        fn write_vectored_at(&mut self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+            self.0.write_vectored_at(bufs, offset)
+        }
+    }
+
+    #[test]
+    fn check_vectored_forwarding() {
+        let o = || VectoredOnly(ReadWriteSeek(std::io::Cursor::new(vec![0u8; 4])));
+        let objs: Vec<Box<dyn ReadWriteAt>> = vec![
+            Box::new(std::cell::RefCell::new(o())),
+            Box::new(std::sync::Mutex::new(o())),
+            Box::new(std::sync::Mutex::new(DerefWrapper(Box::new(o())))),
+        ];
+        for obj in objs {
+            assert_eq!(obj.write_vectored_at(&[IoSlice::new(&[1]), IoSlice::new(&[2, 3])], 1).unwrap(), 3);
+            let (mut a, mut b) = ([0u8; 1], [0u8; 3]);
+            assert_eq!(obj.read_vectored_at(&mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)], 0).unwrap(), 4);
+            assert_eq!((a, b), ([0], [1, 2, 3]));
+        }
+    }
+
+    #[test]
+    fn check_vectored_file() {
+        let mut f = crate::testing::temp_file();
+
+        let n = WriteAtMut::write_vectored_at(&mut f, &[IoSlice::new(&[1, 2]), IoSlice::new(&[]), IoSlice::new(&[3])], 2).unwrap();
+        assert_eq!(n, 3);
+        let (mut a, mut b) = ([0u8; 2], [0u8; 4]);
+        let n = ReadAtMut::read_vectored_at(&mut f, &mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)], 1).unwrap();
+        assert_eq!(n, 4);
+        assert_eq!((a, &b[..2]), ([0, 1], &[2, 3][..]));
+
+        // More buffers than `IOV_MAX` give a short result instead of an error
+        let data = [7u8; 2000];
+        let slices: Vec<_> = data.chunks(1).map(IoSlice::new).collect();
+        let n = WriteAtMut::write_vectored_at(&mut f, &slices, 0).unwrap();
+        assert!(n > 0 && n <= 2000);
+    }
+
+    #[test]
+    fn check_slices_and_vecs() {
+        let v = vec![1u8, 2, 3, 4];
+        let mut b = [0u8; 3];
+        assert_eq!(ReadAt::read_at(&v[..], &mut b[..], 2).unwrap(), 2);
+        assert_eq!(b, [3, 4, 0]);
+        assert_eq!(ReadAt::read_at(&v, &mut b[..], 4).unwrap(), 0);
+        assert_eq!(ReadAt::read_at(&v, &mut b[..], u64::MAX).unwrap(), 0);
+        i_want_immut(&vec![0u8, 0, 0, 7, 8, 9][..]);
+
+        let mut a = [0u8; 4];
+        assert_eq!(WriteAtMut::write_at(&mut a[..], &[9, 9], 3).unwrap(), 1);
+        assert_eq!(a, [0, 0, 0, 9]);
+        let e = WriteAtMut::write_at(&mut a[..], &[9], 4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WriteZero);
+        let e = WriteAtMut::write_all_at(&mut &mut a[..], &[1, 2], 3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WriteZero);
+
+        let mut v = vec![1u8];
+        WriteAtMut::write_all_at(&mut v, &[5, 6], 3).unwrap();
+        assert_eq!(v, vec![1, 0, 0, 5, 6]);
+        WriteAtMut::write_all_at(&mut v, &[7], 0).unwrap();
+        assert_eq!(v, vec![7, 0, 0, 5, 6]);
+        assert!(WriteAtMut::write_at(&mut v, &[1], 1 << 50).is_err());
+        assert_eq!(v.len(), 5);
+
+        let mut bx: Box<[u8]> = v.clone().into_boxed_slice();
+        WriteAtMut::write_all_at(&mut bx, &[1, 2], 1).unwrap();
+        assert_eq!(WriteAtMut::write_at(&mut bx, &[1], 5).unwrap_err().kind(), ErrorKind::WriteZero);
+        let arc: std::sync::Arc<[u8]> = bx.into();
+        ReadAt::read_exact_at(&arc, &mut b[..], 1).unwrap();
+        assert_eq!(b, [1, 2, 5]);
+    }
+
+    #[test]
+    fn check_cursors() {
+        let mut c = std::io::Cursor::new(vec![1u8, 2, 3]);
+        c.set_position(1);
+        WriteAtMut::write_all_at(&mut c, &[8, 9], 2).unwrap();
+        let mut b = [0u8; 4];
+        ReadAt::read_exact_at(&c, &mut b[..], 0).unwrap();
+        assert_eq!(b, [1, 2, 8, 9]);
+        assert_eq!(c.position(), 1);
+        i_want_immut(&std::io::Cursor::new(&b"\0\0\0\x07\x08\x09"[..]));
+
+        let mut a = [0u8; 2];
+        let mut c = std::io::Cursor::new(&mut a[..]);
+        let e = WriteAtMut::write_all_at(&mut c, &[1, 2], 1).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WriteZero);
+        assert_eq!(a, [0, 1]);
+    }
+
+    #[test]
+    fn check_size_forwarding() {
+        fn size<T:SizeAt+?Sized>(t:&T) -> u64 {
+            t.size().unwrap()
+        }
+        let v = vec![0u8; 5];
+        assert_eq!(size(&v[..]), 5);
+        assert_eq!(size(&std::sync::Arc::new(std::sync::Mutex::new(v.clone()))), 5);
+        assert_eq!(size(&std::cell::RefCell::new(DerefWrapper(Box::new(v.clone())))), 5);
+        assert_eq!(size(&std::sync::RwLock::new(std::io::Cursor::new(v.into_boxed_slice()))), 5);
+    }
+
+    #[test]
+    fn check_resize() {
+        let c = std::cell::RefCell::new(std::io::Cursor::new(vec![1u8, 2, 3]));
+        c.set_len(1).unwrap();
+        c.write_all_at(&[5], 2).unwrap();
+        assert_eq!(c.borrow().get_ref(), &vec![1, 0, 5]);
+        let mut v = vec![];
+        assert!(ResizeAtMut::set_len(&mut v, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn check_sync() {
+        struct Flushes<'a>(&'a std::cell::Cell<usize>);
+        impl Write for Flushes<'_> {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> Result<()> {
+                self.0.set(self.0.get() + 1);
+                Ok(())
+            }
+        }
+        impl Seek for Flushes<'_> {
+            fn seek(&mut self, _: SeekFrom) -> Result<u64> {
+                Ok(0)
+            }
+        }
+        let n = std::cell::Cell::new(0);
+        let m = std::sync::Mutex::new(ReadWriteSeek(Flushes(&n)));
+        m.sync_range(0, 10).unwrap();
+        let b = std::rc::Rc::new(&m);
+        b.sync_all().unwrap();
+        assert_eq!(n.get(), 2);
+    }
+
     #[allow(unused)]
     #[cfg(unix)]
     fn check_refc_wrapping_works2() {
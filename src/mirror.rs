@@ -0,0 +1,278 @@
+use super::{Advice, Advise, Discard, Preallocate, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, Result};
+use std::sync::{Mutex, MutexGuard};
+
+/// Chooses the replica to try first for a read of `len` bytes at `offset`, given the number of replicas.
+pub type ReadPolicy = Box<dyn Fn(u64, usize, usize) -> usize + Send + Sync>;
+
+/// RAID-1 style mirroring: writes go to all replicas, reads are served by one of them.
+///
+/// A replica failing an operation is marked unhealthy and is not used anymore,
+/// the operation is retried on other replicas. It fails only when no healthy replicas remain.
+/// Use `take_error` to inspect what happened and to bring a replica back (e.g. after resynchronising it).
+///
+/// `write_at` uses `write_all_at` on each replica, so replicas don't diverge on short writes.
+///
+/// Reads start from the replica chosen by the read policy (by default, the first one),
+/// skipping unhealthy ones.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Mirror,ReadAt,WriteAt};
+/// use std::cell::RefCell;
+///
+/// let m = Mirror::new(vec![RefCell::new(vec![0u8; 4]), RefCell::new(vec![0u8; 4])]);
+/// m.write_all_at(&[1, 2], 1).unwrap();
+/// assert_eq!(*m.replicas()[1].borrow(), vec![0, 1, 2, 0]);
+///
+/// let mut v = [0u8; 2];
+/// m.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(v, [1, 2]);
+/// assert!(m.is_healthy(0) && m.is_healthy(1));
+/// ```
+pub struct Mirror<T> {
+    replicas: Vec<T>,
+    /// Error that made a replica unhealthy, `None` for healthy ones
+    errors: Mutex<Vec<Option<Error>>>,
+    policy: Option<ReadPolicy>,
+}
+
+fn no_healthy_replicas() -> Error {
+    Error::other("no healthy replicas left")
+}
+
+impl<T> Mirror<T> {
+    /// Panics if `replicas` is empty.
+    pub fn new(replicas: Vec<T>) -> Self {
+        assert!(!replicas.is_empty(), "at least one replica is needed");
+        let errors = Mutex::new(replicas.iter().map(|_| None).collect());
+        Mirror {
+            replicas,
+            errors,
+            policy: None,
+        }
+    }
+
+    /// Set the read balancing policy, e.g. `|offset, _len, n| (offset / 65536) as usize % n`.
+    /// Out of range results are taken modulo the number of replicas.
+    pub fn with_read_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(u64, usize, usize) -> usize + Send + Sync + 'static,
+    {
+        self.policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Replicas, in order
+    pub fn replicas(&self) -> &[T] {
+        &self.replicas
+    }
+
+    /// Whether `replica` is still used
+    pub fn is_healthy(&self, replica: usize) -> bool {
+        self.errors()[replica].is_none()
+    }
+
+    /// Take the error that made `replica` unhealthy, marking it healthy again.
+    /// Returns `None` if it was healthy.
+    pub fn take_error(&self, replica: usize) -> Option<Error> {
+        self.errors()[replica].take()
+    }
+
+    /// Get back the replicas
+    pub fn into_inner(self) -> Vec<T> {
+        self.replicas
+    }
+
+    fn errors(&self) -> MutexGuard<'_, Vec<Option<Error>>> {
+        // Only plain data is stored there, so poisoning is harmless
+        self.errors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Try `f` on healthy replicas starting from `first`, until one succeeds
+    fn first_success<R>(&self, first: usize, mut f: impl FnMut(&T) -> Result<R>) -> Result<R> {
+        let n = self.replicas.len();
+        let mut last_error = None;
+        for i in (0..n).map(|k| (first + k) % n) {
+            if !self.is_healthy(i) {
+                continue;
+            }
+            match f(&self.replicas[i]) {
+                Ok(x) => return Ok(x),
+                Err(e) => {
+                    last_error = Some(e.kind());
+                    self.errors()[i] = Some(e);
+                }
+            }
+        }
+        Err(match last_error {
+            Some(kind) => Error::new(kind, "all healthy replicas failed"),
+            None => no_healthy_replicas(),
+        })
+    }
+
+    /// Call `f` on all healthy replicas, succeeding if at least one of them succeeded
+    fn for_all(&self, mut f: impl FnMut(&T) -> Result<()>) -> Result<()> {
+        let mut succeeded = false;
+        let mut last_error = None;
+        for (i, r) in self.replicas.iter().enumerate() {
+            if !self.is_healthy(i) {
+                continue;
+            }
+            match f(r) {
+                Ok(()) => succeeded = true,
+                Err(e) => {
+                    last_error = Some(e.kind());
+                    self.errors()[i] = Some(e);
+                }
+            }
+        }
+        match (succeeded, last_error) {
+            (true, _) => Ok(()),
+            (false, Some(kind)) => Err(Error::new(kind, "all healthy replicas failed")),
+            (false, None) => Err(no_healthy_replicas()),
+        }
+    }
+}
+
+impl<T: ReadAt> ReadAt for Mirror<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = self.replicas.len();
+        let first = match self.policy {
+            Some(ref p) => p(offset, buf.len(), n) % n,
+            None => 0,
+        };
+        self.first_success(first, |r| r.read_at(buf, offset))
+    }
+}
+
+impl<T: WriteAt> WriteAt for Mirror<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.for_all(|r| r.write_all_at(buf, offset))?;
+        Ok(buf.len())
+    }
+}
+
+/// Size of the first healthy replica
+impl<T: SizeAt> SizeAt for Mirror<T> {
+    fn size(&self) -> Result<u64> {
+        self.first_success(0, |r| r.size())
+    }
+}
+
+/// Forwarded to all healthy replicas, like writes.
+impl<T: SyncAt> SyncAt for Mirror<T> {
+    fn flush(&self) -> Result<()> {
+        self.for_all(|r| r.flush())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.for_all(|r| r.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.for_all(|r| r.sync_data())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_all(|r| r.sync_range(offset, len))
+    }
+}
+
+/// Forwarded to all healthy replicas, as any of them may serve reads.
+impl<T: Advise> Advise for Mirror<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.for_all(|r| r.advise(offset, len, advice))
+    }
+}
+
+/// Forwarded to all healthy replicas, like writes.
+impl<T: Preallocate> Preallocate for Mirror<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_all(|r| r.allocate(offset, len))
+    }
+}
+
+/// Forwarded to all healthy replicas, like writes.
+impl<T: Discard> Discard for Mirror<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_all(|r| r.punch_hole(offset, len))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_all(|r| r.zero_range(offset, len))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_all(|r| r.discard(offset, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::io::ErrorKind;
+
+    struct Flaky {
+        data: RefCell<Vec<u8>>,
+        broken: Cell<bool>,
+    }
+    impl Flaky {
+        fn new(len: usize) -> Self {
+            Flaky {
+                data: RefCell::new(vec![0; len]),
+                broken: Cell::new(false),
+            }
+        }
+        fn check(&self) -> Result<()> {
+            if self.broken.get() {
+                return Err(Error::new(ErrorKind::BrokenPipe, "broken"));
+            }
+            Ok(())
+        }
+    }
+    impl ReadAt for Flaky {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            self.check()?;
+            self.data.read_at(buf, offset)
+        }
+    }
+    impl WriteAt for Flaky {
+        fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+            self.check()?;
+            self.data.write_at(buf, offset)
+        }
+    }
+
+    #[test]
+    fn failover() {
+        let m = Mirror::new(vec![Flaky::new(4), Flaky::new(4)]);
+        m.replicas()[0].broken.set(true);
+        m.write_all_at(&[5], 0).unwrap();
+        assert!(!m.is_healthy(0));
+        assert_eq!(m.replicas()[1].data.borrow()[0], 5);
+
+        // Unhealthy replica is not used even when it works again
+        m.replicas()[0].broken.set(false);
+        let mut v = [0u8; 1];
+        m.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [5]);
+
+        m.replicas()[1].broken.set(true);
+        assert_eq!(m.read_at(&mut v[..], 0).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(m.read_at(&mut v[..], 0).unwrap_err().kind(), ErrorKind::Other);
+        assert_eq!(m.take_error(0).unwrap().kind(), ErrorKind::BrokenPipe);
+        assert!(m.take_error(0).is_none());
+        assert_eq!(m.read_at(&mut v[..], 0).unwrap(), 1);
+        assert_eq!(v, [0]);
+    }
+
+    #[test]
+    fn read_policy() {
+        let a = vec![1u8; 4];
+        let b = vec![2u8; 4];
+        let m = Mirror::new(vec![&a, &b]).with_read_policy(|offset, _, n| offset as usize % n);
+        let mut v = [0u8; 1];
+        m.read_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [1]);
+        m.read_at(&mut v[..], 3).unwrap();
+        assert_eq!(v, [2]);
+    }
+}
@@ -0,0 +1,321 @@
+// Mapping files is inherently unsafe, as the data can be changed behind the program's back
+#![allow(unsafe_code)]
+
+use super::{Advice, Advise, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt, WriteAtMut};
+use memmap2::{Mmap, MmapMut};
+use std::fs::File;
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+impl ReadAt for Mmap {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(&self[..], buf, offset)
+    }
+}
+
+impl SizeAt for Mmap {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl SyncAt for Mmap {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ReadAt for MmapMut {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(&self[..], buf, offset)
+    }
+}
+
+/// Writes in place. Writing at or after the end of the map fails with `WriteZero`.
+impl WriteAtMut for MmapMut {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut self[..], buf, offset)
+    }
+}
+
+impl SizeAt for MmapMut {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+/// `flush` is `msync`, making written data visible in the file.
+impl SyncAt for MmapMut {
+    fn flush(&self) -> Result<()> {
+        MmapMut::flush(self)
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        let offset = offset.min(self.len() as u64) as usize;
+        let len = len.min((self.len() - offset) as u64) as usize;
+        self.flush_range(offset, len)
+    }
+}
+
+/// `madvise` on the part of `len` bytes at `offset` that is within the map, on Unix.
+/// `DontNeed` is ignored, as it may discard modifications of private maps.
+#[cfg(unix)]
+fn advise_map(map: &[u8], advise_range: impl FnOnce(memmap2::Advice, usize, usize) -> Result<()>, offset: u64, len: u64, advice: Advice) -> Result<()> {
+    let advice = match advice {
+        Advice::Normal => memmap2::Advice::Normal,
+        Advice::Sequential => memmap2::Advice::Sequential,
+        Advice::Random => memmap2::Advice::Random,
+        Advice::WillNeed => memmap2::Advice::WillNeed,
+        Advice::DontNeed => return Ok(()),
+    };
+    let offset = offset.min(map.len() as u64) as usize;
+    let len = len.min((map.len() - offset) as u64) as usize;
+    if len == 0 {
+        return Ok(());
+    }
+    advise_range(advice, offset, len)
+}
+
+/// Uses `madvise` on Unix, except for `DontNeed`.
+impl Advise for Mmap {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        #[cfg(unix)]
+        return advise_map(self, |a, o, l| self.advise_range(a, o, l), offset, len, advice);
+        #[cfg(not(unix))]
+        {
+            let _ = (offset, len, advice);
+            Ok(())
+        }
+    }
+}
+
+/// Uses `madvise` on Unix, except for `DontNeed`.
+impl Advise for MmapMut {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        #[cfg(unix)]
+        return advise_map(self, |a, o, l| self.advise_range(a, o, l), offset, len, advice);
+        #[cfg(not(unix))]
+        {
+            let _ = (offset, len, advice);
+            Ok(())
+        }
+    }
+}
+
+enum Map {
+    ReadOnly(Mmap),
+    Writable(MmapMut),
+}
+
+impl Map {
+    fn data(&self) -> &[u8] {
+        match self {
+            Map::ReadOnly(m) => m,
+            Map::Writable(m) => m,
+        }
+    }
+}
+
+/// Memory-mapped file, serving reads without syscalls.
+///
+/// Unlike a bare `MmapMut`, writing past the end or `set_len` resizes the file and maps it anew.
+/// The map is kept in a `RwLock`, so reads are concurrent, but writes and resizes are exclusive.
+///
+/// `flush` and `sync_range` are `msync`; `sync_all` and `sync_data` additionally sync the file.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{MmapDevice,ReadAt,SizeAt,WriteAt};
+///
+/// let path = std::env::temp_dir().join(format!("read_write_at_mmap_doc_{}", std::process::id()));
+/// let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+/// // Safety: nothing else modifies the file while it is mapped
+/// let dev = unsafe { MmapDevice::new(file) }.unwrap();
+/// dev.write_all_at(&[1, 2, 3], 10).unwrap();
+/// assert_eq!(dev.size().unwrap(), 13);
+/// let mut v = [0u8; 2];
+/// dev.read_exact_at(&mut v[..], 11).unwrap();
+/// assert_eq!(v, [2, 3]);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct MmapDevice {
+    file: File,
+    map: RwLock<Map>,
+}
+
+/// # Safety
+///
+/// Same as `MmapDevice::new`
+unsafe fn map(file: &File, writable: bool) -> Result<Map> {
+    Ok(if writable {
+        Map::Writable(MmapMut::map_mut(file)?)
+    } else {
+        Map::ReadOnly(Mmap::map(file)?)
+    })
+}
+
+impl MmapDevice {
+    /// Map whole `file` for reading and writing. The file must be opened for both.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by other handles or processes while it is mapped,
+    /// otherwise reads may observe torn data or fault. See `memmap2::MmapMut::map_mut`.
+    pub unsafe fn new(file: File) -> Result<Self> {
+        let map = RwLock::new(map(&file, true)?);
+        Ok(MmapDevice { file, map })
+    }
+
+    /// Map whole `file` for reading only. Writes and resizes fail with `PermissionDenied`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `new`.
+    pub unsafe fn new_read_only(file: File) -> Result<Self> {
+        let map = RwLock::new(map(&file, false)?);
+        Ok(MmapDevice { file, map })
+    }
+
+    /// Whether the file is mapped for writing
+    pub fn is_writable(&self) -> bool {
+        matches!(*self.read(), Map::Writable(_))
+    }
+
+    /// Get a reference to the mapped file
+    ///
+    /// # Safety
+    ///
+    /// Same as `new`: the file must not be modified or truncated through the reference, e.g. with `set_len`,
+    /// while it is mapped.
+    pub unsafe fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Unmap and get back the file. Call `flush` first to make sure written data is in the file.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Map> {
+        // Maps are never left half-updated, so poisoning is harmless
+        self.map.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Map> {
+        self.map.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Resize the file and map it anew, with the write lock held
+    fn resize(&self, map: &mut Map, new_len: u64) -> Result<()> {
+        if let Map::ReadOnly(_) = map {
+            return Err(Error::new(ErrorKind::PermissionDenied, "map is read-only"));
+        }
+        if usize::try_from(new_len).is_err() {
+            return Err(Error::new(ErrorKind::InvalidInput, "length does not fit in address space"));
+        }
+        self.file.set_len(new_len)?;
+        // Safety: the caller of `new` promised the file is not modified by anything else
+        *map = unsafe { self::map(&self.file, true)? };
+        Ok(())
+    }
+}
+
+impl ReadAt for MmapDevice {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(self.read().data(), buf, offset)
+    }
+}
+
+/// Writing past the end extends the file first.
+impl WriteAt for MmapDevice {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let end = match offset.checked_add(buf.len() as u64) {
+            Some(x) => x,
+            None => return Err(Error::new(ErrorKind::InvalidInput, "write end overflows u64")),
+        };
+        let mut map = self.write();
+        if end > map.data().len() as u64 {
+            self.resize(&mut map, end)?;
+        }
+        match &mut *map {
+            Map::Writable(m) => WriteAtMut::write_at(&mut m[..], buf, offset),
+            Map::ReadOnly(_) => Err(Error::new(ErrorKind::PermissionDenied, "map is read-only")),
+        }
+    }
+}
+
+impl SizeAt for MmapDevice {
+    fn size(&self) -> Result<u64> {
+        Ok(self.read().data().len() as u64)
+    }
+}
+
+impl ResizeAt for MmapDevice {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        let mut map = self.write();
+        self.resize(&mut map, new_len)
+    }
+}
+
+impl Advise for MmapDevice {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        match &*self.read() {
+            Map::Writable(m) => Advise::advise(m, offset, len, advice),
+            Map::ReadOnly(m) => Advise::advise(m, offset, len, advice),
+        }
+    }
+}
+
+impl SyncAt for MmapDevice {
+    fn flush(&self) -> Result<()> {
+        match &*self.read() {
+            Map::Writable(m) => m.flush(),
+            Map::ReadOnly(_) => Ok(()),
+        }
+    }
+    fn sync_all(&self) -> Result<()> {
+        SyncAt::flush(self)?;
+        self.file.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        SyncAt::flush(self)?;
+        self.file.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        match &*self.read() {
+            Map::Writable(m) => SyncAt::sync_range(m, offset, len),
+            Map::ReadOnly(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resizes() {
+        let path = crate::testing::temp_path();
+        std::fs::write(&path, [1u8, 2, 3]).unwrap();
+        let file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let dev = unsafe { MmapDevice::new(file) }.unwrap();
+        assert!(dev.is_writable());
+        dev.write_all_at(&[9], 5).unwrap();
+        dev.set_len(2).unwrap();
+        dev.set_len(4).unwrap();
+        dev.advise(1, 100, Advice::WillNeed).unwrap();
+        dev.advise(100, 1, Advice::Random).unwrap();
+        SyncAt::flush(&dev).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), [1, 2, 0, 0]);
+
+        let ro = unsafe { MmapDevice::new_read_only(std::fs::File::open(&path).unwrap()) }.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut v = [0u8; 8];
+        assert_eq!(ro.read_at(&mut v[..], 1).unwrap(), 3);
+        assert_eq!(ro.write_at(&[1], 0).unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,238 @@
+use super::{ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Result;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(PartialEq, Eq)]
+enum Call {
+    Read { offset: u64, len: usize },
+    Write { offset: u64, data: Vec<u8> },
+    Size,
+    SetLen(u64),
+    Flush,
+    SyncAll,
+    SyncData,
+}
+
+impl fmt::Debug for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Call::Read { offset, len } => write!(f, "read_at(offset {}, len {})", offset, len),
+            Call::Write { offset, data } if data.len() <= 16 => write!(f, "write_at(offset {}, {:?})", offset, data),
+            Call::Write { offset, data } => write!(f, "write_at(offset {}, {} bytes {:?}...)", offset, data.len(), &data[..16]),
+            Call::Size => f.write_str("size()"),
+            Call::SetLen(len) => write!(f, "set_len({})", len),
+            Call::Flush => f.write_str("flush()"),
+            Call::SyncAll => f.write_str("sync_all()"),
+            Call::SyncData => f.write_str("sync_data()"),
+        }
+    }
+}
+
+enum Reply {
+    Data(Vec<u8>),
+    Written(usize),
+    Size(u64),
+    Done,
+}
+
+/// Device that expects a scripted sequence of calls and answers them with canned replies,
+/// for deterministic unit tests of code doing positional IO.
+///
+/// Each `expect_*` method appends one expected call together with its reply. Calls must arrive in exactly that order
+/// with exactly those arguments, otherwise the call panics, naming what was expected and what came instead.
+/// Dropping the device (or calling `verify`) panics if some expected calls did not happen.
+/// Note that helpers like `read_exact_at` and `write_all_at` make one call per partial transfer.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{MockDevice,ReadAt,SizeAt};
+/// use std::io::ErrorKind;
+///
+/// let dev = MockDevice::new()
+///     .expect_size(Ok(1000))
+///     .expect_read(996, 8, Ok(vec![1, 2, 3, 4]))
+///     .expect_read(1000, 4, Ok(vec![]));
+///
+/// // Code under test
+/// let size = dev.size().unwrap();
+/// let mut tail = [0u8; 8];
+/// let err = dev.read_exact_at(&mut tail[..], size - 4).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+/// ```
+pub struct MockDevice {
+    expected: Mutex<VecDeque<(Call, Result<Reply>)>>,
+}
+
+impl Default for MockDevice {
+    fn default() -> Self {
+        MockDevice::new()
+    }
+}
+
+impl MockDevice {
+    /// Device expecting no calls
+    pub fn new() -> Self {
+        MockDevice { expected: Mutex::new(VecDeque::new()) }
+    }
+
+    fn expect(mut self, call: Call, reply: Result<Reply>) -> Self {
+        self.expected.get_mut().unwrap_or_else(|e| e.into_inner()).push_back((call, reply));
+        self
+    }
+
+    /// Expect `read_at` at `offset` with a buffer of `len` bytes, returning `reply` (which may be shorter than `len`)
+    pub fn expect_read(self, offset: u64, len: usize, reply: Result<Vec<u8>>) -> Self {
+        assert!(!matches!(reply, Ok(ref x) if x.len() > len), "MockDevice: reply longer than the read");
+        self.expect(Call::Read { offset, len }, reply.map(Reply::Data))
+    }
+
+    /// Expect `write_at` of exactly `data` at `offset`, reporting `reply` bytes written
+    pub fn expect_write(self, offset: u64, data: &[u8], reply: Result<usize>) -> Self {
+        assert!(!matches!(reply, Ok(n) if n > data.len()), "MockDevice: reply longer than the write");
+        self.expect(Call::Write { offset, data: data.to_vec() }, reply.map(Reply::Written))
+    }
+
+    /// Expect `size`
+    pub fn expect_size(self, reply: Result<u64>) -> Self {
+        self.expect(Call::Size, reply.map(Reply::Size))
+    }
+
+    /// Expect `set_len` to `new_len`
+    pub fn expect_set_len(self, new_len: u64, reply: Result<()>) -> Self {
+        self.expect(Call::SetLen(new_len), reply.map(|()| Reply::Done))
+    }
+
+    /// Expect `flush`
+    pub fn expect_flush(self, reply: Result<()>) -> Self {
+        self.expect(Call::Flush, reply.map(|()| Reply::Done))
+    }
+
+    /// Expect `sync_all`
+    pub fn expect_sync_all(self, reply: Result<()>) -> Self {
+        self.expect(Call::SyncAll, reply.map(|()| Reply::Done))
+    }
+
+    /// Expect `sync_data`
+    pub fn expect_sync_data(self, reply: Result<()>) -> Self {
+        self.expect(Call::SyncData, reply.map(|()| Reply::Done))
+    }
+
+    /// Number of expected calls that did not happen yet
+    pub fn remaining(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Panic if some expected calls did not happen yet
+    pub fn verify(&self) {
+        let expected = self.lock();
+        if let Some((call, _)) = expected.front() {
+            panic!("MockDevice: {} expected calls did not happen, next is {:?}", expected.len(), call);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<(Call, Result<Reply>)>> {
+        self.expected.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn call(&self, call: Call) -> Result<Reply> {
+        let next = self.lock().pop_front();
+        match next {
+            Some((expected, reply)) if expected == call => reply,
+            Some((expected, _)) => panic!("MockDevice: expected {:?}, got {:?}", expected, call),
+            None => panic!("MockDevice: unexpected {:?}", call),
+        }
+    }
+}
+
+impl Drop for MockDevice {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+impl ReadAt for MockDevice {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        match self.call(Call::Read { offset, len: buf.len() })? {
+            Reply::Data(data) => {
+                buf[..data.len()].copy_from_slice(&data);
+                Ok(data.len())
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl WriteAt for MockDevice {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        match self.call(Call::Write { offset, data: buf.to_vec() })? {
+            Reply::Written(n) => Ok(n),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl SizeAt for MockDevice {
+    fn size(&self) -> Result<u64> {
+        match self.call(Call::Size)? {
+            Reply::Size(n) => Ok(n),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ResizeAt for MockDevice {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.call(Call::SetLen(new_len)).map(drop)
+    }
+}
+
+impl SyncAt for MockDevice {
+    fn flush(&self) -> Result<()> {
+        self.call(Call::Flush).map(drop)
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.call(Call::SyncAll).map(drop)
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.call(Call::SyncData).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn script() {
+        let dev = MockDevice::new()
+            .expect_write(10, &[1, 2, 3], Ok(2))
+            .expect_write(12, &[3], Err(Error::from(ErrorKind::Interrupted)))
+            .expect_write(12, &[3], Ok(1))
+            .expect_set_len(20, Ok(()))
+            .expect_sync_data(Err(Error::from(ErrorKind::TimedOut)));
+        dev.write_all_at(&[1, 2, 3], 10).unwrap();
+        assert_eq!(dev.remaining(), 2);
+        dev.set_len(20).unwrap();
+        assert_eq!(dev.sync_data().unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected read_at(offset 0, len 4), got read_at(offset 0, len 8)")]
+    fn wrong_call() {
+        let dev = MockDevice::new().expect_read(0, 4, Ok(vec![]));
+        let mut buf = [0u8; 8];
+        let _ = dev.read_at(&mut buf[..], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 expected calls did not happen, next is flush()")]
+    fn missing_call() {
+        let _dev = MockDevice::new().expect_flush(Ok(()));
+    }
+}
@@ -0,0 +1,121 @@
+// Network Block Device protocol constants and framing shared by `nbd_client` and `nbd_server`,
+// see https://github.com/NetworkBlockDevice/nbd/blob/master/doc/proto.md
+// Only the fixed newstyle handshake and simple replies are used.
+// Parts needed by only one side are unused when only one of `nbd-client` and `nbd-server` is enabled.
+#![allow(dead_code)]
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+pub(crate) const NBDMAGIC: u64 = 0x4e42_444d_4147_4943;
+pub(crate) const IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+pub(crate) const OPTION_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+pub(crate) const REQUEST_MAGIC: u32 = 0x2560_9513;
+pub(crate) const SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+// Handshake flags (server) and client flags
+pub(crate) const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+pub(crate) const FLAG_NO_ZEROES: u16 = 1 << 1;
+
+// Options
+pub(crate) const OPT_EXPORT_NAME: u32 = 1;
+pub(crate) const OPT_ABORT: u32 = 2;
+pub(crate) const OPT_LIST: u32 = 3;
+pub(crate) const OPT_INFO: u32 = 6;
+pub(crate) const OPT_GO: u32 = 7;
+
+// Option reply types
+pub(crate) const REP_ACK: u32 = 1;
+pub(crate) const REP_SERVER: u32 = 2;
+pub(crate) const REP_INFO: u32 = 3;
+pub(crate) const REP_FLAG_ERROR: u32 = 1 << 31;
+pub(crate) const REP_ERR_UNSUP: u32 = REP_FLAG_ERROR | 1;
+pub(crate) const REP_ERR_INVALID: u32 = REP_FLAG_ERROR | 3;
+pub(crate) const REP_ERR_UNKNOWN: u32 = REP_FLAG_ERROR | 6;
+
+pub(crate) const INFO_EXPORT: u16 = 0;
+
+// Transmission flags
+pub(crate) const FLAG_HAS_FLAGS: u16 = 1 << 0;
+pub(crate) const FLAG_READ_ONLY: u16 = 1 << 1;
+pub(crate) const FLAG_SEND_FLUSH: u16 = 1 << 2;
+pub(crate) const FLAG_SEND_FUA: u16 = 1 << 3;
+pub(crate) const FLAG_SEND_TRIM: u16 = 1 << 5;
+pub(crate) const FLAG_SEND_WRITE_ZEROES: u16 = 1 << 6;
+
+// Commands and their flags
+pub(crate) const CMD_READ: u16 = 0;
+pub(crate) const CMD_WRITE: u16 = 1;
+pub(crate) const CMD_DISC: u16 = 2;
+pub(crate) const CMD_FLUSH: u16 = 3;
+pub(crate) const CMD_TRIM: u16 = 4;
+pub(crate) const CMD_WRITE_ZEROES: u16 = 6;
+pub(crate) const CMD_FLAG_FUA: u16 = 1 << 0;
+pub(crate) const CMD_FLAG_NO_HOLE: u16 = 1 << 1;
+
+/// Largest payload of a read or write request servers have to accept
+pub(crate) const MAX_PAYLOAD: usize = 32 << 20;
+/// Longest names and messages accepted during the handshake
+pub(crate) const MAX_STRING: u32 = 4096;
+
+// Error values are Linux `errno` ones regardless of the platform
+pub(crate) const EPERM: u32 = 1;
+pub(crate) const EIO: u32 = 5;
+pub(crate) const ENOMEM: u32 = 12;
+pub(crate) const EINVAL: u32 = 22;
+pub(crate) const ENOSPC: u32 = 28;
+pub(crate) const EOVERFLOW: u32 = 75;
+pub(crate) const ENOTSUP: u32 = 95;
+pub(crate) const ESHUTDOWN: u32 = 108;
+
+pub(crate) fn read_u16<R: Read + ?Sized>(r: &mut R) -> Result<u16> {
+    let mut b = [0u8; 2];
+    r.read_exact(&mut b)?;
+    Ok(u16::from_be_bytes(b))
+}
+
+pub(crate) fn read_u32<R: Read + ?Sized>(r: &mut R) -> Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+pub(crate) fn read_u64<R: Read + ?Sized>(r: &mut R) -> Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_be_bytes(b))
+}
+
+/// Read a handshake payload of `len` bytes, refusing unreasonably long ones
+pub(crate) fn read_payload<R: Read + ?Sized>(r: &mut R, len: u32) -> Result<Vec<u8>> {
+    if len > MAX_STRING + 64 {
+        return Err(Error::new(ErrorKind::InvalidData, "NBD handshake payload is too long"));
+    }
+    let mut v = vec![0u8; len as usize];
+    r.read_exact(&mut v)?;
+    Ok(v)
+}
+
+/// Error of a failed NBD command
+pub(crate) fn errno_to_error(errno: u32) -> Error {
+    let kind = match errno {
+        EPERM => ErrorKind::PermissionDenied,
+        ENOMEM => ErrorKind::OutOfMemory,
+        EINVAL | EOVERFLOW => ErrorKind::InvalidInput,
+        ENOTSUP => ErrorKind::Unsupported,
+        ESHUTDOWN => ErrorKind::ConnectionAborted,
+        _ => ErrorKind::Other,
+    };
+    Error::new(kind, format!("NBD server returned error {}", errno))
+}
+
+/// Error value to send for a failed command
+pub(crate) fn error_to_errno(e: &Error) -> u32 {
+    match e.kind() {
+        ErrorKind::PermissionDenied => EPERM,
+        ErrorKind::OutOfMemory => ENOMEM,
+        ErrorKind::InvalidInput | ErrorKind::UnexpectedEof => EINVAL,
+        ErrorKind::WriteZero => ENOSPC,
+        ErrorKind::Unsupported => ENOTSUP,
+        _ => EIO,
+    }
+}
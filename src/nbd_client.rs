@@ -0,0 +1,389 @@
+use super::nbd::*;
+use super::{Discard, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::sync::{Mutex, MutexGuard};
+
+/// Largest length of a single `TRIM` or `WRITE_ZEROES` request, which carry no payload
+const MAX_ZERO_REQUEST: u64 = 1 << 30;
+
+/// Client side of a Network Block Device export, as a positional device.
+///
+/// Connects over any `Read + Write` stream (usually `TcpStream` or `UnixStream`) using the fixed newstyle handshake,
+/// falling back to `NBD_OPT_EXPORT_NAME` for servers without `NBD_OPT_GO`.
+///
+/// Requests are sent one at a time under a lock and carry at most 32 MiB, so `read_at` and `write_at` may be short.
+/// Reads and writes are clipped to the export size, which can't change.
+/// `sync_data` and `sync_all` send `NBD_CMD_FLUSH` if the server supports it.
+/// `Discard` maps to `NBD_CMD_WRITE_ZEROES` and `NBD_CMD_TRIM`, writing zeroes or doing nothing if they are not supported.
+///
+/// A failure while sending or receiving leaves the connection in unknown state, so all following requests fail.
+/// Dropping the device sends `NBD_CMD_DISC`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{NbdDevice,ReadAt,SizeAt};
+///
+/// let dev = NbdDevice::connect("localhost:10809", "disk").unwrap();
+/// let mut mbr = [0u8; 512];
+/// dev.read_exact_at(&mut mbr[..], 0).unwrap();
+/// println!("{} bytes, signature {:x?}", dev.size().unwrap(), &mbr[510..]);
+/// ```
+pub struct NbdDevice<S: Read + Write> {
+    conn: Mutex<Conn<S>>,
+    size: u64,
+    flags: u16,
+}
+
+struct Conn<S> {
+    stream: S,
+    next_handle: u64,
+    broken: bool,
+}
+
+impl NbdDevice<std::net::TcpStream> {
+    /// Connect to an NBD server over TCP and open export `name`
+    pub fn connect<A: std::net::ToSocketAddrs>(addr: A, name: &str) -> Result<Self> {
+        let stream = std::net::TcpStream::connect(addr)?;
+        // Requests are written with a single call, but Nagle's algorithm would still delay them
+        stream.set_nodelay(true)?;
+        NbdDevice::new(stream, name)
+    }
+}
+
+#[cfg(unix)]
+impl NbdDevice<std::os::unix::net::UnixStream> {
+    /// Connect to an NBD server over a Unix socket and open export `name`
+    pub fn connect_unix<P: AsRef<std::path::Path>>(path: P, name: &str) -> Result<Self> {
+        NbdDevice::new(std::os::unix::net::UnixStream::connect(path)?, name)
+    }
+}
+
+impl<S: Read + Write> NbdDevice<S> {
+    /// Do the handshake over an already connected stream, opening export `name`
+    pub fn new(mut stream: S, name: &str) -> Result<Self> {
+        let (size, flags) = handshake(&mut stream, name)?;
+        Ok(NbdDevice {
+            conn: Mutex::new(Conn { stream, next_handle: 0, broken: false }),
+            size,
+            flags,
+        })
+    }
+
+    /// Whether the server refuses writes
+    pub fn is_read_only(&self) -> bool {
+        self.flags & FLAG_READ_ONLY != 0
+    }
+
+    /// Transmission flags announced by the server, as defined by the NBD protocol
+    pub fn transmission_flags(&self) -> u16 {
+        self.flags
+    }
+
+    fn conn(&self) -> Result<MutexGuard<'_, Conn<S>>> {
+        // A panic while holding the lock leaves `broken` set, so poisoning needs no handling
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        if conn.broken {
+            return Err(Error::new(ErrorKind::BrokenPipe, "NBD connection failed earlier"));
+        }
+        Ok(conn)
+    }
+
+    /// Send a request and wait for its reply, reading the reply payload into `data` for reads
+    fn request(&self, cmd: u16, cmd_flags: u16, offset: u64, len: u32, payload: &[u8], data: &mut [u8]) -> Result<()> {
+        let mut conn = self.conn()?;
+        conn.broken = true;
+        let handle = conn.next_handle;
+        conn.next_handle = conn.next_handle.wrapping_add(1);
+        let mut msg = Vec::with_capacity(28 + payload.len());
+        msg.extend_from_slice(&REQUEST_MAGIC.to_be_bytes());
+        msg.extend_from_slice(&cmd_flags.to_be_bytes());
+        msg.extend_from_slice(&cmd.to_be_bytes());
+        msg.extend_from_slice(&handle.to_be_bytes());
+        msg.extend_from_slice(&offset.to_be_bytes());
+        msg.extend_from_slice(&len.to_be_bytes());
+        msg.extend_from_slice(payload);
+        conn.stream.write_all(&msg)?;
+        conn.stream.flush()?;
+
+        if read_u32(&mut conn.stream)? != SIMPLE_REPLY_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "unexpected NBD reply magic"));
+        }
+        let errno = read_u32(&mut conn.stream)?;
+        if read_u64(&mut conn.stream)? != handle {
+            return Err(Error::new(ErrorKind::InvalidData, "NBD reply for an unexpected request"));
+        }
+        if errno != 0 {
+            conn.broken = false;
+            return Err(errno_to_error(errno));
+        }
+        conn.stream.read_exact(data)?;
+        conn.broken = false;
+        Ok(())
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(Error::new(ErrorKind::PermissionDenied, "NBD export is read-only"));
+        }
+        Ok(())
+    }
+
+    /// Length of `offset..offset+len` clipped to the export size
+    fn clip(&self, offset: u64, len: u64) -> u64 {
+        self.size.saturating_sub(offset).min(len)
+    }
+
+    /// Send a payload-less command for `offset..offset+len` in chunks, or write zeroes if the server doesn't support it
+    fn zero_command(&self, supported: bool, cmd: u16, cmd_flags: u16, offset: u64, len: u64) -> Result<()> {
+        self.check_writable()?;
+        let len = self.clip(offset, len);
+        let mut done = 0;
+        if supported {
+            while done < len {
+                let n = (len - done).min(MAX_ZERO_REQUEST);
+                self.request(cmd, cmd_flags, offset + done, n as u32, &[], &mut [])?;
+                done += n;
+            }
+        } else {
+            let zeroes = vec![0u8; len.min(MAX_PAYLOAD as u64) as usize];
+            while done < len {
+                let n = (len - done).min(zeroes.len() as u64) as usize;
+                self.write_all_at(&zeroes[..n], offset + done)?;
+                done += n as u64;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn handshake<S: Read + Write>(stream: &mut S, name: &str) -> Result<(u64, u16)> {
+    if read_u64(stream)? != NBDMAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "not an NBD server"));
+    }
+    if read_u64(stream)? != IHAVEOPT {
+        return Err(Error::new(ErrorKind::Unsupported, "oldstyle NBD handshake is not supported"));
+    }
+    if name.len() > MAX_STRING as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "NBD export name is too long"));
+    }
+    let server_flags = read_u16(stream)?;
+    let client_flags = server_flags & (FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES);
+    stream.write_all(&u32::from(client_flags).to_be_bytes())?;
+
+    // Unfixed newstyle servers may drop the connection on unknown options such as `NBD_OPT_GO`
+    if client_flags & FLAG_FIXED_NEWSTYLE != 0 {
+        let mut data = Vec::with_capacity(6 + name.len());
+        data.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        send_option(stream, OPT_GO, &data)?;
+        if let Some(info) = go_replies(stream)? {
+            return Ok(info);
+        }
+    }
+
+    send_option(stream, OPT_EXPORT_NAME, name.as_bytes())?;
+    let size = read_u64(stream)?;
+    let flags = read_u16(stream)?;
+    if client_flags & FLAG_NO_ZEROES == 0 {
+        let mut zeroes = [0u8; 124];
+        stream.read_exact(&mut zeroes)?;
+    }
+    Ok((size, flags))
+}
+
+fn send_option<S: Write>(stream: &mut S, option: u32, data: &[u8]) -> Result<()> {
+    let mut msg = Vec::with_capacity(16 + data.len());
+    msg.extend_from_slice(&IHAVEOPT.to_be_bytes());
+    msg.extend_from_slice(&option.to_be_bytes());
+    msg.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    msg.extend_from_slice(data);
+    stream.write_all(&msg)?;
+    stream.flush()
+}
+
+/// Replies to `NBD_OPT_GO`: export size and flags, or `None` if the server does not support the option
+fn go_replies<S: Read>(stream: &mut S) -> Result<Option<(u64, u16)>> {
+    let mut info = None;
+    loop {
+        if read_u64(stream)? != OPTION_REPLY_MAGIC || read_u32(stream)? != OPT_GO {
+            return Err(Error::new(ErrorKind::InvalidData, "unexpected NBD option reply"));
+        }
+        let reply = read_u32(stream)?;
+        let len = read_u32(stream)?;
+        let data = read_payload(stream, len)?;
+        match reply {
+            REP_ACK => {
+                return info
+                    .map(Some)
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, "NBD server sent no export information"))
+            }
+            REP_INFO if data.len() >= 12 && u16::from_be_bytes([data[0], data[1]]) == INFO_EXPORT => {
+                let mut size = [0u8; 8];
+                size.copy_from_slice(&data[2..10]);
+                info = Some((u64::from_be_bytes(size), u16::from_be_bytes([data[10], data[11]])));
+            }
+            REP_ERR_UNSUP => return Ok(None),
+            x if x & REP_FLAG_ERROR != 0 => {
+                let kind = if x == REP_ERR_UNKNOWN { ErrorKind::NotFound } else { ErrorKind::Other };
+                let msg = String::from_utf8_lossy(&data);
+                return Err(Error::new(kind, format!("NBD server refused the export: {}", msg)));
+            }
+            // Unknown reply types other than errors are to be ignored
+            _ => {}
+        }
+    }
+}
+
+impl<S: Read + Write> Drop for NbdDevice<S> {
+    fn drop(&mut self) {
+        if let Ok(conn) = self.conn.get_mut() {
+            if !conn.broken {
+                let mut msg = Vec::with_capacity(28);
+                msg.extend_from_slice(&REQUEST_MAGIC.to_be_bytes());
+                msg.extend_from_slice(&0u16.to_be_bytes());
+                msg.extend_from_slice(&CMD_DISC.to_be_bytes());
+                msg.extend_from_slice(&[0u8; 20]);
+                let _ = conn.stream.write_all(&msg).and_then(|()| conn.stream.flush());
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> ReadAt for NbdDevice<S> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = self.clip(offset, buf.len().min(MAX_PAYLOAD) as u64) as usize;
+        if n == 0 {
+            return Ok(0);
+        }
+        self.request(CMD_READ, 0, offset, n as u32, &[], &mut buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Writes past the end of the export fail with `WriteZero`.
+impl<S: Read + Write> WriteAt for NbdDevice<S> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.check_writable()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = self.clip(offset, buf.len().min(MAX_PAYLOAD) as u64) as usize;
+        if n == 0 {
+            return Err(Error::new(ErrorKind::WriteZero, "write past the end of NBD export"));
+        }
+        self.request(CMD_WRITE, 0, offset, n as u32, &buf[..n], &mut [])?;
+        Ok(n)
+    }
+}
+
+impl<S: Read + Write> SizeAt for NbdDevice<S> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.size)
+    }
+}
+
+impl<S: Read + Write> SyncAt for NbdDevice<S> {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+    fn sync_data(&self) -> Result<()> {
+        if self.flags & FLAG_SEND_FLUSH == 0 {
+            return Ok(());
+        }
+        self.request(CMD_FLUSH, 0, 0, 0, &[], &mut [])
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.sync_data()
+    }
+}
+
+impl<S: Read + Write> Discard for NbdDevice<S> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        let supported = self.flags & FLAG_SEND_WRITE_ZEROES != 0;
+        self.zero_command(supported, CMD_WRITE_ZEROES, 0, offset, len)
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        let supported = self.flags & FLAG_SEND_WRITE_ZEROES != 0;
+        self.zero_command(supported, CMD_WRITE_ZEROES, CMD_FLAG_NO_HOLE, offset, len)
+    }
+    /// `NBD_CMD_TRIM`, or nothing if the server doesn't support it
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        if self.flags & FLAG_SEND_TRIM == 0 {
+            return self.check_writable();
+        }
+        self.zero_command(true, CMD_TRIM, 0, offset, len)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    /// Serve a 4 KiB in-memory export named "mem" supporting everything but `TRIM`
+    fn fake_server(mut s: UnixStream) -> Vec<u8> {
+        let mut mem = vec![0u8; 4096];
+        let mut hello = NBDMAGIC.to_be_bytes().to_vec();
+        hello.extend_from_slice(&IHAVEOPT.to_be_bytes());
+        hello.extend_from_slice(&(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).to_be_bytes());
+        s.write_all(&hello).unwrap();
+        assert_eq!(read_u32(&mut s).unwrap(), 3);
+        assert_eq!(read_u64(&mut s).unwrap(), IHAVEOPT);
+        assert_eq!(read_u32(&mut s).unwrap(), OPT_GO);
+        let len = read_u32(&mut s).unwrap();
+        assert_eq!(read_payload(&mut s, len).unwrap(), b"\0\0\0\x03mem\0\0");
+        let flags = FLAG_HAS_FLAGS | FLAG_SEND_FLUSH | FLAG_SEND_WRITE_ZEROES;
+        for (reply, data) in [(REP_INFO, [&[0u8, 0][..], &4096u64.to_be_bytes(), &flags.to_be_bytes()].concat()), (REP_ACK, vec![])] {
+            let mut msg = OPTION_REPLY_MAGIC.to_be_bytes().to_vec();
+            msg.extend_from_slice(&OPT_GO.to_be_bytes());
+            msg.extend_from_slice(&reply.to_be_bytes());
+            msg.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            msg.extend_from_slice(&data);
+            s.write_all(&msg).unwrap();
+        }
+        loop {
+            assert_eq!(read_u32(&mut s).unwrap(), REQUEST_MAGIC);
+            let _flags = read_u16(&mut s).unwrap();
+            let cmd = read_u16(&mut s).unwrap();
+            let handle = read_u64(&mut s).unwrap();
+            let offset = read_u64(&mut s).unwrap() as usize;
+            let len = read_u32(&mut s).unwrap() as usize;
+            let range = offset..offset + len;
+            let mut reply = SIMPLE_REPLY_MAGIC.to_be_bytes().to_vec();
+            reply.extend_from_slice(&0u32.to_be_bytes());
+            reply.extend_from_slice(&handle.to_be_bytes());
+            match cmd {
+                CMD_READ => reply.extend_from_slice(&mem[range]),
+                CMD_WRITE => s.read_exact(&mut mem[range]).unwrap(),
+                CMD_WRITE_ZEROES => mem[range].fill(0),
+                CMD_FLUSH => {}
+                CMD_DISC => return mem,
+                _ => panic!("unexpected command {}", cmd),
+            }
+            s.write_all(&reply).unwrap();
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || fake_server(b));
+        let dev = NbdDevice::new(a, "mem").unwrap();
+        assert_eq!(dev.size().unwrap(), 4096);
+        assert!(!dev.is_read_only());
+        dev.write_all_at(&[1u8; 100], 3990).unwrap();
+        assert_eq!(dev.write_at(&[1u8; 10], 4096).unwrap_err().kind(), ErrorKind::WriteZero);
+        dev.zero_range(4050, 1000).unwrap();
+        dev.sync_all().unwrap();
+        let mut v = [9u8; 200];
+        assert_eq!(dev.read_at(&mut v[..], 3900).unwrap(), 196);
+        assert!(v[..90].iter().all(|&b| b == 0));
+        assert!(v[90..150].iter().all(|&b| b == 1));
+        assert!(v[150..196].iter().all(|&b| b == 0));
+        dev.discard(0, 10).unwrap();
+        drop(dev);
+        assert_eq!(server.join().unwrap()[3990..4050], [1u8; 60]);
+    }
+}
@@ -0,0 +1,312 @@
+use super::nbd::*;
+use super::{ReadAt, SizeAt, SyncAt, WriteAt};
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+/// Serves a `ReadAt + WriteAt + SizeAt + SyncAt` object as a Network Block Device export,
+/// e.g. to attach it as a kernel block device with `nbd-client`.
+///
+/// Supports the fixed newstyle handshake (`NBD_OPT_GO`, `NBD_OPT_INFO`, `NBD_OPT_LIST` and `NBD_OPT_EXPORT_NAME`)
+/// and simple replies. Besides reads and writes, the export advertises `NBD_CMD_FLUSH` and `NBD_FLAG_SEND_FUA`,
+/// mapped to `sync_data`, and `NBD_CMD_WRITE_ZEROES`, done by writing zeroes.
+/// The size is queried once per connection.
+///
+/// Requests of a connection are handled one at a time, in order; `serve` runs a thread per connection.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{NbdDevice,NbdServer,ReadAt,WriteAt};
+///
+/// let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap();
+/// std::thread::spawn(move || {
+///     let server = NbdServer::new(std::sync::Mutex::new(vec![0u8; 1 << 20])).name("mem");
+///     server.serve(&listener)
+/// });
+///
+/// let dev = NbdDevice::connect(addr, "mem").unwrap();
+/// dev.write_all_at(b"hello", 1000).unwrap();
+/// let mut v = [0u8; 5];
+/// dev.read_exact_at(&mut v[..], 1000).unwrap();
+/// assert_eq!(&v, b"hello");
+/// ```
+pub struct NbdServer<T> {
+    device: T,
+    name: String,
+    read_only: bool,
+}
+
+impl<T: ReadAt + WriteAt + SizeAt + SyncAt> NbdServer<T> {
+    /// Export `device` as the default export (empty name), writable
+    pub fn new(device: T) -> Self {
+        NbdServer { device, name: String::new(), read_only: false }
+    }
+
+    /// Name of the export. Clients asking for the default export (empty name) get it as well.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Advertise the export as read-only and refuse writes with `EPERM`
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Get a reference to the exported object
+    pub fn get_ref(&self) -> &T {
+        &self.device
+    }
+
+    /// Get back the exported object
+    pub fn into_inner(self) -> T {
+        self.device
+    }
+
+    /// Accept connections forever, serving each in its own thread.
+    ///
+    /// Errors of individual connections only end those connections; call `serve_connection` to get them.
+    pub fn serve(&self, listener: &std::net::TcpListener) -> Result<()>
+    where
+        T: Sync,
+    {
+        std::thread::scope(|s| loop {
+            let (stream, _) = listener.accept()?;
+            // Replies are written with a single call, but Nagle's algorithm would still delay them
+            let _ = stream.set_nodelay(true);
+            s.spawn(move || self.serve_connection(stream));
+        })
+    }
+
+    /// Do the handshake and handle requests over `stream` until the client disconnects
+    pub fn serve_connection<S: Read + Write>(&self, mut stream: S) -> Result<()> {
+        let mut hello = Vec::with_capacity(18);
+        hello.extend_from_slice(&NBDMAGIC.to_be_bytes());
+        hello.extend_from_slice(&IHAVEOPT.to_be_bytes());
+        hello.extend_from_slice(&(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).to_be_bytes());
+        stream.write_all(&hello)?;
+        stream.flush()?;
+        let client_flags = read_u32(&mut stream)?;
+        if client_flags & !u32::from(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES) != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "unknown NBD client flags"));
+        }
+        let no_zeroes = client_flags & u32::from(FLAG_NO_ZEROES) != 0;
+        match self.negotiate(&mut stream, no_zeroes)? {
+            Some(size) => self.transmission(&mut stream, size),
+            None => Ok(()),
+        }
+    }
+
+    fn transmission_flags(&self) -> u16 {
+        let mut flags = FLAG_HAS_FLAGS | FLAG_SEND_FLUSH | FLAG_SEND_FUA | FLAG_SEND_WRITE_ZEROES;
+        if self.read_only {
+            flags |= FLAG_READ_ONLY;
+        }
+        flags
+    }
+
+    fn name_matches(&self, name: &[u8]) -> bool {
+        name.is_empty() || name == self.name.as_bytes()
+    }
+
+    /// Handle options until the client picks the export; returns its size, or `None` on `NBD_OPT_ABORT`
+    fn negotiate<S: Read + Write>(&self, stream: &mut S, no_zeroes: bool) -> Result<Option<u64>> {
+        loop {
+            if read_u64(stream)? != IHAVEOPT {
+                return Err(Error::new(ErrorKind::InvalidData, "unexpected NBD option magic"));
+            }
+            let option = read_u32(stream)?;
+            let len = read_u32(stream)?;
+            let data = read_payload(stream, len)?;
+            match option {
+                OPT_EXPORT_NAME => {
+                    // There is no way to refuse, only to disconnect
+                    if !self.name_matches(&data) {
+                        return Err(Error::new(ErrorKind::NotFound, "NBD client asked for an unknown export"));
+                    }
+                    let size = self.device.size()?;
+                    let mut msg = Vec::with_capacity(134);
+                    msg.extend_from_slice(&size.to_be_bytes());
+                    msg.extend_from_slice(&self.transmission_flags().to_be_bytes());
+                    if !no_zeroes {
+                        msg.extend_from_slice(&[0u8; 124]);
+                    }
+                    stream.write_all(&msg)?;
+                    stream.flush()?;
+                    return Ok(Some(size));
+                }
+                OPT_GO | OPT_INFO => {
+                    let name = match parse_info_request(&data) {
+                        Some(x) => x,
+                        None => {
+                            reply(stream, option, REP_ERR_INVALID, b"malformed request")?;
+                            continue;
+                        }
+                    };
+                    if !self.name_matches(name) {
+                        reply(stream, option, REP_ERR_UNKNOWN, b"no such export")?;
+                        continue;
+                    }
+                    let size = self.device.size()?;
+                    let mut info = Vec::with_capacity(12);
+                    info.extend_from_slice(&INFO_EXPORT.to_be_bytes());
+                    info.extend_from_slice(&size.to_be_bytes());
+                    info.extend_from_slice(&self.transmission_flags().to_be_bytes());
+                    reply(stream, option, REP_INFO, &info)?;
+                    reply(stream, option, REP_ACK, &[])?;
+                    if option == OPT_GO {
+                        return Ok(Some(size));
+                    }
+                }
+                OPT_LIST => {
+                    let mut server = Vec::with_capacity(4 + self.name.len());
+                    server.extend_from_slice(&(self.name.len() as u32).to_be_bytes());
+                    server.extend_from_slice(self.name.as_bytes());
+                    reply(stream, option, REP_SERVER, &server)?;
+                    reply(stream, option, REP_ACK, &[])?;
+                }
+                OPT_ABORT => {
+                    // The client may close the connection without waiting for the reply
+                    let _ = reply(stream, option, REP_ACK, &[]);
+                    return Ok(None);
+                }
+                _ => reply(stream, option, REP_ERR_UNSUP, &[])?,
+            }
+        }
+    }
+
+    fn transmission<S: Read + Write>(&self, stream: &mut S, size: u64) -> Result<()> {
+        let mut buf = Vec::new();
+        loop {
+            if read_u32(stream)? != REQUEST_MAGIC {
+                return Err(Error::new(ErrorKind::InvalidData, "unexpected NBD request magic"));
+            }
+            let cmd_flags = read_u16(stream)?;
+            let cmd = read_u16(stream)?;
+            let handle = read_u64(stream)?;
+            let offset = read_u64(stream)?;
+            let len = read_u32(stream)?;
+            let in_bounds = offset.checked_add(u64::from(len)).is_some_and(|end| end <= size);
+
+            if cmd == CMD_WRITE {
+                // The payload has to be consumed even if the request fails
+                if len as usize > MAX_PAYLOAD {
+                    return Err(Error::new(ErrorKind::InvalidData, "NBD write request is too long"));
+                }
+                buf.resize(len as usize, 0);
+                stream.read_exact(&mut buf)?;
+            }
+            let result = match cmd {
+                CMD_DISC => return Ok(()),
+                CMD_READ if len as usize > MAX_PAYLOAD => Err(EINVAL),
+                CMD_READ if !in_bounds => Err(EINVAL),
+                CMD_READ => {
+                    buf.resize(len as usize, 0);
+                    self.device.read_exact_at(&mut buf, offset).map_err(|e| error_to_errno(&e))
+                }
+                CMD_WRITE | CMD_WRITE_ZEROES if self.read_only => Err(EPERM),
+                CMD_WRITE | CMD_WRITE_ZEROES if !in_bounds => Err(ENOSPC),
+                CMD_WRITE => self.write(&buf, offset, cmd_flags),
+                CMD_WRITE_ZEROES => self.write_zeroes(offset, u64::from(len), cmd_flags),
+                CMD_FLUSH => self.device.sync_data().map_err(|e| error_to_errno(&e)),
+                _ => Err(EINVAL),
+            };
+
+            let mut msg = Vec::with_capacity(16);
+            msg.extend_from_slice(&SIMPLE_REPLY_MAGIC.to_be_bytes());
+            msg.extend_from_slice(&result.err().unwrap_or(0).to_be_bytes());
+            msg.extend_from_slice(&handle.to_be_bytes());
+            stream.write_all(&msg)?;
+            if cmd == CMD_READ && result.is_ok() {
+                stream.write_all(&buf)?;
+            }
+            stream.flush()?;
+        }
+    }
+
+    fn write(&self, data: &[u8], offset: u64, cmd_flags: u16) -> std::result::Result<(), u32> {
+        self.device.write_all_at(data, offset).map_err(|e| error_to_errno(&e))?;
+        self.fua(cmd_flags)
+    }
+
+    fn write_zeroes(&self, offset: u64, len: u64, cmd_flags: u16) -> std::result::Result<(), u32> {
+        let zeroes = vec![0u8; len.min(MAX_PAYLOAD as u64) as usize];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(zeroes.len() as u64) as usize;
+            self.device.write_all_at(&zeroes[..n], offset + done).map_err(|e| error_to_errno(&e))?;
+            done += n as u64;
+        }
+        self.fua(cmd_flags)
+    }
+
+    /// Sync written data for requests with `NBD_CMD_FLAG_FUA`
+    fn fua(&self, cmd_flags: u16) -> std::result::Result<(), u32> {
+        if cmd_flags & CMD_FLAG_FUA != 0 {
+            self.device.sync_data().map_err(|e| error_to_errno(&e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Export name of an `NBD_OPT_GO` or `NBD_OPT_INFO` request; requested information types are ignored
+fn parse_info_request(data: &[u8]) -> Option<&[u8]> {
+    let name_len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let name = data.get(4..4 + name_len)?;
+    let requests = u16::from_be_bytes(data.get(4 + name_len..6 + name_len)?.try_into().ok()?) as usize;
+    if data.len() != 6 + name_len + 2 * requests {
+        return None;
+    }
+    Some(name)
+}
+
+fn reply<S: Write>(stream: &mut S, option: u32, reply_type: u32, data: &[u8]) -> Result<()> {
+    let mut msg = Vec::with_capacity(20 + data.len());
+    msg.extend_from_slice(&OPTION_REPLY_MAGIC.to_be_bytes());
+    msg.extend_from_slice(&option.to_be_bytes());
+    msg.extend_from_slice(&reply_type.to_be_bytes());
+    msg.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    msg.extend_from_slice(data);
+    stream.write_all(&msg)?;
+    stream.flush()
+}
+
+#[cfg(all(test, unix, feature = "nbd-client"))]
+mod tests {
+    use super::*;
+    use crate::{Discard, NbdDevice};
+    use std::os::unix::net::UnixStream;
+    use std::sync::Mutex;
+
+    #[test]
+    fn with_client() {
+        let server = NbdServer::new(Mutex::new(vec![7u8; 100_000])).name("disk");
+        std::thread::scope(|s| {
+            let (a, b) = UnixStream::pair().unwrap();
+            s.spawn(|| server.serve_connection(b).unwrap());
+            let dev = NbdDevice::new(a, "disk").unwrap();
+            assert_eq!(dev.size().unwrap(), 100_000);
+            dev.write_all_at(&[1u8; 50], 99_950).unwrap();
+            dev.zero_range(0, 10).unwrap();
+            dev.sync_data().unwrap();
+            let mut v = vec![0u8; 100_000];
+            dev.read_exact_at(&mut v[..], 0).unwrap();
+            assert!(v[..10].iter().all(|&b| b == 0));
+            assert!(v[10..99_950].iter().all(|&b| b == 7));
+            assert!(v[99_950..].iter().all(|&b| b == 1));
+        });
+
+        let (a, b) = UnixStream::pair().unwrap();
+        let ro = NbdServer::new(Mutex::new(vec![7u8; 10])).read_only(true);
+        std::thread::spawn(move || ro.serve_connection(b));
+        let dev = NbdDevice::new(a, "").unwrap();
+        assert!(dev.is_read_only());
+        assert_eq!(dev.write_at(&[1], 0).unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        let (a, b) = UnixStream::pair().unwrap();
+        std::thread::spawn(move || server.serve_connection(b));
+        assert_eq!(NbdDevice::new(a, "other").err().unwrap().kind(), ErrorKind::NotFound);
+    }
+}
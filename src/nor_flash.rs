@@ -0,0 +1,420 @@
+// `is_multiple_of` is newer than the supported Rust version
+#![allow(clippy::manual_is_multiple_of)]
+
+use crate::core_io::{self, ReadAtMut, WriteAtMut};
+use core::convert::TryFrom;
+use core::fmt::Debug;
+use embedded_storage::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Error of `NorFlashAt` and `NorFlashDevice`.
+///
+/// Requires `embedded-storage` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashError<E> {
+    /// Offset or length is not a multiple of the flash granularity
+    NotAligned,
+    /// Access beyond the capacity of the flash
+    OutOfBounds,
+    /// Error of the underlying object
+    Other(E),
+}
+
+impl<E: Debug> NorFlashError for FlashError<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            FlashError::NotAligned => NorFlashErrorKind::NotAligned,
+            FlashError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            FlashError::Other(_) => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+fn round_up(x: usize, align: usize) -> usize {
+    x.div_ceil(align) * align
+}
+
+fn to_u32<E>(offset: u64) -> Result<u32, FlashError<E>> {
+    u32::try_from(offset).map_err(|_| FlashError::OutOfBounds)
+}
+
+/// Positional access to an `embedded-storage` NOR flash, via the `core_io` traits.
+///
+/// Reads and writes may have any offset and length: unaligned parts go through the `scratch` buffer.
+/// A write first reads its erase sector; if the touched words read as erased (`0xFF`), they are written in place,
+/// otherwise the sector is patched in `scratch`, erased and written back whole.
+/// This means a power loss during a write may lose the rest of the sector.
+///
+/// `scratch` must hold at least `READ_SIZE` bytes, and at least `ERASE_SIZE` bytes for writing.
+///
+/// Requires `embedded-storage` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{NorFlashAt,NorFlashDevice};
+/// use read_write_at::core_io::{ReadAtMut,WriteAtMut};
+///
+/// // A flash emulated in memory, with 4-byte words and 16-byte sectors
+/// let flash = NorFlashDevice::<_, 4, 16>::new(vec![], 64);
+/// let mut f = NorFlashAt::new(flash, [0u8; 16]);
+/// f.write_all_at(b"hello", 3).unwrap();
+/// f.write_all_at(b"J", 3).unwrap();
+/// let mut v = [0u8; 7];
+/// f.read_exact_at(&mut v[..], 2).unwrap();
+/// assert_eq!(&v, b"\xFFJello\xFF");
+/// ```
+pub struct NorFlashAt<F, B> {
+    flash: F,
+    scratch: B,
+}
+
+impl<F: ReadNorFlash, B: AsMut<[u8]>> NorFlashAt<F, B> {
+    /// Wrap `flash`, using `scratch` for unaligned accesses
+    pub fn new(flash: F, mut scratch: B) -> Self {
+        assert!(scratch.as_mut().len() >= F::READ_SIZE, "scratch buffer is smaller than READ_SIZE");
+        NorFlashAt { flash, scratch }
+    }
+
+    /// Get a reference to the wrapped flash
+    pub fn get_ref(&self) -> &F {
+        &self.flash
+    }
+
+    /// Get back the wrapped flash and the scratch buffer
+    pub fn into_inner(self) -> (F, B) {
+        (self.flash, self.scratch)
+    }
+}
+
+impl<F: ReadNorFlash, B> core_io::ErrorType for NorFlashAt<F, B> {
+    type Error = FlashError<F::Error>;
+}
+
+impl<F: ReadNorFlash, B: AsMut<[u8]>> ReadAtMut for NorFlashAt<F, B> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Self::Error> {
+        let cap = self.flash.capacity() as u64;
+        if buf.is_empty() || offset >= cap {
+            return Ok(0);
+        }
+        let len = buf.len().min((cap - offset) as usize);
+        let rs = F::READ_SIZE;
+        let head = (offset % rs as u64) as usize;
+        let start = to_u32(offset - head as u64)?;
+        if head == 0 && len >= rs {
+            let n = len - len % rs;
+            self.flash.read(start, &mut buf[..n]).map_err(FlashError::Other)?;
+            return Ok(n);
+        }
+        let scratch = self.scratch.as_mut();
+        let chunk = scratch.len() - scratch.len() % rs;
+        let want = round_up(head + len, rs).min(chunk);
+        self.flash.read(start, &mut scratch[..want]).map_err(FlashError::Other)?;
+        let n = (want - head).min(len);
+        buf[..n].copy_from_slice(&scratch[head..head + n]);
+        Ok(n)
+    }
+}
+
+/// Writes at most one erase sector per call.
+impl<F: NorFlash, B: AsMut<[u8]>> WriteAtMut for NorFlashAt<F, B> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let cap = self.flash.capacity() as u64;
+        if offset >= cap {
+            return Err(FlashError::OutOfBounds);
+        }
+        let es = F::ERASE_SIZE;
+        let head = (offset % es as u64) as usize;
+        let n = buf.len().min(es - head).min((cap - offset) as usize);
+        let sector = to_u32(offset - head as u64)?;
+        let sector_end = to_u32(u64::from(sector) + es as u64)?;
+        if head == 0 && n == es {
+            self.flash.erase(sector, sector_end).map_err(FlashError::Other)?;
+            self.flash.write(sector, &buf[..n]).map_err(FlashError::Other)?;
+            return Ok(n);
+        }
+        let scratch = self.scratch.as_mut();
+        assert!(scratch.len() >= es, "scratch buffer is smaller than ERASE_SIZE");
+        let scratch = &mut scratch[..es];
+        self.flash.read(sector, scratch).map_err(FlashError::Other)?;
+        let ws = F::WRITE_SIZE;
+        let words = head - head % ws..round_up(head + n, ws);
+        let erased = scratch[words.clone()].iter().all(|&b| b == 0xFF);
+        scratch[head..head + n].copy_from_slice(&buf[..n]);
+        if erased {
+            let at = sector + words.start as u32;
+            self.flash.write(at, &scratch[words]).map_err(FlashError::Other)?;
+        } else {
+            self.flash.erase(sector, sector_end).map_err(FlashError::Other)?;
+            self.flash.write(sector, scratch).map_err(FlashError::Other)?;
+        }
+        Ok(n)
+    }
+}
+
+impl<F: ReadNorFlash, B> core_io::SizeAt for NorFlashAt<F, B> {
+    fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.flash.capacity() as u64)
+    }
+}
+
+fn check<E>(capacity: usize, offset: u32, len: usize, align: usize) -> Result<(), FlashError<E>> {
+    let offset = offset as usize;
+    if len > capacity || offset > capacity - len {
+        return Err(FlashError::OutOfBounds);
+    }
+    if offset % align != 0 || len % align != 0 {
+        return Err(FlashError::NotAligned);
+    }
+    Ok(())
+}
+
+const ERASED: [u8; 256] = [0xFF; 256];
+
+/// NOR flash emulated on top of an object implementing the `core_io` traits, e.g. for testing flash code on a host.
+///
+/// Erasing fills with `0xFF`, writing can only clear bits (it ANDs data with the old content),
+/// and data past the end of the underlying object reads as erased.
+/// Reads have byte granularity; write word size and erase sector size are the const parameters.
+///
+/// Requires `embedded-storage` feature.
+pub struct NorFlashDevice<T, const WRITE_SIZE: usize, const ERASE_SIZE: usize> {
+    inner: T,
+    capacity: usize,
+}
+
+impl<T, const WRITE_SIZE: usize, const ERASE_SIZE: usize> NorFlashDevice<T, WRITE_SIZE, ERASE_SIZE> {
+    /// Emulate a flash of `capacity` bytes in `inner`
+    pub fn new(inner: T, capacity: usize) -> Self {
+        assert!(WRITE_SIZE > 0 && ERASE_SIZE % WRITE_SIZE == 0, "ERASE_SIZE must be a nonzero multiple of WRITE_SIZE");
+        assert!(ERASE_SIZE > 0 && capacity % ERASE_SIZE == 0, "capacity must be a multiple of ERASE_SIZE");
+        NorFlashDevice { inner, capacity }
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: core_io::ErrorType, const W: usize, const E: usize> ErrorType for NorFlashDevice<T, W, E> {
+    type Error = FlashError<T::Error>;
+}
+
+impl<T: ReadAtMut, const W: usize, const E: usize> ReadNorFlash for NorFlashDevice<T, W, E> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check(self.capacity, offset, bytes.len(), 1)?;
+        let mut done = 0;
+        while done < bytes.len() {
+            match self.inner.read_at(&mut bytes[done..], u64::from(offset) + done as u64) {
+                Ok(0) => {
+                    bytes[done..].fill(0xFF);
+                    break;
+                }
+                Ok(n) => done += n,
+                Err(e) => return Err(FlashError::Other(e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+fn write_all_err<E>(e: core_io::WriteAllError<E>) -> FlashError<E> {
+    match e {
+        // The underlying object refused to grow
+        core_io::WriteAllError::WriteZero => FlashError::OutOfBounds,
+        core_io::WriteAllError::Other(e) => FlashError::Other(e),
+    }
+}
+
+impl<T: ReadAtMut + WriteAtMut, const W: usize, const E: usize> NorFlash
+    for NorFlashDevice<T, W, E>
+{
+    const WRITE_SIZE: usize = W;
+    const ERASE_SIZE: usize = E;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to {
+            return Err(FlashError::OutOfBounds);
+        }
+        check(self.capacity, from, (to - from) as usize, E)?;
+        let mut offset = u64::from(from);
+        while offset < u64::from(to) {
+            let n = ERASED.len().min((u64::from(to) - offset) as usize);
+            self.inner.write_all_at(&ERASED[..n], offset).map_err(write_all_err)?;
+            offset += n as u64;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check(self.capacity, offset, bytes.len(), W)?;
+        let mut old = [0u8; 256];
+        for (i, chunk) in bytes.chunks(old.len()).enumerate() {
+            let at = u64::from(offset) + (i * old.len()) as u64;
+            let old = &mut old[..chunk.len()];
+            self.read(at as u32, old)?;
+            old.iter_mut().zip(chunk).for_each(|(o, b)| *o &= b);
+            self.inner.write_all_at(old, at).map_err(write_all_err)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: ReadAtMut + WriteAtMut, const W: usize, const E: usize>
+    MultiwriteNorFlash for NorFlashDevice<T, W, E>
+{
+}
+
+/// Async NOR flash emulated on top of an object implementing the async traits, like `NorFlashDevice` does for the `core_io` ones.
+///
+/// Requires `embedded-storage-async` feature.
+#[cfg(feature = "embedded-storage-async")]
+pub struct AsyncNorFlashDevice<T, const WRITE_SIZE: usize, const ERASE_SIZE: usize> {
+    inner: T,
+    capacity: usize,
+}
+
+#[cfg(feature = "embedded-storage-async")]
+impl<T, const WRITE_SIZE: usize, const ERASE_SIZE: usize> AsyncNorFlashDevice<T, WRITE_SIZE, ERASE_SIZE> {
+    /// Emulate a flash of `capacity` bytes in `inner`
+    pub fn new(inner: T, capacity: usize) -> Self {
+        assert!(WRITE_SIZE > 0 && ERASE_SIZE % WRITE_SIZE == 0, "ERASE_SIZE must be a nonzero multiple of WRITE_SIZE");
+        assert!(ERASE_SIZE > 0 && capacity % ERASE_SIZE == 0, "capacity must be a multiple of ERASE_SIZE");
+        AsyncNorFlashDevice { inner, capacity }
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "embedded-storage-async")]
+impl<T, const W: usize, const E: usize> ErrorType for AsyncNorFlashDevice<T, W, E> {
+    type Error = FlashError<std::io::Error>;
+}
+
+#[cfg(feature = "embedded-storage-async")]
+fn io_err(e: std::io::Error) -> FlashError<std::io::Error> {
+    if e.kind() == std::io::ErrorKind::WriteZero {
+        FlashError::OutOfBounds
+    } else {
+        FlashError::Other(e)
+    }
+}
+
+#[cfg(feature = "embedded-storage-async")]
+impl<T: crate::AsyncReadAtMut, const W: usize, const E: usize> embedded_storage_async::nor_flash::ReadNorFlash
+    for AsyncNorFlashDevice<T, W, E>
+{
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check(self.capacity, offset, bytes.len(), 1)?;
+        let mut done = 0;
+        while done < bytes.len() {
+            match self.inner.read_at(&mut bytes[done..], u64::from(offset) + done as u64).await {
+                Ok(0) => {
+                    bytes[done..].fill(0xFF);
+                    break;
+                }
+                Ok(n) => done += n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(FlashError::Other(e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(feature = "embedded-storage-async")]
+impl<T: crate::AsyncReadAtMut + crate::AsyncWriteAtMut, const W: usize, const E: usize>
+    embedded_storage_async::nor_flash::NorFlash for AsyncNorFlashDevice<T, W, E>
+{
+    const WRITE_SIZE: usize = W;
+    const ERASE_SIZE: usize = E;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to {
+            return Err(FlashError::OutOfBounds);
+        }
+        check(self.capacity, from, (to - from) as usize, E)?;
+        let mut offset = u64::from(from);
+        while offset < u64::from(to) {
+            let n = ERASED.len().min((u64::from(to) - offset) as usize);
+            self.inner.write_all_at(&ERASED[..n], offset).await.map_err(io_err)?;
+            offset += n as u64;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        use embedded_storage_async::nor_flash::ReadNorFlash as _;
+        check(self.capacity, offset, bytes.len(), W)?;
+        let mut old = [0u8; 256];
+        for (i, chunk) in bytes.chunks(old.len()).enumerate() {
+            let at = u64::from(offset) + (i * old.len()) as u64;
+            let old = &mut old[..chunk.len()];
+            self.read(at as u32, old).await?;
+            old.iter_mut().zip(chunk).for_each(|(o, b)| *o &= b);
+            self.inner.write_all_at(old, at).await.map_err(io_err)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded-storage-async")]
+impl<T: crate::AsyncReadAtMut + crate::AsyncWriteAtMut, const W: usize, const E: usize>
+    embedded_storage_async::nor_flash::MultiwriteNorFlash for AsyncNorFlashDevice<T, W, E>
+{
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn erase_before_write() {
+        let mut flash = NorFlashDevice::<_, 4, 16>::new(alloc::vec::Vec::new(), 64);
+        flash.write(0, &[0x0F; 4]).unwrap();
+        flash.write(0, &[0xF1; 4]).unwrap();
+        assert_eq!(flash.get_ref()[..4], [0x01; 4]);
+        assert_eq!(flash.write(2, &[0; 4]), Err(FlashError::NotAligned));
+
+        let mut f = NorFlashAt::new(flash, [0u8; 16]);
+        f.write_all_at(&[7; 20], 14).unwrap();
+        f.write_all_at(&[8], 15).unwrap();
+        let mut v = [0u8; 22];
+        f.read_exact_at(&mut v[..], 13).unwrap();
+        assert_eq!(v[..4], [0xFF, 7, 8, 7]);
+        assert_eq!(v[4..21], [7; 17]);
+        assert_eq!(v[21], 0xFF);
+        assert_eq!(f.get_ref().get_ref()[..4], [0x01; 4]);
+        assert_eq!(f.write_at(&[1], 64), Err(FlashError::OutOfBounds));
+    }
+}
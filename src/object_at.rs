@@ -0,0 +1,84 @@
+use super::{ReadAt, SizeAt};
+use object::read::{ReadCache, ReadCacheOps};
+
+/// Stream over a `ReadAt + SizeAt` object, for `object::read::ReadCache`, whose references implement `object::ReadRef`.
+///
+/// This lets the `object` crate parse ELF, PE or Mach-O files from any backend, not only from memory.
+/// `ReadCache` keeps every block read by the parser, so memory use grows with the parsed parts of the file.
+///
+/// Requires `object` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::ObjectReadAt;
+/// use object::ReadRef;
+///
+/// let data = b"\x7fELF\x02\x01".to_vec();
+/// let cache = ObjectReadAt::cache(data);
+/// assert_eq!((&cache).len().unwrap(), 6);
+/// assert_eq!((&cache).read_bytes_at(1, 3).unwrap(), b"ELF");
+/// ```
+pub struct ObjectReadAt<T> {
+    inner: T,
+    pos: u64,
+}
+
+impl<T: ReadAt + SizeAt> ObjectReadAt<T> {
+    /// Wrap `inner`
+    pub fn new(inner: T) -> Self {
+        ObjectReadAt { inner, pos: 0 }
+    }
+
+    /// Wrap `inner` into a `ReadCache`, ready to be passed as `&ReadCache` to parsers
+    pub fn cache(inner: T) -> ReadCache<Self> {
+        ReadCache::new(ObjectReadAt::new(inner))
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ReadAt + SizeAt> ReadCacheOps for ObjectReadAt<T> {
+    fn len(&mut self) -> Result<u64, ()> {
+        self.inner.size().map_err(|_| ())
+    }
+
+    fn seek(&mut self, pos: u64) -> Result<u64, ()> {
+        self.pos = pos;
+        Ok(pos)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        let n = self.inner.read_at(buf, self.pos).map_err(|_| ())?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ()> {
+        self.inner.read_exact_at(buf, self.pos).map_err(|_| ())?;
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::ReadRef;
+
+    #[test]
+    fn until_delimiter() {
+        let data = b"name\0rest".to_vec();
+        let cache = ObjectReadAt::cache(&data);
+        assert_eq!((&cache).read_bytes_at_until(0..9, 0).unwrap(), b"name");
+        assert!((&cache).read_bytes_at(5, 5).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use super::{read_up_to, Advice, Advise, Discard, DiscardMut, Preallocate, PreallocateMut, ReadAt, ReadAtMut, SizeAt, SyncAt, SyncAtMut, WriteAt, WriteAtMut};
+use std::io::Result;
+
+/// When `PageCache` writes data to the wrapped object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Every write goes to the wrapped object immediately, cached pages are updated as well
+    WriteThrough,
+    /// Writes only modify cached pages; dirty pages are written on eviction or `flush`
+    WriteBack,
+}
+
+struct Page {
+    start: u64,
+    /// Shorter than page size only at EOF
+    data: Vec<u8>,
+    dirty: bool,
+}
+
+/// Read and write cache of aligned `page_size`-byte pages over a `ReadAt + WriteAt` object.
+///
+/// At most `max_pages` pages are kept, least recently used pages are evicted first.
+/// With `WritePolicy::WriteBack`, dirty pages are written to the wrapped object when evicted
+/// or on `flush` (also called by other `SyncAtMut` methods before forwarding them).
+/// Dirty pages are discarded if the cache is dropped without flushing.
+///
+/// A write to a page that is not cached reads the page first, so partial page writes keep other data.
+///
+/// The cache is changed on every access, so only `Mut` traits are implemented. Wrap it in `RefCell` or `Mutex` for shared access.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{PageCache,WritePolicy,ReadAtMut,WriteAtMut,SyncAtMut};
+///
+/// let file = std::cell::RefCell::new(vec![0u8; 16]);
+/// let mut c = PageCache::new(&file, 8, 4, WritePolicy::WriteBack);
+/// c.write_all_at(&[1, 2], 7).unwrap();
+/// assert_eq!(file.borrow()[7], 0);
+/// assert_eq!(c.dirty_pages(), 2);
+///
+/// let mut v = [0u8; 2];
+/// c.read_exact_at(&mut v[..], 7).unwrap();
+/// assert_eq!(v, [1, 2]);
+///
+/// c.flush().unwrap();
+/// assert_eq!(file.borrow()[7..9], [1, 2]);
+/// ```
+pub struct PageCache<T: ReadAt + WriteAt> {
+    inner: T,
+    /// Most recently used is at the end
+    pages: Vec<Page>,
+    page_size: usize,
+    max_pages: usize,
+    policy: WritePolicy,
+}
+
+impl<T: ReadAt + WriteAt> PageCache<T> {
+    /// Create an empty cache. Panics if `page_size` is zero.
+    pub fn new(inner: T, page_size: usize, max_pages: usize, policy: WritePolicy) -> Self {
+        assert!(page_size > 0, "page_size must be nonzero");
+        PageCache {
+            inner,
+            pages: Vec::with_capacity(max_pages),
+            page_size,
+            max_pages,
+            policy,
+        }
+    }
+
+    /// Write policy specified at construction time
+    pub fn policy(&self) -> WritePolicy {
+        self.policy
+    }
+
+    /// Number of cached pages not yet written to the wrapped object
+    pub fn dirty_pages(&self) -> usize {
+        self.pages.iter().filter(|p| p.dirty).count()
+    }
+
+    /// Evict all clean cached pages overlapping `offset..offset+len`, e.g. after the wrapped object was modified by other handle
+    pub fn invalidate_range(&mut self, offset: u64, len: u64) {
+        let end = offset.saturating_add(len);
+        let ps = self.page_size as u64;
+        self.pages
+            .retain(|p| p.dirty || !(p.start < end && p.start.saturating_add(ps) > offset));
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object. Dirty pages are discarded, so call `flush` first.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn write_back(inner: &T, page: &mut Page) -> Result<()> {
+        if page.dirty {
+            inner.write_all_at(&page.data[..], page.start)?;
+            page.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Write back dirty pages overlapping `offset..offset+len` and evict all pages overlapping it
+    fn evict_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        let end = offset.saturating_add(len);
+        let ps = self.page_size as u64;
+        let inner = &self.inner;
+        let mut result = Ok(());
+        self.pages.retain_mut(|p| {
+            if !(p.start < end && p.start.saturating_add(ps) > offset) || result.is_err() {
+                return true;
+            }
+            result = PageCache::write_back(inner, p);
+            result.is_err()
+        });
+        result
+    }
+
+    /// Find or fetch the page starting at `start`, moving it to most recently used position
+    fn page(&mut self, start: u64) -> Result<&mut Page> {
+        if let Some(i) = self.pages.iter().position(|p| p.start == start) {
+            let page = self.pages.remove(i);
+            self.pages.push(page);
+        } else {
+            if self.pages.len() >= self.max_pages {
+                PageCache::write_back(&self.inner, &mut self.pages[0])?;
+                self.pages.remove(0);
+            }
+            let mut data = vec![0; self.page_size];
+            let n = read_up_to(&self.inner, &mut data[..], start)?;
+            data.truncate(n);
+            self.pages.push(Page {
+                start,
+                data,
+                dirty: false,
+            });
+        }
+        let last = self.pages.len() - 1;
+        Ok(&mut self.pages[last])
+    }
+
+    /// Call `f(inner, page, buffer range, offset within page)` for consecutive per-page pieces of a `len`-byte request,
+    /// stopping when `f` returns `Ok(false)` or an error.
+    fn split<F>(&mut self, len: usize, offset: u64, mut f: F) -> Result<usize>
+    where
+        F: FnMut(&T, &mut Page, std::ops::Range<usize>, usize) -> Result<bool>,
+    {
+        let ps = self.page_size as u64;
+        let mut done = 0;
+        while done < len {
+            let pos = match offset.checked_add(done as u64) {
+                Some(x) => x,
+                None => break,
+            };
+            let start = pos / ps * ps;
+            let within = (pos - start) as usize;
+            let n = (self.page_size - within).min(len - done);
+            let r = self.page(start).map(|_| ());
+            let r = r.and_then(|()| {
+                let last = self.pages.len() - 1;
+                f(&self.inner, &mut self.pages[last], done..done + n, within)
+            });
+            match r {
+                Ok(true) => done += n,
+                Ok(false) => break,
+                Err(e) if done == 0 => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(done)
+    }
+}
+
+impl<T: ReadAt + WriteAt> ReadAtMut for PageCache<T> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if self.max_pages == 0 {
+            return self.inner.read_at(buf, offset);
+        }
+        let mut total = 0;
+        self.split(buf.len(), offset, |_, page, r, within| {
+            let avail = page.data.len().saturating_sub(within).min(r.len());
+            buf[r.start..r.start + avail].copy_from_slice(&page.data[within..within + avail]);
+            total += avail;
+            Ok(avail == r.len())
+        })?;
+        Ok(total)
+    }
+}
+
+impl<T: ReadAt + WriteAt> WriteAtMut for PageCache<T> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        if self.max_pages == 0 {
+            return self.inner.write_at(buf, offset);
+        }
+        let policy = self.policy;
+        let n = self.split(buf.len(), offset, |inner, page, r, within| {
+            if policy == WritePolicy::WriteThrough {
+                inner.write_all_at(&buf[r.clone()], page.start + within as u64)?;
+            } else {
+                page.dirty = true;
+            }
+            let end = within + r.len();
+            if page.data.len() < end {
+                page.data.resize(end, 0);
+            }
+            page.data[within..end].copy_from_slice(&buf[r]);
+            Ok(true)
+        })?;
+        if n > 0 {
+            // Pages cached short at the old EOF now have zeros up to the written data
+            let ps = self.page_size as u64;
+            for p in self.pages.iter_mut().filter(|p| p.start < offset) {
+                let len = (offset - p.start).min(ps) as usize;
+                if p.data.len() < len {
+                    p.data.resize(len, 0);
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Size of the wrapped object, extended by dirty pages written past its end
+impl<T: ReadAt + WriteAt + SizeAt> SizeAt for PageCache<T> {
+    fn size(&self) -> Result<u64> {
+        let dirty_end = self
+            .pages
+            .iter()
+            .filter(|p| p.dirty)
+            .map(|p| p.start + p.data.len() as u64)
+            .max()
+            .unwrap_or(0);
+        Ok(self.inner.size()?.max(dirty_end))
+    }
+}
+
+/// `flush` writes all dirty pages, then flushes the wrapped object. Other methods flush first, then forward.
+impl<T: ReadAt + WriteAt + SyncAt> SyncAtMut for PageCache<T> {
+    fn flush(&mut self) -> Result<()> {
+        // Write in offset order, keeping the LRU order of the cache itself
+        let mut dirty: Vec<usize> = (0..self.pages.len()).filter(|&i| self.pages[i].dirty).collect();
+        dirty.sort_by_key(|&i| self.pages[i].start);
+        for i in dirty {
+            PageCache::write_back(&self.inner, &mut self.pages[i])?;
+        }
+        self.inner.flush()
+    }
+    fn sync_all(&mut self) -> Result<()> {
+        SyncAtMut::flush(self)?;
+        self.inner.sync_all()
+    }
+    fn sync_data(&mut self) -> Result<()> {
+        SyncAtMut::flush(self)?;
+        self.inner.sync_data()
+    }
+    fn sync_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        SyncAtMut::flush(self)?;
+        self.inner.sync_range(offset, len)
+    }
+}
+
+impl<T: ReadAt + WriteAt + Advise> Advise for PageCache<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.inner.advise(offset, len, advice)
+    }
+}
+
+/// Pages overlapping the range are written back if dirty and evicted, then the call is forwarded.
+impl<T: ReadAt + WriteAt + Preallocate> PreallocateMut for PageCache<T> {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.evict_range(offset, len)?;
+        self.inner.allocate(offset, len)
+    }
+}
+
+/// Pages overlapping the range are written back if dirty and evicted, then the call is forwarded.
+impl<T: ReadAt + WriteAt + Discard> DiscardMut for PageCache<T> {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.evict_range(offset, len)?;
+        self.inner.punch_hole(offset, len)
+    }
+    fn zero_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.evict_range(offset, len)?;
+        self.inner.zero_range(offset, len)
+    }
+    fn discard(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.evict_range(offset, len)?;
+        self.inner.discard(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn write_back_on_eviction() {
+        let file = RefCell::new(vec![7u8; 10]);
+        let mut c = PageCache::new(&file, 4, 3, WritePolicy::WriteBack);
+        c.write_all_at(&[1], 1).unwrap();
+        c.write_all_at(&[2, 2], 11).unwrap();
+        assert_eq!(c.size().unwrap(), 13);
+        assert_eq!(*file.borrow(), vec![7u8; 10]);
+
+        // Evicts page 0
+        let mut v = [0u8; 1];
+        c.read_exact_at(&mut v[..], 4).unwrap();
+        assert_eq!(file.borrow()[..3], [7, 1, 7]);
+        assert_eq!(c.dirty_pages(), 2);
+
+        c.read_exact_at(&mut v[..], 1).unwrap();
+        assert_eq!(v, [1]);
+        c.flush().unwrap();
+        assert_eq!(*file.borrow(), vec![7, 1, 7, 7, 7, 7, 7, 7, 7, 7, 0, 2, 2]);
+    }
+
+    #[test]
+    fn write_through() {
+        let file = RefCell::new(vec![0u8; 8]);
+        let mut c = PageCache::new(&file, 4, 2, WritePolicy::WriteThrough);
+        let mut v = [0u8; 6];
+        c.read_exact_at(&mut v[..], 0).unwrap();
+        c.write_all_at(&[3, 3, 3], 2).unwrap();
+        assert_eq!(file.borrow()[..6], [0, 0, 3, 3, 3, 0]);
+        assert_eq!(c.dirty_pages(), 0);
+        c.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [0, 0, 3, 3, 3, 0]);
+        assert_eq!(c.read_at(&mut v[..], 6).unwrap(), 2);
+    }
+
+    #[test]
+    fn write_past_short_page() {
+        for policy in [WritePolicy::WriteThrough, WritePolicy::WriteBack] {
+            let file = std::sync::Mutex::new(vec![1, 2]);
+            let mut c = PageCache::new(&file, 4, 8, policy);
+            c.read_at(&mut [0; 1], 0).unwrap();
+            c.write_all_at(&[9; 4], 4).unwrap();
+            let mut v = [0; 8];
+            c.read_exact_at(&mut v, 0).unwrap();
+            assert_eq!(v, [1, 2, 0, 0, 9, 9, 9, 9]);
+            c.flush().unwrap();
+            assert_eq!(*file.lock().unwrap(), v);
+        }
+    }
+}
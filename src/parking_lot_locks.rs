@@ -0,0 +1,126 @@
+use super::{ReadAt, ReadAtMut, ResizeAt, ResizeAtMut, SizeAt, SyncAt, SyncAtMut, WriteAt, WriteAtMut};
+use std::io::{IoSlice, IoSliceMut, Result};
+
+/// There is no poisoning, so errors only come from the inner object.
+impl<T: ReadAtMut + ?Sized> ReadAt for parking_lot::Mutex<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAtMut::read_at(&mut *self.lock(), buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        ReadAtMut::read_exact_at(&mut *self.lock(), buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        ReadAtMut::read_vectored_at(&mut *self.lock(), bufs, offset)
+    }
+}
+
+/// There is no poisoning, so errors only come from the inner object.
+impl<T: WriteAtMut + ?Sized> WriteAt for parking_lot::Mutex<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut *self.lock(), buf, offset)
+    }
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        WriteAtMut::write_all_at(&mut *self.lock(), buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        WriteAtMut::write_vectored_at(&mut *self.lock(), bufs, offset)
+    }
+}
+
+/// Reads take the read lock, so they can run concurrently.
+impl<T: ReadAt + ?Sized> ReadAt for parking_lot::RwLock<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadAt::read_at(&*self.read(), buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        ReadAt::read_exact_at(&*self.read(), buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        ReadAt::read_vectored_at(&*self.read(), bufs, offset)
+    }
+}
+
+/// Writes take the write lock.
+impl<T: WriteAtMut + ?Sized> WriteAt for parking_lot::RwLock<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        WriteAtMut::write_at(&mut *self.write(), buf, offset)
+    }
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        WriteAtMut::write_all_at(&mut *self.write(), buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        WriteAtMut::write_vectored_at(&mut *self.write(), bufs, offset)
+    }
+}
+
+impl<T: SizeAt + ?Sized> SizeAt for parking_lot::Mutex<T> {
+    fn size(&self) -> Result<u64> {
+        SizeAt::size(&*self.lock())
+    }
+}
+
+impl<T: SizeAt + ?Sized> SizeAt for parking_lot::RwLock<T> {
+    fn size(&self) -> Result<u64> {
+        SizeAt::size(&*self.read())
+    }
+}
+
+impl<T: ResizeAtMut + ?Sized> ResizeAt for parking_lot::Mutex<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        ResizeAtMut::set_len(&mut *self.lock(), new_len)
+    }
+}
+
+impl<T: ResizeAtMut + ?Sized> ResizeAt for parking_lot::RwLock<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        ResizeAtMut::set_len(&mut *self.write(), new_len)
+    }
+}
+
+impl<T: SyncAtMut + ?Sized> SyncAt for parking_lot::Mutex<T> {
+    fn flush(&self) -> Result<()> {
+        SyncAtMut::flush(&mut *self.lock())
+    }
+    fn sync_all(&self) -> Result<()> {
+        SyncAtMut::sync_all(&mut *self.lock())
+    }
+    fn sync_data(&self) -> Result<()> {
+        SyncAtMut::sync_data(&mut *self.lock())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        SyncAtMut::sync_range(&mut *self.lock(), offset, len)
+    }
+}
+
+impl<T: SyncAtMut + ?Sized> SyncAt for parking_lot::RwLock<T> {
+    fn flush(&self) -> Result<()> {
+        SyncAtMut::flush(&mut *self.write())
+    }
+    fn sync_all(&self) -> Result<()> {
+        SyncAtMut::sync_all(&mut *self.write())
+    }
+    fn sync_data(&self) -> Result<()> {
+        SyncAtMut::sync_data(&mut *self.write())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        SyncAtMut::sync_range(&mut *self.write(), offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ReadWriteAt, ReadWriteSeek};
+
+    fn roundtrip<T: ReadWriteAt>(t: &T) {
+        t.write_all_at(&[7, 8], 1).unwrap();
+        let mut v = [0u8; 3];
+        t.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [0, 7, 8]);
+    }
+
+    #[test]
+    fn parking_lot_locks() {
+        roundtrip(&parking_lot::Mutex::new(ReadWriteSeek(std::io::Cursor::new(vec![0u8; 3]))));
+        roundtrip(&parking_lot::RwLock::new(std::sync::Mutex::new(vec![0u8; 3])));
+    }
+}
@@ -0,0 +1,109 @@
+use super::{ReadAt, ReadAtMut, WriteAt, WriteAtMut};
+use bytemuck::{AnyBitPattern, NoUninit, Pod};
+use std::io::Result;
+use std::mem::size_of;
+
+macro_rules! read_pod_trait {
+    ($(#[$attr:meta])* $name:ident: $base:ident, $($mut_:ident)?) => {
+        $(#[$attr])*
+        pub trait $name: $base {
+            /// Read a `T` stored at `offset`, which needs no alignment
+            fn read_pod_at<T: AnyBitPattern>(&$($mut_)? self, offset: u64) -> Result<T> {
+                let mut b = vec![0u8; size_of::<T>()];
+                self.read_exact_at(&mut b, offset)?;
+                Ok(bytemuck::pod_read_unaligned(&b))
+            }
+
+            /// Fill `items` with consecutive records stored at `offset`
+            fn read_pods_at<T: Pod>(&$($mut_)? self, items: &mut [T], offset: u64) -> Result<()> {
+                self.read_exact_at(bytemuck::cast_slice_mut(items), offset)
+            }
+        }
+
+        impl<T: $base + ?Sized> $name for T {}
+    };
+}
+
+macro_rules! write_pod_trait {
+    ($(#[$attr:meta])* $name:ident: $base:ident, $($mut_:ident)?) => {
+        $(#[$attr])*
+        pub trait $name: $base {
+            /// Write bytes of `value` at `offset`
+            fn write_pod_at<T: NoUninit>(&$($mut_)? self, value: &T, offset: u64) -> Result<()> {
+                self.write_all_at(bytemuck::bytes_of(value), offset)
+            }
+
+            /// Write bytes of consecutive `items` at `offset`
+            fn write_pods_at<T: NoUninit>(&$($mut_)? self, items: &[T], offset: u64) -> Result<()> {
+                self.write_all_at(bytemuck::cast_slice(items), offset)
+            }
+        }
+
+        impl<T: $base + ?Sized> $name for T {}
+    };
+}
+
+read_pod_trait!(
+    /// Reads of plain-old-data structs for `ReadAt` objects, in native byte order and layout.
+    ///
+    /// Requires `bytemuck` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use read_write_at::ReadPodAt;
+    ///
+    /// #[derive(Clone, Copy)]
+    /// #[repr(C)]
+    /// struct Header {
+    ///     magic: [u8; 4],
+    ///     version: u16,
+    ///     flags: u16,
+    /// }
+    /// // Safety: no padding, and all fields accept any bits (or use derives of `bytemuck`'s `derive` feature)
+    /// unsafe impl bytemuck::Zeroable for Header {}
+    /// unsafe impl bytemuck::AnyBitPattern for Header {}
+    ///
+    /// let data = &b"...SQFS\x04\x00\x00\x00"[..];
+    /// let h: Header = data.read_pod_at(3).unwrap();
+    /// assert_eq!(&h.magic, b"SQFS");
+    /// assert_eq!(h.version, u16::from_ne_bytes([4, 0]));
+    /// ```
+    ReadPodAt: ReadAt,
+);
+read_pod_trait!(
+    /// Reads of plain-old-data structs for `ReadAtMut` objects, like `ReadPodAt`.
+    ///
+    /// Requires `bytemuck` feature.
+    ReadPodAtMut: ReadAtMut, mut
+);
+write_pod_trait!(
+    /// Writes of plain-old-data structs for `WriteAt` objects, in native byte order and layout.
+    ///
+    /// Requires `bytemuck` feature.
+    WritePodAt: WriteAt,
+);
+write_pod_trait!(
+    /// Writes of plain-old-data structs for `WriteAtMut` objects, like `WritePodAt`.
+    ///
+    /// Requires `bytemuck` feature.
+    WritePodAtMut: WriteAtMut, mut
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{ReadPodAt, WritePodAtMut};
+
+    #[test]
+    fn records() {
+        let mut v = vec![];
+        v.write_pods_at(&[1u32, 2, 3], 1).unwrap();
+        v.write_pod_at(&[9u8, 9], 0).unwrap();
+        assert_eq!(v.len(), 13);
+        let mut r = [0u32; 2];
+        v.read_pods_at(&mut r[..], 5).unwrap();
+        assert_eq!(r, [2, 3]);
+        assert_eq!(v.read_pod_at::<[u8; 4]>(0).unwrap(), [9, 9, 0, 0]);
+        assert_eq!(v.read_pod_at::<u64>(9).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,106 @@
+use super::{ReadAt, ReadAtMut, ReadWriteSeek, ResizeAt, SizeAt, SyncAt, WriteAt, WriteAtMut};
+use std::fs::File;
+use std::io::{Error, IoSlice, IoSliceMut, Result};
+use std::sync::{Mutex, MutexGuard};
+
+/// `File` giving the immutable traits on every target, by seeking and reading or writing under a lock.
+///
+/// This is a fallback for targets where libstd has no positional `FileExt` (and thus `File` implements nothing here).
+/// Calls are serialized, so prefer `File` itself where it implements `ReadAt` and `WriteAt`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{PortableFile,ReadAt,WriteAt};
+///
+/// let path = std::env::temp_dir().join(format!("read_write_at_portable_doc_{}", std::process::id()));
+/// let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+/// let f = PortableFile::new(file);
+/// f.write_all_at(&[1, 2, 3], 4).unwrap();
+/// let mut v = [9u8; 2];
+/// f.read_exact_at(&mut v[..], 3).unwrap();
+/// assert_eq!(v, [0, 1]);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct PortableFile(pub Mutex<File>);
+
+impl PortableFile {
+    /// Wrap `file`
+    pub fn new(file: File) -> Self {
+        PortableFile(Mutex::new(file))
+    }
+
+    /// Get back the file; its cursor is at an unspecified position
+    pub fn into_inner(self) -> File {
+        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, File>> {
+        self.0.lock().map_err(|_| Error::other("poisoned mutex encountered"))
+    }
+}
+
+impl ReadAt for PortableFile {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        ReadWriteSeek(&mut *self.lock()?).read_at(buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        ReadWriteSeek(&mut *self.lock()?).read_exact_at(buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        ReadWriteSeek(&mut *self.lock()?).read_vectored_at(bufs, offset)
+    }
+}
+
+impl WriteAt for PortableFile {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        ReadWriteSeek(&mut *self.lock()?).write_at(buf, offset)
+    }
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        ReadWriteSeek(&mut *self.lock()?).write_all_at(buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        ReadWriteSeek(&mut *self.lock()?).write_vectored_at(bufs, offset)
+    }
+}
+
+impl SizeAt for PortableFile {
+    fn size(&self) -> Result<u64> {
+        Ok(self.lock()?.metadata()?.len())
+    }
+}
+
+impl ResizeAt for PortableFile {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.lock()?.set_len(new_len)
+    }
+}
+
+/// `sync_range` syncs the whole file data.
+impl SyncAt for PortableFile {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.lock()?.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.lock()?.sync_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize() {
+        let file = crate::testing::temp_file();
+        let f = PortableFile::new(file);
+        f.set_len(5).unwrap();
+        assert_eq!(f.size().unwrap(), 5);
+        let mut v = [1u8; 8];
+        assert_eq!(f.read_at(&mut v[..], 2).unwrap(), 3);
+        assert_eq!(v[..4], [0, 0, 0, 1]);
+    }
+}
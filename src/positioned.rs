@@ -0,0 +1,101 @@
+use super::{ReadAt, SizeAt, SyncAtMut, WriteAtMut};
+use std::io::{Error, ErrorKind, Result};
+
+/// Gives the traits of this crate for an object implementing the `positioned-io` ones.
+///
+/// `SizeAt` fails with `Unsupported` if the object does not know its size.
+///
+/// Requires `positioned-io` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{FromPositionedIo,IntoPositionedIo,ReadAt};
+///
+/// // A `positioned_io::ReadAt` implemented using this crate's `ReadAt`, and wrapped back
+/// let data = FromPositionedIo(IntoPositionedIo(vec![1u8, 2, 3]));
+/// let mut v = [0u8; 2];
+/// data.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(v, [2, 3]);
+/// ```
+pub struct FromPositionedIo<T>(pub T);
+
+impl<T: positioned_io::ReadAt> ReadAt for FromPositionedIo<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.0.read_at(offset, buf)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.0.read_exact_at(offset, buf)
+    }
+}
+
+impl<T: positioned_io::WriteAt> WriteAtMut for FromPositionedIo<T> {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.0.write_at(offset, buf)
+    }
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> Result<()> {
+        self.0.write_all_at(offset, buf)
+    }
+}
+
+impl<T: positioned_io::Size> SizeAt for FromPositionedIo<T> {
+    fn size(&self) -> Result<u64> {
+        self.0.size()?.ok_or_else(|| Error::new(ErrorKind::Unsupported, "size is unknown"))
+    }
+}
+
+/// `positioned-io` has no durability calls, so syncing is just flushing.
+impl<T: positioned_io::WriteAt> SyncAtMut for FromPositionedIo<T> {
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Gives the `positioned-io` traits for an object implementing the ones of this crate.
+///
+/// Requires `positioned-io` feature.
+pub struct IntoPositionedIo<T>(pub T);
+
+impl<T: ReadAt> positioned_io::ReadAt for IntoPositionedIo<T> {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> Result<usize> {
+        self.0.read_at(buf, pos)
+    }
+    fn read_exact_at(&self, pos: u64, buf: &mut [u8]) -> Result<()> {
+        self.0.read_exact_at(buf, pos)
+    }
+}
+
+/// `flush` is `SyncAtMut::flush`.
+impl<T: WriteAtMut + SyncAtMut> positioned_io::WriteAt for IntoPositionedIo<T> {
+    fn write_at(&mut self, pos: u64, buf: &[u8]) -> Result<usize> {
+        self.0.write_at(buf, pos)
+    }
+    fn write_all_at(&mut self, pos: u64, buf: &[u8]) -> Result<()> {
+        self.0.write_all_at(buf, pos)
+    }
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<T: SizeAt> positioned_io::Size for IntoPositionedIo<T> {
+    fn size(&self) -> Result<Option<u64>> {
+        self.0.size().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_ways() {
+        let mut f = FromPositionedIo(IntoPositionedIo(vec![]));
+        f.write_all_at(&[5, 6], 3).unwrap();
+        assert_eq!(f.size().unwrap(), 5);
+        assert_eq!((f.0).0, [0, 0, 0, 5, 6]);
+        let mut v = [0u8; 3];
+        assert_eq!(FromPositionedIo(&[1u8, 2][..]).read_at(&mut v[..], 1).unwrap(), 1);
+        assert_eq!(v[0], 2);
+    }
+}
@@ -0,0 +1,129 @@
+use std::convert::TryFrom;
+use std::io::{Error, Result};
+
+/// Reserving storage up front, like `posix_fallocate`, so that later writes to the range don't fail for lack of space.
+///
+/// The object is extended to at least `offset + len` bytes, new bytes read as zeroes.
+/// Data already in the range is left intact.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Preallocate,SizeAt};
+///
+/// let v = std::sync::Mutex::new(vec![1u8, 2]);
+/// v.allocate(1, 4).unwrap();
+/// assert_eq!(v.size().unwrap(), 5);
+/// assert_eq!(*v.lock().unwrap(), [1, 2, 0, 0, 0]);
+/// ```
+pub trait Preallocate {
+    /// Reserve storage for `offset..offset+len`, extending the object if needed
+    fn allocate(&self, offset: u64, len: u64) -> Result<()>;
+}
+
+/// `Preallocate`, but requiring `&mut self`.
+pub trait PreallocateMut {
+    /// Reserve storage for `offset..offset+len`, extending the object if needed
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()>;
+}
+
+impl<T: Preallocate + ?Sized> PreallocateMut for T {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        Preallocate::allocate(self, offset, len)
+    }
+}
+
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: Preallocate + ?Sized> Preallocate for $ptr {
+            fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+                Preallocate::allocate(&**self, offset, len)
+            }
+        }
+    )*};
+}
+
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+impl<T: PreallocateMut + ?Sized> Preallocate for std::cell::RefCell<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        PreallocateMut::allocate(&mut *self.borrow_mut(), offset, len)
+    }
+}
+
+impl<T: PreallocateMut + ?Sized> Preallocate for std::sync::Mutex<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        match self.lock() {
+            Ok(mut x) => PreallocateMut::allocate(&mut *x, offset, len),
+            Err(_) => Err(Error::other("poisoned mutex encountered")),
+        }
+    }
+}
+
+impl<T: PreallocateMut + ?Sized> Preallocate for std::sync::RwLock<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        match self.write() {
+            Ok(mut x) => PreallocateMut::allocate(&mut *x, offset, len),
+            Err(_) => Err(Error::other("poisoned rwlock encountered")),
+        }
+    }
+}
+
+/// With `preallocate` feature on Linux, Android, FreeBSD and Apple targets, uses `fallocate`, `posix_fallocate`
+/// or `F_PREALLOCATE`. Elsewhere, or when the filesystem can't preallocate,
+/// the file is only extended with `set_len` (`SetEndOfFile` on Windows), which does not reserve space on sparse filesystems.
+impl Preallocate for std::fs::File {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        let end = offset
+            .checked_add(len)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::InvalidInput, "allocation end overflows u64"))?;
+        #[cfg(all(
+            feature = "preallocate",
+            any(target_os = "linux", target_os = "android", target_os = "freebsd", target_vendor = "apple")
+        ))]
+        {
+            use rustix::io::Errno;
+            if len == 0 {
+                return Ok(());
+            }
+            match rustix::fs::fallocate(self, rustix::fs::FallocateFlags::empty(), offset, len) {
+                Err(Errno::OPNOTSUPP) | Err(Errno::INVAL) => {}
+                x => return Ok(x?),
+            }
+        }
+        if self.metadata()?.len() < end {
+            self.set_len(end)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extends the vector with zeroes.
+impl PreallocateMut for Vec<u8> {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        let end = offset
+            .checked_add(len)
+            .and_then(|x| usize::try_from(x).ok())
+            .ok_or_else(|| Error::new(std::io::ErrorKind::OutOfMemory, "allocation does not fit in memory"))?;
+        if self.len() < end {
+            self.resize(end, 0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SizeAt;
+
+    #[test]
+    fn file() {
+        let f = crate::testing::temp_file();
+        f.allocate(4096, 8192).unwrap();
+        assert_eq!(f.size().unwrap(), 12288);
+        f.allocate(0, 10).unwrap();
+        assert_eq!(f.size().unwrap(), 12288);
+        assert_eq!(f.allocate(u64::MAX, 2).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
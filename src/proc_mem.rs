@@ -0,0 +1,61 @@
+use super::ReadAt;
+use std::io::Result;
+
+/// Reads virtual memory of another process through `/proc/PID/mem`.
+///
+/// Offsets are addresses in the target process's address space.
+/// Reading unmapped addresses fails with an error (typically `EIO`).
+///
+/// The calling process needs to be allowed to ptrace the target process
+/// (see `ptrace(2)`, "Ptrace access mode checking"); the check happens on `attach`.
+///
+/// Linux-only.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ProcMemReadAt,ReadAt};
+///
+/// static DATA: [u8; 4] = [1, 2, 3, 4];
+///
+/// let m = ProcMemReadAt::attach(std::process::id()).unwrap();
+/// let mut v = [0u8; 4];
+/// m.read_exact_at(&mut v[..], DATA.as_ptr() as u64).unwrap();
+/// assert_eq!(v, DATA);
+/// ```
+pub struct ProcMemReadAt {
+    pid: u32,
+    file: std::fs::File,
+}
+
+impl ProcMemReadAt {
+    /// Open `/proc/{pid}/mem` for reading
+    pub fn attach(pid: u32) -> Result<Self> {
+        let file = std::fs::File::open(format!("/proc/{}/mem", pid))?;
+        Ok(ProcMemReadAt { pid, file })
+    }
+
+    /// Process ID this object is attached to
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+impl ReadAt for ProcMemReadAt {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        // pread64 on the underlying file descriptor
+        std::os::unix::fs::FileExt::read_at(&self.file, buf, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmapped() {
+        let m = ProcMemReadAt::attach(std::process::id()).unwrap();
+        assert_eq!(m.pid(), std::process::id());
+        assert!(m.read_at(&mut [0u8; 4], 0).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+// Locking calls of `libc` and `windows-sys` are raw FFI
+#![cfg_attr(feature = "range_lock", allow(unsafe_code))]
+
+use std::io::Result;
+
+/// Advisory locks on byte ranges of a shared object, for coordinating access between processes.
+///
+/// Ranges with zero `len` are not locked; use `u64::MAX` length to lock till the end and past it.
+/// Portable code should unlock exactly the ranges it locked and not lock them twice:
+/// Unix locks are split, merged and converted, while Windows ones stack.
+///
+/// `std::fs::File` has inherent whole-file locking methods of the same names,
+/// so call these as `RangeLock::lock_shared(&file, ...)` and so on.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{RangeLock,WriteAt};
+///
+/// fn append_record<T: RangeLock + WriteAt>(obj: &T, offset: u64, record: &[u8]) -> std::io::Result<()> {
+///     obj.lock_exclusive(offset, record.len() as u64)?;
+///     let result = obj.write_all_at(record, offset);
+///     obj.unlock(offset, record.len() as u64)?;
+///     result
+/// }
+/// ```
+pub trait RangeLock {
+    /// Wait until `offset..offset+len` can be locked for reading, then lock it
+    fn lock_shared(&self, offset: u64, len: u64) -> Result<()>;
+    /// Wait until `offset..offset+len` can be locked for writing, then lock it
+    fn lock_exclusive(&self, offset: u64, len: u64) -> Result<()>;
+    /// Lock `offset..offset+len` if it can be done without waiting; returns `false` if it is held by someone else
+    fn try_lock(&self, offset: u64, len: u64, mode: LockMode) -> Result<bool>;
+    /// Release the lock on `offset..offset+len`
+    fn unlock(&self, offset: u64, len: u64) -> Result<()>;
+}
+
+/// Kind of lock for `RangeLock::try_lock`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    /// Many holders at once, excluding `Exclusive` ones
+    Shared,
+    /// Single holder
+    Exclusive,
+}
+
+macro_rules! forward_through_pointer {
+    ($($ptr:ty),*) => {$(
+        impl<T: RangeLock + ?Sized> RangeLock for $ptr {
+            fn lock_shared(&self, offset: u64, len: u64) -> Result<()> {
+                RangeLock::lock_shared(&**self, offset, len)
+            }
+            fn lock_exclusive(&self, offset: u64, len: u64) -> Result<()> {
+                RangeLock::lock_exclusive(&**self, offset, len)
+            }
+            fn try_lock(&self, offset: u64, len: u64, mode: LockMode) -> Result<bool> {
+                RangeLock::try_lock(&**self, offset, len, mode)
+            }
+            fn unlock(&self, offset: u64, len: u64) -> Result<()> {
+                RangeLock::unlock(&**self, offset, len)
+            }
+        }
+    )*};
+}
+
+forward_through_pointer!(&T, &mut T, Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+/// Uses open file description locks (`F_OFD_SETLK`) on Linux and Android, which belong to the opened `File`
+/// and conflict between `File`s of the same process as well.
+/// Other Unix systems get classic `F_SETLK` locks, which belong to the process:
+/// they never conflict within it, and closing any descriptor of the file drops them.
+///
+/// Requires `range_lock` feature.
+#[cfg(all(feature = "range_lock", unix))]
+impl RangeLock for std::fs::File {
+    fn lock_shared(&self, offset: u64, len: u64) -> Result<()> {
+        fcntl_lock(self, offset, len, libc::F_RDLCK, true).map(drop)
+    }
+    fn lock_exclusive(&self, offset: u64, len: u64) -> Result<()> {
+        fcntl_lock(self, offset, len, libc::F_WRLCK, true).map(drop)
+    }
+    fn try_lock(&self, offset: u64, len: u64, mode: LockMode) -> Result<bool> {
+        let kind = match mode {
+            LockMode::Shared => libc::F_RDLCK,
+            LockMode::Exclusive => libc::F_WRLCK,
+        };
+        fcntl_lock(self, offset, len, kind, false)
+    }
+    fn unlock(&self, offset: u64, len: u64) -> Result<()> {
+        fcntl_lock(self, offset, len, libc::F_UNLCK, false).map(drop)
+    }
+}
+
+#[cfg(all(feature = "range_lock", unix))]
+fn fcntl_lock(f: &std::fs::File, offset: u64, len: u64, kind: libc::c_int, wait: bool) -> Result<bool> {
+    use std::convert::TryFrom;
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::io::AsRawFd;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let cmd = if wait { libc::F_OFD_SETLKW } else { libc::F_OFD_SETLK };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let cmd = if wait { libc::F_SETLKW } else { libc::F_SETLK };
+
+    if len == 0 {
+        return Ok(true);
+    }
+    let start = libc::off_t::try_from(offset)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "lock offset does not fit in off_t"))?;
+    // Zero `l_len` means "till the end, however far it grows"
+    let end = offset.checked_add(len).and_then(|x| libc::off_t::try_from(x).ok());
+    let l_len = end.map_or(0, |e| e - start);
+
+    // SAFETY: `flock` is plain old data, all-zeroes is a valid value (and required for `l_pid` of OFD locks)
+    let mut fl: libc::flock = unsafe { std::mem::zeroed() };
+    fl.l_type = kind as _;
+    fl.l_whence = libc::SEEK_SET as _;
+    fl.l_start = start;
+    fl.l_len = l_len;
+    loop {
+        // SAFETY: the descriptor is open for the lifetime of `f`, and `fl` outlives the call
+        if unsafe { libc::fcntl(f.as_raw_fd(), cmd, &mut fl as *mut libc::flock) } != -1 {
+            return Ok(true);
+        }
+        let e = Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EAGAIN) | Some(libc::EACCES) if !wait => return Ok(false),
+            _ => return Err(e),
+        }
+    }
+}
+
+/// Uses `LockFileEx` and `UnlockFileEx`. Note that Windows locks are mandatory:
+/// reads and writes through other handles to a locked range fail instead of just ignoring the lock.
+///
+/// Requires `range_lock` feature.
+#[cfg(all(feature = "range_lock", windows))]
+impl RangeLock for std::fs::File {
+    fn lock_shared(&self, offset: u64, len: u64) -> Result<()> {
+        lock_file_ex(self, offset, len, 0).map(drop)
+    }
+    fn lock_exclusive(&self, offset: u64, len: u64) -> Result<()> {
+        use windows_sys::Win32::Storage::FileSystem::LOCKFILE_EXCLUSIVE_LOCK;
+        lock_file_ex(self, offset, len, LOCKFILE_EXCLUSIVE_LOCK).map(drop)
+    }
+    fn try_lock(&self, offset: u64, len: u64, mode: LockMode) -> Result<bool> {
+        use windows_sys::Win32::Storage::FileSystem::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY};
+        let flags = match mode {
+            LockMode::Shared => 0,
+            LockMode::Exclusive => LOCKFILE_EXCLUSIVE_LOCK,
+        };
+        lock_file_ex(self, offset, len, flags | LOCKFILE_FAIL_IMMEDIATELY)
+    }
+    fn unlock(&self, offset: u64, len: u64) -> Result<()> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Storage::FileSystem::UnlockFileEx;
+        if len == 0 {
+            return Ok(());
+        }
+        let mut ov = overlapped(offset);
+        // SAFETY: the handle is open for the lifetime of `self`, and `ov` outlives the synchronous call
+        if unsafe { UnlockFileEx(self.as_raw_handle() as _, 0, len as u32, (len >> 32) as u32, &mut ov) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "range_lock", windows))]
+fn overlapped(offset: u64) -> windows_sys::Win32::System::IO::OVERLAPPED {
+    // SAFETY: `OVERLAPPED` is plain old data, all-zeroes is a valid value
+    let mut ov: windows_sys::Win32::System::IO::OVERLAPPED = unsafe { std::mem::zeroed() };
+    ov.Anonymous.Anonymous.Offset = offset as u32;
+    ov.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
+    ov
+}
+
+#[cfg(all(feature = "range_lock", windows))]
+fn lock_file_ex(f: &std::fs::File, offset: u64, len: u64, flags: u32) -> Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::ERROR_LOCK_VIOLATION;
+    use windows_sys::Win32::Storage::FileSystem::LockFileEx;
+    if len == 0 {
+        return Ok(true);
+    }
+    let mut ov = overlapped(offset);
+    // SAFETY: the handle is open for the lifetime of `f`, and `ov` outlives the call, which is synchronous
+    // as `File`s are not opened for overlapped IO
+    if unsafe { LockFileEx(f.as_raw_handle() as _, flags, 0, len as u32, (len >> 32) as u32, &mut ov) } == 0 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+            return Ok(false);
+        }
+        return Err(e);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "range_lock", any(target_os = "linux", target_os = "android", windows)))]
+    #[test]
+    fn conflicting_handles() {
+        use super::*;
+        let path = crate::testing::temp_path();
+        let open = || std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).unwrap();
+        let a = open();
+        let b = open();
+        RangeLock::lock_exclusive(&a, 10, 10).unwrap();
+        assert!(!RangeLock::try_lock(&b, 15, 10, LockMode::Shared).unwrap());
+        assert!(RangeLock::try_lock(&b, 20, 10, LockMode::Exclusive).unwrap());
+        RangeLock::unlock(&b, 20, 10).unwrap();
+        RangeLock::unlock(&a, 10, 10).unwrap();
+        RangeLock::lock_shared(&b, 0, 100).unwrap();
+        assert!(RangeLock::try_lock(&a, 50, 10, LockMode::Shared).unwrap());
+        assert!(!RangeLock::try_lock(&a, 90, 10, LockMode::Exclusive).unwrap());
+        RangeLock::unlock(&a, 50, 10).unwrap();
+        RangeLock::unlock(&b, 0, 100).unwrap();
+        drop((a, b));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+use super::{LockMode, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::collections::BTreeMap;
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// A held lock: `start..end` in `mode`
+struct Held {
+    start: u64,
+    end: u64,
+    mode: LockMode,
+}
+
+struct Table {
+    held: BTreeMap<u64, Held>,
+    next_id: u64,
+}
+
+/// Lets threads share a `ReadAt + WriteAt` with reader-writer locking per byte range instead of a single `Mutex`.
+///
+/// Each read locks the range it covers for sharing and each write locks it exclusively,
+/// so reads and writes of disjoint ranges (and overlapping reads) run in parallel,
+/// while `read_exact_at` and `write_all_at` never observe or produce torn data, even if the wrapped object does short IO.
+/// `set_len` locks everything from the new length on.
+/// Lock table lookups are linear in the number of currently held locks.
+///
+/// `lock` and `try_lock` hold a range across several operations. While holding a guard,
+/// access the range through `get_ref`: reads and writes through `RangeLocked` itself would wait for the guard.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{LockMode,RangeLocked,ReadAt,WriteAt};
+///
+/// let dev = RangeLocked::new(std::sync::Mutex::new(vec![0u8; 100]));
+/// std::thread::scope(|s| {
+///     s.spawn(|| dev.write_all_at(&[1; 50], 0).unwrap());
+///     s.spawn(|| dev.write_all_at(&[2; 50], 50).unwrap());
+/// });
+/// {
+///     let _guard = dev.lock(0, 10, LockMode::Exclusive);
+///     assert!(dev.try_lock(5, 10, LockMode::Shared).is_none());
+///     dev.get_ref().write_all_at(&[3; 10], 0).unwrap();
+/// }
+/// let mut v = [0u8; 3];
+/// dev.read_exact_at(&mut v[..], 9).unwrap();
+/// assert_eq!(v, [3, 1, 1]);
+/// ```
+pub struct RangeLocked<T> {
+    inner: T,
+    table: Mutex<Table>,
+    released: Condvar,
+}
+
+/// A range of `RangeLocked` locked by `lock` or `try_lock`, released on drop
+pub struct RangeGuard<'a, T> {
+    owner: &'a RangeLocked<T>,
+    id: Option<u64>,
+}
+
+impl<T> Drop for RangeGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.owner.table().held.remove(&id);
+            self.owner.released.notify_all();
+        }
+    }
+}
+
+impl<T> RangeLocked<T> {
+    /// Wrap an object, no locks held initially
+    pub fn new(inner: T) -> Self {
+        RangeLocked {
+            inner,
+            table: Mutex::new(Table { held: BTreeMap::new(), next_id: 0 }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Get a reference to the wrapped object, bypassing the locks
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn table(&self) -> MutexGuard<'_, Table> {
+        // The table is only updated by infallible code, so poisoning is harmless
+        self.table.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lock `offset..offset+len`, waiting for conflicting locks to be released.
+    /// Zero-length ranges lock nothing.
+    pub fn lock(&self, offset: u64, len: u64, mode: LockMode) -> RangeGuard<'_, T> {
+        let mut table = self.table();
+        loop {
+            match insert(&mut table, offset, len, mode) {
+                Some(id) => return RangeGuard { owner: self, id },
+                None => table = self.released.wait(table).unwrap_or_else(|e| e.into_inner()),
+            }
+        }
+    }
+
+    /// Lock `offset..offset+len` if no conflicting locks are held; `None` otherwise
+    pub fn try_lock(&self, offset: u64, len: u64, mode: LockMode) -> Option<RangeGuard<'_, T>> {
+        let id = insert(&mut self.table(), offset, len, mode)?;
+        Some(RangeGuard { owner: self, id })
+    }
+}
+
+/// Record a lock unless it conflicts with a held one; the inner `None` is for empty ranges
+fn insert(table: &mut Table, offset: u64, len: u64, mode: LockMode) -> Option<Option<u64>> {
+    if len == 0 {
+        return Some(None);
+    }
+    let (start, end) = (offset, offset.saturating_add(len));
+    let conflicts = table.held.values().any(|h| {
+        h.start < end && start < h.end && (mode == LockMode::Exclusive || h.mode == LockMode::Exclusive)
+    });
+    if conflicts {
+        return None;
+    }
+    let id = table.next_id;
+    table.next_id += 1;
+    table.held.insert(id, Held { start, end, mode });
+    Some(Some(id))
+}
+
+fn total_len<'a>(lens: impl Iterator<Item = &'a [u8]>) -> u64 {
+    lens.map(|b| b.len() as u64).sum()
+}
+
+impl<T: ReadAt> ReadAt for RangeLocked<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let _g = self.lock(offset, buf.len() as u64, LockMode::Shared);
+        self.inner.read_at(buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let _g = self.lock(offset, buf.len() as u64, LockMode::Shared);
+        self.inner.read_exact_at(buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let _g = self.lock(offset, total_len(bufs.iter().map(|b| &**b)), LockMode::Shared);
+        self.inner.read_vectored_at(bufs, offset)
+    }
+}
+
+impl<T: WriteAt> WriteAt for RangeLocked<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let _g = self.lock(offset, buf.len() as u64, LockMode::Exclusive);
+        self.inner.write_at(buf, offset)
+    }
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        let _g = self.lock(offset, buf.len() as u64, LockMode::Exclusive);
+        self.inner.write_all_at(buf, offset)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let _g = self.lock(offset, total_len(bufs.iter().map(|b| &**b)), LockMode::Exclusive);
+        self.inner.write_vectored_at(bufs, offset)
+    }
+}
+
+impl<T: SizeAt> SizeAt for RangeLocked<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+impl<T: ResizeAt> ResizeAt for RangeLocked<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        let _g = self.lock(new_len, u64::MAX, LockMode::Exclusive);
+        self.inner.set_len(new_len)
+    }
+}
+
+/// `flush` and syncing take no locks: they don't change data.
+impl<T: SyncAt> SyncAt for RangeLocked<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.sync_range(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transfers one byte per call, so unlocked `write_all_at`s of different threads interleave
+    struct ByteAtATime(Mutex<Vec<u8>>);
+
+    impl ReadAt for ByteAtATime {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            let n = buf.len().min(1);
+            self.0.read_at(&mut buf[..n], offset)
+        }
+    }
+
+    impl WriteAt for ByteAtATime {
+        fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+            std::thread::yield_now();
+            let n = buf.len().min(1);
+            self.0.write_at(&buf[..n], offset)
+        }
+    }
+
+    #[test]
+    fn no_torn_writes() {
+        let dev = RangeLocked::new(ByteAtATime(Mutex::new(vec![0u8; 64])));
+        std::thread::scope(|s| {
+            for t in 1..=4u8 {
+                let dev = &dev;
+                s.spawn(move || {
+                    for i in 0..20u64 {
+                        dev.write_all_at(&[t; 32], i % 3 * 16).unwrap();
+                        let mut v = [0u8; 16];
+                        dev.read_exact_at(&mut v[..], 16).unwrap();
+                        assert!(v.iter().all(|&b| b == v[0]));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn conflicts() {
+        let dev = RangeLocked::new(vec![0u8; 10]);
+        let a = dev.lock(10, 10, LockMode::Shared);
+        let b = dev.try_lock(15, 10, LockMode::Shared).unwrap();
+        assert!(dev.try_lock(19, 1, LockMode::Exclusive).is_none());
+        assert!(dev.try_lock(25, 1, LockMode::Exclusive).is_some());
+        assert!(dev.try_lock(0, 0, LockMode::Exclusive).is_some());
+        drop(a);
+        assert!(dev.try_lock(10, 5, LockMode::Exclusive).is_some());
+        drop(b);
+        let _c = dev.lock(0, u64::MAX, LockMode::Exclusive);
+        assert!(dev.try_lock(u64::MAX - 1, 1, LockMode::Shared).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+use super::{read_up_to, Advice, Advise, Discard, DiscardMut, Preallocate, PreallocateMut, ReadAt, ReadAtMut, SizeAt};
+use std::collections::BTreeMap;
+use std::io::Result;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+type Blocks = Arc<Mutex<BTreeMap<u64, Box<[u8]>>>>;
+
+/// Prefetcher for sequential reads over a `ReadAt`.
+///
+/// When a read starts exactly where the previous one ended, the next `blocks_ahead` aligned
+/// `block_size`-byte blocks are read in a background thread into an internal cache.
+/// Reads hitting the cache are served from it (at most one block per call, so they may be short),
+/// other reads go directly to the wrapped object.
+/// At most one background prefetch runs at a time.
+///
+/// Prefetched data may become stale if the wrapped object gets modified; call `invalidate` then.
+///
+/// The access pattern state is changed on reads, so only `ReadAtMut` is implemented.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAhead,ReadAtMut};
+///
+/// let data: Vec<u8> = (0..=255).collect();
+/// let mut r = ReadAhead::new(data, 16, 4);
+/// let mut v = [0u8; 8];
+/// for i in 0..32 {
+///     r.read_exact_at(&mut v[..], i * 8).unwrap();
+///     assert_eq!(v[0], (i * 8) as u8);
+/// }
+/// ```
+pub struct ReadAhead<T: ReadAt + Send + Sync + 'static> {
+    inner: Arc<T>,
+    block_size: usize,
+    blocks_ahead: usize,
+    /// Where the previous read ended
+    next_offset: Option<u64>,
+    blocks: Blocks,
+    pending: Option<JoinHandle<()>>,
+}
+
+fn lock(blocks: &Blocks) -> MutexGuard<'_, BTreeMap<u64, Box<[u8]>>> {
+    // Only plain data is stored there, so poisoning is harmless
+    blocks.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<T: ReadAt + Send + Sync + 'static> ReadAhead<T> {
+    /// Panics if `block_size` is zero.
+    pub fn new(inner: T, block_size: usize, blocks_ahead: usize) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        ReadAhead {
+            inner: Arc::new(inner),
+            block_size,
+            blocks_ahead,
+            next_offset: None,
+            blocks: Arc::new(Mutex::new(BTreeMap::new())),
+            pending: None,
+        }
+    }
+
+    /// Drop all prefetched data, waiting for a running prefetch to finish
+    pub fn invalidate(&mut self) {
+        self.wait();
+        lock(&self.blocks).clear();
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object, waiting for a running prefetch to finish
+    pub fn into_inner(mut self) -> T {
+        self.wait();
+        match Arc::try_unwrap(self.inner) {
+            Ok(x) => x,
+            Err(_) => unreachable!("prefetch thread is finished"),
+        }
+    }
+
+    fn wait(&mut self) {
+        if let Some(h) = self.pending.take() {
+            // A panic in `read_at` of the wrapped object only means no data was prefetched
+            let _ = h.join();
+        }
+    }
+
+    /// Serve a read from a prefetched block, dropping blocks before it
+    fn cached_read(&self, buf: &mut [u8], offset: u64) -> Option<usize> {
+        let bs = self.block_size as u64;
+        let start = offset / bs * bs;
+        let mut blocks = lock(&self.blocks);
+        let data = blocks.get(&start)?;
+        let skip = (offset - start) as usize;
+        let n = data.len().saturating_sub(skip).min(buf.len());
+        buf[..n].copy_from_slice(&data[skip..skip + n]);
+        *blocks = blocks.split_off(&start);
+        Some(n)
+    }
+
+    /// Start prefetching blocks after `offset`, unless a prefetch is already running
+    fn prefetch(&mut self, offset: u64) {
+        if self.pending.as_ref().is_some_and(|h| !h.is_finished()) {
+            return;
+        }
+        self.wait();
+        let bs = self.block_size as u64;
+        let first = offset / bs * bs;
+        let mut todo = Vec::with_capacity(self.blocks_ahead);
+        {
+            let blocks = lock(&self.blocks);
+            for k in 0..self.blocks_ahead as u64 {
+                let start = match k.checked_mul(bs).and_then(|x| first.checked_add(x)) {
+                    Some(x) => x,
+                    None => break,
+                };
+                match blocks.get(&start) {
+                    // Known EOF, nothing to prefetch after it
+                    Some(data) if data.len() < self.block_size => break,
+                    Some(_) => (),
+                    None => todo.push(start),
+                }
+            }
+        }
+        if todo.is_empty() {
+            return;
+        }
+        let inner = self.inner.clone();
+        let blocks = self.blocks.clone();
+        let block_size = self.block_size;
+        let max_blocks = self.blocks_ahead * 2;
+        self.pending = Some(std::thread::spawn(move || {
+            for start in todo {
+                let mut data = vec![0; block_size];
+                let n = match read_up_to(&*inner, &mut data[..], start) {
+                    Ok(n) => n,
+                    Err(_) => return,
+                };
+                data.truncate(n);
+                let mut blocks = lock(&blocks);
+                blocks.insert(start, data.into_boxed_slice());
+                while blocks.len() > max_blocks {
+                    let oldest = *blocks.keys().next().unwrap();
+                    blocks.remove(&oldest);
+                }
+                if n < block_size {
+                    return;
+                }
+            }
+        }));
+    }
+}
+
+impl<T: ReadAt + Send + Sync + 'static> ReadAtMut for ReadAhead<T> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let sequential = self.next_offset == Some(offset);
+        let n = match self.cached_read(buf, offset) {
+            Some(n) => n,
+            None => self.inner.read_at(buf, offset)?,
+        };
+        self.next_offset = offset.checked_add(n as u64);
+        if let (true, Some(next)) = (sequential && n > 0 && self.blocks_ahead > 0, self.next_offset) {
+            self.prefetch(next);
+        }
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt + SizeAt + Send + Sync + 'static> SizeAt for ReadAhead<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+impl<T: ReadAt + Advise + Send + Sync + 'static> Advise for ReadAhead<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.inner.advise(offset, len, advice)
+    }
+}
+
+/// Prefetched data is dropped, then the call is forwarded.
+impl<T: ReadAt + Preallocate + Send + Sync + 'static> PreallocateMut for ReadAhead<T> {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate();
+        self.inner.allocate(offset, len)
+    }
+}
+
+/// Prefetched data is dropped, then the call is forwarded.
+impl<T: ReadAt + Discard + Send + Sync + 'static> DiscardMut for ReadAhead<T> {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate();
+        self.inner.punch_hole(offset, len)
+    }
+    fn zero_range(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate();
+        self.inner.zero_range(offset, len)
+    }
+    fn discard(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.invalidate();
+        self.inner.discard(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting(Vec<u8>, AtomicUsize);
+    impl ReadAt for Counting {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.read_at(buf, offset)
+        }
+    }
+
+    #[test]
+    fn prefetches_sequential_reads() {
+        let mut r = ReadAhead::new(Counting((0..100).collect(), AtomicUsize::new(0)), 10, 3);
+        let mut v = [0u8; 5];
+        r.read_exact_at(&mut v[..], 50).unwrap();
+        r.read_exact_at(&mut v[..], 55).unwrap();
+        r.wait();
+        // Two direct reads, then three prefetched blocks: 60, 70, 80
+        assert_eq!(r.get_ref().1.load(Ordering::SeqCst), 5);
+
+        for i in 0..6 {
+            r.read_exact_at(&mut v[..], 60 + i * 5).unwrap();
+            assert_eq!(v[0], 60 + i as u8 * 5);
+            r.wait();
+        }
+        // Blocks 90 and 100 (empty, EOF) were prefetched, nothing beyond EOF
+        assert_eq!(r.get_ref().1.load(Ordering::SeqCst), 7);
+        assert_eq!(r.read_at(&mut v[..], 95).unwrap(), 5);
+        assert_eq!(r.read_at(&mut v[..], 100).unwrap(), 0);
+
+        r.invalidate();
+        r.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(r.into_inner().1.load(Ordering::SeqCst), 8);
+    }
+}
@@ -0,0 +1,188 @@
+use super::{Advice, Advise, BlockGeometry, Discard, Preallocate, ReadAt, ResizeAt, SizeAt, WriteAt};
+use std::io::{Error, ErrorKind, IoSlice, IoSliceMut, Result};
+
+/// Exposes only the reading side of the wrapped object.
+///
+/// As no write traits are implemented, mutation attempts are rejected at compile time.
+/// There is no `get_ref`, as objects like `File` or `Mutex<Vec<u8>>` could be written through it.
+/// When a `ReadWriteAt` is required (e.g. for a trait object), use `DenyWrites` instead.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,ReadOnly};
+///
+/// let data = std::sync::Mutex::new(vec![1u8, 2, 3]);
+/// let r = ReadOnly::new(&data);
+/// let mut v = [0u8; 2];
+/// r.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(v, [2, 3]);
+/// ```
+pub struct ReadOnly<T> {
+    inner: T,
+}
+
+impl<T> ReadOnly<T> {
+    /// Wrap `inner`
+    pub fn new(inner: T) -> Self {
+        ReadOnly { inner }
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ReadAt> ReadAt for ReadOnly<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.inner.read_at(buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.inner.read_exact_at(buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        self.inner.read_vectored_at(bufs, offset)
+    }
+}
+
+impl<T: SizeAt> SizeAt for ReadOnly<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+/// Forwards reads, but fails all writes and resizes with `PermissionDenied`.
+///
+/// Unlike `ReadOnly`, this can be used where a `ReadWriteAt` is required.
+/// Like `ReadOnly`, it gives no access to the wrapped object short of `into_inner`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{DenyWrites,ReadWriteAt};
+///
+/// let data = vec![1u8, 2, 3];
+/// let obj: Box<dyn ReadWriteAt> = Box::new(DenyWrites::new(data));
+/// let e = obj.write_at(&[5], 0).unwrap_err();
+/// assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+/// ```
+pub struct DenyWrites<T> {
+    inner: T,
+}
+
+impl<T> DenyWrites<T> {
+    /// Wrap `inner`
+    pub fn new(inner: T) -> Self {
+        DenyWrites { inner }
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn denied() -> Error {
+    Error::new(ErrorKind::PermissionDenied, "object is read-only")
+}
+
+impl<T: ReadAt> ReadAt for DenyWrites<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.inner.read_at(buf, offset)
+    }
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.inner.read_exact_at(buf, offset)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        self.inner.read_vectored_at(bufs, offset)
+    }
+}
+
+impl<T> WriteAt for DenyWrites<T> {
+    fn write_at(&self, _buf: &[u8], _offset: u64) -> Result<usize> {
+        Err(denied())
+    }
+    fn write_all_at(&self, _buf: &[u8], _offset: u64) -> Result<()> {
+        Err(denied())
+    }
+    fn write_vectored_at(&self, _bufs: &[IoSlice<'_>], _offset: u64) -> Result<usize> {
+        Err(denied())
+    }
+}
+
+impl<T: SizeAt> SizeAt for DenyWrites<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+impl<T> ResizeAt for DenyWrites<T> {
+    fn set_len(&self, _new_len: u64) -> Result<()> {
+        Err(denied())
+    }
+}
+
+impl<T: Advise> Advise for ReadOnly<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.inner.advise(offset, len, advice)
+    }
+}
+
+impl<T: BlockGeometry> BlockGeometry for ReadOnly<T> {
+    fn logical_block_size(&self) -> Result<u32> {
+        self.inner.logical_block_size()
+    }
+    fn physical_block_size(&self) -> Result<u32> {
+        self.inner.physical_block_size()
+    }
+}
+
+impl<T: Advise> Advise for DenyWrites<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.inner.advise(offset, len, advice)
+    }
+}
+
+impl<T: BlockGeometry> BlockGeometry for DenyWrites<T> {
+    fn logical_block_size(&self) -> Result<u32> {
+        self.inner.logical_block_size()
+    }
+    fn physical_block_size(&self) -> Result<u32> {
+        self.inner.physical_block_size()
+    }
+}
+
+impl<T> Preallocate for DenyWrites<T> {
+    fn allocate(&self, _offset: u64, _len: u64) -> Result<()> {
+        Err(denied())
+    }
+}
+
+impl<T> Discard for DenyWrites<T> {
+    fn punch_hole(&self, _offset: u64, _len: u64) -> Result<()> {
+        Err(denied())
+    }
+    fn zero_range(&self, _offset: u64, _len: u64) -> Result<()> {
+        Err(denied())
+    }
+    fn discard(&self, _offset: u64, _len: u64) -> Result<()> {
+        Err(denied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denies() {
+        let d = DenyWrites::new(vec![1u8, 2]);
+        assert_eq!(d.write_all_at(&[], 0).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(d.set_len(0).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        let mut v = [0u8; 2];
+        d.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(v, [1, 2]);
+        assert_eq!(ReadOnly::new(d.into_inner()).size().unwrap(), 2);
+    }
+}
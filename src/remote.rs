@@ -0,0 +1,299 @@
+//! A minimal protocol for using a positional device over a stream, e.g. across machines over TCP.
+//!
+//! The server greets with an 8-byte magic `RWAT\0\0\0\x01` (the last byte being the protocol version).
+//! Then each request is `op: u8, offset: u64, len: u32` followed by `len` bytes of payload for writes,
+//! and gets a reply `status: u8, value: u64` before the next request is read.
+//! Successful reads are followed by `value` bytes of data, writes report the number of bytes written in `value`
+//! and size requests the size. A nonzero status is an `ErrorKind` code with a `value`-byte UTF-8 message following.
+//! Ops are 1 (read), 2 (write), 3 (size), 4 (flush), 5 (sync_data) and 6 (sync_all). Integers are big-endian.
+//!
+//! There is no authentication or encryption, so expose servers only on trusted networks or tunnel them.
+
+use super::{ReadAt, SizeAt, SyncAt, WriteAt};
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::sync::{Mutex, MutexGuard};
+
+const MAGIC: [u8; 8] = *b"RWAT\0\0\0\x01";
+
+const OP_READ: u8 = 1;
+const OP_WRITE: u8 = 2;
+const OP_SIZE: u8 = 3;
+const OP_FLUSH: u8 = 4;
+const OP_SYNC_DATA: u8 = 5;
+const OP_SYNC_ALL: u8 = 6;
+
+/// Largest payload of a single read or write
+const MAX_PAYLOAD: usize = 16 << 20;
+/// Longest error message sent over the wire
+const MAX_MESSAGE: usize = 4096;
+
+/// Error kinds surviving the trip, encoded as their index plus one. Others become `Other`.
+const KINDS: [ErrorKind; 12] = [
+    ErrorKind::Other,
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::UnexpectedEof,
+    ErrorKind::WriteZero,
+    ErrorKind::Unsupported,
+    ErrorKind::OutOfMemory,
+    ErrorKind::Interrupted,
+    ErrorKind::WouldBlock,
+    ErrorKind::TimedOut,
+];
+
+fn read_header<R: Read>(r: &mut R) -> Result<(u8, u64)> {
+    let mut b = [0u8; 9];
+    r.read_exact(&mut b)?;
+    Ok((b[0], u64::from_be_bytes(b[1..].try_into().unwrap())))
+}
+
+/// Client side of the `remote` protocol, as a positional device.
+///
+/// Connects over any `Read + Write` stream (usually `TcpStream`) to a server started by `serve` or `serve_connection`.
+/// Requests are sent one at a time under a lock and carry at most 16 MiB, so `read_at` and `write_at` may be short.
+/// Errors of the remote device come back with their `ErrorKind` preserved for common kinds.
+///
+/// A failure while sending or receiving leaves the connection in unknown state, so all following requests fail.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{RemoteDevice,ReadAt,SizeAt};
+///
+/// let dev = RemoteDevice::connect("storage.local:7000").unwrap();
+/// let mut header = [0u8; 4096];
+/// dev.read_exact_at(&mut header[..], 0).unwrap();
+/// println!("{} bytes", dev.size().unwrap());
+/// ```
+pub struct RemoteDevice<S: Read + Write> {
+    conn: Mutex<Conn<S>>,
+}
+
+struct Conn<S> {
+    stream: S,
+    broken: bool,
+}
+
+impl RemoteDevice<std::net::TcpStream> {
+    /// Connect to a server over TCP
+    pub fn connect<A: std::net::ToSocketAddrs>(addr: A) -> Result<Self> {
+        let stream = std::net::TcpStream::connect(addr)?;
+        // Requests are written with a single call, but Nagle's algorithm would still delay them
+        stream.set_nodelay(true)?;
+        RemoteDevice::new(stream)
+    }
+}
+
+impl<S: Read + Write> RemoteDevice<S> {
+    /// Check the greeting of a server over an already connected stream
+    pub fn new(mut stream: S) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        stream.read_exact(&mut magic)?;
+        if magic[..4] != MAGIC[..4] {
+            return Err(Error::new(ErrorKind::InvalidData, "not a read_write_at remote server"));
+        }
+        if magic != MAGIC {
+            return Err(Error::new(ErrorKind::Unsupported, "unsupported read_write_at remote protocol version"));
+        }
+        Ok(RemoteDevice { conn: Mutex::new(Conn { stream, broken: false }) })
+    }
+
+    /// Get back the stream
+    pub fn into_inner(self) -> S {
+        self.conn.into_inner().unwrap_or_else(|e| e.into_inner()).stream
+    }
+
+    fn conn(&self) -> Result<MutexGuard<'_, Conn<S>>> {
+        // A panic while holding the lock leaves `broken` set, so poisoning needs no handling
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        if conn.broken {
+            return Err(Error::new(ErrorKind::BrokenPipe, "remote connection failed earlier"));
+        }
+        Ok(conn)
+    }
+
+    /// Send a request and wait for its reply, reading data of successful reads into `data`. Returns `value` of the reply.
+    fn request(&self, op: u8, offset: u64, len: u32, payload: &[u8], data: &mut [u8]) -> Result<u64> {
+        let mut conn = self.conn()?;
+        conn.broken = true;
+        let mut msg = Vec::with_capacity(13 + payload.len());
+        msg.push(op);
+        msg.extend_from_slice(&offset.to_be_bytes());
+        msg.extend_from_slice(&len.to_be_bytes());
+        msg.extend_from_slice(payload);
+        conn.stream.write_all(&msg)?;
+        conn.stream.flush()?;
+
+        let (status, value) = read_header(&mut conn.stream)?;
+        if status != 0 {
+            if value > MAX_MESSAGE as u64 {
+                return Err(Error::new(ErrorKind::InvalidData, "remote error message is too long"));
+            }
+            let mut message = vec![0u8; value as usize];
+            conn.stream.read_exact(&mut message)?;
+            conn.broken = false;
+            let kind = KINDS.get(status as usize - 1).copied().unwrap_or(ErrorKind::Other);
+            return Err(Error::new(kind, String::from_utf8_lossy(&message).into_owned()));
+        }
+        if op == OP_READ {
+            if value > data.len() as u64 {
+                return Err(Error::new(ErrorKind::InvalidData, "remote read returned more than requested"));
+            }
+            conn.stream.read_exact(&mut data[..value as usize])?;
+        }
+        conn.broken = false;
+        Ok(value)
+    }
+}
+
+impl<S: Read + Write> ReadAt for RemoteDevice<S> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = buf.len().min(MAX_PAYLOAD);
+        self.request(OP_READ, offset, n as u32, &[], &mut buf[..n]).map(|x| x as usize)
+    }
+}
+
+impl<S: Read + Write> WriteAt for RemoteDevice<S> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let n = buf.len().min(MAX_PAYLOAD);
+        let written = self.request(OP_WRITE, offset, n as u32, &buf[..n], &mut [])?;
+        if written > n as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, "remote write reported more than sent"));
+        }
+        Ok(written as usize)
+    }
+}
+
+impl<S: Read + Write> SizeAt for RemoteDevice<S> {
+    fn size(&self) -> Result<u64> {
+        self.request(OP_SIZE, 0, 0, &[], &mut [])
+    }
+}
+
+impl<S: Read + Write> SyncAt for RemoteDevice<S> {
+    fn flush(&self) -> Result<()> {
+        self.request(OP_FLUSH, 0, 0, &[], &mut []).map(drop)
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.request(OP_SYNC_DATA, 0, 0, &[], &mut []).map(drop)
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.request(OP_SYNC_ALL, 0, 0, &[], &mut []).map(drop)
+    }
+}
+
+/// Accept connections forever, serving `device` to each in its own thread.
+///
+/// Errors of individual connections only end those connections; call `serve_connection` to get them.
+pub fn serve<T>(device: &T, listener: &std::net::TcpListener) -> Result<()>
+where
+    T: ReadAt + WriteAt + SizeAt + SyncAt + Sync,
+{
+    std::thread::scope(|s| loop {
+        let (stream, _) = listener.accept()?;
+        // Replies are written with a single call, but Nagle's algorithm would still delay them
+        let _ = stream.set_nodelay(true);
+        s.spawn(move || serve_connection(device, stream));
+    })
+}
+
+/// Greet the client and handle requests over `stream` until it disconnects
+pub fn serve_connection<T, S>(device: &T, mut stream: S) -> Result<()>
+where
+    T: ReadAt + WriteAt + SizeAt + SyncAt + ?Sized,
+    S: Read + Write,
+{
+    stream.write_all(&MAGIC)?;
+    stream.flush()?;
+    let mut buf = Vec::new();
+    loop {
+        let mut header = [0u8; 13];
+        match stream.read(&mut header[..1])? {
+            0 => return Ok(()),
+            _ => stream.read_exact(&mut header[1..])?,
+        }
+        let op = header[0];
+        let offset = u64::from_be_bytes(header[1..9].try_into().unwrap());
+        let len = u32::from_be_bytes(header[9..].try_into().unwrap()) as usize;
+        if len > MAX_PAYLOAD {
+            return Err(Error::new(ErrorKind::InvalidData, "remote request is too long"));
+        }
+        buf.resize(len, 0);
+        if op == OP_WRITE {
+            stream.read_exact(&mut buf)?;
+        }
+
+        let result = match op {
+            OP_READ => device.read_at(&mut buf, offset).map(|n| n as u64),
+            OP_WRITE => device.write_at(&buf, offset).map(|n| n as u64),
+            OP_SIZE => device.size(),
+            OP_FLUSH => device.flush().map(|()| 0),
+            OP_SYNC_DATA => device.sync_data().map(|()| 0),
+            OP_SYNC_ALL => device.sync_all().map(|()| 0),
+            _ => Err(Error::new(ErrorKind::InvalidInput, "unknown remote request")),
+        };
+
+        let mut msg = Vec::with_capacity(9);
+        match result {
+            Ok(value) => {
+                msg.push(0);
+                msg.extend_from_slice(&value.to_be_bytes());
+                if op == OP_READ {
+                    msg.extend_from_slice(&buf[..value as usize]);
+                }
+            }
+            Err(e) => {
+                let kind = e.kind();
+                let mut message = e.to_string();
+                while message.len() > MAX_MESSAGE {
+                    message.pop();
+                }
+                msg.push(KINDS.iter().position(|&k| k == kind).unwrap_or(0) as u8 + 1);
+                msg.extend_from_slice(&(message.len() as u64).to_be_bytes());
+                msg.extend_from_slice(message.as_bytes());
+            }
+        }
+        stream.write_all(&msg)?;
+        stream.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZeroDevice;
+    use std::net::{TcpListener, TcpStream};
+
+    fn pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let a = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (a, listener.accept().unwrap().0)
+    }
+
+    #[test]
+    fn round_trip() {
+        let device = Mutex::new(vec![7u8; 100_000]);
+        std::thread::scope(|s| {
+            let (a, b) = pair();
+            s.spawn(|| serve_connection(&device, b).unwrap());
+            let dev = RemoteDevice::new(a).unwrap();
+            assert_eq!(dev.size().unwrap(), 100_000);
+            dev.write_all_at(&[1u8; 50], 99_990).unwrap();
+            assert_eq!(dev.size().unwrap(), 100_040);
+            dev.sync_all().unwrap();
+            let mut v = vec![0u8; 100_100];
+            assert_eq!(dev.read_at(&mut v[..], 0).unwrap(), 100_040);
+            assert!(v[..99_990].iter().all(|&b| b == 7));
+            assert!(v[99_990..100_040].iter().all(|&b| b == 1));
+        });
+
+        let (a, b) = pair();
+        std::thread::spawn(move || serve_connection(&ZeroDevice { len: 10 }, b));
+        let dev = RemoteDevice::new(a).unwrap();
+        assert_eq!(dev.write_at(&[1], 10).unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(dev.read_at(&mut [1u8; 4], 8).unwrap(), 2);
+    }
+}
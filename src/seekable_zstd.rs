@@ -0,0 +1,194 @@
+use super::block_cache::BlockCache;
+use super::{ReadAt, SizeAt};
+use std::io::{Error, ErrorKind, Result};
+
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a5e;
+const SEEKABLE_MAGIC: u32 = 0x8f92_eab1;
+/// Number of frames, descriptor and magic
+const FOOTER_LEN: u64 = 9;
+/// Descriptor bit telling that entries have checksums
+const CHECKSUM_FLAG: u8 = 0x80;
+const DEFAULT_CACHE: usize = 8;
+/// A zstd block decodes to at most 128 KiB and takes at least 4 bytes (a run-length block),
+/// so no frame expands more than this many times
+const MAX_EXPANSION: u64 = 1 << 15;
+
+/// Position of a frame in the file and in the decompressed data
+struct Frame {
+    offset: u64,
+    compressed_len: u32,
+    start: u64,
+    len: u32,
+}
+
+/// Random access to the decompressed content of a file in the zstd seekable format.
+///
+/// The format is a series of independent zstd frames followed by a seek table,
+/// as written by `zstd --seekable`-style tools and the `zstd_seekable` library.
+/// Each read decompresses whole frames; the most recently used ones (8 by default) are cached.
+/// Checksums in the seek table are not verified, but frames carrying their own checksums are verified by zstd.
+///
+/// A single call returns data from at most one frame, so it may be short; use `read_exact_at` for larger requests.
+///
+/// Requires `zstd` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{ReadAt,SeekableZstdReader,SizeAt};
+///
+/// let r = SeekableZstdReader::open(std::fs::File::open("archive.zst")?)?;
+/// let mut buf = vec![0u8; 4096];
+/// r.read_exact_at(&mut buf, r.size()? / 2)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct SeekableZstdReader<T> {
+    inner: T,
+    frames: Vec<Frame>,
+    cache: BlockCache,
+}
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+impl<T: ReadAt + SizeAt> SeekableZstdReader<T> {
+    /// Read the seek table from the end of `inner`
+    pub fn open(inner: T) -> Result<Self> {
+        let size = inner.size()?;
+        if size < FOOTER_LEN + 8 {
+            return Err(invalid("file is too short for a zstd seek table"));
+        }
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        inner.read_exact_at(&mut footer, size - FOOTER_LEN)?;
+        if u32_at(&footer, 5) != SEEKABLE_MAGIC {
+            return Err(invalid("no zstd seek table at the end of the file"));
+        }
+        let count = u32_at(&footer, 0) as u64;
+        let entry_len = if footer[4] & CHECKSUM_FLAG != 0 { 12 } else { 8 };
+        let table_len = count * entry_len + FOOTER_LEN;
+        if size < table_len + 8 {
+            return Err(invalid("zstd seek table is longer than the file"));
+        }
+        let mut table = vec![0u8; (table_len + 8) as usize];
+        inner.read_exact_at(&mut table, size - table_len - 8)?;
+        if u32_at(&table, 0) != SKIPPABLE_MAGIC || u32_at(&table, 4) as u64 != table_len {
+            return Err(invalid("malformed zstd seek table frame"));
+        }
+        let mut frames = Vec::with_capacity(count as usize);
+        let (mut offset, mut start) = (0u64, 0u64);
+        for e in table[8..].chunks_exact(entry_len as usize).take(count as usize) {
+            let (compressed_len, len) = (u32_at(e, 0), u32_at(e, 4));
+            if len as u64 > compressed_len as u64 * MAX_EXPANSION {
+                return Err(invalid("zstd frame is too small for its size in the seek table"));
+            }
+            frames.push(Frame { offset, compressed_len, start, len });
+            offset += compressed_len as u64;
+            start += len as u64;
+        }
+        if offset > size - table_len - 8 {
+            return Err(invalid("zstd seek table describes more data than the file has"));
+        }
+        Ok(SeekableZstdReader { inner, frames, cache: BlockCache::new(DEFAULT_CACHE) })
+    }
+}
+
+impl<T> SeekableZstdReader<T> {
+    /// Number of decompressed frames to keep in memory
+    pub fn cache_frames(mut self, frames: usize) -> Self {
+        self.cache.set_capacity(frames);
+        self
+    }
+
+    /// Number of frames in the file
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ReadAt> ReadAt for SeekableZstdReader<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let i = self.frames.partition_point(|f| f.start + f.len as u64 <= offset);
+        let f = match self.frames.get(i) {
+            Some(f) if !buf.is_empty() => f,
+            _ => return Ok(0),
+        };
+        let data = self.cache.get(i as u64, || {
+            let mut compressed = vec![0u8; f.compressed_len as usize];
+            self.inner.read_exact_at(&mut compressed, f.offset)?;
+            let data = zstd::bulk::decompress(&compressed, f.len as usize)?;
+            if data.len() != f.len as usize {
+                return Err(invalid("zstd frame size differs from the seek table"));
+            }
+            Ok(data)
+        })?;
+        let within = (offset - f.start) as usize;
+        let n = buf.len().min(data.len() - within);
+        buf[..n].copy_from_slice(&data[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl<T> SizeAt for SeekableZstdReader<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.frames.last().map_or(0, |f| f.start + f.len as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let data: Vec<u8> = (0..100_000u32).map(|x| (x / 1000) as u8).collect();
+        let mut file = vec![];
+        let mut table = vec![];
+        for chunk in data.chunks(30_000) {
+            let frame = zstd::bulk::compress(chunk, 1).unwrap();
+            file.extend_from_slice(&frame);
+            table.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            table.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            table.extend_from_slice(&[0; 4]);
+        }
+        table.extend_from_slice(&4u32.to_le_bytes());
+        table.push(CHECKSUM_FLAG);
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        file.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        file.extend_from_slice(&(table.len() as u32).to_le_bytes());
+        file.extend_from_slice(&table);
+
+        let r = SeekableZstdReader::open(&file).unwrap().cache_frames(1);
+        assert_eq!((r.frames(), r.size().unwrap()), (4, 100_000));
+        let mut v = vec![0u8; 100_000];
+        assert_eq!(r.read_at(&mut v, 29_000).unwrap(), 1000);
+        r.read_exact_at(&mut v[..50_000], 25_000).unwrap();
+        assert_eq!(v[..50_000], data[25_000..75_000]);
+        r.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v, data);
+        assert_eq!(r.read_at(&mut v, 100_000).unwrap(), 0);
+
+        // Last frame claiming 4 GiB in the seek table
+        let mut huge = file.clone();
+        let entry = huge.len() - FOOTER_LEN as usize - 12;
+        huge[entry + 4..entry + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(SeekableZstdReader::open(&huge).err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+
+        file.truncate(file.len() - 1);
+        assert!(SeekableZstdReader::open(&file).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use super::rng::Rng;
+use super::{Discard, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Timing model of a `SimulatedDevice`
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::Profile;
+/// use std::time::Duration;
+///
+/// // A slow USB stick
+/// let p = Profile { write_bandwidth: Some(5_000_000), sync_latency: Duration::from_millis(30), ..Profile::ssd() };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// Fixed cost of every call
+    pub latency: Duration,
+    /// Upper bound of a random extra cost of reads and writes
+    pub jitter: Duration,
+    /// Extra cost of a read or write not starting where the previous one ended
+    pub seek: Duration,
+    /// Bytes read per second, `None` for instant transfers
+    pub read_bandwidth: Option<u64>,
+    /// Bytes written per second, `None` for instant transfers
+    pub write_bandwidth: Option<u64>,
+    /// Extra cost of `sync_all`, `sync_data` and `sync_range`
+    pub sync_latency: Duration,
+}
+
+impl Profile {
+    /// Rotating disk: 8ms seeks, up to 4ms of rotational delay, 150 MB/s
+    pub fn hdd() -> Self {
+        Profile {
+            latency: Duration::from_micros(100),
+            jitter: Duration::from_millis(4),
+            seek: Duration::from_millis(8),
+            read_bandwidth: Some(150_000_000),
+            write_bandwidth: Some(150_000_000),
+            sync_latency: Duration::from_millis(10),
+        }
+    }
+
+    /// NVMe SSD: 80µs per call, no seeks, 2 GB/s reads, 1 GB/s writes
+    pub fn ssd() -> Self {
+        Profile {
+            latency: Duration::from_micros(80),
+            jitter: Duration::from_micros(20),
+            seek: Duration::ZERO,
+            read_bandwidth: Some(2_000_000_000),
+            write_bandwidth: Some(1_000_000_000),
+            sync_latency: Duration::from_millis(1),
+        }
+    }
+
+    /// Network storage in a data center: 1ms round trips over a 1 Gbit/s link
+    pub fn network() -> Self {
+        Profile {
+            latency: Duration::from_millis(1),
+            jitter: Duration::from_micros(500),
+            seek: Duration::ZERO,
+            read_bandwidth: Some(110_000_000),
+            write_bandwidth: Some(110_000_000),
+            sync_latency: Duration::from_millis(2),
+        }
+    }
+}
+
+struct State {
+    rng: Rng,
+    /// End of the previous read or write
+    head: Option<u64>,
+    elapsed: Duration,
+}
+
+/// Delays calls to the wrapped object according to a `Profile`, to see how an algorithm would fare on
+/// HDD-like, SSD-like or network-like storage without having it.
+///
+/// Calls are served one at a time, like by a single disk head. Jitter comes from a generator seeded with `seed`,
+/// so runs are reproducible. With `virtual_time`, calls are not delayed at all and only `elapsed` accumulates
+/// the simulated time, which makes comparisons fast and independent of the machine.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Profile,ReadAt,SimulatedDevice};
+///
+/// let data = vec![0u8; 1 << 20];
+/// let hdd = SimulatedDevice::new(&data, Profile::hdd()).virtual_time(true);
+/// let mut buf = vec![0u8; 4096];
+/// for i in 0..16 {
+///     hdd.read_exact_at(&mut buf, i * 65536).unwrap();
+/// }
+/// let scattered = hdd.take_elapsed();
+/// for i in 0..16 {
+///     hdd.read_exact_at(&mut buf, i * 4096).unwrap();
+/// }
+/// assert!(hdd.take_elapsed() < scattered);
+/// ```
+pub struct SimulatedDevice<T> {
+    inner: T,
+    profile: Profile,
+    virtual_time: bool,
+    state: Mutex<State>,
+}
+
+impl<T> SimulatedDevice<T> {
+    /// Wrap `inner`, with seed `0` and real delays
+    pub fn new(inner: T, profile: Profile) -> Self {
+        SimulatedDevice {
+            inner,
+            profile,
+            virtual_time: false,
+            state: Mutex::new(State { rng: Rng::new(0), head: None, elapsed: Duration::ZERO }),
+        }
+    }
+
+    /// Reseed the generator for jitter
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.state.get_mut().unwrap_or_else(|e| e.into_inner()).rng = Rng::new(seed);
+        self
+    }
+
+    /// Only account simulated time instead of sleeping
+    pub fn virtual_time(mut self, virtual_time: bool) -> Self {
+        self.virtual_time = virtual_time;
+        self
+    }
+
+    /// Simulated time spent in calls so far
+    pub fn elapsed(&self) -> Duration {
+        self.lock().elapsed
+    }
+
+    /// Simulated time spent in calls so far, resetting it to zero
+    pub fn take_elapsed(&self) -> Duration {
+        std::mem::take(&mut self.lock().elapsed)
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Spend `cost` while holding the lock, so that calls queue up
+    fn spend(&self, state: &mut State, cost: Duration) {
+        state.elapsed += cost;
+        if !self.virtual_time {
+            std::thread::sleep(cost);
+        }
+    }
+
+    /// Simulate a transfer of `len` bytes at `offset`, then do it
+    fn transfer(&self, offset: u64, len: usize, bandwidth: Option<u64>, f: impl FnOnce() -> Result<usize>) -> Result<usize> {
+        let mut s = self.lock();
+        let p = &self.profile;
+        let mut cost = p.latency;
+        if !p.jitter.is_zero() {
+            cost += Duration::from_nanos(s.rng.below(p.jitter.as_nanos() as u64 + 1));
+        }
+        if s.head != Some(offset) {
+            cost += p.seek;
+        }
+        if let Some(bw) = bandwidth {
+            cost += Duration::from_secs_f64(len as f64 / bw.max(1) as f64);
+        }
+        self.spend(&mut s, cost);
+        let result = f();
+        s.head = Some(offset + *result.as_ref().unwrap_or(&0) as u64);
+        result
+    }
+
+    fn call<R>(&self, cost: Duration, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        let mut s = self.lock();
+        self.spend(&mut s, cost);
+        f()
+    }
+}
+
+impl<T: ReadAt> ReadAt for SimulatedDevice<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.transfer(offset, buf.len(), self.profile.read_bandwidth, || self.inner.read_at(buf, offset))
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let len = bufs.iter().map(|b| b.len()).sum();
+        self.transfer(offset, len, self.profile.read_bandwidth, || self.inner.read_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: WriteAt> WriteAt for SimulatedDevice<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.transfer(offset, buf.len(), self.profile.write_bandwidth, || self.inner.write_at(buf, offset))
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let len = bufs.iter().map(|b| b.len()).sum();
+        self.transfer(offset, len, self.profile.write_bandwidth, || self.inner.write_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: SizeAt> SizeAt for SimulatedDevice<T> {
+    fn size(&self) -> Result<u64> {
+        self.call(self.profile.latency, || self.inner.size())
+    }
+}
+
+impl<T: ResizeAt> ResizeAt for SimulatedDevice<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.call(self.profile.latency, || self.inner.set_len(new_len))
+    }
+}
+
+impl<T: SyncAt> SyncAt for SimulatedDevice<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.call(self.profile.latency + self.profile.sync_latency, || self.inner.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.call(self.profile.latency + self.profile.sync_latency, || self.inner.sync_data())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(self.profile.latency + self.profile.sync_latency, || self.inner.sync_range(offset, len))
+    }
+}
+
+impl<T: Discard> Discard for SimulatedDevice<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(self.profile.latency, || self.inner.punch_hole(offset, len))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(self.profile.latency, || self.inner.zero_range(offset, len))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.call(self.profile.latency, || self.inner.discard(offset, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn costs() {
+        let data = vec![0u8; 1 << 20];
+        let p = Profile {
+            latency: Duration::from_millis(1),
+            jitter: Duration::ZERO,
+            seek: Duration::from_millis(10),
+            read_bandwidth: Some(1_000_000),
+            write_bandwidth: None,
+            sync_latency: Duration::ZERO,
+        };
+        let d = SimulatedDevice::new(&data, p).virtual_time(true);
+        let mut buf = [0u8; 1000];
+        d.read_exact_at(&mut buf[..], 0).unwrap();
+        d.read_exact_at(&mut buf[..], 1000).unwrap();
+        d.read_exact_at(&mut buf[..], 0).unwrap();
+        d.size().unwrap();
+        assert_eq!(d.elapsed(), Duration::from_millis(12 + 2 + 12 + 1));
+
+        let run = |seed| {
+            let d = SimulatedDevice::new(&data, Profile::hdd()).seed(seed).virtual_time(true);
+            for i in 0..10 {
+                d.read_exact_at(&mut [0u8; 512][..], i * 100_000).unwrap();
+            }
+            d.elapsed()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+
+        let p = Profile { latency: Duration::from_millis(20), ..Profile::ssd() };
+        let d = SimulatedDevice::new(&data, p);
+        let start = Instant::now();
+        d.read_exact_at(&mut buf[..], 0).unwrap();
+        d.size().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
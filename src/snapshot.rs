@@ -0,0 +1,303 @@
+use super::{read_up_to, ReadAt, SyncAt, WriteAt, WriteAtMut};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+
+/// Copy-on-write snapshot over a `base` object.
+///
+/// Writes go only to `overlay`, which is expected to be another object of the same address space
+/// (e.g. a sparse temporary file). Blocks that were written to are marked dirty and are subsequently
+/// read from `overlay`, other blocks are read from `base`.
+/// Before the first write to a block, the block is copied from `base` to `overlay`,
+/// so partial writes do not lose data. Data left in `overlay` by writes that were rolled back is never read.
+///
+/// `commit` copies dirty blocks back to `base`, `rollback` forgets them.
+/// `base` needs to be writable only for `commit`, so a read-only base image can be used as a
+/// copy-on-write overlay as well, with all changes staying in `overlay`.
+///
+/// As the dirty block set is changed on writes, only `WriteAtMut` is implemented for writing.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadWriteSeek,SnapshotWriteAt,ReadAt,WriteAtMut};
+/// use std::cell::RefCell;
+/// use std::io::Cursor;
+///
+/// let base = RefCell::new(ReadWriteSeek(Cursor::new(vec![1u8; 8])));
+/// let overlay = RefCell::new(ReadWriteSeek(Cursor::new(vec![0u8; 8])));
+/// let mut s = SnapshotWriteAt::new(base, overlay, 4);
+///
+/// s.write_all_at(&[9, 9], 1).unwrap();
+/// let mut v = vec![0; 8];
+/// ReadAt::read_exact_at(&s, &mut v[..], 0).unwrap();
+/// assert_eq!(v, vec![1, 9, 9, 1, 1, 1, 1, 1]);
+///
+/// s.rollback();
+/// ReadAt::read_exact_at(&s, &mut v[..], 0).unwrap();
+/// assert_eq!(v, vec![1; 8]);
+/// ```
+pub struct SnapshotWriteAt<Base: ReadAt, Overlay: ReadAt + WriteAt> {
+    base: Base,
+    overlay: Overlay,
+    /// Dirty block numbers, with the length of their data in `overlay`, which is shorter than the block only at the end
+    dirty: BTreeMap<u64, u64>,
+    block_size: u64,
+}
+
+/// `SnapshotWriteAt` used as a copy-on-write overlay of a read-only `Base`, with changes kept in `Delta`
+pub type CowOverlay<Base, Delta> = SnapshotWriteAt<Base, Delta>;
+
+impl<Base: ReadAt, Overlay: ReadAt + WriteAt> SnapshotWriteAt<Base, Overlay> {
+    /// Create a snapshot with no dirty blocks. Panics if `block_size` is zero.
+    pub fn new(base: Base, overlay: Overlay, block_size: u64) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        SnapshotWriteAt {
+            base,
+            overlay,
+            dirty: BTreeMap::new(),
+            block_size,
+        }
+    }
+
+    /// Forget all writes done since the last `commit`. `overlay` itself is not modified.
+    pub fn rollback(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// Block size specified at construction time
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Check whether a block with the given number (not offset) is going to be read from `overlay`
+    pub fn is_dirty(&self, block: u64) -> bool {
+        self.dirty.contains_key(&block)
+    }
+
+    /// Get back `base` and `overlay`. Uncommitted writes remain only in `overlay`.
+    pub fn into_inner(self) -> (Base, Overlay) {
+        (self.base, self.overlay)
+    }
+}
+
+impl<Base: ReadAt + WriteAt, Overlay: ReadAt + WriteAt> SnapshotWriteAt<Base, Overlay> {
+    /// Copy all dirty blocks from `overlay` to `base` and clear dirty marks.
+    ///
+    /// If an error happens in the middle, blocks that are already copied are not dirty anymore.
+    pub fn commit(&mut self) -> Result<()> {
+        let mut buf = vec![0; self.block_size as usize];
+        while let Some((&block, &len)) = self.dirty.iter().next() {
+            let offset = block * self.block_size;
+            let n = read_up_to(&self.overlay, &mut buf[..len as usize], offset)?;
+            self.base.write_all_at(&buf[..n], offset)?;
+            self.dirty.remove(&block);
+        }
+        Ok(())
+    }
+}
+
+impl<Base: ReadAt, Overlay: ReadAt + WriteAt> ReadAt for SnapshotWriteAt<Base, Overlay> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let first = offset / self.block_size;
+        let end = offset.saturating_add(buf.len() as u64);
+        // Extend the request over following blocks as long as they come from the same source
+        let mut block = first + 1;
+        if let Some(&len) = self.dirty.get(&first) {
+            // Dirty data ends within the first block that is not full
+            let mut data_end = first * self.block_size + len;
+            while data_end == block.saturating_mul(self.block_size) && data_end < end {
+                match self.dirty.get(&block) {
+                    Some(&len) => data_end += len,
+                    None => break,
+                }
+                block += 1;
+            }
+            if offset >= data_end {
+                return Ok(0);
+            }
+            let len = (data_end.min(end) - offset) as usize;
+            return self.overlay.read_at(&mut buf[..len], offset);
+        }
+        while block.saturating_mul(self.block_size) < end && !self.is_dirty(block) {
+            block += 1;
+        }
+        let len = (block.saturating_mul(self.block_size).min(end) - offset) as usize;
+        self.base.read_at(&mut buf[..len], offset)
+    }
+}
+
+impl<Base: ReadAt, Overlay: ReadAt + WriteAt> WriteAtMut
+    for SnapshotWriteAt<Base, Overlay>
+{
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return self.overlay.write_at(buf, offset);
+        }
+        let end = offset.checked_add(buf.len() as u64).ok_or_else(|| Error::new(
+            ErrorKind::InvalidInput,
+            "write end overflows u64",
+        ))?;
+        let first = offset / self.block_size;
+        let last = (end - 1) / self.block_size;
+        let mut tmp = Vec::new();
+        for block in first..=last {
+            let block_offset = block * self.block_size;
+            let len = match self.dirty.get(&block) {
+                Some(&len) => len,
+                None => {
+                    tmp.resize(self.block_size as usize, 0);
+                    let n = read_up_to(&self.base, &mut tmp[..], block_offset)?;
+                    self.overlay.write_all_at(&tmp[..n], block_offset)?;
+                    self.dirty.insert(block, n as u64);
+                    n as u64
+                }
+            };
+            // Zero what `overlay` has between the data of the block and the write, which is stale
+            let start = offset.max(block_offset) - block_offset;
+            if start > len {
+                tmp.clear();
+                tmp.resize((start - len) as usize, 0);
+                self.overlay.write_all_at(&tmp[..], block_offset + len)?;
+                self.dirty.insert(block, start);
+            }
+        }
+        let n = self.overlay.write_at(buf, offset)?;
+        let written = offset + n as u64;
+        for block in first..=last {
+            let block_offset = block * self.block_size;
+            if block_offset >= written {
+                break;
+            }
+            if let Some(len) = self.dirty.get_mut(&block) {
+                *len = (*len).max(written.min(block_offset.saturating_add(self.block_size)) - block_offset);
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Syncs `overlay`, where uncommitted writes go, then `base`, where `commit` puts them.
+impl<Base, Overlay> SyncAt for SnapshotWriteAt<Base, Overlay>
+where
+    Base: ReadAt + SyncAt,
+    Overlay: ReadAt + WriteAt + SyncAt,
+{
+    fn flush(&self) -> Result<()> {
+        self.overlay.flush()?;
+        self.base.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.overlay.sync_all()?;
+        self.base.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.overlay.sync_data()?;
+        self.base.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.overlay.sync_range(offset, len)?;
+        self.base.sync_range(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadAtMut, ReadWriteSeek};
+    use std::cell::RefCell;
+    use std::io::Cursor;
+
+    type Mem = RefCell<ReadWriteSeek<Cursor<Vec<u8>>>>;
+
+    fn mem(v: Vec<u8>) -> Mem {
+        RefCell::new(ReadWriteSeek(Cursor::new(v)))
+    }
+
+    fn contents(m: &Mem) -> Vec<u8> {
+        m.borrow().0.get_ref().clone()
+    }
+
+    #[test]
+    fn writes_go_to_overlay_until_commit() {
+        let base = mem((0..10).collect());
+        let overlay = mem(vec![]);
+        let mut s = SnapshotWriteAt::new(base, overlay, 4);
+
+        s.write_all_at(&[100, 101, 102], 3).unwrap();
+        assert!(s.is_dirty(0));
+        assert!(s.is_dirty(1));
+        assert!(!s.is_dirty(2));
+
+        let mut v = vec![0; 10];
+        ReadAtMut::read_exact_at(&mut s, &mut v[..], 0).unwrap();
+        assert_eq!(v, vec![0, 1, 2, 100, 101, 102, 6, 7, 8, 9]);
+        assert_eq!(contents(&s.base), (0..10).collect::<Vec<u8>>());
+
+        s.commit().unwrap();
+        assert!(!s.is_dirty(0));
+        let (base, _) = s.into_inner();
+        assert_eq!(contents(&base), vec![0, 1, 2, 100, 101, 102, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn read_only_base() {
+        let base = crate::ReadOnly::new(vec![1u8; 8]);
+        let mut s = SnapshotWriteAt::new(&base, mem(vec![]), 4);
+        s.write_all_at(&[2], 5).unwrap();
+        let mut v = vec![0; 8];
+        ReadAt::read_exact_at(&s, &mut v[..], 0).unwrap();
+        assert_eq!(v, vec![1, 1, 1, 1, 1, 2, 1, 1]);
+        assert_eq!(base.into_inner(), vec![1u8; 8]);
+    }
+
+    #[test]
+    fn rollback_restores_base_view() {
+        let base = mem(vec![5; 6]);
+        let overlay = mem(vec![]);
+        let mut s = SnapshotWriteAt::new(base, overlay, 4);
+
+        s.write_all_at(&[1, 2, 3, 4, 5, 6, 7, 8], 2).unwrap();
+        let mut v = vec![0; 10];
+        ReadAt::read_exact_at(&s, &mut v[..], 0).unwrap();
+        assert_eq!(v, vec![5, 5, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        s.rollback();
+        let mut v = vec![0; 6];
+        ReadAt::read_exact_at(&s, &mut v[..], 0).unwrap();
+        assert_eq!(v, vec![5; 6]);
+        let (base, _) = s.into_inner();
+        assert_eq!(contents(&base), vec![5; 6]);
+    }
+
+    #[test]
+    fn rollback_then_rewrite_past_base() {
+        let mut s = SnapshotWriteAt::new(mem((0..10).collect()), mem(vec![]), 4);
+        s.write_all_at(&[0xa, 0xb], 10).unwrap();
+        s.rollback();
+        s.write_all_at(&[0xff], 8).unwrap();
+
+        let mut v = vec![0; 4];
+        assert_eq!(ReadAt::read_at(&s, &mut v[..], 8).unwrap(), 2);
+        assert_eq!(v[..2], [255, 9]);
+        assert_eq!(ReadAt::read_at(&s, &mut v[..], 10).unwrap(), 0);
+
+        // A later write past the data zeroes the stale bytes before it
+        s.write_all_at(&[0xc], 11).unwrap();
+        assert_eq!(ReadAt::read_at(&s, &mut v[..], 8).unwrap(), 4);
+        assert_eq!(v, [255, 9, 0, 0xc]);
+
+        s.commit().unwrap();
+        let (base, _) = s.into_inner();
+        assert_eq!(contents(&base), vec![0, 1, 2, 3, 4, 5, 6, 7, 255, 9, 0, 0xc]);
+    }
+
+    #[test]
+    fn write_end_overflow() {
+        let mut s = SnapshotWriteAt::new(mem(vec![]), mem(vec![]), 4);
+        let e = s.write_at(&[1, 2], u64::MAX).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,239 @@
+use super::extents::push_extent;
+use super::{DiscardMut, Extent, PreallocateMut, ExtentKind, Extents, ReadAt, ResizeAtMut, SizeAt, SyncAt, WriteAtMut};
+use std::ops::Range;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+
+const PAGE_SIZE: usize = 4096;
+
+/// In-memory object storing only pages that were written to, so it can represent huge sparse address spaces.
+///
+/// Behaves like `Vec<u8>`: unwritten areas read as zeroes, reads stop at the current length,
+/// writing past the end extends it.
+///
+/// Writes need `&mut self`, so only `WriteAtMut` is implemented. Wrap it in `RefCell` or `Mutex` to get `WriteAt`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,SizeAt,SparseMem,WriteAtMut};
+///
+/// let mut m = SparseMem::new();
+/// m.write_all_at(&[1, 2], 1 << 40).unwrap();
+/// assert_eq!(m.size().unwrap(), (1 << 40) + 2);
+/// assert_eq!(m.allocated_pages(), 1);
+///
+/// let mut v = [9u8; 3];
+/// m.read_exact_at(&mut v[..], (1 << 40) - 1).unwrap();
+/// assert_eq!(v, [0, 1, 2]);
+/// ```
+#[derive(Default, Clone)]
+pub struct SparseMem {
+    pages: BTreeMap<u64, Box<[u8]>>,
+    len: u64,
+}
+
+impl std::fmt::Debug for SparseMem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SparseMem").field("len", &self.len).field("allocated_pages", &self.pages.len()).finish()
+    }
+}
+
+impl SparseMem {
+    /// Size of a page
+    pub const PAGE_SIZE: usize = PAGE_SIZE;
+
+    /// Create an empty object
+    pub fn new() -> Self {
+        SparseMem::default()
+    }
+
+    /// Create an object of `len` zero bytes, without allocating them
+    pub fn with_len(len: u64) -> Self {
+        SparseMem {
+            pages: BTreeMap::new(),
+            len,
+        }
+    }
+
+    /// Number of pages that have memory allocated
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl ReadAt for SparseMem {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = self.len.saturating_sub(offset).min(buf.len() as u64) as usize;
+        let mut done = 0;
+        while done < n {
+            let pos = offset + done as u64;
+            let within = (pos % PAGE_SIZE as u64) as usize;
+            let m = (PAGE_SIZE - within).min(n - done);
+            let dst = &mut buf[done..done + m];
+            match self.pages.get(&(pos / PAGE_SIZE as u64)) {
+                Some(page) => dst.copy_from_slice(&page[within..within + m]),
+                None => dst.fill(0),
+            }
+            done += m;
+        }
+        Ok(n)
+    }
+}
+
+/// Writing past the end extends the object, filling the gap with zeroes.
+impl WriteAtMut for SparseMem {
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let end = match offset.checked_add(buf.len() as u64) {
+            Some(x) => x,
+            None => return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "write end overflows u64",
+            )),
+        };
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let within = (pos % PAGE_SIZE as u64) as usize;
+            let m = (PAGE_SIZE - within).min(buf.len() - done);
+            let page = self
+                .pages
+                .entry(pos / PAGE_SIZE as u64)
+                .or_insert_with(|| vec![0; PAGE_SIZE].into_boxed_slice());
+            page[within..within + m].copy_from_slice(&buf[done..done + m]);
+            done += m;
+        }
+        self.len = self.len.max(end);
+        Ok(buf.len())
+    }
+}
+
+impl SizeAt for SparseMem {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len)
+    }
+}
+
+/// Truncation frees pages past the new end, extension allocates nothing.
+impl ResizeAtMut for SparseMem {
+    fn set_len(&mut self, new_len: u64) -> Result<()> {
+        if new_len < self.len {
+            let first_gone = new_len.div_ceil(PAGE_SIZE as u64);
+            self.pages.split_off(&first_gone);
+            // Clear the tail of the last partial page, so re-extending reads zeroes
+            let within = (new_len % PAGE_SIZE as u64) as usize;
+            if within != 0 {
+                if let Some(page) = self.pages.get_mut(&(new_len / PAGE_SIZE as u64)) {
+                    page[within..].fill(0);
+                }
+            }
+        }
+        self.len = new_len;
+        Ok(())
+    }
+}
+
+/// Only extends the object: pages are allocated on writes anyway.
+impl PreallocateMut for SparseMem {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        let end = offset.checked_add(len).ok_or_else(|| Error::new(
+            ErrorKind::InvalidInput,
+            "allocation end overflows u64",
+        ))?;
+        self.len = self.len.max(end);
+        Ok(())
+    }
+}
+
+/// Pages entirely in the range are freed, others are zeroed where they overlap it.
+impl DiscardMut for SparseMem {
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        let end = offset.saturating_add(len).min(self.len);
+        if offset >= end {
+            return Ok(());
+        }
+        let ps = PAGE_SIZE as u64;
+        let touched: Vec<u64> = self.pages.range(offset / ps..=(end - 1) / ps).map(|(&k, _)| k).collect();
+        for index in touched {
+            let from = offset.max(index * ps);
+            let to = end.min(index.saturating_add(1).saturating_mul(ps));
+            if to - from == ps {
+                self.pages.remove(&index);
+            } else if let Some(page) = self.pages.get_mut(&index) {
+                page[(from - index * ps) as usize..(to - index * ps) as usize].fill(0);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SyncAt for SparseMem {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Allocated pages are data, the rest are holes.
+impl Extents for SparseMem {
+    fn extents(&self, range: Range<u64>) -> Result<Vec<Extent>> {
+        let end = range.end.min(self.len);
+        let mut v = vec![];
+        let page_size = PAGE_SIZE as u64;
+        let mut pos = range.start;
+        while pos < end {
+            let page_end = (pos / page_size + 1).saturating_mul(page_size).min(end);
+            let kind = match self.pages.contains_key(&(pos / page_size)) {
+                true => ExtentKind::Data,
+                false => ExtentKind::Hole,
+            };
+            push_extent(&mut v, pos, page_end - pos, kind);
+            pos = page_end;
+        }
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages() {
+        let mut m = SparseMem::with_len(10);
+        let mut v = [9u8; 20];
+        assert_eq!(m.read_at(&mut v[..], 0).unwrap(), 10);
+        assert_eq!(m.allocated_pages(), 0);
+
+        let data = vec![7u8; PAGE_SIZE + 2];
+        m.write_all_at(&data[..], PAGE_SIZE as u64 - 1).unwrap();
+        assert_eq!(m.allocated_pages(), 3);
+        assert_eq!(m.size().unwrap(), 2 * PAGE_SIZE as u64 + 1);
+        assert_eq!(m.read_at(&mut v[..2], PAGE_SIZE as u64 * 2).unwrap(), 1);
+        assert_eq!(v[..2], [7, 0]);
+
+        m.set_len(PAGE_SIZE as u64).unwrap();
+        assert_eq!(m.allocated_pages(), 1);
+        m.set_len(PAGE_SIZE as u64 * 3).unwrap();
+        let mut page = vec![1u8; PAGE_SIZE];
+        m.read_exact_at(&mut page[..], PAGE_SIZE as u64).unwrap();
+        assert!(page.iter().all(|&b| b == 0));
+
+        m.set_len(2).unwrap();
+        m.set_len(PAGE_SIZE as u64).unwrap();
+        m.read_exact_at(&mut v[..], PAGE_SIZE as u64 - 20).unwrap();
+        assert_eq!(v, [0; 20]);
+    }
+
+    #[test]
+    fn punch_hole_at_end_of_address_space() {
+        let mut m = SparseMem::new();
+        m.write_all_at(&[1; 4], u64::MAX - 4).unwrap();
+        m.punch_hole(u64::MAX - 3, 10).unwrap();
+        let mut v = [9u8; 4];
+        m.read_exact_at(&mut v[..], u64::MAX - 4).unwrap();
+        assert_eq!(v, [1, 0, 0, 0]);
+    }
+}
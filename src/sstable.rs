@@ -0,0 +1,313 @@
+use super::{ReadAt, SizeAt, SubRegionAt};
+use std::io::{Error, ErrorKind, Result};
+
+const LEVELDB_MAGIC: u64 = 0xdb47_7524_8b80_fb57;
+const ROCKSDB_MAGIC: u64 = 0x88e2_41b7_85f4_cff7;
+const LEGACY_FOOTER_LEN: u64 = 48;
+const ROCKSDB_FOOTER_LEN: u64 = 53;
+/// Compression type byte and CRC32 following each block
+const BLOCK_TRAILER_LEN: u64 = 5;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Decode a varint64 from `buf` at `*pos`, advancing `*pos`
+fn varint(buf: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut result = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *buf.get(*pos).ok_or_else(|| invalid("truncated varint in sstable"))?;
+        *pos += 1;
+        result |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err(invalid("overlong varint in sstable"))
+}
+
+fn block_handle(buf: &[u8], pos: &mut usize) -> Result<(u64, u64)> {
+    let offset = varint(buf, pos)?;
+    let size = varint(buf, pos)?;
+    Ok((offset, size))
+}
+
+/// Index of data blocks of a LevelDB or RocksDB (block-based table) SST file.
+///
+/// Only the index is parsed; each data block is exposed as a `SubRegionAt` over the source,
+/// without its trailer. Decompression and parsing of data blocks are left to the caller.
+///
+/// RocksDB format versions up to 5 are supported; versions 4 and 5, the defaults of current RocksDB,
+/// delta-encode the block handles in the index, which is decoded here.
+/// Compressed index blocks, partitioned indexes and format version 6 and above are not supported.
+///
+/// Requires `sstable` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{ReadAt,SizeAt,SstBlockReadAt};
+///
+/// let f = std::fs::File::open("000005.ldb").unwrap();
+/// let sst = SstBlockReadAt::open(f).unwrap();
+/// for i in 0..sst.block_count() {
+///     let block = sst.block_at(i).unwrap();
+///     let mut data = vec![0; block.size().unwrap() as usize];
+///     block.read_exact_at(&mut data[..], 0).unwrap();
+/// }
+/// ```
+pub struct SstBlockReadAt<T: ReadAt + SizeAt> {
+    source: T,
+    data_block_index: Vec<(u64, u64)>,
+}
+
+impl<T: ReadAt + SizeAt> SstBlockReadAt<T> {
+    /// Read the footer and the index block from the end of `source`
+    pub fn open(source: T) -> Result<Self> {
+        let size = source.size()?;
+        let mut format_version = 0;
+        if size < LEGACY_FOOTER_LEN {
+            return Err(invalid("file is too short to be an sstable"));
+        }
+        let mut magic = [0u8; 8];
+        source.read_exact_at(&mut magic, size - 8)?;
+        let magic = u64::from_le_bytes(magic);
+
+        let handles = match magic {
+            LEVELDB_MAGIC => {
+                let mut footer = [0u8; LEGACY_FOOTER_LEN as usize - 8];
+                source.read_exact_at(&mut footer, size - LEGACY_FOOTER_LEN)?;
+                footer.to_vec()
+            }
+            ROCKSDB_MAGIC => {
+                if size < ROCKSDB_FOOTER_LEN {
+                    return Err(invalid("file is too short to be an sstable"));
+                }
+                let mut footer = [0u8; ROCKSDB_FOOTER_LEN as usize - 8];
+                source.read_exact_at(&mut footer, size - ROCKSDB_FOOTER_LEN)?;
+                let mut version = [0u8; 4];
+                version.copy_from_slice(&footer[41..45]);
+                format_version = u32::from_le_bytes(version);
+                if format_version > 5 {
+                    return Err(Error::new(
+                        ErrorKind::Unsupported,
+                        "unsupported RocksDB table format version",
+                    ));
+                }
+                // Skip the checksum type byte
+                footer[1..41].to_vec()
+            }
+            _ => return Err(invalid("bad sstable magic number")),
+        };
+
+        let mut pos = 0;
+        let _metaindex = block_handle(&handles, &mut pos)?;
+        let (index_offset, index_size) = block_handle(&handles, &mut pos)?;
+
+        let index_end = index_offset
+            .checked_add(index_size)
+            .and_then(|x| x.checked_add(BLOCK_TRAILER_LEN))
+            .ok_or_else(|| invalid("index block handle overflows"))?;
+        if index_end > size || index_size < 4 {
+            return Err(invalid("index block handle is out of bounds"));
+        }
+        let mut index = vec![0u8; (index_size + BLOCK_TRAILER_LEN) as usize];
+        source.read_exact_at(&mut index[..], index_offset)?;
+        if index[index_size as usize] != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "compressed sstable index blocks are not supported",
+            ));
+        }
+        index.truncate(index_size as usize);
+
+        let data_block_index = parse_index_block(&index[..], size, format_version >= 4)?;
+        Ok(SstBlockReadAt {
+            source,
+            data_block_index,
+        })
+    }
+
+    /// Number of data blocks
+    pub fn block_count(&self) -> u64 {
+        self.data_block_index.len() as u64
+    }
+
+    /// `(offset, size)` pairs of all data blocks, in key order
+    pub fn data_block_index(&self) -> &[(u64, u64)] {
+        &self.data_block_index[..]
+    }
+
+    /// Get the region of source holding contents of data block number `block_num`
+    pub fn block_at(&self, block_num: u64) -> Result<SubRegionAt<&T>> {
+        let &(offset, size) = self
+            .data_block_index
+            .get(block_num as usize)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no such sstable data block"))?;
+        Ok(SubRegionAt::new(&self.source, offset, size))
+    }
+
+    /// Get back the source
+    pub fn into_inner(self) -> T {
+        self.source
+    }
+}
+
+/// Parse the data block handles of an index block. With `delta_encoded` (RocksDB format version 4 and above),
+/// entries have no value length, and entries sharing a key prefix with the previous one store only the
+/// difference of block sizes, the block starting right after the previous one.
+fn parse_index_block(block: &[u8], file_size: u64, delta_encoded: bool) -> Result<Vec<(u64, u64)>> {
+    let mut num_restarts = [0u8; 4];
+    num_restarts.copy_from_slice(&block[block.len() - 4..]);
+    let num_restarts = u64::from(u32::from_le_bytes(num_restarts));
+    let entries_len = (block.len() as u64 - 4)
+        .checked_sub(num_restarts * 4)
+        .ok_or_else(|| invalid("bad restart count in sstable index block"))?;
+    let entries = &block[..entries_len as usize];
+
+    let mut result = Vec::new();
+    let mut pos = 0;
+    while pos < entries.len() {
+        let shared = varint(entries, &mut pos)?;
+        let non_shared = varint(entries, &mut pos)?;
+        let value_len = if delta_encoded { 0 } else { varint(entries, &mut pos)? };
+        let value_start = (pos as u64)
+            .checked_add(non_shared)
+            .filter(|&x| x.saturating_add(value_len) <= entries.len() as u64)
+            .ok_or_else(|| invalid("truncated sstable index entry"))? as usize;
+        let (offset, size) = match result.last() {
+            Some(&(prev_offset, prev_size)) if delta_encoded && shared != 0 => {
+                pos = value_start;
+                let zigzag = varint(entries, &mut pos)?;
+                let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+                let size = (prev_size as i64)
+                    .checked_add(delta)
+                    .filter(|&x| x >= 0)
+                    .ok_or_else(|| invalid("bad size delta in sstable index entry"))?;
+                (prev_offset + prev_size + BLOCK_TRAILER_LEN, size as u64)
+            }
+            _ if delta_encoded => {
+                pos = value_start;
+                block_handle(entries, &mut pos)?
+            }
+            _ => {
+                let value = &entries[value_start..value_start + value_len as usize];
+                pos = value_start + value_len as usize;
+                block_handle(value, &mut 0)?
+            }
+        };
+        if offset.saturating_add(size) > file_size {
+            return Err(invalid("data block handle is out of bounds"));
+        }
+        result.push((offset, size));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_varint(v: &mut Vec<u8>, mut x: u64) {
+        while x >= 0x80 {
+            v.push((x as u8) | 0x80);
+            x >>= 7;
+        }
+        v.push(x as u8);
+    }
+
+    fn put_block(f: &mut Vec<u8>, block: &[u8]) -> (u64, u64) {
+        let h = (f.len() as u64, block.len() as u64);
+        f.extend_from_slice(block);
+        f.extend_from_slice(&[0, 0, 0, 0, 0]);
+        h
+    }
+
+    /// LevelDB table, or RocksDB table with `rocksdb_version`
+    fn build_sst(blocks: &[&[u8]], rocksdb_version: Option<u32>) -> Vec<u8> {
+        let mut f = Vec::new();
+        let handles: Vec<_> = blocks.iter().map(|b| put_block(&mut f, b)).collect();
+        let delta_encoded = matches!(rocksdb_version, Some(v) if v >= 4);
+
+        let mut index = Vec::new();
+        for (i, &(o, s)) in handles.iter().enumerate() {
+            // Keys are "k" followed by a letter, sharing "k" with the previous key
+            let (shared, key) = if i > 0 { (1, vec![b'a' + i as u8]) } else { (0, vec![b'k', b'a']) };
+            let mut value = Vec::new();
+            if delta_encoded && shared != 0 {
+                let delta = s as i64 - handles[i - 1].1 as i64;
+                put_varint(&mut value, ((delta << 1) ^ (delta >> 63)) as u64);
+            } else {
+                put_varint(&mut value, o);
+                put_varint(&mut value, s);
+            }
+            put_varint(&mut index, shared);
+            put_varint(&mut index, key.len() as u64);
+            if !delta_encoded {
+                put_varint(&mut index, value.len() as u64);
+            }
+            index.extend_from_slice(&key);
+            index.extend_from_slice(&value);
+        }
+        index.extend_from_slice(&0u32.to_le_bytes());
+        index.extend_from_slice(&1u32.to_le_bytes());
+        let metaindex = put_block(&mut f, &[0, 0, 0, 0, 1, 0, 0, 0]);
+        let index = put_block(&mut f, &index);
+
+        let mut footer = Vec::new();
+        if rocksdb_version.is_some() {
+            footer.push(1);
+        }
+        put_varint(&mut footer, metaindex.0);
+        put_varint(&mut footer, metaindex.1);
+        put_varint(&mut footer, index.0);
+        put_varint(&mut footer, index.1);
+        match rocksdb_version {
+            Some(v) => {
+                footer.resize(41, 0);
+                footer.extend_from_slice(&v.to_le_bytes());
+                footer.extend_from_slice(&ROCKSDB_MAGIC.to_le_bytes());
+            }
+            None => {
+                footer.resize(40, 0);
+                footer.extend_from_slice(&LEVELDB_MAGIC.to_le_bytes());
+            }
+        }
+        f.extend_from_slice(&footer);
+        f
+    }
+
+    #[test]
+    fn reads_data_blocks() {
+        let f = build_sst(&[b"hello", b"world!"], None);
+        let sst = SstBlockReadAt::open(f).unwrap();
+        assert_eq!(sst.block_count(), 2);
+        assert_eq!(sst.data_block_index(), &[(0, 5), (10, 6)]);
+
+        let b = sst.block_at(1).unwrap();
+        let mut v = [0; 6];
+        b.read_exact_at(&mut v[..], 0).unwrap();
+        assert_eq!(&v[..], b"world!");
+        assert_eq!(b.read_at(&mut v[..], 6).unwrap(), 0);
+
+        assert!(sst.block_at(2).is_err());
+    }
+
+    #[test]
+    fn delta_encoded_index() {
+        let blocks: [&[u8]; 3] = [b"hello", b"world!", b"!"];
+        for version in [2, 5] {
+            let sst = SstBlockReadAt::open(build_sst(&blocks, Some(version))).unwrap();
+            assert_eq!(sst.data_block_index(), &[(0, 5), (10, 6), (21, 1)]);
+        }
+        let e = SstBlockReadAt::open(build_sst(&blocks, Some(6))).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let f = vec![0u8; 100];
+        let e = SstBlockReadAt::open(f).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,146 @@
+use super::{Discard, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use cipher::{Iv, Key, KeyIvInit, StreamCipher, StreamCipherSeek};
+use std::io::{Error, ErrorKind, Result};
+
+/// Encryption of the wrapped object with a seekable stream cipher like AES-CTR or ChaCha20, keyed by absolute byte offset.
+///
+/// Byte `i` of the object is XORed with byte `i` of the keystream, so reads and writes of any offset and length
+/// are encrypted independently, with no read-modify-write and no change of sizes.
+/// The flip side is that the same offset always gets the same keystream: an attacker seeing two versions of
+/// the ciphertext learns the XOR of the plaintexts, and modifications are not detected. Prefer `EncryptedDevice`
+/// when data is rewritten in place and such an attacker matters.
+///
+/// A cipher is initialized with the key and the nonce for every call and seeked to the offset.
+/// Offsets beyond the keystream length of the cipher (256 GiB for the original ChaCha20) fail with `InvalidInput`.
+///
+/// Requires `stream-cipher` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,StreamEncrypted,WriteAt};
+/// use std::sync::Mutex;
+///
+/// type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+/// let d = StreamEncrypted::<_, Aes128Ctr>::new(Mutex::new(vec![]), &[0x42; 16].into(), &[0; 16].into());
+/// d.write_all_at(b"hello, world", 0).unwrap();
+/// assert_ne!(&d.get_ref().lock().unwrap()[..], b"hello, world");
+///
+/// let mut buf = [0u8; 5];
+/// d.read_exact_at(&mut buf[..], 7).unwrap();
+/// assert_eq!(&buf, b"world");
+/// ```
+pub struct StreamEncrypted<T, C: KeyIvInit> {
+    inner: T,
+    key: Key<C>,
+    iv: Iv<C>,
+}
+
+impl<T, C: KeyIvInit + StreamCipher + StreamCipherSeek> StreamEncrypted<T, C> {
+    /// Wrap `inner`, encrypting with cipher `C` with `key` and nonce `iv`
+    pub fn new(inner: T, key: &Key<C>, iv: &Iv<C>) -> Self {
+        StreamEncrypted { inner, key: key.clone(), iv: iv.clone() }
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// XOR `buf` with the keystream at `offset`
+    fn apply(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let beyond = || Error::new(ErrorKind::InvalidInput, "offset is beyond the keystream of the cipher");
+        let mut c = C::new(&self.key, &self.iv);
+        c.try_seek(offset).map_err(|_| beyond())?;
+        c.try_apply_keystream(buf).map_err(|_| beyond())
+    }
+}
+
+impl<T: ReadAt, C: KeyIvInit + StreamCipher + StreamCipherSeek> ReadAt for StreamEncrypted<T, C> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let n = self.inner.read_at(buf, offset)?;
+        self.apply(&mut buf[..n], offset)?;
+        Ok(n)
+    }
+}
+
+impl<T: WriteAt, C: KeyIvInit + StreamCipher + StreamCipherSeek> WriteAt for StreamEncrypted<T, C> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let mut data = buf.to_vec();
+        self.apply(&mut data, offset)?;
+        self.inner.write_at(&data, offset)
+    }
+}
+
+impl<T: SizeAt, C: KeyIvInit> SizeAt for StreamEncrypted<T, C> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+/// Bytes added by growing read as keystream, not zeros
+impl<T: ResizeAt, C: KeyIvInit> ResizeAt for StreamEncrypted<T, C> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.inner.set_len(new_len)
+    }
+}
+
+impl<T: SyncAt, C: KeyIvInit> SyncAt for StreamEncrypted<T, C> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.sync_range(offset, len)
+    }
+}
+
+/// Discarded bytes read as keystream, not zeros
+impl<T: Discard, C: KeyIvInit> Discard for StreamEncrypted<T, C> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.punch_hole(offset, len)
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.zero_range(offset, len)
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.discard(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chacha20::cipher::KeyIvInit;
+    use chacha20::{ChaCha20, ChaCha20Legacy};
+    use std::sync::Mutex;
+
+    #[test]
+    fn offsets() {
+        let d = StreamEncrypted::<_, ChaCha20Legacy>::new(Mutex::new(vec![]), &[7; 32].into(), &[1; 8].into());
+        let plain: Vec<u8> = (0..1000u32).map(|x| x as u8).collect();
+        d.write_all_at(&plain, 0).unwrap();
+        d.write_all_at(&plain[3..70], 3).unwrap();
+
+        let mut whole = plain.clone();
+        ChaCha20Legacy::new(&[7; 32].into(), &[1; 8].into()).apply_keystream(&mut whole);
+        assert_eq!(*d.get_ref().lock().unwrap(), whole);
+
+        let mut v = [0u8; 100];
+        d.read_exact_at(&mut v[..], 777).unwrap();
+        assert_eq!(v[..], plain[777..877]);
+
+        let d = StreamEncrypted::<_, ChaCha20>::new(Mutex::new(vec![]), &[7; 32].into(), &[1; 12].into());
+        assert_eq!(d.write_at(&[1], 300 << 30).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,220 @@
+use super::{Advice, Advise, Discard, Preallocate, ReadAt, SyncAt, WriteAt};
+use std::io::Result;
+
+/// RAID-0 style striping: consecutive `stripe_size`-byte stripes go round-robin to the backends.
+///
+/// Stripe `k` is stored in backend `k % n` at offset `(k / n) * stripe_size`.
+/// Reads and writes crossing stripe boundaries are split; if a backend returns less than requested,
+/// the operation stops there and returns the amount processed so far.
+/// `Advise`, `Preallocate` and `Discard` make one call per backend, covering the part of the range stored there.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Stripe,ReadAt,WriteAt};
+/// use std::cell::RefCell;
+///
+/// let s = Stripe::new(vec![RefCell::new(vec![]), RefCell::new(vec![])], 2);
+/// s.write_all_at(&[1, 2, 3, 4, 5], 0).unwrap();
+/// assert_eq!(*s.backends()[0].borrow(), vec![1, 2, 5]);
+/// assert_eq!(*s.backends()[1].borrow(), vec![3, 4]);
+///
+/// let mut v = [0u8; 3];
+/// s.read_exact_at(&mut v[..], 1).unwrap();
+/// assert_eq!(v, [2, 3, 4]);
+/// ```
+pub struct Stripe<T> {
+    backends: Vec<T>,
+    stripe_size: u64,
+}
+
+impl<T> Stripe<T> {
+    /// Panics if `backends` is empty or `stripe_size` is zero.
+    pub fn new(backends: Vec<T>, stripe_size: u64) -> Self {
+        assert!(!backends.is_empty(), "at least one backend is needed");
+        assert!(stripe_size > 0, "stripe_size must be nonzero");
+        Stripe {
+            backends,
+            stripe_size,
+        }
+    }
+
+    /// Size of a stripe
+    pub fn stripe_size(&self) -> u64 {
+        self.stripe_size
+    }
+
+    /// Backends, in round-robin order
+    pub fn backends(&self) -> &[T] {
+        &self.backends
+    }
+
+    /// Get back the backends
+    pub fn into_inner(self) -> Vec<T> {
+        self.backends
+    }
+
+    /// Backend index, offset in that backend and bytes till stripe end for a logical `offset`
+    fn locate(&self, offset: u64) -> (usize, u64, u64) {
+        let n = self.backends.len() as u64;
+        let stripe = offset / self.stripe_size;
+        let within = offset % self.stripe_size;
+        (
+            (stripe % n) as usize,
+            (stripe / n) * self.stripe_size + within,
+            self.stripe_size - within,
+        )
+    }
+
+    /// Call `f(backend, backend offset, len)` once for each backend storing a part of `offset..offset+len`,
+    /// which is contiguous in the backend, stopping at the first error
+    fn for_range<F>(&self, offset: u64, len: u64, mut f: F) -> Result<()>
+    where
+        F: FnMut(&T, u64, u64) -> Result<()>,
+    {
+        if len == 0 {
+            return Ok(());
+        }
+        let (n, size) = (self.backends.len() as u64, self.stripe_size);
+        let last = offset.saturating_add(len) - 1;
+        let (first_stripe, last_stripe) = (offset / size, last / size);
+        for i in 0..n {
+            // First and last logical bytes of the range stored in backend `i`
+            let skip = (i + n - first_stripe % n) % n;
+            let first = match skip {
+                0 => offset,
+                _ => match first_stripe.checked_add(skip).and_then(|k| k.checked_mul(size)) {
+                    Some(x) if x <= last => x,
+                    _ => continue,
+                },
+            };
+            let back = (last_stripe % n + n - i) % n;
+            let last = match back {
+                0 => last,
+                _ => (last_stripe - back) * size + size - 1,
+            };
+            let (b, start, _) = self.locate(first);
+            let end = self.locate(last).1 + 1;
+            f(&self.backends[b], start, end - start)?;
+        }
+        Ok(())
+    }
+
+    /// Call `f(backend, buffer range, backend offset)` for consecutive per-stripe pieces of a `len`-byte request,
+    /// stopping at the first short or failed piece.
+    fn split<F>(&self, len: usize, offset: u64, mut f: F) -> Result<usize>
+    where
+        F: FnMut(&T, std::ops::Range<usize>, u64) -> Result<usize>,
+    {
+        let mut done = 0;
+        while done < len {
+            let pos = match offset.checked_add(done as u64) {
+                Some(x) => x,
+                None => break,
+            };
+            let (i, o, avail) = self.locate(pos);
+            let n = avail.min((len - done) as u64) as usize;
+            let m = match f(&self.backends[i], done..done + n, o) {
+                Ok(m) => m,
+                Err(e) if done == 0 => return Err(e),
+                Err(_) => break,
+            };
+            done += m;
+            if m < n {
+                break;
+            }
+        }
+        Ok(done)
+    }
+}
+
+impl<T: ReadAt> ReadAt for Stripe<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.split(buf.len(), offset, |t, r, o| t.read_at(&mut buf[r], o))
+    }
+}
+
+impl<T: WriteAt> WriteAt for Stripe<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.split(buf.len(), offset, |t, r, o| t.write_at(&buf[r], o))
+    }
+}
+
+/// Forwarded to every backend; `sync_range` falls back to `sync_data`.
+impl<T: SyncAt> SyncAt for Stripe<T> {
+    fn flush(&self) -> Result<()> {
+        self.backends.iter().try_for_each(|t| t.flush())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.backends.iter().try_for_each(|t| t.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.backends.iter().try_for_each(|t| t.sync_data())
+    }
+}
+
+impl<T: Advise> Advise for Stripe<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.advise(o, l, advice))
+    }
+}
+
+impl<T: Preallocate> Preallocate for Stripe<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.allocate(o, l))
+    }
+}
+
+impl<T: Discard> Discard for Stripe<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.punch_hole(o, l))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.zero_range(o, l))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |t, o, l| t.discard(o, l))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn mapping() {
+        let s = Stripe::new(vec![RefCell::new(vec![]), RefCell::new(vec![]), RefCell::new(vec![])], 4);
+        let data: Vec<u8> = (0..30).collect();
+        s.write_all_at(&data[..], 0).unwrap();
+        assert_eq!(*s.backends()[1].borrow(), vec![4, 5, 6, 7, 16, 17, 18, 19, 28, 29]);
+        assert_eq!(s.locate(29), (1, 9, 3));
+
+        let mut v = [0u8; 10];
+        s.read_exact_at(&mut v[..], 11).unwrap();
+        assert_eq!(v, [11, 12, 13, 14, 15, 16, 17, 18, 19, 20]);
+        // Backend 1 ends at offset 10, which is logical offset 30
+        assert_eq!(s.read_at(&mut v[..], 29).unwrap(), 1);
+    }
+
+    struct Calls(RefCell<Vec<(u64, u64)>>);
+    impl Discard for Calls {
+        fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+            self.0.borrow_mut().push((offset, len));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn one_call_per_backend() {
+        let s = Stripe::new((0..3).map(|_| Calls(RefCell::new(vec![]))).collect(), 4);
+        let calls = |s: &Stripe<Calls>| s.backends().iter().map(|c| c.0.take()).collect::<Vec<_>>();
+        s.punch_hole(6, 20).unwrap();
+        assert_eq!(calls(&s), [vec![(4, 6)], vec![(2, 6)], vec![(0, 8)]]);
+        s.punch_hole(1, 2).unwrap();
+        assert_eq!(calls(&s), [vec![(1, 2)], vec![], vec![]]);
+        s.punch_hole(0, u64::MAX).unwrap();
+        let all = calls(&s);
+        assert!(all.iter().all(|c| c.len() == 1 && c[0].0 == 0));
+    }
+}
@@ -0,0 +1,273 @@
+use super::{Advice, Advise, BlockGeometry, Discard, Preallocate, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// A window of `length` bytes starting at `base` of the wrapped object.
+///
+/// Offsets are relative to `base`. Reads are truncated at the end of the window (returning `0` past it),
+/// so `read_exact_at` fails with `UnexpectedEof` when going past the end.
+/// Writes are truncated as well; writing at or after the end fails with `WriteZero`.
+/// If `base + offset` does not fit in `u64`, operations fail with `InvalidInput`.
+///
+/// `ReadAtMut` and `WriteAtMut` come from `ReadAt` and `WriteAt`,
+/// so objects implementing only the `Mut` traits need to be wrapped into e.g. `RefCell` first.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,SubRange};
+///
+/// let v = vec![1u8,2,3,4,5];
+/// let r = SubRange::new(&v, 1, 3);
+/// let mut b = vec![0; 4];
+/// assert_eq!(r.read_at(&mut b[..], 0).unwrap(), 3);
+/// assert_eq!(b, vec![2,3,4,0]);
+/// assert_eq!(r.remaining_at(1), Some(2));
+/// ```
+pub struct SubRange<T> {
+    inner: T,
+    base: u64,
+    length: u64,
+}
+
+/// Old name of `SubRange`
+pub type SubRegionAt<T> = SubRange<T>;
+
+/// Bounded window into a sub-range of a device, as returned by `SubRange::slice`, e.g. a partition or a per-tenant area
+pub type Slice<T> = SubRange<T>;
+
+impl<T> SubRange<T> {
+    /// Create a window of `length` bytes starting at `base` in `inner`.
+    pub fn new(inner: T, base: u64, length: u64) -> Self {
+        SubRange {
+            inner,
+            base,
+            length,
+        }
+    }
+
+    /// Like `new`, but fails with `InvalidInput` if `base + length` overflows `u64`.
+    pub fn new_checked(inner: T, base: u64, length: u64) -> Result<Self> {
+        if base.checked_add(length).is_none() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "sub range end overflows u64",
+            ));
+        }
+        Ok(SubRange::new(inner, base, length))
+    }
+
+    /// Offset of the window start in the wrapped object
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Length of the window
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Whether the window is zero-sized
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Number of bytes from `offset` to the end of the window, `None` if `offset` is beyond the end.
+    pub fn remaining_at(&self, offset: u64) -> Option<u64> {
+        self.length.checked_sub(offset)
+    }
+
+    /// A nested window of up to `len` bytes starting at `offset` of this one, e.g. a partition inside a disk image.
+    ///
+    /// The new window is clamped to this window's end, so it never exposes data outside it.
+    pub fn slice(&self, offset: u64, len: u64) -> Slice<&T> {
+        let offset = offset.min(self.length);
+        SubRange::new(
+            &self.inner,
+            self.base.saturating_add(offset),
+            len.min(self.length - offset),
+        )
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Call `f(offset, len)` with `offset..offset+len` clipped to the window and translated, unless it is empty
+    fn for_range(&self, offset: u64, len: u64, f: impl FnOnce(u64, u64) -> Result<()>) -> Result<()> {
+        let len = match self.remaining_at(offset) {
+            None | Some(0) => return Ok(()),
+            Some(r) => r.min(len),
+        };
+        match self.base.checked_add(offset) {
+            Some(o) => f(o, len),
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "sub range offset overflows u64",
+            )),
+        }
+    }
+
+    /// Translate `offset`, clamping buffer length to the window.
+    /// Returns `None` if `offset` is at or beyond the window end.
+    fn translate(&self, offset: u64, buflen: usize) -> Option<Result<(u64, usize)>> {
+        match self.remaining_at(offset) {
+            None | Some(0) => None,
+            Some(r) => Some(match self.base.checked_add(offset) {
+                Some(o) => Ok((o, r.min(buflen as u64) as usize)),
+                None => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "sub range offset overflows u64",
+                )),
+            }),
+        }
+    }
+}
+
+impl<T:ReadAt> ReadAt for SubRange<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        match self.translate(offset, buf.len()) {
+            Some(x) => {
+                let (o, n) = x?;
+                self.inner.read_at(&mut buf[..n], o)
+            }
+            None => Ok(0),
+        }
+    }
+}
+
+impl<T:WriteAt> WriteAt for SubRange<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.translate(offset, buf.len()) {
+            Some(x) => {
+                let (o, n) = x?;
+                self.inner.write_at(&buf[..n], o)
+            }
+            None => Err(Error::new(
+                ErrorKind::WriteZero,
+                "write past the end of a sub range",
+            )),
+        }
+    }
+}
+
+impl<T> SizeAt for SubRange<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.length)
+    }
+}
+
+/// `sync_range` is clamped to the window and translated, other methods are forwarded as is.
+impl<T:SyncAt> SyncAt for SubRange<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |o, l| self.inner.sync_range(o, l))
+    }
+}
+
+/// The range is clipped to the window and translated.
+impl<T:Advise> Advise for SubRange<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        self.for_range(offset, len, |o, l| self.inner.advise(o, l, advice))
+    }
+}
+
+impl<T: BlockGeometry> BlockGeometry for SubRange<T> {
+    fn logical_block_size(&self) -> Result<u32> {
+        self.inner.logical_block_size()
+    }
+    fn physical_block_size(&self) -> Result<u32> {
+        self.inner.physical_block_size()
+    }
+}
+
+/// The range is clipped to the window and translated.
+impl<T:Preallocate> Preallocate for SubRange<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |o, l| self.inner.allocate(o, l))
+    }
+}
+
+/// Ranges are clipped to the window and translated.
+impl<T:Discard> Discard for SubRange<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |o, l| self.inner.punch_hole(o, l))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |o, l| self.inner.zero_range(o, l))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.for_range(offset, len, |o, l| self.inner.discard(o, l))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn bounds() {
+        let v: Vec<u8> = (0..10).collect();
+        let r = SubRange::new(&v, 2, 5);
+        let mut b = [0u8; 8];
+
+        assert_eq!(r.read_at(&mut b[..], 3).unwrap(), 2);
+        assert_eq!(b[..2], [5, 6]);
+        assert_eq!(r.read_at(&mut b[..], 5).unwrap(), 0);
+        assert_eq!(r.read_at(&mut b[..], 6).unwrap(), 0);
+        let e = r.read_exact_at(&mut b[..3], 3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(r.remaining_at(5), Some(0));
+        assert_eq!(r.remaining_at(6), None);
+
+        let s = r.slice(1, 10);
+        assert_eq!((s.base(), s.len()), (3, 4));
+        assert_eq!(s.read_at(&mut b[..], 0).unwrap(), 4);
+        assert_eq!(b[..4], [3, 4, 5, 6]);
+        assert!(r.slice(6, 1).is_empty());
+
+        let z = SubRange::new(&v, 2, 0);
+        assert!(z.is_empty());
+        assert_eq!(z.read_at(&mut b[..], 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn writes() {
+        let c = RefCell::new(vec![0u8; 6]);
+        let r = SubRange::new(&c, 1, 3);
+        assert_eq!(r.write_at(&[1, 2, 3, 4], 1).unwrap(), 2);
+        assert_eq!(r.write_at(&[], 3).unwrap(), 0);
+        assert_eq!(r.write_at(&[1], 3).unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(r.write_all_at(&[7, 7], 2).unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(*c.borrow(), vec![0, 0, 1, 7, 0, 0]);
+    }
+
+    #[test]
+    fn overflow() {
+        let v = vec![0u8; 4];
+        assert!(SubRange::new_checked(&v, u64::MAX - 1, 2).is_err());
+        assert!(SubRange::new_checked(&v, u64::MAX - 1, 1).is_ok());
+
+        let r = SubRange::new(&v, u64::MAX - 1, 4);
+        let mut b = [0u8; 1];
+        assert_eq!(r.read_at(&mut b[..], 1).unwrap(), 0);
+        assert_eq!(r.read_at(&mut b[..], 2).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,175 @@
+use super::{Advice, Advise, BlockGeometry, Discard, Preallocate, ReadAt, SizeAt, SyncAt, WriteAt};
+use std::io::{Error, ErrorKind, Result};
+
+/// Exposes only the first `limit` bytes of the wrapped object.
+///
+/// Like a `SubRange` starting at `0`: reads are truncated at `limit` (returning `0` past it),
+/// writes are truncated too, and writing at or after `limit` fails with `WriteZero`.
+/// Unlike `SubRange`, `size` is that of the wrapped object when it is smaller than `limit`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{CursorAt,Take};
+/// use std::io::Read;
+///
+/// let v = vec![1u8, 2, 3, 4, 5];
+/// let mut head = vec![];
+/// CursorAt::new(Take::new(&v, 3)).read_to_end(&mut head).unwrap();
+/// assert_eq!(head, [1, 2, 3]);
+/// ```
+pub struct Take<T> {
+    inner: T,
+    limit: u64,
+}
+
+impl<T> Take<T> {
+    /// Expose first `limit` bytes of `inner`
+    pub fn new(inner: T, limit: u64) -> Self {
+        Take { inner, limit }
+    }
+
+    /// Maximum end offset of accesses
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Change the maximum end offset of accesses
+    pub fn set_limit(&mut self, limit: u64) {
+        self.limit = limit;
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Buffer length clamped to the limit, `0` if `offset` is at or after it
+    fn clamp(&self, offset: u64, buflen: usize) -> usize {
+        self.limit.saturating_sub(offset).min(buflen as u64) as usize
+    }
+}
+
+impl<T: ReadAt> ReadAt for Take<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        match self.clamp(offset, buf.len()) {
+            0 => Ok(0),
+            n => self.inner.read_at(&mut buf[..n], offset),
+        }
+    }
+}
+
+impl<T: WriteAt> WriteAt for Take<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.clamp(offset, buf.len()) {
+            0 => Err(Error::new(ErrorKind::WriteZero, "write past the limit")),
+            n => self.inner.write_at(&buf[..n], offset),
+        }
+    }
+}
+
+impl<T: SizeAt> SizeAt for Take<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.inner.size()?.min(self.limit))
+    }
+}
+
+/// `sync_range` is clamped to the limit, other methods are forwarded as is.
+impl<T: SyncAt> SyncAt for Take<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        match self.limit.saturating_sub(offset).min(len) {
+            0 => Ok(()),
+            len => self.inner.sync_range(offset, len),
+        }
+    }
+}
+
+/// The range is clamped to the limit.
+impl<T: Advise> Advise for Take<T> {
+    fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        match self.limit.saturating_sub(offset).min(len) {
+            0 => Ok(()),
+            len => self.inner.advise(offset, len, advice),
+        }
+    }
+}
+
+impl<T: BlockGeometry> BlockGeometry for Take<T> {
+    fn logical_block_size(&self) -> Result<u32> {
+        self.inner.logical_block_size()
+    }
+    fn physical_block_size(&self) -> Result<u32> {
+        self.inner.physical_block_size()
+    }
+}
+
+/// The range is clamped to the limit.
+impl<T: Preallocate> Preallocate for Take<T> {
+    fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        match self.limit.saturating_sub(offset).min(len) {
+            0 => Ok(()),
+            len => self.inner.allocate(offset, len),
+        }
+    }
+}
+
+/// Ranges are clamped to the limit.
+impl<T: Discard> Discard for Take<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        match self.limit.saturating_sub(offset).min(len) {
+            0 => Ok(()),
+            len => self.inner.punch_hole(offset, len),
+        }
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        match self.limit.saturating_sub(offset).min(len) {
+            0 => Ok(()),
+            len => self.inner.zero_range(offset, len),
+        }
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        match self.limit.saturating_sub(offset).min(len) {
+            0 => Ok(()),
+            len => self.inner.discard(offset, len),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn limits() {
+        let c = RefCell::new(vec![0u8; 2]);
+        let mut t = Take::new(&c, 4);
+        assert_eq!(t.size().unwrap(), 2);
+        assert_eq!(t.write_at(&[1, 2, 3], 2).unwrap(), 2);
+        assert_eq!(t.write_at(&[1], 4).unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(t.size().unwrap(), 4);
+        let mut b = [9u8; 8];
+        assert_eq!(t.read_at(&mut b[..], 1).unwrap(), 3);
+        assert_eq!(b[..3], [0, 1, 2]);
+        t.set_limit(1);
+        assert_eq!(t.read_at(&mut b[..], 1).unwrap(), 0);
+        assert_eq!(t.size().unwrap(), 1);
+    }
+}
@@ -0,0 +1,253 @@
+use super::{ReadAt, SizeAt, SubRange};
+use std::io::{Error, ErrorKind, Result};
+
+const BLOCK: u64 = 512;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Numeric header field: octal text, or big-endian base-256 if the high bit of the first byte is set
+fn number(field: &[u8]) -> Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let b = &field[field.len().saturating_sub(8)..];
+        return Ok(b.iter().fold(0u64, |n, &x| n << 8 | x as u64) & !(1 << 63));
+    }
+    let text = field.split(|&b| b == 0).next().unwrap_or(&[]);
+    let text = std::str::from_utf8(text).map_err(|_| invalid("malformed number in tar header"))?;
+    let text = text.trim_matches(' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid("malformed number in tar header"))
+}
+
+/// NUL-terminated string field
+fn text(field: &[u8]) -> &[u8] {
+    field.split(|&b| b == 0).next().unwrap_or(&[])
+}
+
+/// Value of `key` in pax extended header records `data`
+fn pax_value<'a>(data: &'a [u8], key: &str) -> Option<&'a [u8]> {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?.strip_suffix(b"\n")?;
+        let eq = record.iter().position(|&b| b == b'=')?;
+        if &record[..eq] == key.as_bytes() {
+            return Some(&record[eq + 1..]);
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+/// A member of a tar archive
+#[derive(Debug, Clone)]
+pub struct TarMember {
+    name: String,
+    kind: u8,
+    offset: u64,
+    size: u64,
+}
+
+impl TarMember {
+    /// Path in the archive, with invalid UTF-8 replaced. Long names from GNU and pax headers are supported.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type flag of the header, e.g. `b'0'` for a regular file, `b'5'` for a directory and `b'2'` for a symlink
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    /// Whether the member is a regular file
+    pub fn is_file(&self) -> bool {
+        matches!(self.kind, b'0' | 0 | b'7')
+    }
+
+    /// Offset of the content in the archive
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the content
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Index of the members of an uncompressed tar archive, exposing each one as a `SubRange` of it.
+///
+/// `open` walks the archive once, reading only the 512-byte headers (and the content of GNU long name and
+/// pax headers), to record where the content of each member is. Members are then read in place,
+/// with no extraction, e.g. single files out of a large OCI image layer. Compressed tarballs need to be
+/// wrapped into something giving random access first, like `SeekableZstdReader` or `BgzfReader`.
+///
+/// GNU sparse members are indexed, but their content is the sparse data as stored, not the expanded file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{ReadAt,TarArchive};
+///
+/// let layer = TarArchive::open(std::fs::File::open("layer.tar")?)?;
+/// let i = layer.find("etc/os-release").expect("member exists");
+/// let mut text = vec![0u8; layer.members()[i].size() as usize];
+/// layer.member(i).expect("index is valid").read_exact_at(&mut text, 0)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct TarArchive<T> {
+    inner: T,
+    members: Vec<TarMember>,
+}
+
+impl<T: ReadAt + SizeAt> TarArchive<T> {
+    /// Index the members of `inner`, stopping at the end-of-archive block or the end of `inner`
+    pub fn open(inner: T) -> Result<Self> {
+        let size = inner.size()?;
+        let mut members = vec![];
+        let (mut long_name, mut pax): (Option<Vec<u8>>, Option<Vec<u8>>) = (None, None);
+        let mut offset = 0u64;
+        while offset + BLOCK <= size {
+            let mut h = [0u8; BLOCK as usize];
+            inner.read_exact_at(&mut h, offset)?;
+            if h.iter().all(|&b| b == 0) {
+                break;
+            }
+            let sum = h.iter().enumerate().map(|(i, &b)| if (148..156).contains(&i) { 32 } else { b as u64 });
+            if sum.sum::<u64>() != number(&h[148..156])? {
+                return Err(invalid("tar header checksum mismatch"));
+            }
+            let data = offset + BLOCK;
+            let mut len = number(&h[124..136])?;
+            if len > size - data {
+                return Err(invalid("tar member is longer than the archive"));
+            }
+            match h[156] {
+                b'L' | b'x' => {
+                    let mut content = vec![0u8; len as usize];
+                    inner.read_exact_at(&mut content, data)?;
+                    if h[156] == b'L' {
+                        long_name = Some(text(&content).to_vec());
+                    } else {
+                        pax = Some(content);
+                    }
+                }
+                b'K' | b'g' => {}
+                kind => {
+                    let mut name = match (&long_name, pax.as_deref().and_then(|p| pax_value(p, "path"))) {
+                        (_, Some(path)) => path.to_vec(),
+                        (Some(long), None) => long.clone(),
+                        (None, None) => text(&h[..100]).to_vec(),
+                    };
+                    if long_name.is_none() && pax.is_none() && h[257..262] == *b"ustar" && h[345] != 0 {
+                        let mut full = text(&h[345..500]).to_vec();
+                        full.push(b'/');
+                        full.extend_from_slice(&name);
+                        name = full;
+                    }
+                    if let Some(s) = pax.as_deref().and_then(|p| pax_value(p, "size")) {
+                        len = std::str::from_utf8(s)
+                            .ok()
+                            .and_then(|s| s.parse().ok())
+                            .ok_or_else(|| invalid("malformed size in pax header"))?;
+                    }
+                    let name = String::from_utf8_lossy(&name).into_owned();
+                    members.push(TarMember { name, kind, offset: data, size: len });
+                    long_name = None;
+                    pax = None;
+                }
+            }
+            offset = data
+                .checked_add(len)
+                .and_then(|end| end.checked_add(BLOCK - 1))
+                .map(|end| end / BLOCK * BLOCK)
+                .filter(|&end| end <= size)
+                .ok_or_else(|| invalid("tar member is longer than the archive"))?;
+        }
+        Ok(TarArchive { inner, members })
+    }
+}
+
+impl<T> TarArchive<T> {
+    /// Members in the order of the archive
+    pub fn members(&self) -> &[TarMember] {
+        &self.members
+    }
+
+    /// Index of the last member named `name`, which is the one that extraction would leave
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.members.iter().rposition(|m| m.name == name)
+    }
+
+    /// Content of member `index` as a window of the archive
+    pub fn member(&self, index: usize) -> Option<SubRange<&T>> {
+        let m = self.members.get(index)?;
+        Some(SubRange::new(&self.inner, m.offset, m.size))
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, kind: u8, len: usize) -> Vec<u8> {
+        let mut h = vec![0u8; 512];
+        h[..name.len()].copy_from_slice(name.as_bytes());
+        h[124..135].copy_from_slice(format!("{:011o}", len).as_bytes());
+        h[156] = kind;
+        h[257..263].copy_from_slice(b"ustar\0");
+        h[148..156].copy_from_slice(b"        ");
+        let sum: u32 = h.iter().map(|&b| b as u32).sum();
+        h[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        h
+    }
+
+    fn member(tar: &mut Vec<u8>, name: &str, kind: u8, data: &[u8]) {
+        tar.extend_from_slice(&header(name, kind, data.len()));
+        tar.extend_from_slice(data);
+        tar.resize(tar.len().div_ceil(512) * 512, 0);
+    }
+
+    #[test]
+    fn members() {
+        let long = "a/".repeat(80) + "long.txt";
+        let mut tar = vec![];
+        member(&mut tar, "dir/", b'5', &[]);
+        member(&mut tar, "dir/hello.txt", b'0', b"hello, world\n");
+        member(&mut tar, "././@LongLink", b'L', format!("{}\0", long).as_bytes());
+        member(&mut tar, "long", b'0', &[7; 1000]);
+        member(&mut tar, "pax", b'x', b"21 path=pax/name.bin\n");
+        member(&mut tar, "ignored", b'0', &[]);
+        tar.extend_from_slice(&[0; 1024]);
+
+        let a = TarArchive::open(&tar).unwrap();
+        let names: Vec<_> = a.members().iter().map(|m| m.name()).collect();
+        assert_eq!(names, ["dir/", "dir/hello.txt", long.as_str(), "pax/name.bin"]);
+        assert!(!a.members()[0].is_file() && a.members()[1].is_file());
+
+        let hello = a.member(a.find("dir/hello.txt").unwrap()).unwrap();
+        let mut v = vec![0u8; 1000];
+        assert_eq!(hello.read_at(&mut v, 7).unwrap(), 6);
+        assert_eq!(&v[..6], b"world\n");
+        a.member(2).unwrap().read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v, [7; 1000]);
+        assert!(a.member(4).is_none());
+
+        tar[600] ^= 1;
+        assert!(TarArchive::open(&tar).is_err());
+    }
+}
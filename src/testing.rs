@@ -0,0 +1,393 @@
+//! Checking implementations of the traits against a reference model.
+//!
+//! `check` applies a random but reproducible sequence of `Op`s to a candidate object and to an in-memory `Vec<u8>`
+//! model at the same time, comparing every result; `check_ops` does the same with a given sequence,
+//! e.g. a failing one reported in a `Mismatch`. It is meant for tests of custom backends and adapters:
+//!
+//! ```
+//! use read_write_at::testing::{check,Config};
+//!
+//! let mut device = std::sync::Mutex::new(vec![0u8; 1000]);
+//! check(&mut device, &Config { seed: 42, ..Config::default() }).unwrap();
+//! // On failure, the error shows the seed, the failing operation and the operations before it
+//! ```
+//!
+//! The candidate's initial contents, read as a whole at the start, become the initial contents of the model,
+//! so it should be small. Reads may legally return less than requested, so they are repeated until the buffer is full
+//! or the end is reached; writes are done with `write_all_at`. Errors of the candidate are mismatches.
+
+use super::rng::Rng;
+use super::{ReadAtMut, ResizeAtMut, SizeAt, SyncAtMut, WriteAtMut};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::ErrorKind;
+
+/// An operation applied by `check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Read up to `len` bytes at `offset`
+    Read {
+        /// Where to read
+        offset: u64,
+        /// How much to read
+        len: usize,
+    },
+    /// Write all of `data` at `offset`, extending the object with zeros over any gap
+    Write {
+        /// Where to write
+        offset: u64,
+        /// What to write
+        data: Vec<u8>,
+    },
+    /// Truncate or extend with zeros
+    SetLen(u64),
+    /// Compare sizes
+    Size,
+    /// `flush`, which must not change the contents
+    Flush,
+}
+
+/// Parameters of `check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Seed of the random sequence; the same seed gives the same sequence against the same initial size
+    pub seed: u64,
+    /// Number of operations to apply
+    pub ops: usize,
+    /// Largest size writes and `SetLen` may grow the object to
+    pub max_size: u64,
+    /// Longest read or write
+    pub max_len: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { seed: 0, ops: 1000, max_size: 65536, max_len: 4096 }
+    }
+}
+
+/// Candidate disagreed with the model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Seed of the sequence, if it was random
+    pub seed: Option<u64>,
+    /// Operations applied, the last being the one that failed; replay them with `check_ops`
+    pub ops: Vec<Op>,
+    /// What the model returned
+    pub expected: String,
+    /// What the candidate returned
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            write!(f, "seed {}: ", seed)?;
+        }
+        match self.ops.last() {
+            Some(op) => write!(f, "operation #{} {}", self.ops.len() - 1, short(op))?,
+            None => f.write_str("reading initial contents")?,
+        }
+        write!(f, ": expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// `Debug` of `op` without the whole data buffer
+fn short(op: &Op) -> String {
+    match op {
+        Op::Write { offset, data } => format!("Write {{ offset: {}, len: {} }}", offset, data.len()),
+        x => format!("{:?}", x),
+    }
+}
+
+/// Abstracts over candidates with and without `ResizeAtMut`
+trait Candidate: ReadAtMut + WriteAtMut + SizeAt + SyncAtMut {
+    fn resizable(&self) -> bool;
+    fn resize(&mut self, new_len: u64) -> Option<std::io::Result<()>>;
+}
+
+struct Resizable<'a, D: ?Sized>(&'a mut D);
+struct Fixed<'a, D: ?Sized>(&'a mut D);
+
+macro_rules! forward {
+    ($w:ident) => {
+        impl<D: ReadAtMut + ?Sized> ReadAtMut for $w<'_, D> {
+            fn read_at(&mut self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+                self.0.read_at(buf, offset)
+            }
+        }
+        impl<D: WriteAtMut + ?Sized> WriteAtMut for $w<'_, D> {
+            fn write_at(&mut self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+                self.0.write_at(buf, offset)
+            }
+            fn write_all_at(&mut self, buf: &[u8], offset: u64) -> std::io::Result<()> {
+                self.0.write_all_at(buf, offset)
+            }
+        }
+        impl<D: SizeAt + ?Sized> SizeAt for $w<'_, D> {
+            fn size(&self) -> std::io::Result<u64> {
+                self.0.size()
+            }
+        }
+        impl<D: SyncAtMut + ?Sized> SyncAtMut for $w<'_, D> {
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.flush()
+            }
+        }
+    };
+}
+forward!(Resizable);
+forward!(Fixed);
+
+impl<D: ReadAtMut + WriteAtMut + SizeAt + SyncAtMut + ResizeAtMut + ?Sized> Candidate for Resizable<'_, D> {
+    fn resizable(&self) -> bool {
+        true
+    }
+    fn resize(&mut self, new_len: u64) -> Option<std::io::Result<()>> {
+        Some(self.0.set_len(new_len))
+    }
+}
+
+impl<D: ReadAtMut + WriteAtMut + SizeAt + SyncAtMut + ?Sized> Candidate for Fixed<'_, D> {
+    fn resizable(&self) -> bool {
+        false
+    }
+    fn resize(&mut self, _new_len: u64) -> Option<std::io::Result<()>> {
+        None
+    }
+}
+
+/// Apply `config.ops` random operations to `device` and to the model, including writes beyond the end and resizes
+pub fn check<D>(device: &mut D, config: &Config) -> Result<(), Mismatch>
+where
+    D: ReadAtMut + WriteAtMut + SizeAt + SyncAtMut + ResizeAtMut + ?Sized,
+{
+    run(&mut Resizable(device), Source::Random(Rng::new(config.seed), config))
+}
+
+/// Like `check`, for objects of fixed size: writes stay within the initial size and there are no `SetLen`s
+pub fn check_fixed_size<D>(device: &mut D, config: &Config) -> Result<(), Mismatch>
+where
+    D: ReadAtMut + WriteAtMut + SizeAt + SyncAtMut + ?Sized,
+{
+    run(&mut Fixed(device), Source::Random(Rng::new(config.seed), config))
+}
+
+/// Apply `ops` to `device` and to the model
+pub fn check_ops<D>(device: &mut D, ops: &[Op]) -> Result<(), Mismatch>
+where
+    D: ReadAtMut + WriteAtMut + SizeAt + SyncAtMut + ResizeAtMut + ?Sized,
+{
+    run(&mut Resizable(device), Source::Given(ops.iter()))
+}
+
+enum Source<'a> {
+    Random(Rng, &'a Config),
+    Given(std::slice::Iter<'a, Op>),
+}
+
+impl Source<'_> {
+    fn next(&mut self, done: usize, size: u64, resizable: bool) -> Option<Op> {
+        let (rng, config) = match self {
+            Source::Given(ops) => return ops.next().cloned(),
+            Source::Random(_, config) if done >= config.ops => return None,
+            Source::Random(rng, config) => (rng, config),
+        };
+        let max_len = config.max_len.max(1) as u64;
+        let limit = if resizable { config.max_size.max(size) } else { size };
+        let op = match rng.below(100) {
+            0..=34 => Op::Read { offset: rng.below(size + max_len / 2 + 1), len: rng.below(max_len + 1) as usize },
+            35..=69 if limit > 0 => {
+                let offset = rng.below(limit.min(size + max_len));
+                let len = 1 + rng.below(max_len.min(limit - offset));
+                let mut data = vec![0u8; len as usize];
+                rng.fill(&mut data);
+                Op::Write { offset, data }
+            }
+            70..=79 if resizable => Op::SetLen(rng.below(config.max_size + 1)),
+            80..=89 => Op::Flush,
+            _ => Op::Size,
+        };
+        Some(op)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        match self {
+            Source::Random(_, config) => Some(config.seed),
+            Source::Given(_) => None,
+        }
+    }
+}
+
+/// Read up to `len` bytes, repeating short reads
+fn read_full<D: ReadAtMut + ?Sized>(device: &mut D, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    let mut filled = 0;
+    while filled < len {
+        match device.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+fn describe(data: &[u8]) -> String {
+    if data.len() <= 16 {
+        format!("{:?}", data)
+    } else {
+        format!("{} bytes {:?}...", data.len(), &data[..16])
+    }
+}
+
+fn run(device: &mut dyn Candidate, mut source: Source<'_>) -> Result<(), Mismatch> {
+    let seed = source.seed();
+    let mut ops = Vec::new();
+    let fail = |ops: &Vec<Op>, expected: String, actual: String| Err(Mismatch { seed, ops: ops.clone(), expected, actual });
+    let err = |e: std::io::Error| format!("error {}", e);
+
+    let initial = device.size().and_then(|size| {
+        let size = usize::try_from(size).map_err(|_| std::io::Error::other("candidate too large"))?;
+        read_full(device, 0, size)
+    });
+    let mut model = match initial {
+        Ok(x) => x,
+        Err(e) => return fail(&ops, "readable contents".to_owned(), err(e)),
+    };
+    let resizable = device.resizable();
+
+    while let Some(op) = source.next(ops.len(), model.len() as u64, resizable) {
+        ops.push(op.clone());
+        match op {
+            Op::Read { offset, len } => {
+                let start = usize::try_from(offset).unwrap_or(usize::MAX).min(model.len());
+                let expected = &model[start..start.saturating_add(len).min(model.len())];
+                match read_full(device, offset, len) {
+                    Ok(ref actual) if actual[..] == *expected => {}
+                    Ok(actual) => return fail(&ops, describe(expected), describe(&actual)),
+                    Err(e) => return fail(&ops, describe(expected), err(e)),
+                }
+            }
+            Op::Write { offset, ref data } => {
+                if let Err(e) = device.write_all_at(data, offset) {
+                    return fail(&ops, "success".to_owned(), err(e));
+                }
+                let start = offset as usize;
+                if model.len() < start + data.len() {
+                    model.resize(start + data.len(), 0);
+                }
+                model[start..start + data.len()].copy_from_slice(data);
+            }
+            Op::SetLen(len) => {
+                match device.resize(len) {
+                    Some(Ok(())) => {}
+                    Some(Err(e)) => return fail(&ops, "success".to_owned(), err(e)),
+                    None => return fail(&ops, "success".to_owned(), "no ResizeAt".to_owned()),
+                }
+                model.resize(len as usize, 0);
+            }
+            Op::Size => match device.size() {
+                Ok(x) if x == model.len() as u64 => {}
+                Ok(x) => return fail(&ops, model.len().to_string(), x.to_string()),
+                Err(e) => return fail(&ops, model.len().to_string(), err(e)),
+            },
+            Op::Flush => {
+                if let Err(e) = device.flush() {
+                    return fail(&ops, "success".to_owned(), err(e));
+                }
+            }
+        }
+    }
+
+    // Final comparison of the whole contents
+    let op = Op::Read { offset: 0, len: model.len() + 1 };
+    ops.push(op);
+    match read_full(device, 0, model.len() + 1) {
+        Ok(ref actual) if *actual == model => Ok(()),
+        Ok(actual) => fail(&ops, describe(&model), describe(&actual)),
+        Err(e) => fail(&ops, describe(&model), err(e)),
+    }
+}
+
+/// Path in the temporary directory that no other test of this process uses
+#[cfg(test)]
+pub(crate) fn temp_path() -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("read_write_at_test_{}_{}", std::process::id(), n))
+}
+
+/// Empty read-write file, already removed from the directory so that it goes away with the handle
+#[cfg(test)]
+pub(crate) fn temp_file() -> std::fs::File {
+    let path = temp_path();
+    let f = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadAt, SizeAt, SyncAt, WriteAt};
+    use std::io::Result;
+    use std::sync::Mutex;
+
+    /// Loses writes reaching beyond 900
+    struct Buggy(Mutex<Vec<u8>>);
+
+    impl ReadAt for Buggy {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+            self.0.read_at(buf, offset)
+        }
+    }
+    impl WriteAt for Buggy {
+        fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+            if offset + buf.len() as u64 > 900 {
+                return Ok(buf.len());
+            }
+            self.0.write_at(buf, offset)
+        }
+    }
+    impl SizeAt for Buggy {
+        fn size(&self) -> Result<u64> {
+            self.0.size()
+        }
+    }
+    impl SyncAt for Buggy {
+        fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reference() {
+        for seed in 0..20 {
+            let mut v = Mutex::new(vec![1u8; 100]);
+            check(&mut v, &Config { seed, ops: 200, max_size: 5000, max_len: 300 }).unwrap();
+            let mut v = vec![2u8; 1000];
+            check(&mut v, &Config { seed, ops: 200, max_size: 5000, max_len: 300 }).unwrap();
+            let mut s = [3u8; 1000];
+            check_fixed_size(&mut &mut s[..], &Config { seed, ops: 200, max_size: 5000, max_len: 300 }).unwrap();
+        }
+    }
+
+    #[test]
+    fn mismatch() {
+        let ops = [Op::Write { offset: 990, data: vec![5; 10] }, Op::Read { offset: 995, len: 10 }];
+        let mut v = Mutex::new(vec![0u8; 1000]);
+        check_ops(&mut v, &ops).unwrap();
+
+        let mut b = Buggy(Mutex::new(vec![0u8; 1000]));
+        let err = check_fixed_size(&mut b, &Config { max_len: 10, ..Config::default() }).unwrap_err();
+        assert_eq!(err.seed, Some(0));
+        assert!(matches!(err.ops.last(), Some(Op::Read { .. })));
+        assert!(err.to_string().starts_with("seed 0: operation #"), "{}", err);
+    }
+}
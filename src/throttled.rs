@@ -0,0 +1,249 @@
+use super::{Discard, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Limits of a `Throttled` direction. Each limit allows bursts of one second worth of it.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::Rate;
+///
+/// let r = Rate { ops_per_second: Some(100), ..Rate::bytes(10 << 20) };
+/// assert_eq!(r.bytes_per_second, Some(10 << 20));
+/// assert_eq!(Rate::default(), Rate::UNLIMITED);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rate {
+    /// Bytes transferred per second, `None` for no limit
+    pub bytes_per_second: Option<u64>,
+    /// Calls per second, `None` for no limit
+    pub ops_per_second: Option<u64>,
+}
+
+impl Rate {
+    /// No limits
+    pub const UNLIMITED: Rate = Rate { bytes_per_second: None, ops_per_second: None };
+
+    /// Limit bandwidth only
+    pub fn bytes(bytes_per_second: u64) -> Self {
+        Rate { bytes_per_second: Some(bytes_per_second), ops_per_second: None }
+    }
+
+    /// Limit IOPS only
+    pub fn ops(ops_per_second: u64) -> Self {
+        Rate { bytes_per_second: None, ops_per_second: Some(ops_per_second) }
+    }
+}
+
+/// Token bucket which may go into debt, so requests larger than the burst still pass
+struct Bucket {
+    rate: Option<u64>,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Option<u64>) -> Self {
+        Bucket { rate, tokens: rate.unwrap_or(0) as f64, last: Instant::now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(r) = self.rate {
+            let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * r as f64).min(r as f64);
+        }
+        self.last = now;
+    }
+
+    /// How long until the debt is paid off
+    fn wait_time(&self) -> Duration {
+        match self.rate {
+            Some(r) if self.tokens < 0.0 => Duration::from_secs_f64(-self.tokens / r.max(1) as f64),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn take(&mut self, n: u64) {
+        if self.rate.is_some() {
+            self.tokens -= n as f64;
+        }
+    }
+}
+
+struct Limiter {
+    bytes: Bucket,
+    ops: Bucket,
+}
+
+impl Limiter {
+    fn new(rate: Rate) -> Self {
+        Limiter { bytes: Bucket::new(rate.bytes_per_second), ops: Bucket::new(rate.ops_per_second) }
+    }
+}
+
+/// Rate-limits reads and writes of the wrapped object, e.g. to keep a background job from starving other users of a disk.
+///
+/// Reads and writes have separate `Rate`s. A call first waits until neither bucket is in debt,
+/// then takes an op token and, after completion, a token for each byte transferred;
+/// so a large request passes at once and the following ones wait it off.
+/// Waiting callers queue up behind the one sleeping. `Discard` counts as a write op without bytes,
+/// other traits are forwarded without limits.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{Combinators,Rate,ReadAt};
+///
+/// let data = vec![0u8; 1 << 20];
+/// // A scrub reading at most 100 MB/s in at most 1000 requests per second
+/// let r = (&data).throttled(Rate { bytes_per_second: Some(100_000_000), ops_per_second: Some(1000) });
+/// let mut buf = vec![0u8; 65536];
+/// for i in 0..16 {
+///     r.read_exact_at(&mut buf[..], i * 65536).unwrap();
+/// }
+/// ```
+pub struct Throttled<T> {
+    inner: T,
+    read: Mutex<Limiter>,
+    write: Mutex<Limiter>,
+}
+
+fn lock(limiter: &Mutex<Limiter>) -> MutexGuard<'_, Limiter> {
+    // Buckets are always consistent, so poisoning is harmless
+    limiter.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<T> Throttled<T> {
+    /// Wrap `inner` with limits for reads and for writes
+    pub fn new(inner: T, read: Rate, write: Rate) -> Self {
+        Throttled { inner, read: Mutex::new(Limiter::new(read)), write: Mutex::new(Limiter::new(write)) }
+    }
+
+    /// Change read limits, starting with full buckets
+    pub fn set_read_rate(&self, rate: Rate) {
+        *lock(&self.read) = Limiter::new(rate);
+    }
+
+    /// Change write limits, starting with full buckets
+    pub fn set_write_rate(&self, rate: Rate) {
+        *lock(&self.write) = Limiter::new(rate);
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Wait for tokens, then run `op` and pay for the bytes it transferred
+fn throttle(limiter: &Mutex<Limiter>, op: impl FnOnce() -> Result<usize>) -> Result<usize> {
+    {
+        let mut l = lock(limiter);
+        loop {
+            let now = Instant::now();
+            l.bytes.refill(now);
+            l.ops.refill(now);
+            let wait = l.bytes.wait_time().max(l.ops.wait_time());
+            if wait.is_zero() {
+                break;
+            }
+            std::thread::sleep(wait);
+        }
+        l.ops.take(1);
+    }
+    let n = op()?;
+    lock(limiter).bytes.take(n as u64);
+    Ok(n)
+}
+
+impl<T: ReadAt> ReadAt for Throttled<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        throttle(&self.read, || self.inner.read_at(buf, offset))
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        throttle(&self.read, || self.inner.read_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: WriteAt> WriteAt for Throttled<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        throttle(&self.write, || self.inner.write_at(buf, offset))
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        throttle(&self.write, || self.inner.write_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: SizeAt> SizeAt for Throttled<T> {
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+}
+
+impl<T: ResizeAt> ResizeAt for Throttled<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.inner.set_len(new_len)
+    }
+}
+
+impl<T: SyncAt> SyncAt for Throttled<T> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.sync_range(offset, len)
+    }
+}
+
+impl<T: Discard> Discard for Throttled<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        throttle(&self.write, || self.inner.punch_hole(offset, len).map(|()| 0)).map(drop)
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        throttle(&self.write, || self.inner.zero_range(offset, len).map(|()| 0)).map(drop)
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        throttle(&self.write, || self.inner.discard(offset, len).map(|()| 0)).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let data = vec![0u8; 100];
+        let t = Throttled::new(&data, Rate::bytes(1000), Rate::ops(20));
+        let start = Instant::now();
+        let mut buf = [0u8; 100];
+        // One second of burst, then half a second of debt
+        for _ in 0..15 {
+            t.read_exact_at(&mut buf[..], 0).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(350));
+
+        let v = Mutex::new(vec![0u8; 10]);
+        let t = Throttled::new(&v, Rate::UNLIMITED, Rate::ops(20));
+        let start = Instant::now();
+        for i in 0..30 {
+            t.write_all_at(&[i], 0).unwrap();
+            t.read_exact_at(&mut buf[..1], 0).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(350));
+        assert_eq!(v.lock().unwrap()[0], 29);
+    }
+}
@@ -0,0 +1,138 @@
+use super::BoxFuture;
+#[cfg(unix)]
+use super::{AsyncReadAt, AsyncWriteAt};
+#[cfg(windows)]
+use super::{AsyncReadAtMut, AsyncWriteAtMut};
+use std::io::{Error, Result};
+
+/// Get a separate `std::fs::File` handle for the same open file, to be moved to a blocking thread
+fn std_file(f: &tokio::fs::File) -> Result<std::fs::File> {
+    #[cfg(unix)]
+    let h = std::os::fd::AsFd::as_fd(f).try_clone_to_owned()?;
+    #[cfg(windows)]
+    let h = std::os::windows::io::AsHandle::as_handle(f).try_clone_to_owned()?;
+    Ok(std::fs::File::from(h))
+}
+
+async fn blocking<F, R>(f: F) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(x) => x,
+        Err(e) => Err(Error::other(e)),
+    }
+}
+
+async fn read_at(f: &tokio::fs::File, buf: &mut [u8], offset: u64) -> Result<usize> {
+    let f = std_file(f)?;
+    let len = buf.len();
+    let data = blocking(move || {
+        let mut data = vec![0; len];
+        #[cfg(unix)]
+        let n = crate::ReadAt::read_at(&f, &mut data[..], offset)?;
+        #[cfg(windows)]
+        let n = std::os::windows::fs::FileExt::seek_read(&f, &mut data[..], offset)?;
+        data.truncate(n);
+        Ok(data)
+    })
+    .await?;
+    buf[..data.len()].copy_from_slice(&data[..]);
+    Ok(data.len())
+}
+
+async fn write_at(f: &tokio::fs::File, buf: &[u8], offset: u64) -> Result<usize> {
+    let f = std_file(f)?;
+    let data = buf.to_vec();
+    blocking(move || {
+        #[cfg(unix)]
+        return crate::WriteAt::write_at(&f, &data[..], offset);
+        #[cfg(windows)]
+        return std::os::windows::fs::FileExt::seek_write(&f, &data[..], offset);
+    })
+    .await
+}
+
+#[cfg(unix)]
+async fn read_exact_at(f: &tokio::fs::File, buf: &mut [u8], offset: u64) -> Result<()> {
+    let f = std_file(f)?;
+    let len = buf.len();
+    let data = blocking(move || {
+        let mut data = vec![0; len];
+        crate::ReadAt::read_exact_at(&f, &mut data[..], offset)?;
+        Ok(data)
+    })
+    .await?;
+    buf.copy_from_slice(&data[..]);
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn write_all_at(f: &tokio::fs::File, buf: &[u8], offset: u64) -> Result<()> {
+    let f = std_file(f)?;
+    let data = buf.to_vec();
+    blocking(move || crate::WriteAt::write_all_at(&f, &data[..], offset)).await
+}
+
+/// Uses `spawn_blocking` with a duplicated file handle, so must be called within a Tokio runtime.
+///
+/// Data is copied via a temporary buffer.
+#[cfg(unix)]
+impl AsyncReadAt for tokio::fs::File {
+    fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(read_at(self, buf, offset))
+    }
+    fn read_exact_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(read_exact_at(self, buf, offset))
+    }
+}
+
+/// Uses `spawn_blocking` with a duplicated file handle, so must be called within a Tokio runtime.
+///
+/// Data is copied via a temporary buffer.
+#[cfg(unix)]
+impl AsyncWriteAt for tokio::fs::File {
+    fn write_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(write_at(self, buf, offset))
+    }
+    fn write_all_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(write_all_at(self, buf, offset))
+    }
+}
+
+/// Note that cursor is affected, like with `std::fs::File`. That why it's `AsyncReadAtMut` instead of `AsyncReadAt`
+#[cfg(windows)]
+impl AsyncReadAtMut for tokio::fs::File {
+    fn read_at<'a>(&'a mut self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(read_at(self, buf, offset))
+    }
+}
+
+/// Note that cursor is affected, like with `std::fs::File`. That why it's `AsyncWriteAtMut` instead of `AsyncWriteAt`
+#[cfg(windows)]
+impl AsyncWriteAtMut for tokio::fs::File {
+    fn write_at<'a>(&'a mut self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(write_at(self, buf, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsyncReadAtMut, AsyncWriteAtMut};
+
+    #[test]
+    fn tokio_file() {
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let mut f = tokio::fs::File::from_std(crate::testing::temp_file());
+
+            f.write_all_at(&[1, 2, 3], 4).await.unwrap();
+            let mut v = [9u8; 5];
+            f.read_exact_at(&mut v[..], 2).await.unwrap();
+            assert_eq!(v, [0, 0, 1, 2, 3]);
+            assert_eq!(f.read_at(&mut v[..], 6).await.unwrap(), 1);
+            assert_eq!(v[0], 3);
+        });
+    }
+}
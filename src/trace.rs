@@ -0,0 +1,344 @@
+use super::{Discard, IoOp, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// FNV-1a, to tell whether data differs between recording and replay
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ *b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// One call recorded by `Recorder`.
+///
+/// The text form, produced by `Display` and accepted by `FromStr`, is one line of space-separated fields:
+/// start time in microseconds, `op` name, `offset`, `len`, `done` (`-` for errors) and optionally `hash` in hex,
+/// e.g. `1520 read 4096 512 512 9f3a04fe11c2b6d1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// When the call started, since the creation of the `Recorder`
+    pub start: Duration,
+    /// Kind of call
+    pub op: IoOp,
+    /// Offset of reads, writes, `sync_range` and discards; `0` for others
+    pub offset: u64,
+    /// Requested length of reads, writes, `sync_range` and discards, new length of `set_len`; `0` for others
+    pub len: u64,
+    /// Bytes transferred by reads and writes, size returned by `size`, `0` for others; `None` if the call failed
+    pub done: Option<u64>,
+    /// Hash of the transferred data, if the `Recorder` hashes data
+    pub hash: Option<u64>,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {} ", self.start.as_micros(), self.op.name(), self.offset, self.len)?;
+        match self.done {
+            Some(n) => write!(f, "{}", n)?,
+            None => f.write_str("-")?,
+        }
+        if let Some(h) = self.hash {
+            write!(f, " {:016x}", h)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TraceEntry {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let bad = || Error::new(ErrorKind::InvalidData, format!("malformed trace line: {:?}", s));
+        let mut fields = s.split_ascii_whitespace();
+        let mut next = || fields.next().ok_or_else(bad);
+        let start = Duration::from_micros(next()?.parse().map_err(|_| bad())?);
+        let op = next()?;
+        let op = *IoOp::ALL.iter().find(|x| x.name() == op).ok_or_else(bad)?;
+        let offset = next()?.parse().map_err(|_| bad())?;
+        let len = next()?.parse().map_err(|_| bad())?;
+        let done = match next()? {
+            "-" => None,
+            x => Some(x.parse().map_err(|_| bad())?),
+        };
+        let hash = match fields.next() {
+            Some(x) => Some(u64::from_str_radix(x, 16).map_err(|_| bad())?),
+            None => None,
+        };
+        if fields.next().is_some() {
+            return Err(bad());
+        }
+        Ok(TraceEntry { start, op, offset, len, done, hash })
+    }
+}
+
+/// Records every call to the wrapped object as a `TraceEntry`, to be saved and later fed to `replay`.
+///
+/// With `hash_data`, reads and writes also record a hash of the data, so that `replay` can detect reads returning
+/// something else than during recording. The trace grows in memory until taken with `take_trace`.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{replay,Recorder,ReadAt,TraceEntry,WriteAt};
+/// use std::sync::Mutex;
+///
+/// let dev = Recorder::new(Mutex::new(vec![0u8; 4096])).hash_data(true);
+/// dev.write_all_at(&[1, 2, 3], 100).unwrap();
+/// let mut buf = [0u8; 3];
+/// dev.read_exact_at(&mut buf[..], 100).unwrap();
+///
+/// // Save as text and load back
+/// let text: String = dev.take_trace().iter().map(|e| format!("{}\n", e)).collect();
+/// let trace: Vec<TraceEntry> = text.lines().map(|l| l.parse().unwrap()).collect();
+///
+/// // Replay against another backend: writes carry zeros, so the read no longer matches
+/// let copy = Mutex::new(vec![0u8; 4096]);
+/// let report = replay(&trace, &copy, false).unwrap();
+/// assert_eq!((report.ops, report.mismatches), (2, 1));
+/// ```
+pub struct Recorder<T> {
+    inner: T,
+    start: Instant,
+    hash_data: bool,
+    trace: Mutex<Vec<TraceEntry>>,
+}
+
+impl<T> Recorder<T> {
+    /// Wrap `inner`, starting the clock for `TraceEntry::start`
+    pub fn new(inner: T) -> Self {
+        Recorder { inner, start: Instant::now(), hash_data: false, trace: Mutex::new(vec![]) }
+    }
+
+    /// Also record hashes of data read and written
+    pub fn hash_data(mut self, hash_data: bool) -> Self {
+        self.hash_data = hash_data;
+        self
+    }
+
+    /// Copy of the trace so far
+    pub fn trace(&self) -> Vec<TraceEntry> {
+        self.lock().clone()
+    }
+
+    /// Take the trace so far, leaving an empty one
+    pub fn take_trace(&self) -> Vec<TraceEntry> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<TraceEntry>> {
+        self.trace.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run `f` and record it. On success `f` also returns the `done` field and the hash of data.
+    fn record<R>(
+        &self,
+        op: IoOp,
+        offset: u64,
+        len: u64,
+        f: impl FnOnce() -> Result<(R, u64, Option<u64>)>,
+    ) -> Result<R> {
+        let start = self.start.elapsed();
+        let (result, done, hash) = match f() {
+            Ok((x, done, hash)) => (Ok(x), Some(done), hash),
+            Err(e) => (Err(e), None, None),
+        };
+        self.lock().push(TraceEntry { start, op, offset, len, done, hash });
+        result
+    }
+
+    fn hash(&self, data: &[u8]) -> Option<u64> {
+        if self.hash_data {
+            Some(fnv1a(data))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: ReadAt> ReadAt for Recorder<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.record(IoOp::Read, offset, buf.len() as u64, || {
+            let n = self.inner.read_at(buf, offset)?;
+            Ok((n, n as u64, self.hash(&buf[..n])))
+        })
+    }
+}
+
+impl<T: WriteAt> WriteAt for Recorder<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.record(IoOp::Write, offset, buf.len() as u64, || {
+            let n = self.inner.write_at(buf, offset)?;
+            Ok((n, n as u64, self.hash(&buf[..n])))
+        })
+    }
+}
+
+/// Fields of a successful call that transfers no data
+fn nothing(r: Result<()>) -> Result<((), u64, Option<u64>)> {
+    r.map(|()| ((), 0, None))
+}
+
+impl<T: SizeAt> SizeAt for Recorder<T> {
+    fn size(&self) -> Result<u64> {
+        self.record(IoOp::Size, 0, 0, || self.inner.size().map(|n| (n, n, None)))
+    }
+}
+
+impl<T: ResizeAt> ResizeAt for Recorder<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.record(IoOp::Resize, 0, new_len, || nothing(self.inner.set_len(new_len)))
+    }
+}
+
+impl<T: SyncAt> SyncAt for Recorder<T> {
+    fn flush(&self) -> Result<()> {
+        self.record(IoOp::Sync, 0, 0, || nothing(self.inner.flush()))
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.record(IoOp::Sync, 0, 0, || nothing(self.inner.sync_all()))
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.record(IoOp::Sync, 0, 0, || nothing(self.inner.sync_data()))
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.record(IoOp::Sync, offset, len, || nothing(self.inner.sync_range(offset, len)))
+    }
+}
+
+impl<T: Discard> Discard for Recorder<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.record(IoOp::Discard, offset, len, || nothing(self.inner.punch_hole(offset, len)))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.record(IoOp::Discard, offset, len, || nothing(self.inner.zero_range(offset, len)))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.record(IoOp::Discard, offset, len, || nothing(self.inner.discard(offset, len)))
+    }
+}
+
+/// Outcome of `replay`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Calls issued
+    pub ops: u64,
+    /// Bytes read and written
+    pub bytes: u64,
+    /// Entries not replayed: discards and calls that failed during recording
+    pub skipped: u64,
+    /// Reads that returned a different length, or different data when hashes were recorded
+    pub mismatches: u64,
+    /// Wall time of the replay
+    pub elapsed: Duration,
+}
+
+/// Issue the calls of `trace` against `device`, one at a time in order, to reproduce a workload.
+///
+/// Writes carry zeros, as the trace lacks the data; syncs other than `sync_range` become `sync_data`. With `timing`, each call waits until its recorded start time
+/// (relative to the replay start), otherwise calls go back to back. Discards, which not all backends support,
+/// and calls that failed during recording are skipped. The first error of a replayed call is returned.
+pub fn replay<D>(trace: &[TraceEntry], device: &D, timing: bool) -> Result<ReplayReport>
+where
+    D: ReadAt + WriteAt + SizeAt + ResizeAt + SyncAt + ?Sized,
+{
+    let start = Instant::now();
+    let mut report = ReplayReport::default();
+    let mut buf = Vec::new();
+    for e in trace {
+        let done = match e.done {
+            Some(x) if e.op != IoOp::Discard => x,
+            _ => {
+                report.skipped += 1;
+                continue;
+            }
+        };
+        if timing {
+            if let Some(wait) = e.start.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        report.ops += 1;
+        let len = usize::try_from(e.len).map_err(|_| Error::new(ErrorKind::InvalidData, "trace entry too long"))?;
+        match e.op {
+            IoOp::Read => {
+                buf.clear();
+                buf.resize(len, 0);
+                let n = device.read_at(&mut buf, e.offset)?;
+                report.bytes += n as u64;
+                if n as u64 != done || e.hash.is_some_and(|h| h != fnv1a(&buf[..n])) {
+                    report.mismatches += 1;
+                }
+            }
+            IoOp::Write => {
+                buf.clear();
+                buf.resize(done as usize, 0);
+                device.write_all_at(&buf, e.offset)?;
+                report.bytes += done;
+            }
+            IoOp::Size => drop(device.size()?),
+            IoOp::Resize => device.set_len(e.len)?,
+            IoOp::Sync if e.len > 0 => device.sync_range(e.offset, e.len)?,
+            IoOp::Sync => device.sync_data()?,
+            IoOp::Discard => unreachable!(),
+        }
+    }
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_form() {
+        let e = TraceEntry {
+            start: Duration::from_micros(1520),
+            op: IoOp::Read,
+            offset: 4096,
+            len: 512,
+            done: Some(512),
+            hash: Some(0x9f3a04fe11c2b6d1),
+        };
+        assert_eq!(e.to_string(), "1520 read 4096 512 512 9f3a04fe11c2b6d1");
+        assert_eq!(e.to_string().parse::<TraceEntry>().unwrap(), e);
+        let e = TraceEntry { op: IoOp::Resize, done: None, hash: None, ..e };
+        assert_eq!(e.to_string(), "1520 resize 4096 512 -");
+        assert_eq!(e.to_string().parse::<TraceEntry>().unwrap(), e);
+        assert!("1 read 2 3".parse::<TraceEntry>().is_err());
+        assert!("1 seek 2 3 4".parse::<TraceEntry>().is_err());
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let dev = Recorder::new(Mutex::new(vec![5u8; 100])).hash_data(true);
+        let mut buf = [0u8; 10];
+        dev.read_exact_at(&mut buf[..], 95).unwrap_err();
+        dev.set_len(200).unwrap();
+        dev.write_all_at(&[0; 10], 150).unwrap();
+        dev.read_exact_at(&mut buf[..], 150).unwrap();
+        dev.sync_all().unwrap();
+        let trace = dev.trace();
+        assert_eq!(trace.len(), 6);
+        assert_eq!((trace[0].done, trace[1].done), (Some(5), Some(0)));
+
+        let copy = Recorder::new(Mutex::new(vec![5u8; 100]));
+        let report = replay(&trace, &copy, false).unwrap();
+        assert_eq!((report.ops, report.bytes, report.skipped, report.mismatches), (6, 25, 0, 0));
+        let ops: Vec<_> = copy.trace().iter().map(|e| (e.op, e.offset, e.len)).collect();
+        let orig: Vec<_> = trace.iter().map(|e| (e.op, e.offset, e.len)).collect();
+        assert_eq!(ops, orig);
+        assert_eq!(copy.size().unwrap(), 200);
+    }
+}
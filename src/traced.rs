@@ -0,0 +1,202 @@
+use super::{Discard, ReadAt, ResizeAt, SizeAt, SyncAt, WriteAt};
+use std::fmt::Debug;
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::time::Instant;
+use tracing::field::Empty;
+
+/// Emits a `tracing` span for each call to the wrapped object and an event when it completes.
+///
+/// Spans are named `io`, at `TRACE` level, with fields `device` (see `name`), `op` (method name)
+/// and, where applicable, `offset` and `len`.
+/// Completion events have `elapsed_us` and either `result` (`TRACE` level) or `error` and `kind` (`DEBUG` level).
+/// Spans of inner layers nest into those of outer ones, so each layer of a stack can get its own `Traced` and name
+/// to see how a request gets split and where it fails.
+/// Requires `tracing` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,SubRange,Traced};
+///
+/// let data = vec![0u8; 4096];
+/// let part = Traced::new(SubRange::new(Traced::new(&data).name("disk"), 1024, 1024)).name("partition");
+/// let mut buf = [0u8; 512];
+/// part.read_exact_at(&mut buf[..], 0).unwrap();
+/// ```
+pub struct Traced<T> {
+    inner: T,
+    name: Option<String>,
+}
+
+impl<T> Traced<T> {
+    /// Wrap `inner`, without a `device` field
+    pub fn new(inner: T) -> Self {
+        Traced { inner, name: None }
+    }
+
+    /// Set `device` field of spans to tell layers and devices apart
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn trace<R: Debug>(
+        &self,
+        op: &'static str,
+        offset: Option<u64>,
+        len: Option<u64>,
+        f: impl FnOnce() -> Result<R>,
+    ) -> Result<R> {
+        let span = tracing::trace_span!("io", device = Empty, op, offset = Empty, len = Empty);
+        if let Some(ref name) = self.name {
+            span.record("device", name.as_str());
+        }
+        if let Some(offset) = offset {
+            span.record("offset", offset);
+        }
+        if let Some(len) = len {
+            span.record("len", len);
+        }
+        let _guard = span.enter();
+        let start = Instant::now();
+        let result = f();
+        let elapsed_us = start.elapsed().as_micros() as u64;
+        match result {
+            Ok(ref x) => tracing::trace!(elapsed_us, result = ?x),
+            Err(ref e) => tracing::debug!(elapsed_us, error = %e, kind = ?e.kind()),
+        }
+        result
+    }
+}
+
+fn total_len<B: std::ops::Deref<Target = [u8]>>(bufs: &[B]) -> u64 {
+    bufs.iter().map(|b| b.len() as u64).sum()
+}
+
+impl<T: ReadAt> ReadAt for Traced<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.trace("read_at", Some(offset), Some(buf.len() as u64), || self.inner.read_at(buf, offset))
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let len = total_len(bufs);
+        self.trace("read_vectored_at", Some(offset), Some(len), || self.inner.read_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: WriteAt> WriteAt for Traced<T> {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.trace("write_at", Some(offset), Some(buf.len() as u64), || self.inner.write_at(buf, offset))
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        self.trace("write_vectored_at", Some(offset), Some(total_len(bufs)), || self.inner.write_vectored_at(bufs, offset))
+    }
+}
+
+impl<T: SizeAt> SizeAt for Traced<T> {
+    fn size(&self) -> Result<u64> {
+        self.trace("size", None, None, || self.inner.size())
+    }
+}
+
+impl<T: ResizeAt> ResizeAt for Traced<T> {
+    fn set_len(&self, new_len: u64) -> Result<()> {
+        self.trace("set_len", None, Some(new_len), || self.inner.set_len(new_len))
+    }
+}
+
+impl<T: SyncAt> SyncAt for Traced<T> {
+    fn flush(&self) -> Result<()> {
+        self.trace("flush", None, None, || self.inner.flush())
+    }
+    fn sync_all(&self) -> Result<()> {
+        self.trace("sync_all", None, None, || self.inner.sync_all())
+    }
+    fn sync_data(&self) -> Result<()> {
+        self.trace("sync_data", None, None, || self.inner.sync_data())
+    }
+    fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.trace("sync_range", Some(offset), Some(len), || self.inner.sync_range(offset, len))
+    }
+}
+
+impl<T: Discard> Discard for Traced<T> {
+    fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.trace("punch_hole", Some(offset), Some(len), || self.inner.punch_hole(offset, len))
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> Result<()> {
+        self.trace("zero_range", Some(offset), Some(len), || self.inner.zero_range(offset, len))
+    }
+    fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        self.trace("discard", Some(offset), Some(len), || self.inner.discard(offset, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DenyWrites;
+    use std::fmt::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Writes span fields and events as lines of text
+    struct Log(Arc<Mutex<String>>);
+
+    impl Visit for Log {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            write!(self.0.lock().unwrap(), " {}={:?}", field.name(), value).unwrap();
+        }
+    }
+
+    impl Subscriber for Log {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.0.lock().unwrap().push_str("\nspan");
+            span.record(&mut Log(self.0.clone()));
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut Log(self.0.clone()));
+        }
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            self.0.lock().unwrap().push_str("\nevent");
+            event.record(&mut Log(self.0.clone()));
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn fields() {
+        let log = Arc::new(Mutex::new(String::new()));
+        let data = vec![0u8; 10];
+        let t = Traced::new(DenyWrites::new(&data)).name("disk");
+        tracing::subscriber::with_default(Log(log.clone()), || {
+            let mut buf = [0u8; 4];
+            assert_eq!(t.read_at(&mut buf[..], 8).unwrap(), 2);
+            t.write_at(&buf[..], 0).unwrap_err();
+        });
+        let log = log.lock().unwrap();
+        let lines: Vec<&str> = log.lines().skip(1).collect();
+        assert_eq!(lines.len(), 4, "{}", log);
+        assert_eq!(lines[0], "span op=\"read_at\" device=\"disk\" offset=8 len=4");
+        assert!(lines[1].starts_with("event elapsed_us=") && lines[1].ends_with(" result=2"));
+        assert!(lines[2].starts_with("span op=\"write_at\""));
+        assert!(lines[3].contains(" kind=PermissionDenied"), "{}", log);
+    }
+}
@@ -0,0 +1,410 @@
+// Talking to the ublk driver needs raw `io_uring` commands pointing at buffers, and a shared mapping of I/O descriptors
+#![allow(unsafe_code)]
+
+use super::{Discard, ReadAt, SizeAt, SyncAt, WriteAt};
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use rustix::io::Errno;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Definitions of `linux/ublk_cmd.h`
+const CMD_ADD_DEV: u8 = 0x04;
+const CMD_DEL_DEV: u8 = 0x05;
+const CMD_START_DEV: u8 = 0x06;
+const CMD_STOP_DEV: u8 = 0x07;
+const CMD_SET_PARAMS: u8 = 0x08;
+const IO_FETCH_REQ: u8 = 0x20;
+const IO_COMMIT_AND_FETCH_REQ: u8 = 0x21;
+
+const F_CMD_IOCTL_ENCODE: u64 = 1 << 6;
+
+const IO_OP_READ: u32 = 0;
+const IO_OP_WRITE: u32 = 1;
+const IO_OP_FLUSH: u32 = 2;
+const IO_OP_DISCARD: u32 = 3;
+const IO_OP_WRITE_ZEROES: u32 = 5;
+const IO_F_FUA: u32 = 1 << 13;
+const IO_F_NOUNMAP: u32 = 1 << 15;
+
+const ATTR_READ_ONLY: u32 = 1 << 0;
+const ATTR_VOLATILE_CACHE: u32 = 1 << 2;
+const ATTR_FUA: u32 = 1 << 3;
+const PARAM_TYPE_BASIC: u32 = 1 << 0;
+const PARAM_TYPE_DISCARD: u32 = 1 << 1;
+
+/// `sizeof(struct ublksrv_ctrl_cmd)`
+const CTRL_CMD_LEN: usize = 32;
+/// `sizeof(struct ublksrv_ctrl_dev_info)`
+const DEV_INFO_LEN: usize = 64;
+/// `sizeof(struct ublksrv_io_cmd)`
+const IO_CMD_LEN: usize = 16;
+/// Length of `struct ublk_params` up to and including `discard`
+const PARAMS_LEN: usize = 60;
+
+/// Largest request, which is also the size of each buffer
+const MAX_IO: usize = 512 << 10;
+/// How often the queue thread checks whether starting the device failed
+const POLL_INTERVAL: types::Timespec = types::Timespec::new().sec(1);
+
+/// `struct ublksrv_io_desc`, as shared by the driver
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct IoDesc {
+    op_flags: u32,
+    nr_sectors: u32,
+    start_sector: u64,
+    addr: u64,
+}
+
+/// Exports an object as a Linux block device `/dev/ublkbN` using the `ublk` driver, like a loop device of it.
+///
+/// Needs `ublk_drv` kernel module with ioctl-encoded commands (Linux 6.5 or later) and `CAP_SYS_ADMIN`.
+/// The device size is that of the object at start, rounded down to the logical block size.
+/// Requests are handled one at a time by a single queue; the kernel gets told the device has a volatile cache,
+/// so it sends flushes, which map to `sync_data`, and writes with FUA, which also get `sync_range`.
+/// Discard and write-zeroes requests go to `Discard`.
+/// Requires `ublk` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{stop_ublk,ResizeAtMut,SparseMem,UblkServer};
+///
+/// let mut mem = SparseMem::new();
+/// mem.set_len(1 << 30).unwrap();
+/// let server = UblkServer::new(std::sync::Mutex::new(mem)).queue_depth(64);
+/// server.run(|id| {
+///     println!("/dev/ublkb{} is ready", id);
+///     std::thread::spawn(move || {
+///         std::thread::sleep(std::time::Duration::from_secs(60));
+///         stop_ublk(id).unwrap();
+///     });
+/// }).unwrap();
+/// ```
+pub struct UblkServer<T> {
+    device: T,
+    queue_depth: u16,
+    block_size: u32,
+    read_only: bool,
+}
+
+impl<T: ReadAt + WriteAt + SizeAt + SyncAt + Discard> UblkServer<T> {
+    /// Wrap `device` to be exported with 512-byte blocks and 32 requests in flight
+    pub fn new(device: T) -> Self {
+        UblkServer { device, queue_depth: 32, block_size: 512, read_only: false }
+    }
+
+    /// Set how many requests the kernel can queue, from 1 to 4096
+    pub fn queue_depth(mut self, queue_depth: u16) -> Self {
+        self.queue_depth = queue_depth;
+        self
+    }
+
+    /// Set the logical block size, a power of two from 512 up to the page size
+    pub fn logical_block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Make the block device read-only
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Get a reference to the exported object
+    pub fn get_ref(&self) -> &T {
+        &self.device
+    }
+
+    /// Get back the exported object
+    pub fn into_inner(self) -> T {
+        self.device
+    }
+
+    /// Create and start the block device, calling `started` with its number `N` once it runs.
+    ///
+    /// Serves requests until the device gets stopped by `stop_ublk` (or e.g. `ublk del`), then deletes it.
+    pub fn run<F: FnOnce(u32)>(&self, started: F) -> Result<()>
+    where
+        T: Sync,
+    {
+        if !(1..=4096).contains(&self.queue_depth) {
+            return Err(Error::new(ErrorKind::InvalidInput, "ublk queue depth must be from 1 to 4096"));
+        }
+        if !self.block_size.is_power_of_two() || self.block_size < 512 || self.block_size as usize > rustix::param::page_size() {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid ublk logical block size"));
+        }
+        let size = self.device.size()? & !(u64::from(self.block_size) - 1);
+        let mut ctrl = Control::open()?;
+
+        let mut info = [0u8; DEV_INFO_LEN];
+        info[0..2].copy_from_slice(&1u16.to_ne_bytes());
+        info[2..4].copy_from_slice(&self.queue_depth.to_ne_bytes());
+        info[8..12].copy_from_slice(&(MAX_IO as u32).to_ne_bytes());
+        info[12..16].copy_from_slice(&u32::MAX.to_ne_bytes());
+        info[24..32].copy_from_slice(&F_CMD_IOCTL_ENCODE.to_ne_bytes());
+        ctrl.command(CMD_ADD_DEV, u32::MAX, 0, &mut info)?;
+        let id = u32::from_ne_bytes(info[12..16].try_into().unwrap());
+
+        let result = self.run_added(&mut ctrl, id, size, started);
+        // Deleting waits for the character device to be closed, which `run_added` did
+        let deleted = ctrl.command(CMD_DEL_DEV, id, 0, &mut []);
+        result.and(deleted)
+    }
+
+    fn run_added<F: FnOnce(u32)>(&self, ctrl: &mut Control, id: u32, size: u64, started: F) -> Result<()>
+    where
+        T: Sync,
+    {
+        let shift = self.block_size.trailing_zeros() as u8;
+        let mut params = [0u8; PARAMS_LEN];
+        params[0..4].copy_from_slice(&(PARAMS_LEN as u32).to_ne_bytes());
+        params[4..8].copy_from_slice(&(PARAM_TYPE_BASIC | PARAM_TYPE_DISCARD).to_ne_bytes());
+        let attrs = ATTR_VOLATILE_CACHE | ATTR_FUA | if self.read_only { ATTR_READ_ONLY } else { 0 };
+        params[8..12].copy_from_slice(&attrs.to_ne_bytes());
+        params[12..16].copy_from_slice(&[shift; 4]);
+        params[16..20].copy_from_slice(&((MAX_IO >> 9) as u32).to_ne_bytes());
+        params[24..32].copy_from_slice(&(size >> 9).to_ne_bytes());
+        // discard_granularity, max_discard_sectors, max_write_zeroes_sectors and max_discard_segments
+        params[44..48].copy_from_slice(&self.block_size.to_ne_bytes());
+        params[48..52].copy_from_slice(&(u32::MAX >> 9).to_ne_bytes());
+        params[52..56].copy_from_slice(&(u32::MAX >> 9).to_ne_bytes());
+        params[56..58].copy_from_slice(&1u16.to_ne_bytes());
+        ctrl.command(CMD_SET_PARAMS, id, 0, &mut params)?;
+
+        let cdev = open_char_device(id)?;
+        let depth = usize::from(self.queue_depth);
+        let page = rustix::param::page_size();
+        let descs = Mapping::new(&cdev, (depth * std::mem::size_of::<IoDesc>()).div_ceil(page) * page)?;
+        let mut bufs: Vec<Vec<u8>> = (0..depth).map(|_| vec![0u8; MAX_IO]).collect();
+        let mut ring = IoUring::new(depth.next_power_of_two() as u32)?;
+        // Fetching here, as starting would wait forever if the queue thread failed before that
+        for (tag, buf) in bufs.iter_mut().enumerate() {
+            push_io(&mut ring, &cdev, IO_FETCH_REQ, tag as u16, -1, buf.as_mut_ptr())?;
+        }
+        ring.submit()?;
+
+        let start_failed = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            let queue = s.spawn(|| self.serve_queue(&cdev, &descs, ring, bufs, &start_failed));
+            let start = ctrl.command(CMD_START_DEV, id, u64::from(std::process::id()), &mut []);
+            match start {
+                Ok(()) => started(id),
+                Err(_) => start_failed.store(true, Ordering::Relaxed),
+            }
+            let served = queue.join().unwrap_or_else(|_| Err(Error::other("ublk queue thread panicked")));
+            // The device may still run if serving failed
+            let _ = ctrl.command(CMD_STOP_DEV, id, 0, &mut []);
+            start.and(served)
+        })
+    }
+
+    /// Handle requests until all tags get aborted (or starting the device fails)
+    fn serve_queue(
+        &self,
+        cdev: &File,
+        descs: &Mapping,
+        mut ring: IoUring,
+        mut bufs: Vec<Vec<u8>>,
+        start_failed: &AtomicBool,
+    ) -> Result<()> {
+        let mut active = bufs.len();
+        let result = (|| {
+            while active > 0 {
+                let args = types::SubmitArgs::new().timespec(&POLL_INTERVAL);
+                match ring.submitter().submit_with_args(1, &args) {
+                    Ok(_) => {}
+                    Err(e) if e.raw_os_error() == Some(Errno::TIME.raw_os_error()) => {}
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+                if start_failed.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                let done: Vec<(u64, i32)> = ring.completion().map(|c| (c.user_data(), c.result())).collect();
+                for (tag, res) in done {
+                    let tag = tag as usize;
+                    // `UBLK_IO_RES_ABORT` when the device is stopping
+                    if res < 0 {
+                        active -= 1;
+                        continue;
+                    }
+                    let result = self.handle(descs.desc(tag), &mut bufs[tag]);
+                    push_io(&mut ring, cdev, IO_COMMIT_AND_FETCH_REQ, tag as u16, result, bufs[tag].as_mut_ptr())?;
+                }
+            }
+            Ok(())
+        })();
+        drop(ring);
+        if result.is_err() {
+            // The kernel may still fill buffers of pending requests until the device is stopped
+            std::mem::forget(bufs);
+        }
+        result
+    }
+
+    /// Serve one request, giving the `result` to commit
+    fn handle(&self, desc: IoDesc, buf: &mut [u8]) -> i32 {
+        let op = desc.op_flags & 0xFF;
+        let offset = desc.start_sector << 9;
+        let len = u64::from(desc.nr_sectors) << 9;
+        let result = match op {
+            IO_OP_READ | IO_OP_WRITE if len > buf.len() as u64 => Err(Error::from_raw_os_error(Errno::INVAL.raw_os_error())),
+            IO_OP_READ => read_full(&self.device, &mut buf[..len as usize], offset),
+            _ if op != IO_OP_FLUSH && self.read_only => Err(Error::from_raw_os_error(Errno::ROFS.raw_os_error())),
+            IO_OP_WRITE => self.device.write_all_at(&buf[..len as usize], offset).and_then(|()| {
+                if desc.op_flags & IO_F_FUA != 0 {
+                    self.device.sync_range(offset, len)
+                } else {
+                    Ok(())
+                }
+            }),
+            IO_OP_FLUSH => self.device.sync_data(),
+            IO_OP_DISCARD => self.device.discard(offset, len),
+            IO_OP_WRITE_ZEROES if desc.op_flags & IO_F_NOUNMAP != 0 => self.device.zero_range(offset, len),
+            IO_OP_WRITE_ZEROES => self.device.punch_hole(offset, len),
+            _ => Err(Error::from_raw_os_error(Errno::OPNOTSUPP.raw_os_error())),
+        };
+        match result {
+            Ok(()) if op == IO_OP_READ || op == IO_OP_WRITE => len as i32,
+            Ok(()) => 0,
+            Err(e) => -errno(&e),
+        }
+    }
+}
+
+/// Stop block device `/dev/ublkbN`, making `UblkServer::run` serving it return
+pub fn stop_ublk(id: u32) -> Result<()> {
+    Control::open()?.command(CMD_STOP_DEV, id, 0, &mut [])
+}
+
+/// `/dev/ublk-control` and a ring for sending commands to it
+struct Control {
+    file: File,
+    ring: IoUring<squeue::Entry128, cqueue::Entry>,
+}
+
+impl Control {
+    fn open() -> Result<Self> {
+        let file = std::fs::OpenOptions::new().read(true).write(true).open("/dev/ublk-control")?;
+        let ring = IoUring::builder().build(4)?;
+        Ok(Control { file, ring })
+    }
+
+    /// Send a control command with `buf` as its `addr` and `len`, waiting for completion
+    fn command(&mut self, nr: u8, id: u32, data: u64, buf: &mut [u8]) -> Result<()> {
+        let mut cmd = [0u8; 80];
+        cmd[0..4].copy_from_slice(&id.to_ne_bytes());
+        cmd[4..6].copy_from_slice(&u16::MAX.to_ne_bytes());
+        cmd[6..8].copy_from_slice(&(buf.len() as u16).to_ne_bytes());
+        if !buf.is_empty() {
+            cmd[8..16].copy_from_slice(&(buf.as_mut_ptr() as u64).to_ne_bytes());
+        }
+        cmd[16..24].copy_from_slice(&data.to_ne_bytes());
+        let op = rustix::ioctl::opcode::read_write::<[u8; CTRL_CMD_LEN]>(b'u', nr);
+        let entry = opcode::UringCmd80::new(types::Fd(self.file.as_raw_fd()), op).cmd(cmd).build();
+        // `buf` outlives the command, as it is waited for here
+        unsafe { self.ring.submission().push(&entry) }.map_err(|_| Error::other("ublk control ring is full"))?;
+        self.ring.submit_and_wait(1)?;
+        let res = self.ring.completion().next().map(|c| c.result()).ok_or_else(|| Error::other("no ublk completion"))?;
+        if res < 0 {
+            return Err(Error::from_raw_os_error(-res));
+        }
+        Ok(())
+    }
+}
+
+fn push_io(ring: &mut IoUring, cdev: &File, nr: u8, tag: u16, result: i32, buf: *mut u8) -> Result<()> {
+    let mut cmd = [0u8; IO_CMD_LEN];
+    cmd[2..4].copy_from_slice(&tag.to_ne_bytes());
+    cmd[4..8].copy_from_slice(&result.to_ne_bytes());
+    cmd[8..16].copy_from_slice(&(buf as u64).to_ne_bytes());
+    let op = rustix::ioctl::opcode::read_write::<[u8; IO_CMD_LEN]>(b'u', nr);
+    let entry = opcode::UringCmd16::new(types::Fd(cdev.as_raw_fd()), op).cmd(cmd).build().user_data(u64::from(tag));
+    // Buffers live as long as requests are pending, see `serve_queue`.
+    // The ring has an entry for each tag and each tag has at most one command pending.
+    unsafe { ring.submission().push(&entry) }.map_err(|_| Error::other("ublk queue ring is full"))
+}
+
+/// `/dev/ublkcN`, waiting a bit for udev to create it
+fn open_char_device(id: u32) -> Result<File> {
+    let path = format!("/dev/ublkc{}", id);
+    let mut tries = 0;
+    loop {
+        match std::fs::OpenOptions::new().read(true).write(true).open(&path) {
+            Err(e) if e.kind() == ErrorKind::NotFound && tries < 50 => {
+                tries += 1;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            x => return x,
+        }
+    }
+}
+
+/// Read-only shared mapping of the I/O descriptors of the first queue
+struct Mapping {
+    ptr: *mut std::ffi::c_void,
+    len: usize,
+}
+
+// Only read with volatile reads, by the queue thread
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(cdev: &File, len: usize) -> Result<Self> {
+        use rustix::mm::{mmap, MapFlags, ProtFlags};
+        // A fresh mapping of the whole area the driver expects
+        let ptr = unsafe { mmap(std::ptr::null_mut(), len, ProtFlags::READ, MapFlags::SHARED | MapFlags::POPULATE, cdev, 0)? };
+        Ok(Mapping { ptr, len })
+    }
+
+    fn desc(&self, tag: usize) -> IoDesc {
+        assert!((tag + 1) * std::mem::size_of::<IoDesc>() <= self.len);
+        // The driver fills the descriptor before completing the fetch of its tag
+        unsafe { std::ptr::read_volatile((self.ptr as *const IoDesc).add(tag)) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // Nothing refers into the mapping anymore
+        let _ = unsafe { rustix::mm::munmap(self.ptr, self.len) };
+    }
+}
+
+fn read_full<T: ReadAt + ?Sized>(device: &T, buf: &mut [u8], offset: u64) -> Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match device.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    // Past the end of a shrunk object
+    buf[filled..].fill(0);
+    Ok(())
+}
+
+/// `errno` value to report for `e`
+fn errno(e: &Error) -> i32 {
+    if let Some(x) = e.raw_os_error() {
+        return x;
+    }
+    match e.kind() {
+        ErrorKind::NotFound => Errno::NOENT,
+        ErrorKind::PermissionDenied => Errno::ACCESS,
+        ErrorKind::InvalidInput => Errno::INVAL,
+        ErrorKind::WriteZero => Errno::NOSPC,
+        ErrorKind::Unsupported => Errno::OPNOTSUPP,
+        ErrorKind::OutOfMemory => Errno::NOMEM,
+        ErrorKind::TimedOut => Errno::TIMEDOUT,
+        _ => Errno::IO,
+    }
+    .raw_os_error()
+}
@@ -0,0 +1,374 @@
+// Buffers given to the kernel must stay valid until completion, which the compiler can't check
+#![allow(unsafe_code)]
+
+use super::{AsyncReadAt, AsyncWriteAt, BoxFuture};
+use io_uring::{opcode, squeue, types, IoUring};
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{Error, Result};
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+/// `user_data` of the entry asking the completion thread to finish
+const SHUTDOWN: u64 = u64::MAX;
+
+struct Op {
+    /// Owned by the op, not by the future, so dropping the future before completion is sound
+    buf: Vec<u8>,
+    result: Option<i32>,
+    waker: Option<Waker>,
+    /// The future was dropped, so the completion thread just discards the result
+    abandoned: bool,
+}
+
+#[derive(Default)]
+struct State {
+    ops: HashMap<u64, Op>,
+    next_id: u64,
+}
+
+struct Shared {
+    ring: IoUring,
+    /// Also serializes access to the submission queue
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // State is not left inconsistent on panics
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Push an entry made by `build` from the op's buffer pointer and length for each of `bufs`, then submit them all at once
+    fn submit<F>(&self, bufs: Vec<Vec<u8>>, build: F) -> Vec<Result<u64>>
+    where
+        F: Fn(usize, *mut u8, u32) -> squeue::Entry,
+    {
+        let mut st = self.lock();
+        let mut ids = Vec::with_capacity(bufs.len());
+        for (i, buf) in bufs.into_iter().enumerate() {
+            let id = st.next_id;
+            st.next_id += 1;
+            let mut op = Op {
+                buf,
+                result: None,
+                waker: None,
+                abandoned: false,
+            };
+            let len = op.buf.len().min(u32::MAX as usize) as u32;
+            let entry = build(i, op.buf.as_mut_ptr(), len).user_data(id);
+            // Heap memory of `op.buf` does not move when `op` is moved into the map
+            st.ops.insert(id, op);
+            ids.push(match self.push(&entry) {
+                Ok(()) => Ok(id),
+                Err(e) => {
+                    st.ops.remove(&id);
+                    Err(e)
+                }
+            });
+        }
+        // On error, queued entries are submitted by the next `submit` or `push` call
+        let _ = self.ring.submit();
+        ids
+    }
+
+    /// Push to the submission queue, submitting queued entries first if it is full. Call with `state` locked.
+    fn push(&self, entry: &squeue::Entry) -> Result<()> {
+        for _ in 0..2 {
+            // Safety: the submission queue is only accessed with `state` locked.
+            // The buffer is owned by the op, which is not dropped before its completion is reaped.
+            if unsafe { self.ring.submission_shared().push(entry) }.is_ok() {
+                return Ok(());
+            }
+            self.ring.submit()?;
+        }
+        Err(Error::other("io_uring submission queue is full"))
+    }
+
+    /// Completion thread: wakes futures of completed ops until asked to finish and no ops are in flight
+    fn complete(&self) {
+        let mut shutdown = false;
+        loop {
+            // Errors like `EINTR` or `EBUSY` are transient, completions are reaped anyway
+            let _ = self.ring.submit_and_wait(1);
+            let mut st = self.lock();
+            // Safety: the completion queue is only accessed by this thread
+            for cqe in unsafe { self.ring.completion_shared() } {
+                let id = cqe.user_data();
+                if id == SHUTDOWN {
+                    shutdown = true;
+                    continue;
+                }
+                let abandoned = match st.ops.get_mut(&id) {
+                    Some(op) => {
+                        op.result = Some(cqe.result());
+                        if let Some(w) = op.waker.take() {
+                            w.wake();
+                        }
+                        op.abandoned
+                    }
+                    None => false,
+                };
+                if abandoned {
+                    st.ops.remove(&id);
+                }
+            }
+            if shutdown && st.ops.is_empty() {
+                return;
+            }
+        }
+    }
+}
+
+/// Waits for the op `id` to complete, giving its result and buffer
+struct Completion<'a> {
+    shared: &'a Shared,
+    id: u64,
+    done: bool,
+}
+
+impl Future for Completion<'_> {
+    type Output = (i32, Vec<u8>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut st = self.shared.lock();
+        let op = st.ops.get_mut(&self.id).expect("op is kept until its future is done");
+        if op.result.is_none() {
+            op.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let op = st.ops.remove(&self.id).unwrap();
+        drop(st);
+        self.done = true;
+        Poll::Ready((op.result.unwrap(), op.buf))
+    }
+}
+
+impl Drop for Completion<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut st = self.shared.lock();
+        let finished = match st.ops.get_mut(&self.id) {
+            Some(op) => {
+                op.abandoned = true;
+                op.result.is_some()
+            }
+            None => false,
+        };
+        if finished {
+            st.ops.remove(&self.id);
+        }
+    }
+}
+
+fn to_result(res: i32) -> Result<usize> {
+    if res < 0 {
+        Err(Error::from_raw_os_error(-res))
+    } else {
+        Ok(res as usize)
+    }
+}
+
+/// File doing positional I/O through a Linux io_uring instead of a syscall per request.
+///
+/// Requires `uring` feature and Linux 5.6 or newer.
+///
+/// Requests of many concurrently polled futures share the ring, and `read_batch` or `write_batch`
+/// submit several requests with a single syscall. Completions are reaped by a background thread,
+/// which wakes the futures, so any executor can be used.
+///
+/// Data goes through buffers owned by the ring rather than the caller's ones,
+/// so dropping a future before its completion is safe (the request is still carried out).
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{AsyncReadAt,UringFile};
+///
+/// async fn head(path: &str) -> std::io::Result<[u8; 16]> {
+///     let f = UringFile::new(std::fs::File::open(path)?, 64)?;
+///     let mut v = [0u8; 16];
+///     f.read_exact_at(&mut v[..], 0).await?;
+///     Ok(v)
+/// }
+/// # let _ = head("/etc/hostname");
+/// ```
+pub struct UringFile {
+    shared: Arc<Shared>,
+    completion_thread: Option<JoinHandle<()>>,
+    file: File,
+}
+
+impl UringFile {
+    /// Set up a ring with `entries` submission queue entries for `file`
+    pub fn new(file: File, entries: u32) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            ring: IoUring::new(entries)?,
+            state: Mutex::new(State::default()),
+        });
+        let s = shared.clone();
+        let completion_thread = Some(std::thread::spawn(move || s.complete()));
+        Ok(UringFile {
+            shared,
+            completion_thread,
+            file,
+        })
+    }
+
+    /// Get a reference to the file
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Read into each buffer at its offset, submitting all requests at once.
+    /// Results are in the same order as requests; reads may be short.
+    pub fn read_batch<'a, 'b: 'a>(&'a self, reqs: &'a mut [(&'b mut [u8], u64)]) -> BoxFuture<'a, Vec<Result<usize>>> {
+        Box::pin(async move {
+            let fd = types::Fd(self.file.as_raw_fd());
+            let bufs = reqs.iter().map(|(b, _)| vec![0; b.len()]).collect();
+            let offsets: Vec<u64> = reqs.iter().map(|r| r.1).collect();
+            let ids = self.shared.submit(bufs, |i, ptr, len| {
+                opcode::Read::new(fd, ptr, len).offset(offsets[i]).build()
+            });
+            let completions = self.completions(ids);
+            let mut results = Vec::with_capacity(completions.len());
+            for (c, (buf, _)) in completions.into_iter().zip(reqs.iter_mut()) {
+                results.push(match c {
+                    Ok(c) => {
+                        let (res, data) = c.await;
+                        let n = to_result(res);
+                        if let Ok(n) = n {
+                            buf[..n].copy_from_slice(&data[..n]);
+                        }
+                        n
+                    }
+                    Err(e) => Err(e),
+                });
+            }
+            results
+        })
+    }
+
+    /// Write each buffer at its offset, submitting all requests at once.
+    /// Results are in the same order as requests; writes may be short.
+    pub fn write_batch<'a>(&'a self, reqs: &'a [(&'a [u8], u64)]) -> BoxFuture<'a, Vec<Result<usize>>> {
+        Box::pin(async move {
+            let fd = types::Fd(self.file.as_raw_fd());
+            let bufs = reqs.iter().map(|(b, _)| b.to_vec()).collect();
+            let ids = self.shared.submit(bufs, |i, ptr, len| {
+                opcode::Write::new(fd, ptr, len).offset(reqs[i].1).build()
+            });
+            let completions = self.completions(ids);
+            let mut results = Vec::with_capacity(completions.len());
+            for c in completions {
+                results.push(match c {
+                    Ok(c) => to_result(c.await.0),
+                    Err(e) => Err(e),
+                });
+            }
+            results
+        })
+    }
+
+    /// Futures for all submitted ops at once, so dropping the batch future abandons all of them
+    fn completions(&self, ids: Vec<Result<u64>>) -> Vec<Result<Completion<'_>>> {
+        ids.into_iter()
+            .map(|id| {
+                id.map(|id| Completion {
+                    shared: &self.shared,
+                    id,
+                    done: false,
+                })
+            })
+            .collect()
+    }
+}
+
+impl AsyncReadAt for UringFile {
+    fn read_at<'a>(&'a self, buf: &'a mut [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let mut reqs = [(buf, offset)];
+            self.read_batch(&mut reqs[..]).await.pop().unwrap()
+        })
+    }
+}
+
+impl AsyncWriteAt for UringFile {
+    fn write_at<'a>(&'a self, buf: &'a [u8], offset: u64) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let reqs = [(buf, offset)];
+            self.write_batch(&reqs[..]).await.pop().unwrap()
+        })
+    }
+}
+
+/// Waits for all requests in flight, so the file is not closed under them.
+impl Drop for UringFile {
+    fn drop(&mut self) {
+        let entry = opcode::Nop::new().build().user_data(SHUTDOWN);
+        let pushed = {
+            let _st = self.shared.lock();
+            self.shared.push(&entry).is_ok()
+        };
+        if pushed && self.shared.ring.submit().is_ok() {
+            if let Some(h) = self.completion_thread.take() {
+                let _ = h.join();
+            }
+        }
+        // Otherwise the thread keeps the ring alive and blocked; leaking it is better than a use-after-free
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Wake;
+
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+        loop {
+            if let Poll::Ready(x) = f.as_mut().poll(&mut cx) {
+                return x;
+            }
+            std::thread::park();
+        }
+    }
+
+    #[test]
+    fn batches() {
+        let file = crate::testing::temp_file();
+        let f = match UringFile::new(file, 4) {
+            Ok(f) => f,
+            // io_uring may be unavailable, e.g. disabled by seccomp
+            Err(_) => return,
+        };
+        let w = block_on(f.write_batch(&[(&[1, 2, 3][..], 0), (&[7; 8][..], 10)]));
+        assert_eq!(w.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(), [3, 8]);
+
+        // More requests than queue entries
+        let mut bufs = [[0u8; 2]; 6];
+        let mut reqs: Vec<_> = bufs.iter_mut().zip(0..).map(|(b, i)| (&mut b[..], i * 4)).collect();
+        let r = block_on(f.read_batch(&mut reqs[..]));
+        assert_eq!(r.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(), [2, 2, 2, 2, 2, 0]);
+        assert_eq!(bufs[..5], [[1, 2], [0, 0], [0, 0], [7, 7], [7, 7]]);
+
+        let mut v = [0u8; 4];
+        block_on(f.read_exact_at(&mut v[..], 1)).unwrap();
+        assert_eq!(v, [2, 3, 0, 0]);
+    }
+}
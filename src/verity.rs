@@ -0,0 +1,328 @@
+use super::checksummed::ChecksumError;
+use super::{read_up_to, ReadAt, SizeAt, WriteAt};
+use digest::{Digest, Output};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::{Mutex, MutexGuard};
+
+/// Upper bound of data handled by a single read; larger requests are served partially
+const MAX_CHUNK: u64 = 1 << 20;
+
+/// Positions of hash blocks in the tree
+#[derive(Debug)]
+struct Layout {
+    block_size: usize,
+    /// Hashes per hash block
+    per_block: u64,
+    data_blocks: u64,
+    /// Offset of the first hash block of each level, bottom level first. The top level has a single block.
+    levels: Vec<u64>,
+    tree_size: u64,
+}
+
+impl Layout {
+    fn new(block_size: usize, digest_size: usize, data_size: u64) -> Result<Self> {
+        let per_block = (block_size / digest_size) as u64;
+        if per_block < 2 {
+            return Err(Error::new(ErrorKind::InvalidInput, "block must fit at least two hashes"));
+        }
+        let data_blocks = data_size.div_ceil(block_size as u64);
+        let mut counts = vec![data_blocks.div_ceil(per_block).max(1)];
+        while counts[counts.len() - 1] > 1 {
+            counts.push(counts[counts.len() - 1].div_ceil(per_block));
+        }
+        // Levels are stored top first, like in dm-verity
+        let mut levels = vec![0; counts.len()];
+        let mut offset = 0u64;
+        for (level, count) in counts.iter().enumerate().rev() {
+            levels[level] = offset;
+            offset = count
+                .checked_mul(block_size as u64)
+                .and_then(|x| x.checked_add(offset))
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "hash tree size overflows u64"))?;
+        }
+        Ok(Layout { block_size, per_block, data_blocks, levels, tree_size: offset })
+    }
+}
+
+/// Parameters of a hash tree for `VerityReader`: block size and salt.
+///
+/// Data is split into blocks, the last one padded with zeros, and each block is hashed together with the salt.
+/// Hashes are packed into hash blocks of the same size, which are hashed in turn, level by level,
+/// until a single block remains, whose hash is the root hash. Levels are stored in the tree object top first.
+/// This follows dm-verity, but the format is not compatible with it.
+///
+/// Requires `digest` feature.
+///
+/// # Examples
+///
+/// ```
+/// use read_write_at::{ReadAt,VerityBuilder};
+/// use sha2::Sha256;
+/// use std::sync::Mutex;
+///
+/// let image = vec![7u8; 100_000];
+/// let tree = Mutex::new(vec![]);
+/// let root = VerityBuilder::new(4096).salt(b"v1").build::<Sha256, _, _>(&image, &tree).unwrap();
+///
+/// // Later, with the root hash coming from a trusted source
+/// let v = VerityBuilder::new(4096).salt(b"v1").open::<Sha256, _, _>(&image, &tree, &root).unwrap();
+/// let mut buf = [0u8; 10];
+/// v.read_exact_at(&mut buf[..], 99_990).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct VerityBuilder {
+    block_size: usize,
+    salt: Vec<u8>,
+}
+
+impl VerityBuilder {
+    /// Use blocks of `block_size` bytes, with no salt. Panics if `block_size` is zero.
+    pub fn new(block_size: usize) -> Self {
+        assert!(block_size > 0, "block_size must be nonzero");
+        VerityBuilder { block_size, salt: vec![] }
+    }
+
+    /// Prepend `salt` to every hashed block
+    pub fn salt(mut self, salt: &[u8]) -> Self {
+        self.salt = salt.to_vec();
+        self
+    }
+
+    /// Size of the hash tree of `data_size` bytes of data with digest `D`
+    pub fn tree_size<D: Digest>(&self, data_size: u64) -> Result<u64> {
+        Ok(Layout::new(self.block_size, <D as Digest>::output_size(), data_size)?.tree_size)
+    }
+
+    /// Write the hash tree of `data` to `tree` at offset `0`, returning the root hash
+    pub fn build<D, Data, HashTree>(&self, data: &Data, tree: &HashTree) -> Result<Output<D>>
+    where
+        D: Digest,
+        Data: ReadAt + SizeAt + ?Sized,
+        HashTree: WriteAt + ?Sized,
+    {
+        let layout = Layout::new(self.block_size, <D as Digest>::output_size(), data.size()?)?;
+        let bs = self.block_size;
+        let mut buf = vec![0u8; bs];
+        let leaves = (0..layout.data_blocks).map(|i| {
+            let n = read_up_to(data, &mut buf, i * bs as u64)?;
+            buf[n..].fill(0);
+            Ok(hash_block::<D>(&self.salt, &buf))
+        });
+        let mut hashes = self.write_level::<D, _>(tree, layout.levels[0], leaves)?;
+        for &offset in &layout.levels[1..] {
+            hashes = self.write_level::<D, _>(tree, offset, hashes.into_iter().map(Ok))?;
+        }
+        Ok(hashes.remove(0))
+    }
+
+    /// Verify reads of `data` against `tree` and the trusted `root` hash, as returned by `build` with the same parameters
+    pub fn open<D, Data, HashTree>(&self, data: Data, tree: HashTree, root: &[u8]) -> Result<VerityReader<Data, HashTree, D>>
+    where
+        D: Digest,
+        Data: ReadAt + SizeAt,
+        HashTree: ReadAt,
+    {
+        if root.len() != <D as Digest>::output_size() {
+            return Err(Error::new(ErrorKind::InvalidInput, "root hash has a wrong length"));
+        }
+        let size = data.size()?;
+        Ok(VerityReader {
+            layout: Layout::new(self.block_size, <D as Digest>::output_size(), size)?,
+            size,
+            data,
+            tree,
+            salt: self.salt.clone(),
+            root: Output::<D>::clone_from_slice(root),
+            verified: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Pack `hashes` into hash blocks written at `offset`, returning hashes of the written blocks
+    fn write_level<D, HashTree>(
+        &self,
+        tree: &HashTree,
+        mut offset: u64,
+        hashes: impl Iterator<Item = Result<Output<D>>>,
+    ) -> Result<Vec<Output<D>>>
+    where
+        D: Digest,
+        HashTree: WriteAt + ?Sized,
+    {
+        let (bs, ds) = (self.block_size, <D as Digest>::output_size());
+        let mut block = vec![0u8; bs];
+        let (mut pos, mut above) = (0, vec![]);
+        let mut flush = |block: &mut Vec<u8>, above: &mut Vec<Output<D>>| -> Result<()> {
+            tree.write_all_at(block, offset)?;
+            above.push(hash_block::<D>(&self.salt, block));
+            offset += bs as u64;
+            block.fill(0);
+            Ok(())
+        };
+        for h in hashes {
+            block[pos..pos + ds].copy_from_slice(&h?);
+            pos += ds;
+            if pos + ds > bs {
+                flush(&mut block, &mut above)?;
+                pos = 0;
+            }
+        }
+        // Even no hashes at all make one block
+        if pos > 0 || above.is_empty() {
+            flush(&mut block, &mut above)?;
+        }
+        Ok(above)
+    }
+}
+
+fn hash_block<D: Digest>(salt: &[u8], block: &[u8]) -> Output<D> {
+    D::new().chain_update(salt).chain_update(block).finalize()
+}
+
+/// Read-only view of `Data` verifying every block against a hash tree up to a trusted root hash, like dm-verity.
+///
+/// Created with `VerityBuilder::open`. Reads of a block not matching the tree fail with a `ChecksumError`
+/// giving the offset of the data block, whether the data or the tree was modified.
+/// Verified hash blocks are remembered, so each one is read and checked once; this takes up to the size of the tree in memory.
+///
+/// A single call handles at most 1 MiB (or one block, if larger), so it may be short; use `read_exact_at` for larger requests.
+///
+/// Requires `digest` feature.
+pub struct VerityReader<Data, HashTree, D: Digest> {
+    data: Data,
+    tree: HashTree,
+    salt: Vec<u8>,
+    root: Output<D>,
+    layout: Layout,
+    size: u64,
+    /// Contents of verified hash blocks by their offsets in the tree
+    verified: Mutex<HashMap<u64, Vec<u8>>>,
+}
+
+impl<Data, HashTree, D: Digest> VerityReader<Data, HashTree, D> {
+    /// Get a reference to the data object
+    pub fn get_ref(&self) -> &Data {
+        &self.data
+    }
+
+    /// Get a reference to the hash tree object
+    pub fn tree(&self) -> &HashTree {
+        &self.tree
+    }
+
+    /// Get back the data and the hash tree objects
+    pub fn into_inner(self) -> (Data, HashTree) {
+        (self.data, self.tree)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, Vec<u8>>> {
+        self.verified.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<Data, HashTree: ReadAt, D: Digest> VerityReader<Data, HashTree, D> {
+    /// Check data block `index` with contents `block` against the tree
+    fn verify(&self, index: u64, block: &[u8]) -> Result<()> {
+        let corrupted = || Error::new(ErrorKind::InvalidData, ChecksumError::new(index * self.layout.block_size as u64));
+        let (bs, ds) = (self.layout.block_size, <D as Digest>::output_size());
+        let mut want = hash_block::<D>(&self.salt, block);
+        let mut idx = index;
+        let mut pending = vec![];
+        let mut trusted = false;
+        for &level in &self.layout.levels {
+            let offset = level + idx / self.layout.per_block * bs as u64;
+            let pos = (idx % self.layout.per_block) as usize * ds;
+            if let Some(b) = self.lock().get(&offset) {
+                if b[pos..pos + ds] != want[..] {
+                    return Err(corrupted());
+                }
+                trusted = true;
+                break;
+            }
+            let mut b = vec![0u8; bs];
+            if read_up_to(&self.tree, &mut b, offset)? < bs || b[pos..pos + ds] != want[..] {
+                return Err(corrupted());
+            }
+            want = hash_block::<D>(&self.salt, &b);
+            pending.push((offset, b));
+            idx /= self.layout.per_block;
+        }
+        // Unless a verified block was reached, the chain must end at the root
+        if !trusted && want != self.root {
+            return Err(corrupted());
+        }
+        self.lock().extend(pending);
+        Ok(())
+    }
+}
+
+impl<Data: ReadAt, HashTree: ReadAt, D: Digest> ReadAt for VerityReader<Data, HashTree, D> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if buf.is_empty() || offset >= self.size {
+            return Ok(0);
+        }
+        let bs = self.layout.block_size as u64;
+        let first = offset / bs;
+        let end = (offset + buf.len() as u64).min(self.size).min((first + (MAX_CHUNK / bs).max(1)) * bs);
+        let mut data = vec![0u8; ((end.div_ceil(bs) - first) * bs) as usize];
+        let n = read_up_to(&self.data, &mut data, first * bs)?;
+        if (n as u64) < end - first * bs {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "data shrank after opening"));
+        }
+        for (i, block) in data.chunks(bs as usize).enumerate() {
+            self.verify(first + i as u64, block)?;
+        }
+        let within = (offset - first * bs) as usize;
+        let len = (end - offset) as usize;
+        buf[..len].copy_from_slice(&data[within..within + len]);
+        Ok(len)
+    }
+}
+
+/// Size of the data when it was opened
+impl<Data, HashTree, D: Digest> SizeAt for VerityReader<Data, HashTree, D> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Sha256;
+    use std::cell::RefCell;
+
+    #[test]
+    fn tampering() {
+        // 512-byte blocks hold 16 hashes, so 293 blocks make levels of 19, 2 and 1 hash blocks
+        let data = RefCell::new((0..150_000u32).map(|x| (x / 7) as u8).collect::<Vec<u8>>());
+        let tree = RefCell::new(vec![]);
+        let b = VerityBuilder::new(512).salt(b"salt");
+        let root = b.build::<Sha256, _, _>(&data, &tree).unwrap();
+        assert_eq!(tree.borrow().len(), 22 * 512);
+        assert_eq!(b.tree_size::<Sha256>(150_000).unwrap(), 22 * 512);
+
+        let v = b.open::<Sha256, _, _>(&data, &tree, &root).unwrap();
+        let mut buf = vec![0u8; 150_000];
+        v.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(buf, *data.borrow());
+
+        data.borrow_mut()[5000] ^= 1;
+        let e = v.read_exact_at(&mut buf[..10], 4999).unwrap_err();
+        assert_eq!(e.get_ref().unwrap().downcast_ref::<ChecksumError>().unwrap().offset(), 4608);
+        data.borrow_mut()[5000] ^= 1;
+
+        // Cached hash blocks are not read again, but a fresh reader notices a modified tree
+        tree.borrow_mut()[3 * 512] ^= 1;
+        v.read_exact_at(&mut buf[..10], 0).unwrap();
+        let v = b.open::<Sha256, _, _>(&data, &tree, &root).unwrap();
+        assert!(v.read_exact_at(&mut buf[..10], 0).is_err());
+        assert!(v.read_exact_at(&mut buf[..10], 140_000).is_ok());
+
+        let mut wrong = root;
+        wrong[0] ^= 1;
+        let v = b.open::<Sha256, _, _>(&data, &tree, &wrong).unwrap();
+        assert!(v.read_exact_at(&mut buf[..10], 140_000).is_err());
+        assert!(b.open::<Sha256, _, _>(&data, &tree, &root[1..]).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+// WASI calls are raw FFI
+#![allow(unsafe_code)]
+
+use super::{ReadAt, WriteAt};
+use std::fs::File;
+use std::io::{Error, IoSlice, IoSliceMut, Result};
+use std::os::fd::AsRawFd;
+
+fn errno(e: wasi::Errno) -> Error {
+    // libstd uses WASI error numbers as OS error codes
+    Error::from_raw_os_error(i32::from(e.raw()))
+}
+
+/// `fd_pread`, which does not move the cursor
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let iov = wasi::Iovec {
+            buf: buf.as_mut_ptr(),
+            buf_len: buf.len(),
+        };
+        // Safety: the buffer is valid for writes of its length during the call
+        unsafe { wasi::fd_pread(self.as_raw_fd() as wasi::Fd, &[iov], offset) }.map_err(errno)
+    }
+    fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<usize> {
+        let iovs: Vec<wasi::Iovec> = bufs
+            .iter_mut()
+            .map(|b| wasi::Iovec {
+                buf: b.as_mut_ptr(),
+                buf_len: b.len(),
+            })
+            .collect();
+        // Safety: the buffers are valid for writes of their lengths during the call
+        unsafe { wasi::fd_pread(self.as_raw_fd() as wasi::Fd, &iovs, offset) }.map_err(errno)
+    }
+}
+
+/// `fd_pwrite`, which does not move the cursor
+impl WriteAt for File {
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let iov = wasi::Ciovec {
+            buf: buf.as_ptr(),
+            buf_len: buf.len(),
+        };
+        // Safety: the buffer is valid for reads of its length during the call
+        unsafe { wasi::fd_pwrite(self.as_raw_fd() as wasi::Fd, &[iov], offset) }.map_err(errno)
+    }
+    fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<usize> {
+        let iovs: Vec<wasi::Ciovec> = bufs
+            .iter()
+            .map(|b| wasi::Ciovec {
+                buf: b.as_ptr(),
+                buf_len: b.len(),
+            })
+            .collect();
+        // Safety: the buffers are valid for reads of their lengths during the call
+        unsafe { wasi::fd_pwrite(self.as_raw_fd() as wasi::Fd, &iovs, offset) }.map_err(errno)
+    }
+}
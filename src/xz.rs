@@ -0,0 +1,290 @@
+use super::block_cache::BlockCache;
+use super::crc::crc32;
+use super::{ReadAt, SizeAt};
+use std::io::{Error, ErrorKind, Result};
+
+const HEADER_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0];
+const FOOTER_MAGIC: [u8; 2] = *b"YZ";
+/// Stream header and stream footer are both 12 bytes
+const HEADER_LEN: u64 = 12;
+const DEFAULT_CACHE: usize = 4;
+/// An LZMA2 chunk decodes to at most 2 MiB and takes at least 8 bytes,
+/// so no block expands more than this many times
+const MAX_EXPANSION: u64 = 1 << 18;
+
+/// Position of an xz block in the file and in the decompressed data
+struct Block {
+    offset: u64,
+    /// Size of the block without its padding, as recorded in the index
+    unpadded: u64,
+    start: u64,
+    len: u64,
+    /// Stream flags of the stream containing the block
+    flags: [u8; 2],
+}
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+/// Size of a block check field for stream flags `flags`
+fn check_len(flags: [u8; 2]) -> u64 {
+    match flags[1] & 0xf {
+        0 => 0,
+        n => 4 << ((n - 1) / 3),
+    }
+}
+
+fn padded(n: u64) -> u64 {
+    (n + 3) & !3
+}
+
+/// Parse a variable-length integer from the start of `b`, advancing it
+fn varint(b: &mut &[u8]) -> Result<u64> {
+    let mut n = 0u64;
+    for i in 0..9 {
+        let (&byte, rest) = b.split_first().ok_or_else(|| invalid("truncated xz index"))?;
+        *b = rest;
+        n |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(invalid("malformed integer in xz index"))
+}
+
+fn push_varint(v: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        v.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    v.push(n as u8);
+}
+
+/// `(unpadded, uncompressed)` sizes of the blocks in `index`, which includes its CRC32
+fn parse_index(index: &[u8]) -> Result<Vec<(u64, u64)>> {
+    let (body, sum) = index.split_at(index.len() - 4);
+    if index[0] != 0 || crc32(body) != u32_at(sum, 0) {
+        return Err(invalid("corrupted xz index"));
+    }
+    let mut rest = &body[1..];
+    let count = varint(&mut rest)?;
+    let mut records = vec![];
+    for _ in 0..count {
+        records.push((varint(&mut rest)?, varint(&mut rest)?));
+    }
+    if rest.len() > 3 || rest.iter().any(|&b| b != 0) {
+        return Err(invalid("malformed xz index"));
+    }
+    Ok(records)
+}
+
+/// Random access to the decompressed content of an `.xz` file, decompressing only the blocks needed.
+///
+/// `open` reads the index at the end of each stream (concatenated streams and stream padding are supported)
+/// to find where the blocks are. Each read decompresses whole blocks; the most recently used ones
+/// (4 by default) are cached. Block checks and index checksums are verified.
+///
+/// This only helps with files made of many blocks, like those written by `xz -T0` or `xz --block-size`:
+/// a file compressed as one block, as plain `xz` does, has to be decompressed entirely for any read.
+/// Decoding uses `lzma-rs`, which supports only the LZMA2 filter, not BCJ or delta filters.
+///
+/// A single call returns data from at most one block, so it may be short; use `read_exact_at` for larger requests.
+///
+/// Requires `xz` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{ReadAt,SizeAt,XzReader};
+///
+/// let r = XzReader::open(std::fs::File::open("image.raw.xz")?)?;
+/// let mut sector = [0u8; 512];
+/// r.read_exact_at(&mut sector[..], r.size()? - 512)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct XzReader<T> {
+    inner: T,
+    blocks: Vec<Block>,
+    cache: BlockCache,
+}
+
+impl<T: ReadAt + SizeAt> XzReader<T> {
+    /// Read the indexes of all streams of `inner`, starting from the end
+    pub fn open(inner: T) -> Result<Self> {
+        let mut end = inner.size()?;
+        let mut streams = vec![];
+        while end > 0 {
+            let mut padding = [0u8; 4];
+            if end >= 4 {
+                inner.read_exact_at(&mut padding, end - 4)?;
+            }
+            if end % 4 != 0 || end < HEADER_LEN * 2 {
+                return Err(invalid("file is too short or misaligned for an xz stream"));
+            }
+            if padding == [0; 4] {
+                end -= 4;
+                continue;
+            }
+            let mut footer = [0u8; HEADER_LEN as usize];
+            inner.read_exact_at(&mut footer, end - HEADER_LEN)?;
+            if footer[10..] != FOOTER_MAGIC || crc32(&footer[4..10]) != u32_at(&footer, 0) {
+                return Err(invalid("no xz stream footer at the end of the stream"));
+            }
+            let flags = [footer[8], footer[9]];
+            let index_len = (u32_at(&footer, 4) as u64 + 1) * 4;
+            let index_end = end - HEADER_LEN;
+            if index_len + HEADER_LEN > index_end {
+                return Err(invalid("xz index is longer than the stream"));
+            }
+            let mut index = vec![0u8; index_len as usize];
+            inner.read_exact_at(&mut index, index_end - index_len)?;
+            let records = parse_index(&index)?;
+            let blocks_len = records.iter().try_fold(0u64, |s, r| s.checked_add(padded(r.0)));
+            let start = blocks_len
+                .and_then(|l| (index_end - index_len).checked_sub(l + HEADER_LEN))
+                .ok_or_else(|| invalid("xz index describes more data than the file has"))?;
+            let mut header = [0u8; HEADER_LEN as usize];
+            inner.read_exact_at(&mut header, start)?;
+            if header[..6] != HEADER_MAGIC || header[6..8] != flags || crc32(&flags) != u32_at(&header, 8) {
+                return Err(invalid("xz stream header does not match its footer"));
+            }
+            streams.push((start + HEADER_LEN, flags, records));
+            end = start;
+        }
+        let mut blocks = vec![];
+        let mut start = 0u64;
+        for (mut offset, flags, records) in streams.into_iter().rev() {
+            for (unpadded, len) in records {
+                if unpadded < 8 + check_len(flags) {
+                    return Err(invalid("xz block is shorter than its header and check"));
+                }
+                if len > unpadded.saturating_mul(MAX_EXPANSION) {
+                    return Err(invalid("xz block is too small for its uncompressed size"));
+                }
+                blocks.push(Block { offset, unpadded, start, len, flags });
+                offset += padded(unpadded);
+                start = start.checked_add(len).ok_or_else(|| invalid("xz uncompressed size overflows"))?;
+            }
+        }
+        Ok(XzReader { inner, blocks, cache: BlockCache::new(DEFAULT_CACHE) })
+    }
+}
+
+impl<T> XzReader<T> {
+    /// Number of decompressed blocks to keep in memory
+    pub fn cache_blocks(mut self, blocks: usize) -> Self {
+        self.cache.set_capacity(blocks);
+        self
+    }
+
+    /// Number of blocks in all streams of the file
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Single-block xz stream with block `block`, which `lzma-rs` can decode on its own
+fn single_block_stream(b: &Block, block: &[u8]) -> Vec<u8> {
+    let mut s = Vec::with_capacity(block.len() + 64);
+    s.extend_from_slice(&HEADER_MAGIC);
+    s.extend_from_slice(&b.flags);
+    s.extend_from_slice(&crc32(&b.flags).to_le_bytes());
+    s.extend_from_slice(block);
+
+    let mut index = vec![0, 1];
+    push_varint(&mut index, b.unpadded);
+    push_varint(&mut index, b.len);
+    index.resize(padded(index.len() as u64) as usize, 0);
+    index.extend_from_slice(&crc32(&index).to_le_bytes());
+    s.extend_from_slice(&index);
+
+    let mut footer = (index.len() as u32 / 4 - 1).to_le_bytes().to_vec();
+    footer.extend_from_slice(&b.flags);
+    s.extend_from_slice(&crc32(&footer).to_le_bytes());
+    s.extend_from_slice(&footer);
+    s.extend_from_slice(&FOOTER_MAGIC);
+    s
+}
+
+impl<T: ReadAt> ReadAt for XzReader<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let i = self.blocks.partition_point(|b| b.start + b.len <= offset);
+        let b = match self.blocks.get(i) {
+            Some(b) if !buf.is_empty() => b,
+            _ => return Ok(0),
+        };
+        let data = self.cache.get(i as u64, || {
+            let mut block = vec![0u8; padded(b.unpadded) as usize];
+            self.inner.read_exact_at(&mut block, b.offset)?;
+            let stream = single_block_stream(b, &block);
+            let mut data = vec![];
+            lzma_rs::xz_decompress(&mut &stream[..], &mut data).map_err(|e| match e {
+                lzma_rs::error::Error::IoError(e) => e,
+                _ => invalid("corrupted xz block"),
+            })?;
+            if data.len() as u64 != b.len {
+                return Err(invalid("xz block size differs from the index"));
+            }
+            Ok(data)
+        })?;
+        let within = (offset - b.start) as usize;
+        let n = buf.len().min(data.len() - within);
+        buf[..n].copy_from_slice(&data[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl<T> SizeAt for XzReader<T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.blocks.last().map_or(0, |b| b.start + b.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concatenated_streams() {
+        let data: Vec<u8> = (0..100_000u32).map(|x| (x / 700) as u8).collect();
+        let mut file = vec![];
+        for chunk in data.chunks(40_000) {
+            lzma_rs::xz_compress(&mut &chunk[..], &mut file).unwrap();
+            file.extend_from_slice(&[0; 8]);
+        }
+        let r = XzReader::open(&file).unwrap().cache_blocks(1);
+        assert_eq!((r.blocks(), r.size().unwrap()), (3, 100_000));
+
+        let mut v = vec![0u8; 100_000];
+        assert_eq!(r.read_at(&mut v, 39_000).unwrap(), 1000);
+        r.read_exact_at(&mut v[..50_000], 30_000).unwrap();
+        assert_eq!(v[..50_000], data[30_000..80_000]);
+        r.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v, data);
+        assert_eq!(r.read_at(&mut v, 100_000).unwrap(), 0);
+
+        // Index claiming a too large uncompressed size for a block
+        let block = |len| Block { offset: HEADER_LEN, unpadded: 12, start: 0, len, flags: [0, 0] };
+        assert_eq!(XzReader::open(&single_block_stream(&block(1000), &[0; 12])).unwrap().blocks(), 1);
+        assert!(XzReader::open(&single_block_stream(&block(1 << 40), &[0; 12])).is_err());
+
+        let last = file.len() - 9;
+        file[last] ^= 1;
+        assert!(XzReader::open(&file).is_err());
+    }
+}
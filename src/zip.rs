@@ -0,0 +1,401 @@
+use super::block_cache::BlockCache;
+use super::crc::crc32;
+use super::{ReadAt, SizeAt, SubRange};
+use std::io::{Error, ErrorKind, Result};
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const EOCD64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const EOCD64_SIGNATURE: u32 = 0x0606_4b50;
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+const EOCD_LEN: u64 = 22;
+const CENTRAL_LEN: usize = 46;
+const LOCAL_LEN: usize = 30;
+const ZIP64_EXTRA: u16 = 0x0001;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+const DEFAULT_CACHE: usize = 8;
+/// Deflate cannot expand data more than about 1032 times
+const MAX_DEFLATE_EXPANSION: u64 = 1032;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+fn u64_at(b: &[u8], i: usize) -> u64 {
+    u32_at(b, i) as u64 | (u32_at(b, i + 4) as u64) << 32
+}
+
+/// An entry of the central directory of a zip file
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    name: String,
+    method: u16,
+    flags: u16,
+    crc: u32,
+    compressed_size: u64,
+    size: u64,
+    header_offset: u64,
+}
+
+impl ZipEntry {
+    /// Path of the entry in the archive, with invalid UTF-8 replaced
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Uncompressed size
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Size of the data in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// Compression method, e.g. 0 for stored and 8 for deflate
+    pub fn method(&self) -> u16 {
+        self.method
+    }
+
+    /// Whether the entry is stored uncompressed and unencrypted, so it can be read without decoding
+    pub fn is_stored(&self) -> bool {
+        self.method == STORED && !self.is_encrypted()
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.flags & 1 != 0
+    }
+}
+
+/// Replace 32-bit fields of `e` saturated to `u32::MAX` with the values from the zip64 extra field in `extra`
+fn apply_zip64(e: &mut ZipEntry, mut extra: &[u8]) -> Result<()> {
+    while extra.len() >= 4 {
+        let (id, len) = (u16_at(extra, 0), u16_at(extra, 2) as usize);
+        let data = extra.get(4..4 + len).ok_or_else(|| invalid("malformed zip extra field"))?;
+        if id == ZIP64_EXTRA {
+            let mut fields = data.chunks_exact(8).map(|c| u64_at(c, 0));
+            for v in [&mut e.size, &mut e.compressed_size, &mut e.header_offset] {
+                if *v == u32::MAX as u64 {
+                    *v = fields.next().ok_or_else(|| invalid("zip64 extra field is too short"))?;
+                }
+            }
+            return Ok(());
+        }
+        extra = &extra[4 + len..];
+    }
+    Ok(())
+}
+
+/// Index of the entries of a zip file, giving positional access to their content.
+///
+/// `open` reads only the central directory at the end of the file; zip64 archives are supported.
+/// `stored` exposes an uncompressed entry as a `SubRange` of the archive, with no decoding or copying,
+/// as is common for assets in pak-style archives. `entry` gives a `ReadAt` for stored and deflated entries alike:
+/// a deflated entry is decompressed entirely on its first read, verified against its CRC32,
+/// and kept among the most recently used ones (8 by default).
+///
+/// Entries with other compression methods or encryption fail with `Unsupported`.
+///
+/// Requires `zip` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use read_write_at::{ReadAt,ZipArchive};
+///
+/// let archive = ZipArchive::open(std::fs::File::open("assets.pak")?)?;
+/// let i = archive.find("textures/wall.dds").expect("entry exists");
+/// let texture = archive.stored(i)?;
+/// let mut header = [0u8; 128];
+/// texture.read_exact_at(&mut header[..], 0)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ZipArchive<T> {
+    inner: T,
+    entries: Vec<ZipEntry>,
+    /// Entry data lies before the central directory
+    data_end: u64,
+    cache: BlockCache,
+}
+
+impl<T: ReadAt + SizeAt> ZipArchive<T> {
+    /// Read the central directory of `inner`
+    pub fn open(inner: T) -> Result<Self> {
+        let size = inner.size()?;
+        if size < EOCD_LEN {
+            return Err(invalid("file is too short for a zip archive"));
+        }
+        // The end of central directory record is followed by a comment of at most 64 KiB
+        let tail_len = size.min(EOCD_LEN + u16::MAX as u64);
+        let mut tail = vec![0u8; tail_len as usize];
+        inner.read_exact_at(&mut tail, size - tail_len)?;
+        let eocd = (0..=tail.len() - EOCD_LEN as usize)
+            .rev()
+            .find(|&i| u32_at(&tail, i) == EOCD_SIGNATURE)
+            .ok_or_else(|| invalid("no zip end of central directory record"))?;
+        let eocd_offset = size - tail_len + eocd as u64;
+        let eocd = &tail[eocd..];
+        let mut count = u16_at(eocd, 10) as u64;
+        let mut dir_len = u32_at(eocd, 12) as u64;
+        let mut dir_offset = u32_at(eocd, 16) as u64;
+
+        if eocd_offset >= 20 && (count == u16::MAX as u64 || dir_offset == u32::MAX as u64) {
+            let mut locator = [0u8; 20];
+            inner.read_exact_at(&mut locator, eocd_offset - 20)?;
+            if u32_at(&locator, 0) == EOCD64_LOCATOR_SIGNATURE {
+                let mut eocd64 = [0u8; 56];
+                inner.read_exact_at(&mut eocd64, u64_at(&locator, 8))?;
+                if u32_at(&eocd64, 0) != EOCD64_SIGNATURE {
+                    return Err(invalid("malformed zip64 end of central directory record"));
+                }
+                count = u64_at(&eocd64, 32);
+                dir_len = u64_at(&eocd64, 40);
+                dir_offset = u64_at(&eocd64, 48);
+            }
+        }
+        if dir_offset.checked_add(dir_len).filter(|&end| end <= eocd_offset).is_none() {
+            return Err(invalid("zip central directory is outside the file"));
+        }
+
+        let mut dir = vec![0u8; dir_len as usize];
+        inner.read_exact_at(&mut dir, dir_offset)?;
+        let mut entries = Vec::with_capacity(count.min(dir_len / CENTRAL_LEN as u64) as usize);
+        let mut rest = &dir[..];
+        for _ in 0..count {
+            if rest.len() < CENTRAL_LEN || u32_at(rest, 0) != CENTRAL_SIGNATURE {
+                return Err(invalid("malformed zip central directory"));
+            }
+            let name_len = u16_at(rest, 28) as usize;
+            let extra_len = u16_at(rest, 30) as usize;
+            let comment_len = u16_at(rest, 32) as usize;
+            let len = CENTRAL_LEN + name_len + extra_len + comment_len;
+            if rest.len() < len {
+                return Err(invalid("malformed zip central directory"));
+            }
+            let mut e = ZipEntry {
+                name: String::from_utf8_lossy(&rest[CENTRAL_LEN..CENTRAL_LEN + name_len]).into_owned(),
+                method: u16_at(rest, 10),
+                flags: u16_at(rest, 8),
+                crc: u32_at(rest, 16),
+                compressed_size: u32_at(rest, 20) as u64,
+                size: u32_at(rest, 24) as u64,
+                header_offset: u32_at(rest, 42) as u64,
+            };
+            apply_zip64(&mut e, &rest[CENTRAL_LEN + name_len..CENTRAL_LEN + name_len + extra_len])?;
+            let end = e.header_offset.saturating_add(LOCAL_LEN as u64).saturating_add(e.compressed_size);
+            if end > dir_offset {
+                return Err(invalid("zip entry extends past the central directory"));
+            }
+            let plausible = match e.method {
+                STORED if !e.is_encrypted() => e.size == e.compressed_size,
+                DEFLATED => e.size <= e.compressed_size.saturating_mul(MAX_DEFLATE_EXPANSION).saturating_add(64),
+                _ => true,
+            };
+            if !plausible {
+                return Err(invalid("zip entry size does not match its compressed size"));
+            }
+            entries.push(e);
+            rest = &rest[len..];
+        }
+        Ok(ZipArchive { inner, entries, data_end: dir_offset, cache: BlockCache::new(DEFAULT_CACHE) })
+    }
+}
+
+impl<T> ZipArchive<T> {
+    /// Number of decompressed entries to keep in memory
+    pub fn cache_entries(mut self, entries: usize) -> Self {
+        self.cache.set_capacity(entries);
+        self
+    }
+
+    /// Entries in the order of the central directory
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Index of the first entry named `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.name == name)
+    }
+
+    /// Get a reference to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get back the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn get(&self, index: usize) -> Result<&ZipEntry> {
+        self.entries
+            .get(index)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no zip entry with this index"))
+    }
+}
+
+impl<T: ReadAt> ZipArchive<T> {
+    /// Offset of the data of entry `e`, found in its local header
+    fn data_offset(&self, e: &ZipEntry) -> Result<u64> {
+        let mut h = [0u8; LOCAL_LEN];
+        self.inner.read_exact_at(&mut h, e.header_offset)?;
+        if u32_at(&h, 0) != LOCAL_SIGNATURE {
+            return Err(invalid("no zip local header at the offset in the central directory"));
+        }
+        let data = e.header_offset + (LOCAL_LEN + u16_at(&h, 26) as usize + u16_at(&h, 28) as usize) as u64;
+        if data + e.compressed_size > self.data_end {
+            return Err(invalid("zip entry extends past the central directory"));
+        }
+        Ok(data)
+    }
+
+    /// Content of stored entry `index` as a window of the archive. Fails with `Unsupported` if it is compressed.
+    pub fn stored(&self, index: usize) -> Result<SubRange<&T>> {
+        let e = self.get(index)?;
+        if !e.is_stored() {
+            return Err(Error::new(ErrorKind::Unsupported, "zip entry is not stored uncompressed"));
+        }
+        Ok(SubRange::new(&self.inner, self.data_offset(e)?, e.size))
+    }
+
+    /// Reader of the uncompressed content of entry `index`, which is stored or deflated
+    pub fn entry(&self, index: usize) -> Result<ZipEntryReader<'_, T>> {
+        let e = self.get(index)?;
+        if e.is_encrypted() || (e.method != STORED && e.method != DEFLATED) {
+            return Err(Error::new(ErrorKind::Unsupported, "zip entry is encrypted or uses an unsupported method"));
+        }
+        Ok(ZipEntryReader { archive: self, index, data: self.data_offset(e)? })
+    }
+}
+
+/// Content of a stored or deflated zip entry, created by `ZipArchive::entry`
+pub struct ZipEntryReader<'a, T> {
+    archive: &'a ZipArchive<T>,
+    index: usize,
+    data: u64,
+}
+
+impl<T> ZipEntryReader<'_, T> {
+    /// The entry being read
+    pub fn entry(&self) -> &ZipEntry {
+        &self.archive.entries[self.index]
+    }
+}
+
+impl<T: ReadAt> ReadAt for ZipEntryReader<'_, T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let e = self.entry();
+        if e.method == STORED {
+            return SubRange::new(&self.archive.inner, self.data, e.size).read_at(buf, offset);
+        }
+        if offset >= e.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let data = self.archive.cache.get(self.index as u64, || {
+            let mut compressed = vec![0u8; e.compressed_size as usize];
+            self.archive.inner.read_exact_at(&mut compressed, self.data)?;
+            let data = miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, e.size as usize)
+                .map_err(|_| invalid("corrupted deflated zip entry"))?;
+            if data.len() as u64 != e.size || crc32(&data) != e.crc {
+                return Err(invalid("zip entry differs from its size or CRC32"));
+            }
+            Ok(data)
+        })?;
+        let within = offset as usize;
+        let n = buf.len().min(data.len() - within);
+        buf[..n].copy_from_slice(&data[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl<T> SizeAt for ZipEntryReader<'_, T> {
+    fn size(&self) -> Result<u64> {
+        Ok(self.entry().size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Zip file with entries `(name, data, deflate)`
+    fn zip(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let (mut file, mut dir) = (vec![], vec![]);
+        for &(name, data, deflate) in files {
+            let stored = if deflate { miniz_oxide::deflate::compress_to_vec(data, 6) } else { data.to_vec() };
+            let mut common = vec![20, 0, 0, 0, if deflate { 8 } else { 0 }, 0, 0, 0, 0, 0];
+            common.extend_from_slice(&crc32(data).to_le_bytes());
+            common.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+            dir.extend_from_slice(&CENTRAL_SIGNATURE.to_le_bytes());
+            dir.extend_from_slice(&[20, 0]);
+            dir.extend_from_slice(&common);
+            dir.extend_from_slice(&[0; 12]);
+            dir.extend_from_slice(&(file.len() as u32).to_le_bytes());
+            dir.extend_from_slice(name.as_bytes());
+
+            file.extend_from_slice(&LOCAL_SIGNATURE.to_le_bytes());
+            file.extend_from_slice(&common);
+            file.extend_from_slice(&[3, 0]);
+            file.extend_from_slice(name.as_bytes());
+            file.extend_from_slice(&[0; 3]);
+            file.extend_from_slice(&stored);
+        }
+        let dir_offset = file.len() as u32;
+        file.extend_from_slice(&dir);
+        file.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&(files.len() as u16).to_le_bytes().repeat(2));
+        file.extend_from_slice(&(dir.len() as u32).to_le_bytes());
+        file.extend_from_slice(&dir_offset.to_le_bytes());
+        file.extend_from_slice(&[2, 0, b'h', b'i']);
+        file
+    }
+
+    #[test]
+    fn stored_and_deflated() {
+        let text: Vec<u8> = (0..50_000u32).map(|x| b"zip entry "[x as usize % 10]).collect();
+        let file = zip(&[("a.bin", &[1, 2, 3, 4, 5], false), ("dir/b.txt", &text, true)]);
+        let archive = ZipArchive::open(&file).unwrap();
+        assert_eq!(archive.entries().len(), 2);
+        assert_eq!(archive.find("dir/b.txt"), Some(1));
+        assert!(archive.entries()[1].compressed_size() < 1000);
+
+        let a = archive.stored(0).unwrap();
+        let mut v = vec![0u8; 50_000];
+        assert_eq!(a.read_at(&mut v, 1).unwrap(), 4);
+        assert_eq!(v[..4], [2, 3, 4, 5]);
+        assert_eq!(archive.stored(1).err().map(|e| e.kind()), Some(ErrorKind::Unsupported));
+
+        let b = archive.entry(1).unwrap();
+        assert_eq!(b.size().unwrap(), 50_000);
+        b.read_exact_at(&mut v[..20], 49_980).unwrap();
+        assert_eq!(v[..20], text[49_980..]);
+        b.read_exact_at(&mut v, 0).unwrap();
+        assert_eq!(v, text);
+        assert_eq!(archive.entry(0).unwrap().read_at(&mut v, 0).unwrap(), 5);
+
+        // Compressed size of the second entry in the central directory, claiming 4 GiB
+        let mut huge = file.clone();
+        let dir = huge.len() - 24 - (46 + 9);
+        huge[dir + 20..dir + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(ZipArchive::open(&huge).err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+        // Uncompressed size of the stored first entry, not matching its compressed size
+        let mut huge = file.clone();
+        huge[file.len() - 24 - (2 * 46 + 5 + 9) + 24] = 0xff;
+        assert_eq!(ZipArchive::open(&huge).err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+    }
+}